@@ -11,6 +11,9 @@ fn main() {
         .compile(
             &[
                 "protos/auth.proto",
+                "protos/bundle.proto",
+                "protos/packet.proto",
+                "protos/searcher.proto",
                 "protos/shared.proto",
                 "protos/shredstream.proto",
             ],
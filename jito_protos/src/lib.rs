@@ -9,3 +9,15 @@ pub mod auth {
 pub mod shredstream {
     tonic::include_proto!("shredstream");
 }
+
+pub mod packet {
+    tonic::include_proto!("packet");
+}
+
+pub mod bundle {
+    tonic::include_proto!("bundle");
+}
+
+pub mod searcher {
+    tonic::include_proto!("searcher");
+}
@@ -0,0 +1,715 @@
+use crate::bonding_curve::{fee, CurveError, GlobalAccount};
+use crate::capture::{replay_entries, ReplaySpeed};
+use crate::instruction::PumpInstruction;
+use crate::lookup_table::LookupTableCache;
+use crate::message::{MessageView, ResolvedMessage};
+use crate::processor::mint_state::{MintStates, TradeSide};
+use crate::exit::{ExitEngine, ExitOrder};
+use crate::strategy::{EventContext, Intent, Order, StrategyEvent, StrategySet};
+use crate::transaction::PUMP_PROGRAM_ID;
+use crate::utils::auto_trader::SnipeParams;
+use serde::Serialize;
+use solana_entry::entry::Entry;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// 从发出信号到交易上链的延迟
+#[derive(Clone, Copy, Debug, Serialize)]
+pub enum Latency {
+    // 在信号之后第N个slot成交
+    Slots(u64),
+    // 在信号之后N毫秒成交
+    Millis(u64),
+}
+
+/// 回测配置
+#[derive(Clone, Debug)]
+pub struct BacktestConfig {
+    pub params: SnipeParams,
+    pub latency: Latency,
+    // 协议手续费 (基点)
+    pub fee_bps: u64,
+    // 每笔交易的网络费用和小费 (lamports)
+    pub tx_fee_lamports: u64,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            params: SnipeParams::default(),
+            latency: Latency::Slots(1),
+            fee_bps: 100,
+            tx_fee_lamports: 5_000,
+        }
+    }
+}
+
+/// 交易结果状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TradeStatus {
+    // 已买入并卖出
+    Closed,
+    // 数据结束时仍持有，按最后价格估值
+    Open,
+    // 成交时价格超过最大花费
+    SlippageExceeded,
+    // 成交时曲线已完成
+    CurveComplete,
+}
+
+/// 单笔回测交易
+#[derive(Clone, Debug, Serialize)]
+pub struct TradeRecord {
+    pub strategy: String,
+    pub mint: String,
+    pub status: TradeStatus,
+    pub signal_slot: u64,
+    pub signal_time_ms: u64,
+    pub entry_slot: Option<u64>,
+    pub entry_time_ms: Option<u64>,
+    pub exit_slot: Option<u64>,
+    pub exit_time_ms: Option<u64>,
+    pub token_amount: u64,
+    // 买入总花费，含手续费和网络费用 (lamports)
+    pub sol_in: u64,
+    // 卖出净收入 (lamports)
+    pub sol_out: u64,
+    pub pnl_lamports: i64,
+    pub hold_ms: Option<u64>,
+}
+
+/// 汇总统计
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BacktestSummary {
+    pub entries: u64,
+    pub mints_created: u64,
+    pub signals: u64,
+    pub filled: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub win_rate: f64,
+    pub total_pnl_lamports: i64,
+    pub max_drawdown_lamports: i64,
+    pub avg_hold_ms: f64,
+}
+
+/// 回测报告
+#[derive(Clone, Debug, Serialize)]
+pub struct BacktestReport {
+    pub summary: BacktestSummary,
+    pub trades: Vec<TradeRecord>,
+}
+
+impl BacktestReport {
+    /// 将报告写为JSON文件
+    pub fn write_json(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// 将逐笔交易写为CSV文件
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "strategy,mint,status,signal_slot,signal_time_ms,entry_slot,entry_time_ms,exit_slot,exit_time_ms,token_amount,sol_in,sol_out,pnl_lamports,hold_ms"
+        )?;
+        let opt = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+        for trade in &self.trades {
+            writeln!(
+                writer,
+                "{},{},{:?},{},{},{},{},{},{},{},{},{},{},{}",
+                trade.strategy,
+                trade.mint,
+                trade.status,
+                trade.signal_slot,
+                trade.signal_time_ms,
+                opt(trade.entry_slot),
+                opt(trade.entry_time_ms),
+                opt(trade.exit_slot),
+                opt(trade.exit_time_ms),
+                trade.token_amount,
+                trade.sol_in,
+                trade.sol_out,
+                trade.pnl_lamports,
+                opt(trade.hold_ms)
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 待成交订单
+enum OrderSide {
+    // 策略的买入意图，成交后持仓使用其中的预算和退出规则
+    Buy { order: Order, token_amount: u64, max_sol_cost: u64 },
+    Sell { exit: ExitOrder },
+}
+
+// 仓位按策略和代币区分，不同策略可以同时持有同一个代币
+type PositionKey = (String, Pubkey);
+
+struct PendingOrder {
+    key: PositionKey,
+    side: OrderSide,
+    signal_slot: u64,
+    signal_ns: u64,
+    // 满足其一即成交
+    execute_slot: Option<u64>,
+    execute_ns: Option<u64>,
+}
+
+/// 持仓
+struct Position {
+    trade: TradeRecord,
+    entry_ns: u64,
+}
+
+/// 使用录制数据回放策略的回测器
+///
+/// 事件与实盘一样交给同一组策略，买卖意图在配置的延迟后按内盘曲线精确成交；
+/// 持仓由与实盘相同的退出引擎按止盈、止损、移动止损和最长持仓时间卖出，
+/// 成交时所得低于最少接收的卖出失败，持仓保留到下次触发。
+/// 每个策略对每个代币最多持有一笔仓位。
+pub struct Backtester {
+    config: BacktestConfig,
+    // 新曲线的初始参数，手续费使用回测配置
+    global: GlobalAccount,
+    mints: MintStates,
+    strategies: StrategySet,
+    exits: ExitEngine,
+    // 地址查找表缓存，用于解析v0消息加载的账户
+    lookup_tables: LookupTableCache,
+    orders: Vec<PendingOrder>,
+    positions: HashMap<PositionKey, Position>,
+    trades: Vec<TradeRecord>,
+    summary: BacktestSummary,
+    current_slot: u64,
+    current_ns: u64,
+}
+
+impl Backtester {
+    pub fn new(config: BacktestConfig, strategies: StrategySet) -> Self {
+        let global = GlobalAccount {
+            fee_basis_points: config.fee_bps,
+            ..GlobalAccount::default()
+        };
+        Self {
+            config,
+            global,
+            mints: MintStates::default(),
+            strategies,
+            exits: ExitEngine::new(),
+            lookup_tables: LookupTableCache::offline(),
+            orders: Vec::new(),
+            positions: HashMap::new(),
+            trades: Vec::new(),
+            summary: BacktestSummary::default(),
+            current_slot: 0,
+            current_ns: 0,
+        }
+    }
+
+    /// 设置地址查找表缓存
+    pub fn set_lookup_tables(&mut self, lookup_tables: LookupTableCache) {
+        self.lookup_tables = lookup_tables;
+    }
+
+    /// 回测捕获文件
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 回测配置
+    /// * `strategies` - 参与回测的策略
+    /// * `lookup_tables` - 地址查找表缓存，通常从快照加载
+    /// * `path` - 捕获文件或包含捕获文件的目录
+    pub async fn run_capture(
+        config: BacktestConfig,
+        strategies: StrategySet,
+        lookup_tables: LookupTableCache,
+        path: impl AsRef<Path>,
+    ) -> Result<BacktestReport, Box<dyn Error>> {
+        let mut backtester = Self::new(config, strategies);
+        backtester.set_lookup_tables(lookup_tables);
+        replay_entries(path, ReplaySpeed::Max, |slot, received_at_ns, entries| {
+            backtester.process_entries(slot, received_at_ns, &entries);
+        })
+        .await?;
+        Ok(backtester.finish())
+    }
+
+    /// 处理一批Entry，先成交到期订单，再应用其中的交易
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - Entry所属slot
+    /// * `received_at_ns` - 接收时间，自UNIX纪元起的纳秒数
+    /// * `entries` - 去重后的Entry
+    pub fn process_entries(&mut self, slot: u64, received_at_ns: u64, entries: &[Entry]) {
+        self.current_slot = self.current_slot.max(slot);
+        self.current_ns = self.current_ns.max(received_at_ns);
+        self.summary.entries += entries.len() as u64;
+
+        self.execute_due_orders();
+        self.check_exits();
+
+        for entry in entries {
+            for transaction in &entry.transactions {
+                self.process_transaction(transaction);
+            }
+        }
+    }
+
+    fn process_transaction(&mut self, transaction: &VersionedTransaction) {
+        let message = &transaction.message;
+        self.lookup_tables.observe(message);
+        if !message.static_keys().contains(&PUMP_PROGRAM_ID) {
+            return;
+        }
+        // 查找表未缓存时只能解析静态账户，加载的账户以默认公钥占位
+        let resolved = self
+            .lookup_tables
+            .resolve(message)
+            .unwrap_or_else(|| ResolvedMessage::unresolved(message));
+        // 与实盘一致，丢弃无法解析或内盘曲线账户不符的指令
+        let instructions: Vec<PumpInstruction> = resolved.pump_instructions().filter_map(Result::ok).collect();
+        let in_create_tx = instructions
+            .iter()
+            .any(|instruction| matches!(instruction, PumpInstruction::Create { .. }));
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        let slot = self.current_slot;
+
+        for instruction in &instructions {
+            match instruction {
+                PumpInstruction::Create { accounts, .. } => {
+                    let exits = &self.exits;
+                    self.mints.track(accounts.mint, &self.global, accounts.user, slot, |mint| exits.holds(mint));
+                    self.summary.mints_created += 1;
+                }
+                PumpInstruction::Buy { args, accounts } => {
+                    if let Some(state) = self.mints.get_mut(&accounts.mint) {
+                        let _ = state.record_trade(slot, signature, TradeSide::Buy, accounts.user, args.amount, args.max_sol_cost);
+                    }
+                }
+                PumpInstruction::Sell { args, accounts } => {
+                    // 储备不足说明有未观察到的交易，忽略这笔卖出
+                    if let Some(state) = self.mints.get_mut(&accounts.mint) {
+                        let _ = state.record_trade(slot, signature, TradeSide::Sell, accounts.user, args.amount, args.min_sol_output);
+                    }
+                }
+                _ => {}
+            }
+
+            let event = match StrategyEvent::from_instruction(instruction) {
+                Some(event) => event,
+                None => continue,
+            };
+            let ctx = EventContext {
+                in_create_tx,
+                state: self.mints.get(&event.mint()),
+            };
+            for order in self.strategies.on_event(&event, &ctx) {
+                self.signal(order);
+            }
+        }
+    }
+
+    // 按延迟计算订单的成交时机
+    fn schedule(&mut self, key: PositionKey, side: OrderSide, signal_ns: u64) {
+        let (execute_slot, execute_ns) = match self.config.latency {
+            Latency::Slots(slots) => (Some(self.current_slot + slots), None),
+            Latency::Millis(ms) => (None, Some(signal_ns + ms * 1_000_000)),
+        };
+        self.orders.push(PendingOrder {
+            key,
+            side,
+            signal_slot: self.current_slot,
+            signal_ns,
+            execute_slot,
+            execute_ns,
+        });
+    }
+
+    fn signal(&mut self, order: Order) {
+        match order.intent {
+            Intent::Buy { mint, lamports } => self.signal_buy(order, mint, lamports),
+            Intent::Sell { mint, basis_points } => {
+                let fee_bps = self.config.fee_bps;
+                let exit = self.mints.get(&mint).and_then(|state| {
+                    self.exits.sell_share(&order.strategy, &mint, basis_points, &state.curve, fee_bps)
+                });
+                if let Some(exit) = exit {
+                    self.signal_sell(exit);
+                }
+            }
+            Intent::Ignore => {}
+        }
+    }
+
+    fn signal_buy(&mut self, order: Order, mint: Pubkey, lamports: u64) {
+        // 开始回测之前创建的代币没有曲线，无法模拟成交
+        let token_amount = self
+            .mints
+            .get(&mint)
+            .and_then(|state| self.config.params.token_amount(lamports, &state.curve, self.config.fee_bps).ok());
+        let token_amount = match token_amount {
+            Some(token_amount) if token_amount > 0 => token_amount,
+            _ => {
+                order.budget.release(&mint);
+                self.strategies.closed(&order.strategy, &mint);
+                return;
+            }
+        };
+        self.summary.signals += 1;
+        let key = (order.strategy.clone(), mint);
+        let side = OrderSide::Buy {
+            order,
+            token_amount,
+            max_sol_cost: lamports,
+        };
+        self.schedule(key, side, self.current_ns);
+    }
+
+    fn signal_sell(&mut self, exit: ExitOrder) {
+        let key = (exit.strategy.clone(), exit.mint);
+        self.schedule(key, OrderSide::Sell { exit }, self.current_ns);
+    }
+
+    // 按当前曲线检查持仓的退出条件
+    fn check_exits(&mut self) {
+        let now_ms = self.current_ns / 1_000_000;
+        for exit in self.exits.evaluate(&self.mints, self.config.fee_bps, now_ms) {
+            self.signal_sell(exit);
+        }
+    }
+
+    fn execute_due_orders(&mut self) {
+        let (slot, now) = (self.current_slot, self.current_ns);
+        let (due, waiting): (Vec<_>, Vec<_>) = self.orders.drain(..).partition(|order| {
+            order.execute_slot.is_some_and(|s| slot >= s) || order.execute_ns.is_some_and(|t| now >= t)
+        });
+        self.orders = waiting;
+        for order in due {
+            match order.side {
+                OrderSide::Buy { .. } => self.fill_buy(order),
+                OrderSide::Sell { exit } => self.fill_sell(&order.key, &exit),
+            }
+        }
+    }
+
+    fn fee(&self, lamports: u64) -> u64 {
+        fee(lamports, self.config.fee_bps)
+    }
+
+    fn fill_buy(&mut self, pending: PendingOrder) {
+        let (order, token_amount, max_sol_cost) = match pending.side {
+            OrderSide::Buy { order, token_amount, max_sol_cost } => (order, token_amount, max_sol_cost),
+            OrderSide::Sell { .. } => return,
+        };
+        let (strategy, mint) = pending.key;
+        let mut trade = TradeRecord {
+            strategy: strategy.clone(),
+            mint: mint.to_string(),
+            status: TradeStatus::Closed,
+            signal_slot: pending.signal_slot,
+            signal_time_ms: pending.signal_ns / 1_000_000,
+            entry_slot: None,
+            entry_time_ms: None,
+            exit_slot: None,
+            exit_time_ms: None,
+            token_amount,
+            sol_in: 0,
+            sol_out: 0,
+            pnl_lamports: 0,
+            hold_ms: None,
+        };
+
+        match self.apply_buy(&mint, token_amount, max_sol_cost) {
+            Ok((filled, cost)) => {
+                trade.token_amount = filled;
+                trade.sol_in = cost + self.fee(cost) + self.config.tx_fee_lamports;
+                trade.entry_slot = Some(self.current_slot);
+                trade.entry_time_ms = Some(self.current_ns / 1_000_000);
+                self.summary.filled += 1;
+
+                self.exits.open(order, filled, trade.sol_in, self.current_ns / 1_000_000);
+                self.positions.insert((strategy, mint), Position { trade, entry_ns: self.current_ns });
+            }
+            Err(status) => {
+                trade.status = status;
+                self.trades.push(trade);
+                order.budget.release(&mint);
+                self.strategies.closed(&strategy, &mint);
+            }
+        }
+    }
+
+    // 按当前曲线成交买入，返回成交数量和花费，失败时返回交易状态
+    fn apply_buy(&mut self, mint: &Pubkey, token_amount: u64, max_sol_cost: u64) -> Result<(u64, u64), TradeStatus> {
+        let fee_bps = self.config.fee_bps;
+        let curve = match self.mints.get_mut(mint) {
+            Some(state) => &mut state.curve,
+            None => return Err(TradeStatus::CurveComplete),
+        };
+        // 与链上一致，先按当前曲线计算花费，超过最大花费则交易失败
+        let cost = curve.buy_cost(token_amount).map_err(|e| match e {
+            CurveError::Complete => TradeStatus::CurveComplete,
+            _ => TradeStatus::SlippageExceeded,
+        })?;
+        if cost + fee(cost, fee_bps) > max_sol_cost {
+            return Err(TradeStatus::SlippageExceeded);
+        }
+        curve.apply_buy(token_amount).map_err(|_| TradeStatus::CurveComplete)
+    }
+
+    fn fill_sell(&mut self, key: &PositionKey, exit: &ExitOrder) {
+        let fee_bps = self.config.fee_bps;
+        let (token_amount, min_sol_output) = (exit.token_amount, exit.min_sol_output);
+        // 与链上一致，扣除手续费后低于最少接收时交易失败，曲线不变
+        let proceeds = self.mints.get_mut(&key.1).and_then(|state| {
+            let proceeds = state.curve.sell_quote(token_amount).ok()?;
+            if proceeds - fee(proceeds, fee_bps) < min_sol_output {
+                return None;
+            }
+            state.curve.apply_sell(token_amount).ok()
+        });
+        let proceeds = match proceeds {
+            Some(proceeds) => proceeds,
+            None => {
+                self.exits.sell_result(exit, false);
+                return;
+            }
+        };
+        let position = match self.positions.get_mut(key) {
+            Some(position) => position,
+            None => return,
+        };
+        position.trade.sol_out += proceeds
+            .saturating_sub(fee(proceeds, fee_bps))
+            .saturating_sub(self.config.tx_fee_lamports);
+        if !self.exits.sell_result(exit, true) {
+            return;
+        }
+        self.strategies.closed(&key.0, &key.1);
+
+        let Position { mut trade, entry_ns } = self.positions.remove(key).unwrap();
+        trade.exit_slot = Some(self.current_slot);
+        trade.exit_time_ms = Some(self.current_ns / 1_000_000);
+        trade.hold_ms = Some(self.current_ns.saturating_sub(entry_ns) / 1_000_000);
+        trade.pnl_lamports = trade.sol_out as i64 - trade.sol_in as i64;
+        self.trades.push(trade);
+    }
+
+    /// 结束回测，未平仓的仓位按最后的曲线价格估值，并生成报告
+    pub fn finish(mut self) -> BacktestReport {
+        let open: Vec<PositionKey> = self.positions.keys().cloned().collect();
+        for key in open {
+            let Position { mut trade, entry_ns } = self.positions.remove(&key).unwrap();
+            // 在途卖出没有成交，与剩余持仓一起估值
+            let remaining = self
+                .exits
+                .get(&key.0, &key.1)
+                .map(|position| position.remaining + position.in_flight)
+                .unwrap_or_default();
+            let proceeds = self
+                .mints
+                .get(&key.1)
+                .and_then(|state| state.curve.sell_quote(remaining).ok())
+                .unwrap_or_default();
+            trade.status = TradeStatus::Open;
+            trade.sol_out += proceeds.saturating_sub(self.fee(proceeds));
+            trade.hold_ms = Some(self.current_ns.saturating_sub(entry_ns) / 1_000_000);
+            trade.pnl_lamports = trade.sol_out as i64 - trade.sol_in as i64;
+            self.trades.push(trade);
+        }
+
+        // 按平仓时间计算累计收益和最大回撤
+        let mut settled: Vec<&TradeRecord> = self.trades.iter().filter(|t| t.entry_slot.is_some()).collect();
+        settled.sort_by_key(|t| t.exit_time_ms.unwrap_or(u64::MAX));
+
+        let summary = &mut self.summary;
+        let (mut cumulative, mut peak, mut total_hold) = (0i64, 0i64, 0u64);
+        for trade in &settled {
+            cumulative += trade.pnl_lamports;
+            peak = peak.max(cumulative);
+            summary.max_drawdown_lamports = summary.max_drawdown_lamports.max(peak - cumulative);
+            if trade.pnl_lamports > 0 {
+                summary.wins += 1;
+            } else {
+                summary.losses += 1;
+            }
+            total_hold += trade.hold_ms.unwrap_or_default();
+        }
+        summary.total_pnl_lamports = cumulative;
+        if !settled.is_empty() {
+            summary.win_rate = summary.wins as f64 / settled.len() as f64;
+            summary.avg_hold_ms = total_hold as f64 / settled.len() as f64;
+        }
+
+        BacktestReport {
+            summary: self.summary,
+            trades: self.trades,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{CaptureRecord, CaptureWriter};
+    use crate::exit::ExitRules;
+    use crate::instruction::tests::{buy_instruction, create_instruction};
+    use crate::strategy::{Budget, PriceRangeStrategy};
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::signature::Signature;
+    use solana_sdk::transaction::Transaction;
+
+    const MS: u64 = 1_000_000;
+    const BUY_LAMPORTS: u64 = 100_000_000;
+
+    fn entry(payer: &Pubkey, instructions: &[Instruction]) -> Entry {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(payer));
+        // 回放时按签名去重，未签名的交易使用不同的占位签名
+        transaction.signatures.fill_with(Signature::new_unique);
+        Entry {
+            num_hashes: 1,
+            hash: Hash::new_unique(),
+            transactions: vec![transaction.into()],
+        }
+    }
+
+    fn tick() -> Entry {
+        Entry {
+            num_hashes: 1,
+            hash: Hash::new_unique(),
+            transactions: vec![],
+        }
+    }
+
+    // 开发者创建代币并在同一交易中买入，触发价格区间策略
+    fn launch(mint: &Pubkey, creator: &Pubkey) -> Entry {
+        entry(
+            creator,
+            &[create_instruction(mint, creator), buy_instruction(mint, creator, 50_000_000_000_000, 2_000_000_000)],
+        )
+    }
+
+    // 其他交易者大额买入，推高价格
+    fn whale_buy(mint: &Pubkey) -> Entry {
+        let whale = Pubkey::new_unique();
+        entry(&whale, &[buy_instruction(mint, &whale, 200_000_000_000_000, u64::MAX)])
+    }
+
+    fn backtester(latency: Latency, exit: ExitRules, budget: Budget) -> Backtester {
+        let mut strategies = StrategySet::new();
+        strategies.add(Box::new(PriceRangeStrategy::new("range", 0, u64::MAX, BUY_LAMPORTS)), budget, exit);
+        let config = BacktestConfig {
+            latency,
+            ..BacktestConfig::default()
+        };
+        Backtester::new(config, strategies)
+    }
+
+    #[tokio::test]
+    async fn replays_capture_and_closes_profitable_trade() {
+        let (mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let dir = std::env::temp_dir().join(format!("backtest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut writer = CaptureWriter::new(&dir, u64::MAX).unwrap();
+        let timeline = [
+            (10, 0, vec![launch(&mint, &creator)]),
+            (11, 400, vec![]),
+            (12, 800, vec![whale_buy(&mint)]),
+            // 持仓超过最长持仓时间，下一个slot卖出
+            (13, 1_500, vec![]),
+            (14, 1_900, vec![]),
+        ];
+        for (slot, ms, entries) in timeline {
+            let entries = if entries.is_empty() { vec![tick()] } else { entries };
+            writer
+                .write(&CaptureRecord {
+                    slot,
+                    received_at_ns: ms * MS,
+                    endpoint: 0,
+                    entries: bincode::serialize(&entries).unwrap(),
+                })
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        let exit = ExitRules {
+            max_hold_ms: Some(1_000),
+            ..ExitRules::default()
+        };
+        let mut strategies = StrategySet::new();
+        strategies.add(Box::new(PriceRangeStrategy::new("range", 0, u64::MAX, BUY_LAMPORTS)), Budget::unlimited(), exit);
+        let report = Backtester::run_capture(BacktestConfig::default(), strategies, LookupTableCache::offline(), &dir)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.trades.len(), 1);
+        let trade = &report.trades[0];
+        assert_eq!(trade.status, TradeStatus::Closed);
+        assert_eq!((trade.signal_slot, trade.entry_slot, trade.exit_slot), (10, Some(11), Some(14)));
+        assert_eq!(trade.hold_ms, Some(1_500));
+        assert!(trade.sol_in <= BUY_LAMPORTS + 5_000);
+        assert!(trade.sol_out > trade.sol_in);
+        assert_eq!(trade.pnl_lamports, trade.sol_out as i64 - trade.sol_in as i64);
+
+        let summary = &report.summary;
+        assert_eq!((summary.mints_created, summary.signals, summary.filled), (1, 1, 1));
+        assert_eq!((summary.wins, summary.losses), (1, 0));
+        assert_eq!(summary.total_pnl_lamports, trade.pnl_lamports);
+        assert_eq!(summary.max_drawdown_lamports, 0);
+    }
+
+    #[test]
+    fn price_move_before_fill_exceeds_slippage() {
+        let (mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let budget = Budget::new(BUY_LAMPORTS);
+        let mut backtester = backtester(Latency::Slots(2), ExitRules::default(), budget.clone());
+
+        backtester.process_entries(10, 0, &[launch(&mint, &creator)]);
+        assert_eq!(budget.used(), BUY_LAMPORTS);
+        backtester.process_entries(11, 400 * MS, &[whale_buy(&mint)]);
+        backtester.process_entries(12, 800 * MS, &[]);
+
+        // 成交失败时释放预算，不计入盈亏
+        assert_eq!(budget.used(), 0);
+        let report = backtester.finish();
+        assert_eq!(report.trades.len(), 1);
+        assert_eq!(report.trades[0].status, TradeStatus::SlippageExceeded);
+        assert_eq!(report.trades[0].entry_slot, None);
+        assert_eq!((report.summary.filled, report.summary.wins, report.summary.losses), (0, 0, 0));
+        assert_eq!(report.summary.total_pnl_lamports, 0);
+    }
+
+    #[test]
+    fn open_position_is_marked_at_last_curve_price() {
+        let (mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut backtester = backtester(Latency::Millis(100), ExitRules::default(), Budget::unlimited());
+
+        backtester.process_entries(10, 0, &[launch(&mint, &creator)]);
+        backtester.process_entries(10, 150 * MS, &[]);
+        let curve_at_entry = backtester.mints.get(&mint).unwrap().curve.clone();
+
+        let report = backtester.finish();
+        let trade = &report.trades[0];
+        assert_eq!(trade.status, TradeStatus::Open);
+        assert_eq!(trade.entry_time_ms, Some(150));
+        let value = curve_at_entry.sell_quote(trade.token_amount).unwrap();
+        assert_eq!(trade.sol_out, value - fee(value, 100));
+        // 买入和卖出的手续费使估值低于成本
+        assert!(trade.pnl_lamports < 0);
+        assert_eq!(report.summary.losses, 1);
+    }
+}
@@ -0,0 +1,365 @@
+use borsh::BorshDeserialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::error::Error;
+use std::fmt;
+
+use crate::transaction::{GLOBAL_ACCOUNT, PUMP_PROGRAM_ID};
+
+/// 基点分母
+pub const BASIS_POINTS: u64 = 10_000;
+/// 账户数据前的Anchor鉴别器长度
+const ACCOUNT_DISCRIMINATOR_SIZE: usize = 8;
+/// 内盘曲线PDA种子
+pub const BONDING_CURVE_SEED: &[u8] = b"bonding-curve";
+
+/// 通过RPC读取Global账户
+pub async fn fetch_global_account(rpc_url: &str) -> Result<GlobalAccount, Box<dyn Error + Send + Sync>> {
+    let rpc_client = RpcClient::new(rpc_url.to_string());
+    let data = rpc_client.get_account_data(&GLOBAL_ACCOUNT).await?;
+    Ok(GlobalAccount::from_account_data(&data)?)
+}
+
+/// 通过RPC读取代币的内盘曲线账户
+pub async fn fetch_bonding_curve(rpc_url: &str, mint: &Pubkey) -> Result<BondingCurve, Box<dyn Error + Send + Sync>> {
+    let rpc_client = RpcClient::new(rpc_url.to_string());
+    let data = rpc_client.get_account_data(&bonding_curve_pda(mint)).await?;
+    Ok(BondingCurve::from_account_data(&data)?)
+}
+
+/// 内盘曲线计算错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CurveError {
+    /// 曲线已完成，不再接受交易
+    Complete,
+    /// 请求的数量超出储备
+    InsufficientReserves,
+    /// 成交金额超出指令的SOL限制
+    SlippageExceeded,
+    /// 账户数据无法解析
+    InvalidAccountData(String),
+}
+
+impl fmt::Display for CurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveError::Complete => write!(f, "内盘曲线已完成"),
+            CurveError::InsufficientReserves => write!(f, "储备不足"),
+            CurveError::SlippageExceeded => write!(f, "超出滑点限制"),
+            CurveError::InvalidAccountData(msg) => write!(f, "账户数据无效: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CurveError {}
+
+/// 解析带Anchor鉴别器的账户数据，允许账户末尾存在新增字段
+fn deserialize_account<T: BorshDeserialize>(data: &[u8]) -> Result<T, CurveError> {
+    let mut body = data
+        .get(ACCOUNT_DISCRIMINATOR_SIZE..)
+        .ok_or_else(|| CurveError::InvalidAccountData("数据长度不足".to_string()))?;
+    T::deserialize(&mut body).map_err(|e| CurveError::InvalidAccountData(e.to_string()))
+}
+
+/// 按基点计算手续费，向上取整，与链上保持一致
+pub fn fee(amount: u64, fee_basis_points: u64) -> u64 {
+    (amount as u128 * fee_basis_points as u128).div_ceil(BASIS_POINTS as u128) as u64
+}
+
+/// 扣除滑点后的最少接收
+pub fn with_slippage_min(amount: u64, slippage_basis_points: u64) -> u64 {
+    let keep = BASIS_POINTS.saturating_sub(slippage_basis_points);
+    (amount as u128 * keep as u128 / BASIS_POINTS as u128) as u64
+}
+
+/// 计算代币的内盘曲线PDA
+pub fn bonding_curve_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[BONDING_CURVE_SEED, mint.as_ref()], &PUMP_PROGRAM_ID).0
+}
+
+/// Pump程序的Global账户，保存新曲线的初始参数和手续费
+#[derive(BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GlobalAccount {
+    pub initialized: bool,
+    pub authority: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+}
+
+impl Default for GlobalAccount {
+    /// 主网当前参数，无法读取Global账户时使用
+    fn default() -> Self {
+        Self {
+            initialized: true,
+            authority: Pubkey::default(),
+            fee_recipient: crate::transaction::FEE_RECIPIENT,
+            initial_virtual_token_reserves: 1_073_000_000_000_000,
+            initial_virtual_sol_reserves: 30_000_000_000,
+            initial_real_token_reserves: 793_100_000_000_000,
+            token_total_supply: 1_000_000_000_000_000,
+            fee_basis_points: 100,
+        }
+    }
+}
+
+impl GlobalAccount {
+    /// 从链上账户数据解析
+    pub fn from_account_data(data: &[u8]) -> Result<Self, CurveError> {
+        deserialize_account(data)
+    }
+
+    /// 新代币创建时的内盘曲线
+    pub fn new_curve(&self) -> BondingCurve {
+        BondingCurve {
+            virtual_token_reserves: self.initial_virtual_token_reserves,
+            virtual_sol_reserves: self.initial_virtual_sol_reserves,
+            real_token_reserves: self.initial_real_token_reserves,
+            real_sol_reserves: 0,
+            token_total_supply: self.token_total_supply,
+            complete: false,
+        }
+    }
+}
+
+/// 单个代币的内盘曲线账户
+///
+/// 价格由虚拟储备按常数乘积决定，全部使用u128整数计算，与链上结果逐lamport一致。
+#[derive(BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BondingCurve {
+    pub virtual_token_reserves: u64,
+    pub virtual_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub token_total_supply: u64,
+    pub complete: bool,
+}
+
+impl BondingCurve {
+    /// 从链上账户数据解析
+    pub fn from_account_data(data: &[u8]) -> Result<Self, CurveError> {
+        deserialize_account(data)
+    }
+
+    /// 买入指定数量代币需要的SOL（不含手续费）
+    ///
+    /// 请求数量超过可售代币时按可售代币计算。
+    pub fn buy_cost(&self, token_amount: u64) -> Result<u64, CurveError> {
+        if self.complete {
+            return Err(CurveError::Complete);
+        }
+        let token_amount = token_amount.min(self.real_token_reserves);
+        if token_amount == 0 {
+            return Ok(0);
+        }
+        if token_amount >= self.virtual_token_reserves {
+            return Err(CurveError::InsufficientReserves);
+        }
+        let numerator = token_amount as u128 * self.virtual_sol_reserves as u128;
+        let denominator = (self.virtual_token_reserves - token_amount) as u128;
+        Ok((numerator / denominator + 1) as u64)
+    }
+
+    /// 投入指定SOL（不含手续费）可买到的代币数量，不超过可售代币
+    pub fn buy_quote(&self, sol_amount: u64) -> Result<u64, CurveError> {
+        if self.complete {
+            return Err(CurveError::Complete);
+        }
+        if sol_amount == 0 {
+            return Ok(0);
+        }
+        let numerator = sol_amount as u128 * self.virtual_token_reserves as u128;
+        let denominator = self.virtual_sol_reserves as u128 + sol_amount as u128;
+        let tokens = (numerator / denominator) as u64;
+        Ok(tokens.min(self.real_token_reserves))
+    }
+
+    /// 在含手续费的SOL预算内可买到的代币数量
+    pub fn buy_quote_with_fee(&self, sol_budget: u64, fee_basis_points: u64) -> Result<u64, CurveError> {
+        let net = (sol_budget as u128 * BASIS_POINTS as u128 / (BASIS_POINTS + fee_basis_points) as u128) as u64;
+        let mut tokens = self.buy_quote(net)?;
+        // 手续费向上取整可能使总花费略超预算，逐步减少到预算以内
+        while tokens > 0 {
+            let cost = self.buy_cost(tokens)?;
+            if cost + fee(cost, fee_basis_points) <= sol_budget {
+                break;
+            }
+            tokens -= 1;
+        }
+        Ok(tokens)
+    }
+
+    /// 卖出指定数量代币得到的SOL（不含手续费）
+    pub fn sell_quote(&self, token_amount: u64) -> Result<u64, CurveError> {
+        if self.complete {
+            return Err(CurveError::Complete);
+        }
+        let numerator = token_amount as u128 * self.virtual_sol_reserves as u128;
+        let denominator = self.virtual_token_reserves as u128 + token_amount as u128;
+        let proceeds = (numerator / denominator) as u64;
+        if proceeds > self.real_sol_reserves {
+            return Err(CurveError::InsufficientReserves);
+        }
+        Ok(proceeds)
+    }
+
+    /// 卖出指定数量代币扣除手续费后得到的SOL
+    pub fn sell_quote_with_fee(&self, token_amount: u64, fee_basis_points: u64) -> Result<u64, CurveError> {
+        let proceeds = self.sell_quote(token_amount)?;
+        Ok(proceeds - fee(proceeds, fee_basis_points))
+    }
+
+    /// 应用一笔买入，返回实际成交的代币数量和花费的SOL（不含手续费）
+    pub fn apply_buy(&mut self, token_amount: u64) -> Result<(u64, u64), CurveError> {
+        let token_amount = token_amount.min(self.real_token_reserves);
+        let sol_cost = self.buy_cost(token_amount)?;
+        self.virtual_token_reserves -= token_amount;
+        self.real_token_reserves -= token_amount;
+        self.virtual_sol_reserves += sol_cost;
+        self.real_sol_reserves += sol_cost;
+        // 可售代币耗尽后曲线完成，等待迁移
+        if self.real_token_reserves == 0 {
+            self.complete = true;
+        }
+        Ok((token_amount, sol_cost))
+    }
+
+    /// 应用一笔卖出，返回得到的SOL（不含手续费）
+    pub fn apply_sell(&mut self, token_amount: u64) -> Result<u64, CurveError> {
+        let proceeds = self.sell_quote(token_amount)?;
+        self.virtual_token_reserves += token_amount;
+        self.real_token_reserves += token_amount;
+        self.virtual_sol_reserves -= proceeds;
+        self.real_sol_reserves -= proceeds;
+        Ok(proceeds)
+    }
+
+    /// 当前价格 (SOL/代币)，仅用于显示
+    pub fn price_sol(&self) -> f64 {
+        if self.virtual_token_reserves == 0 {
+            return 0.0;
+        }
+        let virtual_sol = self.virtual_sol_reserves as f64 / 1_000_000_000.0;
+        let virtual_token = self.virtual_token_reserves as f64 / 1_000_000.0;
+        virtual_sol / virtual_token
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 简单的线性同余生成器，使性质测试可重复
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            self.0 >> 11
+        }
+
+        fn below(&mut self, max: u64) -> u64 {
+            self.next() % max.max(1)
+        }
+    }
+
+    fn fresh_curve() -> BondingCurve {
+        GlobalAccount::default().new_curve()
+    }
+
+    /// 随机交易若干笔后的曲线
+    fn random_curve(rng: &mut Lcg) -> BondingCurve {
+        let mut curve = fresh_curve();
+        for _ in 0..rng.below(20) {
+            let tokens = rng.below(curve.real_token_reserves / 4);
+            curve.apply_buy(tokens).unwrap();
+        }
+        curve
+    }
+
+    #[test]
+    fn matches_known_fresh_curve_trades() {
+        // 新曲线上1 SOL（不含手续费）买到约3461万个代币，与链上首笔买入一致
+        let curve = fresh_curve();
+        assert_eq!(curve.buy_quote(1_000_000_000).unwrap(), 34_612_903_225_806);
+        assert_eq!(curve.buy_cost(34_612_903_225_806).unwrap(), 1_000_000_000);
+        assert_eq!(curve.buy_cost(1_000_000_000_000).unwrap(), 27_985_075);
+        assert_eq!(fee(1_000_000_000, 100), 10_000_000);
+        assert_eq!(fee(1, 100), 1);
+
+        // 买入后立即卖出，由于取整少得1 lamport
+        let mut curve = fresh_curve();
+        let (tokens, cost) = curve.apply_buy(34_612_903_225_806).unwrap();
+        assert_eq!(cost, 1_000_000_000);
+        assert_eq!(curve.sell_quote(tokens).unwrap(), 999_999_999);
+    }
+
+    #[test]
+    fn buy_quote_never_exceeds_budget() {
+        let mut rng = Lcg(1);
+        for _ in 0..500 {
+            let curve = random_curve(&mut rng);
+            let budget = rng.below(50_000_000_000);
+            let tokens = curve.buy_quote_with_fee(budget, 100).unwrap();
+            let cost = curve.buy_cost(tokens).unwrap();
+            assert!(cost + fee(cost, 100) <= budget, "{:?} {} {}", curve, budget, tokens);
+            // 多买1个代币就会超出预算（可售代币耗尽时除外）
+            if tokens < curve.real_token_reserves {
+                let more = curve.buy_cost(tokens + 1).unwrap();
+                assert!(more + fee(more, 100) > budget);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_never_profits() {
+        let mut rng = Lcg(2);
+        for _ in 0..500 {
+            let mut curve = random_curve(&mut rng);
+            let k_before = curve.virtual_sol_reserves as u128 * curve.virtual_token_reserves as u128;
+            let (tokens, cost) = curve.apply_buy(rng.below(curve.real_token_reserves)).unwrap();
+            let k_after_buy = curve.virtual_sol_reserves as u128 * curve.virtual_token_reserves as u128;
+            let proceeds = curve.apply_sell(tokens).unwrap();
+            let k_after_sell = curve.virtual_sol_reserves as u128 * curve.virtual_token_reserves as u128;
+
+            assert!(proceeds <= cost);
+            assert!(k_after_buy >= k_before);
+            assert!(k_after_sell >= k_after_buy);
+        }
+    }
+
+    #[test]
+    fn exhausting_real_reserves_completes_curve() {
+        let mut curve = fresh_curve();
+        let (tokens, _) = curve.apply_buy(u64::MAX).unwrap();
+        assert_eq!(tokens, GlobalAccount::default().initial_real_token_reserves);
+        assert!(curve.complete);
+        assert!(matches!(curve.buy_quote(1), Err(CurveError::Complete)));
+        assert_eq!(curve.real_token_reserves, 0);
+    }
+
+    #[test]
+    fn slippage_bounds() {
+        assert_eq!(with_slippage_min(1_000_000, 1500), 850_000);
+        assert_eq!(with_slippage_min(1_000_000, 20_000), 0);
+    }
+
+    #[test]
+    fn parses_account_with_discriminator_and_trailing_fields() {
+        let curve = fresh_curve();
+        let mut data = vec![0u8; ACCOUNT_DISCRIMINATOR_SIZE];
+        data.extend_from_slice(&curve.virtual_token_reserves.to_le_bytes());
+        data.extend_from_slice(&curve.virtual_sol_reserves.to_le_bytes());
+        data.extend_from_slice(&curve.real_token_reserves.to_le_bytes());
+        data.extend_from_slice(&curve.real_sol_reserves.to_le_bytes());
+        data.extend_from_slice(&curve.token_total_supply.to_le_bytes());
+        data.push(curve.complete as u8);
+        data.extend_from_slice(&[7u8; 32]);
+        assert_eq!(BondingCurve::from_account_data(&data).unwrap(), curve);
+        assert!(BondingCurve::from_account_data(&data[..4]).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use jito_protos::bundle::{
+    bundle_result, rejected, Accepted, Bundle, BundleResult, Dropped, DroppedReason, Finalized,
+    InternalError, Processed, Rejected,
+};
+use jito_protos::searcher::{
+    searcher_service_server::{SearcherService, SearcherServiceServer},
+    ConnectedLeadersRegionedRequest, ConnectedLeadersRegionedResponse, ConnectedLeadersRequest,
+    ConnectedLeadersResponse, GetRegionsRequest, GetRegionsResponse, GetTipAccountsRequest,
+    GetTipAccountsResponse, NextScheduledLeaderRequest, NextScheduledLeaderResponse,
+    SendBundleRequest, SendBundleResponse, SubscribeBundleResultsRequest,
+};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{transport::Server, Request, Response, Status};
+
+/// 捆绑结果订阅的推送端
+type ResultSender = mpsc::Sender<Result<BundleResult, Status>>;
+
+/// 模拟区块引擎对每个捆绑返回的结果
+#[derive(Clone, Copy, Debug)]
+pub enum MockBundleBehavior {
+    /// 依次推送accepted、processed、finalized
+    Accept,
+    /// 直接推送rejected
+    Reject,
+    /// 先推送accepted，再推送dropped
+    Drop,
+    /// 以ResourceExhausted拒绝发送捆绑，模拟限流
+    RateLimit,
+}
+
+/// 本地模拟的SearcherService，用于在不连接真实区块引擎的情况下验证捆绑发送流程
+#[derive(Clone)]
+pub struct MockSearcherService {
+    tip_accounts: Vec<String>,
+    behavior: MockBundleBehavior,
+    slot: u64,
+    next_id: Arc<AtomicU64>,
+    received: Arc<StdMutex<Vec<Bundle>>>,
+    subscribers: Arc<StdMutex<Vec<ResultSender>>>,
+}
+
+impl MockSearcherService {
+    /// 创建模拟服务
+    ///
+    /// # 参数
+    ///
+    /// * `tip_accounts` - GetTipAccounts返回的小费账户
+    /// * `behavior` - 对收到的捆绑返回的结果类型
+    pub fn new(tip_accounts: Vec<String>, behavior: MockBundleBehavior) -> Self {
+        Self {
+            tip_accounts,
+            behavior,
+            slot: 1,
+            next_id: Arc::new(AtomicU64::new(0)),
+            received: Arc::new(StdMutex::new(Vec::new())),
+            subscribers: Arc::new(StdMutex::new(Vec::new())),
+        }
+    }
+
+    /// 已收到的捆绑
+    pub fn received_bundles(&self) -> Vec<Bundle> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// 结束所有捆绑结果订阅，模拟区块引擎断开连接
+    pub fn close_subscriptions(&self) {
+        self.subscribers.lock().unwrap().clear();
+    }
+
+    /// 当前的捆绑结果订阅数
+    pub fn subscriptions(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    fn results_for(&self, bundle_id: &str) -> Vec<BundleResult> {
+        let accepted = bundle_result::Result::Accepted(Accepted {
+            slot: self.slot,
+            validator_identity: "mock-validator".to_string(),
+        });
+        let results = match self.behavior {
+            MockBundleBehavior::Accept => vec![
+                accepted,
+                bundle_result::Result::Processed(Processed {
+                    validator_identity: "mock-validator".to_string(),
+                    slot: self.slot,
+                    bundle_index: 0,
+                }),
+                bundle_result::Result::Finalized(Finalized {}),
+            ],
+            MockBundleBehavior::Reject => vec![bundle_result::Result::Rejected(Rejected {
+                reason: Some(rejected::Reason::InternalError(InternalError {
+                    msg: "mock rejection".to_string(),
+                })),
+            })],
+            MockBundleBehavior::Drop => vec![
+                accepted,
+                bundle_result::Result::Dropped(Dropped {
+                    reason: DroppedReason::BlockhashExpired as i32,
+                }),
+            ],
+            MockBundleBehavior::RateLimit => vec![],
+        };
+        results
+            .into_iter()
+            .map(|result| BundleResult {
+                bundle_id: bundle_id.to_string(),
+                result: Some(result),
+            })
+            .collect()
+    }
+
+    /// 在本地随机端口启动服务，返回可直接用于`BundleSender::connect`的地址
+    pub async fn serve(self) -> std::io::Result<(String, JoinHandle<()>)> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let addr = listener.local_addr()?;
+        let handle = tokio::spawn(async move {
+            if let Err(e) = Server::builder()
+                .add_service(SearcherServiceServer::new(self))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+            {
+                println!("模拟SearcherService退出: {:?}", e);
+            }
+        });
+        Ok((format!("http://{}", addr), handle))
+    }
+}
+
+#[tonic::async_trait]
+impl SearcherService for MockSearcherService {
+    type SubscribeBundleResultsStream = ReceiverStream<Result<BundleResult, Status>>;
+
+    async fn subscribe_bundle_results(
+        &self,
+        _request: Request<SubscribeBundleResultsRequest>,
+    ) -> Result<Response<Self::SubscribeBundleResultsStream>, Status> {
+        let (tx, rx) = mpsc::channel(64);
+        self.subscribers.lock().unwrap().push(tx);
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn send_bundle(
+        &self,
+        request: Request<SendBundleRequest>,
+    ) -> Result<Response<SendBundleResponse>, Status> {
+        if let MockBundleBehavior::RateLimit = self.behavior {
+            return Err(Status::resource_exhausted("mock rate limit"));
+        }
+        let bundle = request
+            .into_inner()
+            .bundle
+            .ok_or_else(|| Status::invalid_argument("missing bundle"))?;
+        self.received.lock().unwrap().push(bundle);
+
+        let uuid = format!("mock-bundle-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let results = self.results_for(&uuid);
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        for result in results {
+            for subscriber in &subscribers {
+                let _ = subscriber.send(Ok(result.clone())).await;
+            }
+        }
+
+        Ok(Response::new(SendBundleResponse { uuid }))
+    }
+
+    async fn get_next_scheduled_leader(
+        &self,
+        _request: Request<NextScheduledLeaderRequest>,
+    ) -> Result<Response<NextScheduledLeaderResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_connected_leaders(
+        &self,
+        _request: Request<ConnectedLeadersRequest>,
+    ) -> Result<Response<ConnectedLeadersResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_connected_leaders_regioned(
+        &self,
+        _request: Request<ConnectedLeadersRegionedRequest>,
+    ) -> Result<Response<ConnectedLeadersRegionedResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+
+    async fn get_tip_accounts(
+        &self,
+        _request: Request<GetTipAccountsRequest>,
+    ) -> Result<Response<GetTipAccountsResponse>, Status> {
+        Ok(Response::new(GetTipAccountsResponse {
+            accounts: self.tip_accounts.clone(),
+        }))
+    }
+
+    async fn get_regions(
+        &self,
+        _request: Request<GetRegionsRequest>,
+    ) -> Result<Response<GetRegionsResponse>, Status> {
+        Err(Status::unimplemented("mock"))
+    }
+}
@@ -0,0 +1,516 @@
+use jito_protos::bundle::{bundle_result, rejected, Bundle, BundleResult};
+use jito_protos::packet::{Meta, Packet};
+use jito_protos::searcher::{
+    searcher_service_client::SearcherServiceClient, GetTipAccountsRequest, SendBundleRequest,
+    SubscribeBundleResultsRequest,
+};
+use solana_sdk::{
+    instruction::Instruction, pubkey::Pubkey, system_instruction,
+    transaction::VersionedTransaction,
+};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Notify};
+use tonic::transport::Channel;
+use tonic::{Code, Status, Streaming};
+
+#[cfg(test)]
+mod mock;
+
+/// 保留的捆绑结果数量上限
+const MAX_TRACKED_RESULTS: usize = 1024;
+
+/// 捆绑结果订阅断开后重新订阅的初始间隔和最大间隔
+const RESUBSCRIBE_DELAY_MIN: Duration = Duration::from_millis(100);
+const RESUBSCRIBE_DELAY_MAX: Duration = Duration::from_secs(5);
+
+/// 与区块引擎通信失败的原因
+#[derive(Clone, Debug, PartialEq)]
+pub enum BundleError {
+    /// 连接区块引擎失败
+    Connect(String),
+    /// 区块引擎拒绝了请求，如限流或捆绑无效
+    Rejected { code: Code, message: String },
+    /// 区块引擎没有返回可用的小费账户
+    TipAccounts(String),
+    /// 交易序列化失败
+    Serialize(String),
+    /// 等待结果期间捆绑结果订阅断开，断开期间的结果不会再收到
+    Disconnected,
+}
+
+impl BundleError {
+    /// 是否为临时故障，重试可能成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BundleError::Connect(_) => true,
+            BundleError::Rejected { code, .. } => matches!(
+                code,
+                Code::Unavailable | Code::ResourceExhausted | Code::DeadlineExceeded | Code::Aborted
+            ),
+            BundleError::TipAccounts(_) | BundleError::Serialize(_) | BundleError::Disconnected => false,
+        }
+    }
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Connect(e) => write!(f, "连接区块引擎失败: {}", e),
+            BundleError::Rejected { code, message } => write!(f, "区块引擎拒绝请求 ({:?}): {}", code, message),
+            BundleError::TipAccounts(e) => write!(f, "小费账户无效: {}", e),
+            BundleError::Serialize(e) => write!(f, "交易序列化失败: {}", e),
+            BundleError::Disconnected => write!(f, "捆绑结果订阅已断开"),
+        }
+    }
+}
+
+impl Error for BundleError {}
+
+impl From<Status> for BundleError {
+    fn from(status: Status) -> Self {
+        BundleError::Rejected {
+            code: status.code(),
+            message: status.message().to_string(),
+        }
+    }
+}
+
+impl From<tonic::transport::Error> for BundleError {
+    fn from(e: tonic::transport::Error) -> Self {
+        BundleError::Connect(e.to_string())
+    }
+}
+
+/// 捆绑交易在区块引擎中的处理结果
+#[derive(Clone, Debug, PartialEq)]
+pub enum BundleOutcome {
+    /// 被区块引擎接受并转发给验证者
+    Accepted { slot: u64, validator_identity: String },
+    /// 已被处理（processed确认级别）
+    Processed { slot: u64, validator_identity: String },
+    /// 已达到finalized确认级别
+    Finalized,
+    /// 被区块引擎拒绝
+    Rejected(String),
+    /// 已转发但未能上链
+    Dropped(String),
+}
+
+impl BundleOutcome {
+    /// 是否为最终结果，之后不会再有该捆绑的事件
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            BundleOutcome::Finalized | BundleOutcome::Rejected(_) | BundleOutcome::Dropped(_)
+        )
+    }
+
+    fn from_result(result: bundle_result::Result) -> Self {
+        match result {
+            bundle_result::Result::Accepted(accepted) => BundleOutcome::Accepted {
+                slot: accepted.slot,
+                validator_identity: accepted.validator_identity,
+            },
+            bundle_result::Result::Processed(processed) => BundleOutcome::Processed {
+                slot: processed.slot,
+                validator_identity: processed.validator_identity,
+            },
+            bundle_result::Result::Finalized(_) => BundleOutcome::Finalized,
+            bundle_result::Result::Rejected(rejected) => {
+                let reason = match rejected.reason {
+                    Some(rejected::Reason::StateAuctionBidRejected(r)) => format!(
+                        "竞价未进入状态拍卖赢家 (出价: {} lamports) {}",
+                        r.simulated_bid_lamports,
+                        r.msg.unwrap_or_default()
+                    ),
+                    Some(rejected::Reason::WinningBatchBidRejected(r)) => format!(
+                        "竞价低于其他拍卖赢家 (出价: {} lamports) {}",
+                        r.simulated_bid_lamports,
+                        r.msg.unwrap_or_default()
+                    ),
+                    Some(rejected::Reason::SimulationFailure(r)) => format!(
+                        "模拟失败 (交易: {}) {}",
+                        r.tx_signature,
+                        r.msg.unwrap_or_default()
+                    ),
+                    Some(rejected::Reason::InternalError(r)) => format!("内部错误: {}", r.msg),
+                    Some(rejected::Reason::DroppedBundle(r)) => format!("捆绑被丢弃: {}", r.msg),
+                    None => "未知原因".to_string(),
+                };
+                BundleOutcome::Rejected(reason)
+            }
+            bundle_result::Result::Dropped(dropped) => {
+                BundleOutcome::Dropped(format!("{:?}", dropped.reason()))
+            }
+        }
+    }
+}
+
+/// 已收到的捆绑结果，按到达顺序保留有限数量
+struct ResultBook {
+    outcomes: HashMap<String, BundleOutcome>,
+    order: VecDeque<String>,
+}
+
+impl ResultBook {
+    fn insert(&mut self, bundle_id: String, outcome: BundleOutcome) {
+        // 保留每个捆绑的最新结果，已是最终结果的不再被覆盖
+        match self.outcomes.get_mut(&bundle_id) {
+            Some(existing) => {
+                if !existing.is_terminal() {
+                    *existing = outcome;
+                }
+                return;
+            }
+            None => {
+                self.order.push_back(bundle_id.clone());
+                self.outcomes.insert(bundle_id, outcome);
+            }
+        }
+        while self.order.len() > MAX_TRACKED_RESULTS {
+            if let Some(old) = self.order.pop_front() {
+                self.outcomes.remove(&old);
+            }
+        }
+    }
+}
+
+/// Jito捆绑交易发送器
+///
+/// 通过SearcherService获取小费账户、发送捆绑并订阅捆绑结果。
+pub struct BundleSender {
+    client: SearcherServiceClient<Channel>,
+    tip_accounts: Mutex<Vec<Pubkey>>,
+    results: Arc<StdMutex<ResultBook>>,
+    notify: Arc<Notify>,
+    // 捆绑结果订阅断开的次数，等待中的调用据此得知结果可能已丢失
+    disconnects: Arc<AtomicU64>,
+}
+
+impl BundleSender {
+    /// 连接区块引擎并启动捆绑结果订阅
+    ///
+    /// # 参数
+    ///
+    /// * `block_engine_url` - 区块引擎gRPC地址
+    pub async fn connect(block_engine_url: &str) -> Result<Arc<Self>, BundleError> {
+        let client = SearcherServiceClient::connect(block_engine_url.to_string()).await?;
+        let sender = Arc::new(Self {
+            client,
+            tip_accounts: Mutex::new(Vec::new()),
+            results: Arc::new(StdMutex::new(ResultBook {
+                outcomes: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+            notify: Arc::new(Notify::new()),
+            disconnects: Arc::new(AtomicU64::new(0)),
+        });
+        sender.subscribe_bundle_results().await?;
+        Ok(sender)
+    }
+
+    async fn open_result_stream(client: &SearcherServiceClient<Channel>) -> Result<Streaming<BundleResult>, Status> {
+        Ok(client
+            .clone()
+            .subscribe_bundle_results(SubscribeBundleResultsRequest {})
+            .await?
+            .into_inner())
+    }
+
+    /// 订阅捆绑结果，在后台任务中记录每个捆绑的处理结果
+    ///
+    /// 订阅断开时通知所有等待中的调用，并按退避间隔重新订阅。
+    async fn subscribe_bundle_results(&self) -> Result<(), BundleError> {
+        let mut stream = Self::open_result_stream(&self.client).await?;
+        let client = self.client.clone();
+        let results = self.results.clone();
+        let notify = self.notify.clone();
+        let disconnects = self.disconnects.clone();
+
+        tokio::spawn(async move {
+            loop {
+                loop {
+                    match stream.message().await {
+                        Ok(Some(BundleResult { bundle_id, result: Some(result) })) => {
+                            let outcome = BundleOutcome::from_result(result);
+                            println!("捆绑 {} 结果: {:?}", bundle_id, outcome);
+                            results.lock().unwrap().insert(bundle_id, outcome);
+                            notify.notify_waiters();
+                        }
+                        Ok(Some(_)) => {}
+                        Ok(None) => {
+                            println!("捆绑结果订阅已结束");
+                            break;
+                        }
+                        Err(e) => {
+                            println!("捆绑结果订阅失败: {:?}", e);
+                            break;
+                        }
+                    }
+                }
+                disconnects.fetch_add(1, Ordering::Relaxed);
+                notify.notify_waiters();
+
+                let mut delay = RESUBSCRIBE_DELAY_MIN;
+                stream = loop {
+                    tokio::time::sleep(delay).await;
+                    match Self::open_result_stream(&client).await {
+                        Ok(stream) => {
+                            println!("已重新订阅捆绑结果");
+                            break stream;
+                        }
+                        Err(e) => {
+                            println!("重新订阅捆绑结果失败: {:?}，{}毫秒后重试", e, delay.as_millis());
+                            delay = (delay * 2).min(RESUBSCRIBE_DELAY_MAX);
+                        }
+                    }
+                };
+            }
+        });
+        Ok(())
+    }
+
+    /// 获取小费账户列表，首次调用后缓存
+    pub async fn get_tip_accounts(&self) -> Result<Vec<Pubkey>, BundleError> {
+        let mut cached = self.tip_accounts.lock().await;
+        if !cached.is_empty() {
+            return Ok(cached.clone());
+        }
+
+        let response = self
+            .client
+            .clone()
+            .get_tip_accounts(GetTipAccountsRequest {})
+            .await?
+            .into_inner();
+        let accounts = response
+            .accounts
+            .iter()
+            .map(|account| Pubkey::from_str(account).map_err(|e| BundleError::TipAccounts(format!("{}: {}", account, e))))
+            .collect::<Result<Vec<_>, _>>()?;
+        if accounts.is_empty() {
+            return Err(BundleError::TipAccounts("区块引擎未返回小费账户".to_string()));
+        }
+
+        *cached = accounts.clone();
+        Ok(accounts)
+    }
+
+    /// 构造小费转账指令，随机选择一个小费账户以减少写锁竞争
+    ///
+    /// # 参数
+    ///
+    /// * `payer` - 支付小费的账户
+    /// * `tip_lamports` - 小费金额(lamports)
+    pub async fn tip_instruction(&self, payer: &Pubkey, tip_lamports: u64) -> Result<Instruction, BundleError> {
+        let accounts = self.get_tip_accounts().await?;
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as usize)
+            .unwrap_or(0);
+        let tip_account = accounts[seed % accounts.len()];
+        Ok(system_instruction::transfer(payer, &tip_account, tip_lamports))
+    }
+
+    /// 发送捆绑交易，返回区块引擎分配的捆绑UUID
+    ///
+    /// # 参数
+    ///
+    /// * `transactions` - 已签名的交易列表，需自行包含小费转账
+    pub async fn send_bundle(&self, transactions: &[VersionedTransaction]) -> Result<String, BundleError> {
+        let packets = transactions
+            .iter()
+            .map(|tx| {
+                let data = bincode::serialize(tx).map_err(|e| BundleError::Serialize(e.to_string()))?;
+                Ok(Packet {
+                    meta: Some(Meta {
+                        size: data.len() as u64,
+                        ..Default::default()
+                    }),
+                    data,
+                })
+            })
+            .collect::<Result<Vec<_>, BundleError>>()?;
+
+        let response = self
+            .client
+            .clone()
+            .send_bundle(SendBundleRequest {
+                bundle: Some(Bundle { header: None, packets }),
+            })
+            .await?
+            .into_inner();
+
+        Ok(response.uuid)
+    }
+
+    /// 等待指定捆绑的最终结果（finalized/拒绝/丢弃）
+    ///
+    /// 超时前未收到最终结果时返回最近一次的中间结果，从未收到结果则返回`None`。
+    /// 等待期间捆绑结果订阅断开时返回错误，断开期间的结果不会再收到。
+    ///
+    /// # 参数
+    ///
+    /// * `bundle_id` - 捆绑UUID
+    /// * `timeout` - 最长等待时间
+    pub async fn wait_for_result(
+        &self,
+        bundle_id: &str,
+        timeout: Duration,
+    ) -> Result<Option<BundleOutcome>, BundleError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let disconnects = self.disconnects.load(Ordering::Relaxed);
+        loop {
+            // 先注册通知再检查结果，避免错过在两者之间到达的结果
+            let notified = self.notify.notified();
+            let latest = self.results.lock().unwrap().outcomes.get(bundle_id).cloned();
+            if latest.as_ref().is_some_and(BundleOutcome::is_terminal) {
+                return Ok(latest);
+            }
+            if self.disconnects.load(Ordering::Relaxed) != disconnects {
+                return Err(BundleError::Disconnected);
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Ok(self.results.lock().unwrap().outcomes.get(bundle_id).cloned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockBundleBehavior, MockSearcherService};
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::transaction::Transaction;
+
+    const TIP_ACCOUNT: &str = "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5";
+
+    fn transfer_tx(payer: &Keypair) -> VersionedTransaction {
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], Hash::default()).into()
+    }
+
+    async fn connect(behavior: MockBundleBehavior) -> (MockSearcherService, Arc<BundleSender>) {
+        let mock = MockSearcherService::new(vec![TIP_ACCOUNT.to_string()], behavior);
+        let (url, _handle) = mock.clone().serve().await.unwrap();
+        let sender = BundleSender::connect(&url).await.unwrap();
+        (mock, sender)
+    }
+
+    #[test]
+    fn result_book_keeps_latest_until_terminal() {
+        let mut book = ResultBook { outcomes: HashMap::new(), order: VecDeque::new() };
+        let accepted = BundleOutcome::Accepted { slot: 1, validator_identity: "v".to_string() };
+        book.insert("b".to_string(), accepted);
+        book.insert("b".to_string(), BundleOutcome::Dropped("expired".to_string()));
+        book.insert("b".to_string(), BundleOutcome::Finalized);
+        assert_eq!(book.outcomes["b"], BundleOutcome::Dropped("expired".to_string()));
+        assert_eq!(book.order.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_bundle_with_tip_instruction() {
+        let (mock, sender) = connect(MockBundleBehavior::Accept).await;
+        let payer = Keypair::new();
+
+        let tip = sender.tip_instruction(&payer.pubkey(), 10_000).await.unwrap();
+        assert_eq!(tip.accounts[1].pubkey, Pubkey::from_str(TIP_ACCOUNT).unwrap());
+
+        let id = sender.send_bundle(&[transfer_tx(&payer), transfer_tx(&payer)]).await.unwrap();
+        assert_eq!(id, "mock-bundle-0");
+        let received = mock.received_bundles();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].packets.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn accepted_bundle_waits_for_finalized() {
+        let (_mock, sender) = connect(MockBundleBehavior::Accept).await;
+        let id = sender.send_bundle(&[transfer_tx(&Keypair::new())]).await.unwrap();
+        let outcome = sender.wait_for_result(&id, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(outcome, Some(BundleOutcome::Finalized));
+    }
+
+    #[tokio::test]
+    async fn dropped_after_accepted_reports_dropped() {
+        let (_mock, sender) = connect(MockBundleBehavior::Drop).await;
+        let id = sender.send_bundle(&[transfer_tx(&Keypair::new())]).await.unwrap();
+        let outcome = sender.wait_for_result(&id, Duration::from_secs(5)).await.unwrap();
+        assert!(matches!(outcome, Some(BundleOutcome::Dropped(_))), "{:?}", outcome);
+    }
+
+    #[tokio::test]
+    async fn rejected_bundle_reports_reason() {
+        let (_mock, sender) = connect(MockBundleBehavior::Reject).await;
+        let id = sender.send_bundle(&[transfer_tx(&Keypair::new())]).await.unwrap();
+        match sender.wait_for_result(&id, Duration::from_secs(5)).await.unwrap() {
+            Some(BundleOutcome::Rejected(reason)) => assert!(reason.contains("mock rejection")),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_bundle_times_out() {
+        let (_mock, sender) = connect(MockBundleBehavior::Accept).await;
+        assert_eq!(sender.wait_for_result("missing", Duration::from_millis(50)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn resubscribes_and_fails_waiters_when_stream_drops() {
+        let (mock, sender) = connect(MockBundleBehavior::Accept).await;
+        let waiter = {
+            let sender = Arc::clone(&sender);
+            tokio::spawn(async move { sender.wait_for_result("pending", Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        mock.close_subscriptions();
+        let result = tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(result, Err(BundleError::Disconnected));
+
+        // 重新订阅后的捆绑结果照常收到
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while mock.subscriptions() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let id = sender.send_bundle(&[transfer_tx(&Keypair::new())]).await.unwrap();
+        let outcome = sender.wait_for_result(&id, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(outcome, Some(BundleOutcome::Finalized));
+    }
+
+    #[tokio::test]
+    async fn invalid_tip_accounts_are_typed_errors() {
+        for accounts in [vec![], vec!["not-a-pubkey".to_string()]] {
+            let mock = MockSearcherService::new(accounts, MockBundleBehavior::Accept);
+            let (url, _handle) = mock.serve().await.unwrap();
+            let sender = BundleSender::connect(&url).await.unwrap();
+            let result = sender.tip_instruction(&Pubkey::new_unique(), 10_000).await;
+            assert!(matches!(result, Err(BundleError::TipAccounts(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn refused_bundle_keeps_status_code() {
+        let (_mock, sender) = connect(MockBundleBehavior::RateLimit).await;
+        let error = sender.send_bundle(&[transfer_tx(&Keypair::new())]).await.unwrap_err();
+        assert!(matches!(error, BundleError::Rejected { code: Code::ResourceExhausted, .. }));
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn unreachable_block_engine_is_a_connect_error() {
+        let result = BundleSender::connect("http://127.0.0.1:1").await;
+        assert!(matches!(result, Err(BundleError::Connect(_))));
+    }
+}
@@ -0,0 +1,405 @@
+use crate::client::merge::{EntryDeduplicator, RawArrival};
+use crate::utils::deserialize_entries;
+use jito_protos::shredstream::Entry as ProtoEntry;
+use solana_entry::entry::Entry;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tokio::time::sleep_until;
+
+/// 捕获文件头部的魔数和版本
+const CAPTURE_MAGIC: &[u8; 6] = b"JSSCAP";
+const CAPTURE_VERSION: u16 = 1;
+/// 捕获文件扩展名
+pub const CAPTURE_EXTENSION: &str = "jsscap";
+/// 记录头部大小：slot(8) + 接收时间(8) + 端点(2)
+const RECORD_HEADER_SIZE: usize = 18;
+/// 单条记录的长度上限，防止损坏的长度前缀导致超大内存分配
+const MAX_RECORD_SIZE: usize = 32 * 1024 * 1024;
+/// 运行时定时刷新捕获文件的间隔，避免进程崩溃时丢失缓冲区中的记录
+pub const CAPTURE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 一条捕获记录，对应一条收到的proto Entry消息
+#[derive(Clone, Debug)]
+pub struct CaptureRecord {
+    pub slot: u64,
+    // 接收时间，自UNIX纪元起的纳秒数
+    pub received_at_ns: u64,
+    // 消息来源端点的序号
+    pub endpoint: u16,
+    // 原始的Vec<Entry>序列化字节
+    pub entries: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// 从原始到达消息构建记录
+    pub fn from_arrival(arrival: &RawArrival) -> Self {
+        let received_at_ns = arrival
+            .received_at_utc
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            slot: arrival.entry.slot,
+            received_at_ns,
+            endpoint: arrival.endpoint as u16,
+            entries: arrival.entry.entries.clone(),
+        }
+    }
+
+    /// 还原为到达消息，接收时间使用记录中的时间
+    pub fn into_arrival(self) -> RawArrival {
+        RawArrival {
+            endpoint: self.endpoint as usize,
+            received_at: Instant::now(),
+            received_at_utc: UNIX_EPOCH + Duration::from_nanos(self.received_at_ns),
+            entry: ProtoEntry {
+                slot: self.slot,
+                entries: self.entries,
+            },
+        }
+    }
+}
+
+/// 写入文件头部
+fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(CAPTURE_MAGIC)?;
+    writer.write_all(&CAPTURE_VERSION.to_le_bytes())
+}
+
+/// 按大小轮转的捕获文件写入器
+///
+/// 每条记录格式为 [u32小端记录长度][u64 slot][u64 接收纳秒][u16 端点][entries字节]，
+/// 当前文件超过`max_file_bytes`后在同一目录下新建文件。
+pub struct CaptureWriter {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    writer: Option<BufWriter<File>>,
+    file_bytes: u64,
+    file_seq: u32,
+    records: u64,
+}
+
+impl CaptureWriter {
+    /// 创建写入器
+    ///
+    /// # 参数
+    ///
+    /// * `dir` - 捕获文件目录，不存在时自动创建
+    /// * `max_file_bytes` - 单个文件的最大字节数
+    pub fn new(dir: impl AsRef<Path>, max_file_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_file_bytes,
+            writer: None,
+            file_bytes: 0,
+            file_seq: 0,
+            records: 0,
+        })
+    }
+
+    /// 新建下一个捕获文件
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        self.file_seq += 1;
+        let name = format!(
+            "capture-{}-{:04}.{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            self.file_seq,
+            CAPTURE_EXTENSION
+        );
+        let path = self.dir.join(name);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        write_header(&mut writer)?;
+        println!("写入捕获文件: {}", path.display());
+        self.writer = Some(writer);
+        self.file_bytes = (CAPTURE_MAGIC.len() + 2) as u64;
+        Ok(())
+    }
+
+    /// 写入一条记录
+    pub fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        if self.writer.is_none() || self.file_bytes >= self.max_file_bytes {
+            self.rotate()?;
+        }
+        let len = RECORD_HEADER_SIZE + record.entries.len();
+        if len > MAX_RECORD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("捕获记录过大: {} 字节", len),
+            ));
+        }
+        let len = len as u32;
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&record.slot.to_le_bytes())?;
+        writer.write_all(&record.received_at_ns.to_le_bytes())?;
+        writer.write_all(&record.endpoint.to_le_bytes())?;
+        writer.write_all(&record.entries)?;
+        self.file_bytes += 4 + len as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// 已写入的记录数
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for CaptureWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// 顺序读取单个捕获文件的记录
+pub struct CaptureReader {
+    reader: BufReader<File>,
+}
+
+impl CaptureReader {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..6] != CAPTURE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "不是有效的捕获文件"));
+        }
+        let version = u16::from_le_bytes([header[6], header[7]]);
+        if version != CAPTURE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("不支持的捕获文件版本: {}", version),
+            ));
+        }
+        Ok(Self { reader })
+    }
+
+    /// 读取下一条记录，文件结束时返回None
+    pub fn next_record(&mut self) -> io::Result<Option<CaptureRecord>> {
+        let mut len_buf = [0u8; 4];
+        match self.reader.read_exact(&mut len_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_le_bytes(len_buf) as usize;
+        if !(RECORD_HEADER_SIZE..=MAX_RECORD_SIZE).contains(&len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("捕获记录长度无效: {}", len),
+            ));
+        }
+        let mut buf = vec![0u8; len];
+        self.reader.read_exact(&mut buf)?;
+        Ok(Some(CaptureRecord {
+            slot: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            received_at_ns: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            endpoint: u16::from_le_bytes([buf[16], buf[17]]),
+            entries: buf.split_off(RECORD_HEADER_SIZE),
+        }))
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// 列出路径下的捕获文件，路径为目录时按文件名排序返回其中所有捕获文件
+pub fn capture_files(path: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.extension().is_some_and(|ext| ext == CAPTURE_EXTENSION))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// 回放速度
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    // 按记录的原始间隔回放
+    Original,
+    // 按倍数加速回放
+    Accelerated(f64),
+    // 不等待，尽可能快地回放
+    Max,
+}
+
+impl std::str::FromStr for ReplaySpeed {
+    type Err = String;
+
+    /// 支持 "original"、"max" 和 "10x" 这样的倍数
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "original" | "1x" => Ok(ReplaySpeed::Original),
+            "max" => Ok(ReplaySpeed::Max),
+            other => other
+                .trim_end_matches('x')
+                .parse::<f64>()
+                .ok()
+                .filter(|factor| *factor > 0.0)
+                .map(ReplaySpeed::Accelerated)
+                .ok_or_else(|| format!("无效的回放速度: {}", s)),
+        }
+    }
+}
+
+/// 按指定速度回放捕获文件，每条记录调用一次回调
+///
+/// # 参数
+///
+/// * `path` - 捕获文件或包含捕获文件的目录
+/// * `speed` - 回放速度
+/// * `on_record` - 处理每条记录的回调
+pub async fn replay(
+    path: impl AsRef<Path>,
+    speed: ReplaySpeed,
+    mut on_record: impl FnMut(CaptureRecord),
+) -> io::Result<u64> {
+    let factor = match speed {
+        ReplaySpeed::Original => Some(1.0),
+        ReplaySpeed::Accelerated(factor) => Some(factor),
+        ReplaySpeed::Max => None,
+    };
+    let mut origin: Option<(u64, Instant)> = None;
+    let mut count = 0;
+
+    for file in capture_files(path)? {
+        for record in CaptureReader::open(&file)? {
+            let record = record?;
+            if let Some(factor) = factor {
+                let (first_ns, started) = *origin.get_or_insert((record.received_at_ns, Instant::now()));
+                let offset = record.received_at_ns.saturating_sub(first_ns) as f64 / factor;
+                sleep_until((started + Duration::from_nanos(offset as u64)).into()).await;
+            }
+            on_record(record);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// 回放捕获文件，并按实时运行时的规则对多端点到达去重
+///
+/// # 参数
+///
+/// * `path` - 捕获文件或包含捕获文件的目录
+/// * `speed` - 回放速度
+/// * `on_entries` - 以(slot, 接收纳秒, 首次到达的Entry)调用的回调
+pub async fn replay_entries(
+    path: impl AsRef<Path>,
+    speed: ReplaySpeed,
+    mut on_entries: impl FnMut(u64, u64, Vec<Entry>),
+) -> io::Result<u64> {
+    let stats = Arc::new(StdMutex::new(Vec::new()));
+    let mut deduplicator = EntryDeduplicator::new(stats.clone());
+
+    replay(path, speed, |record| {
+        {
+            // 统计表按记录中出现的端点序号扩容
+            let mut stats = stats.lock().unwrap();
+            if stats.len() <= record.endpoint as usize {
+                stats.resize_with(record.endpoint as usize + 1, Default::default);
+            }
+        }
+        let slot = record.slot;
+        let received_at_ns = record.received_at_ns;
+        let arrival = record.into_arrival();
+        match deserialize_entries(&arrival.entry.entries) {
+            Ok(entries) => {
+                let entries = deduplicator.process(&arrival, entries);
+                if !entries.is_empty() {
+                    on_entries(slot, received_at_ns, entries);
+                }
+            }
+            Err(e) => println!("slot {} 反序列化失败: {}", slot, e),
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("capture-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn record(slot: u64) -> CaptureRecord {
+        CaptureRecord {
+            slot,
+            received_at_ns: slot * 1_000,
+            endpoint: (slot % 2) as u16,
+            entries: vec![slot as u8; 16],
+        }
+    }
+
+    #[test]
+    fn writes_and_reads_rotated_files() {
+        let dir = temp_dir("rotate");
+        let mut writer = CaptureWriter::new(&dir, 64).unwrap();
+        for slot in 0..5 {
+            writer.write(&record(slot)).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(writer.records(), 5);
+
+        let files = capture_files(&dir).unwrap();
+        assert!(files.len() > 1);
+        let slots: Vec<u64> = files
+            .iter()
+            .flat_map(|file| CaptureReader::open(file).unwrap())
+            .map(|record| record.unwrap().slot)
+            .collect();
+        assert_eq!(slots, vec![0, 1, 2, 3, 4]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_oversized_length_prefix() {
+        let dir = temp_dir("corrupt");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("corrupt.{}", CAPTURE_EXTENSION));
+        let mut file = File::create(&path).unwrap();
+        write_header(&mut file).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        drop(file);
+
+        let err = CaptureReader::open(&path).unwrap().next_record().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_replay_speed() {
+        assert_eq!("original".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Original));
+        assert_eq!("MAX".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Max));
+        assert_eq!("10x".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Accelerated(10.0)));
+        assert!("0x".parse::<ReplaySpeed>().is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use jito_protos::auth::{
+    auth_service_client::AuthServiceClient, GenerateAuthChallengeRequest,
+    GenerateAuthTokensRequest, RefreshAccessTokenRequest, Role, Token,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tonic::metadata::MetadataValue;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+
+/// 在访问令牌过期前多久刷新
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+/// 刷新失败后的重试间隔
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// 为每个请求附加访问令牌的拦截器，未认证时不附加任何内容
+#[derive(Clone, Default)]
+pub struct AuthInterceptor {
+    access_token: Arc<RwLock<Option<String>>>,
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = self.access_token.read().unwrap().as_ref() {
+            let value: MetadataValue<_> = format!("Bearer {}", token)
+                .parse()
+                .map_err(|_| Status::internal("无效的访问令牌"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+/// 当前持有的令牌对
+struct AuthTokens {
+    access_token: Token,
+    refresh_token: Token,
+}
+
+/// 令牌过期时间，缺失时视为已过期
+fn expires_at(token: &Token) -> SystemTime {
+    token
+        .expires_at_utc
+        .as_ref()
+        .map(|ts| UNIX_EPOCH + Duration::new(ts.seconds.max(0) as u64, ts.nanos.max(0) as u32))
+        .unwrap_or(UNIX_EPOCH)
+}
+
+/// 基于auth.proto挑战/令牌流程的认证器
+///
+/// 使用配置的密钥对签名挑战获取令牌，并在访问令牌过期前自动刷新。
+pub struct Authenticator {
+    client: AuthServiceClient<Channel>,
+    keypair: Arc<Keypair>,
+    role: Role,
+    access_token: Arc<RwLock<Option<String>>>,
+    tokens: Mutex<Option<AuthTokens>>,
+}
+
+impl Authenticator {
+    /// 连接认证服务并完成首次认证
+    ///
+    /// # 参数
+    ///
+    /// * `auth_url` - 认证服务地址
+    /// * `keypair` - 用于签名挑战的密钥对
+    /// * `role` - 申请的角色
+    pub async fn connect(auth_url: &str, keypair: Arc<Keypair>, role: Role) -> Result<Arc<Self>, Box<dyn Error + Send + Sync>> {
+        let client = AuthServiceClient::connect(auth_url.to_string()).await?;
+        let authenticator = Arc::new(Self {
+            client,
+            keypair,
+            role,
+            access_token: Arc::new(RwLock::new(None)),
+            tokens: Mutex::new(None),
+        });
+        authenticator.authenticate().await?;
+        Ok(authenticator)
+    }
+
+    /// 创建共享当前访问令牌的拦截器
+    pub fn interceptor(&self) -> AuthInterceptor {
+        AuthInterceptor {
+            access_token: self.access_token.clone(),
+        }
+    }
+
+    /// 完整的挑战认证流程：获取挑战、签名、换取令牌
+    pub async fn authenticate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut client = self.client.clone();
+        let pubkey = self.keypair.pubkey();
+
+        let challenge = client
+            .generate_auth_challenge(GenerateAuthChallengeRequest {
+                role: self.role as i32,
+                pubkey: pubkey.to_bytes().to_vec(),
+            })
+            .await?
+            .into_inner()
+            .challenge;
+
+        // 服务端要求签名的内容为 "{pubkey}-{challenge}"
+        let challenge = format!("{}-{}", pubkey, challenge);
+        let signed_challenge = self.keypair.sign_message(challenge.as_bytes());
+
+        let response = client
+            .generate_auth_tokens(GenerateAuthTokensRequest {
+                challenge,
+                client_pubkey: pubkey.to_bytes().to_vec(),
+                signed_challenge: signed_challenge.as_ref().to_vec(),
+            })
+            .await?
+            .into_inner();
+
+        let access_token = response.access_token.ok_or("认证响应缺少访问令牌")?;
+        let refresh_token = response.refresh_token.ok_or("认证响应缺少刷新令牌")?;
+
+        *self.access_token.write().unwrap() = Some(access_token.value.clone());
+        *self.tokens.lock().await = Some(AuthTokens { access_token, refresh_token });
+        println!("认证成功: {}", pubkey);
+        Ok(())
+    }
+
+    /// 刷新访问令牌，刷新令牌即将过期或已失效时重新走完整认证流程
+    pub async fn refresh(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let refresh_token = {
+            let tokens = self.tokens.lock().await;
+            match tokens.as_ref() {
+                Some(tokens) if expires_at(&tokens.refresh_token) > SystemTime::now() + REFRESH_MARGIN => {
+                    Some(tokens.refresh_token.value.clone())
+                }
+                _ => None,
+            }
+        };
+        let refresh_token = match refresh_token {
+            Some(token) => token,
+            None => return self.authenticate().await,
+        };
+
+        let response = match self
+            .client
+            .clone()
+            .refresh_access_token(RefreshAccessTokenRequest { refresh_token })
+            .await
+        {
+            Ok(response) => response,
+            // 刷新令牌已被吊销或过期，重试无意义，重新走完整认证流程
+            Err(status) if status.code() == Code::Unauthenticated => {
+                println!("刷新令牌已失效: {}，重新认证...", status.message());
+                return self.authenticate().await;
+            }
+            Err(status) => return Err(status.into()),
+        };
+        let access_token = response
+            .into_inner()
+            .access_token
+            .ok_or("刷新响应缺少访问令牌")?;
+
+        *self.access_token.write().unwrap() = Some(access_token.value.clone());
+        if let Some(tokens) = self.tokens.lock().await.as_mut() {
+            tokens.access_token = access_token;
+        }
+        println!("访问令牌已刷新");
+        Ok(())
+    }
+
+    /// 距离下一次需要刷新的时间
+    async fn time_until_refresh(&self) -> Duration {
+        let tokens = self.tokens.lock().await;
+        match tokens.as_ref() {
+            Some(tokens) => expires_at(&tokens.access_token)
+                .checked_sub(REFRESH_MARGIN)
+                .and_then(|at| at.duration_since(SystemTime::now()).ok())
+                .unwrap_or(Duration::ZERO),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// 启动后台刷新任务，在访问令牌过期前刷新
+    pub fn spawn_refresh_task(self: &Arc<Self>) -> JoinHandle<()> {
+        let authenticator = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                sleep(authenticator.time_until_refresh().await).await;
+                if let Err(e) = authenticator.refresh().await {
+                    println!("刷新访问令牌失败: {:?}，{}秒后重试", e, REFRESH_RETRY_DELAY.as_secs());
+                    sleep(REFRESH_RETRY_DELAY).await;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockAuthService;
+
+    async fn connect(access_ttl: Duration, refresh_ttl: Duration) -> (MockAuthService, Arc<Authenticator>) {
+        let mock = MockAuthService::new(access_ttl, refresh_ttl);
+        let (url, _handle) = mock.clone().serve().await.unwrap();
+        let authenticator = Authenticator::connect(&url, Arc::new(Keypair::new()), Role::ShredstreamSubscriber)
+            .await
+            .unwrap();
+        (mock, authenticator)
+    }
+
+    fn current_token(authenticator: &Authenticator) -> String {
+        authenticator.access_token.read().unwrap().clone().unwrap()
+    }
+
+    #[tokio::test]
+    async fn interceptor_attaches_issued_token() {
+        let (mock, authenticator) = connect(Duration::from_secs(600), Duration::from_secs(3600)).await;
+        let token = current_token(&authenticator);
+        assert!(mock.is_valid_access_token(&token));
+
+        let request = authenticator.interceptor().call(Request::new(())).unwrap();
+        let header = request.metadata().get("authorization").unwrap().to_str().unwrap();
+        assert_eq!(header, format!("Bearer {}", token));
+    }
+
+    #[tokio::test]
+    async fn unauthenticated_interceptor_adds_nothing() {
+        let request = AuthInterceptor::default().call(Request::new(())).unwrap();
+        assert!(request.metadata().get("authorization").is_none());
+    }
+
+    #[tokio::test]
+    async fn refresh_replaces_access_token() {
+        let (mock, authenticator) = connect(Duration::from_secs(600), Duration::from_secs(3600)).await;
+        let before = current_token(&authenticator);
+        authenticator.refresh().await.unwrap();
+        let after = current_token(&authenticator);
+        assert_ne!(before, after);
+        assert!(mock.is_valid_access_token(&after));
+    }
+
+    #[tokio::test]
+    async fn refresh_token_near_expiry_reauthenticates() {
+        let (mock, authenticator) = connect(Duration::from_secs(600), Duration::from_secs(30)).await;
+        let issued = mock.issued_count();
+        authenticator.refresh().await.unwrap();
+        // 挑战、访问令牌、刷新令牌各计一次
+        assert_eq!(mock.issued_count(), issued + 3);
+        assert!(mock.is_valid_access_token(&current_token(&authenticator)));
+    }
+
+    #[tokio::test]
+    async fn revoked_refresh_token_falls_back_to_authenticate() {
+        let (mock, authenticator) = connect(Duration::from_secs(600), Duration::from_secs(3600)).await;
+        mock.revoke_all();
+        authenticator.refresh().await.unwrap();
+        assert!(mock.is_valid_access_token(&current_token(&authenticator)));
+    }
+
+    #[test]
+    fn missing_expiry_counts_as_expired() {
+        let token = Token { value: "t".to_string(), expires_at_utc: None };
+        assert_eq!(expires_at(&token), UNIX_EPOCH);
+    }
+}
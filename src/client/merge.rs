@@ -0,0 +1,240 @@
+use jito_protos::shredstream::Entry as ProtoEntry;
+use solana_entry::entry::Entry;
+use solana_sdk::{hash::Hash, signature::Signature};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// 去重记录保留的slot数量
+const DEDUP_RETAIN_SLOTS: u64 = 64;
+
+/// 从某个端点收到的原始消息
+pub struct RawArrival {
+    pub endpoint: usize,
+    pub entry: ProtoEntry,
+    pub received_at: Instant,
+    pub received_at_utc: SystemTime,
+}
+
+/// 单个端点的到达统计
+#[derive(Clone, Debug, Default)]
+pub struct EndpointStats {
+    pub url: String,
+    pub messages: u64,
+    // 首先送达的Entry数量
+    pub wins: u64,
+    // 晚于其他端点送达的Entry数量
+    pub losses: u64,
+    pub total_lag: Duration,
+    pub max_lag: Duration,
+}
+
+impl EndpointStats {
+    /// 首先送达的比例
+    pub fn win_rate(&self) -> f64 {
+        let total = self.wins + self.losses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.wins as f64 / total as f64
+    }
+
+    /// 落后时的平均延迟（毫秒）
+    pub fn avg_lag_ms(&self) -> f64 {
+        if self.losses == 0 {
+            return 0.0;
+        }
+        self.total_lag.as_secs_f64() * 1000.0 / self.losses as f64
+    }
+}
+
+impl fmt::Display for EndpointStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} 消息: {} 领先: {} 落后: {} 胜率: {:.1}% 平均落后: {:.3}ms 最大落后: {:.3}ms",
+            self.url,
+            self.messages,
+            self.wins,
+            self.losses,
+            self.win_rate() * 100.0,
+            self.avg_lag_ms(),
+            self.max_lag.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// 多端点共享的统计表
+pub type SharedEndpointStats = Arc<StdMutex<Vec<EndpointStats>>>;
+
+/// 多端点Entry去重器
+///
+/// 以(slot, Entry哈希)为键记录每个Entry第一次到达的端点和时间，
+/// 后续端点送达的相同Entry被丢弃并记录落后时间；同一交易签名也只会被放行一次。
+pub struct EntryDeduplicator {
+    first_seen: HashMap<(u64, Hash), (usize, Instant)>,
+    seen_signatures: HashMap<Signature, u64>,
+    stats: SharedEndpointStats,
+    highest_slot: u64,
+}
+
+impl EntryDeduplicator {
+    pub fn new(stats: SharedEndpointStats) -> Self {
+        Self {
+            first_seen: HashMap::new(),
+            seen_signatures: HashMap::new(),
+            stats,
+            highest_slot: 0,
+        }
+    }
+
+    /// 过滤出首次出现的Entry，并更新端点统计
+    ///
+    /// # 参数
+    ///
+    /// * `arrival` - 原始消息及其来源端点
+    /// * `entries` - 从该消息反序列化出的Entry
+    pub fn process(&mut self, arrival: &RawArrival, entries: Vec<Entry>) -> Vec<Entry> {
+        let slot = arrival.entry.slot;
+        let mut fresh = Vec::with_capacity(entries.len());
+        let mut stats = self.stats.lock().unwrap();
+        stats[arrival.endpoint].messages += 1;
+
+        for mut entry in entries {
+            match self.first_seen.get(&(slot, entry.hash)) {
+                Some((winner, first_at)) => {
+                    if *winner != arrival.endpoint {
+                        let lag = arrival.received_at.saturating_duration_since(*first_at);
+                        let endpoint = &mut stats[arrival.endpoint];
+                        endpoint.losses += 1;
+                        endpoint.total_lag += lag;
+                        endpoint.max_lag = endpoint.max_lag.max(lag);
+                    }
+                }
+                None => {
+                    self.first_seen.insert((slot, entry.hash), (arrival.endpoint, arrival.received_at));
+                    stats[arrival.endpoint].wins += 1;
+
+                    if !entry.transactions.is_empty() {
+                        let seen = &mut self.seen_signatures;
+                        entry.transactions.retain(|tx| match tx.signatures.first() {
+                            Some(signature) => seen.insert(*signature, slot).is_none(),
+                            None => true,
+                        });
+                        // 交易全部重复的Entry不再下发，避免被误认为tick
+                        if entry.transactions.is_empty() {
+                            continue;
+                        }
+                    }
+                    fresh.push(entry);
+                }
+            }
+        }
+        drop(stats);
+
+        if slot > self.highest_slot {
+            self.highest_slot = slot;
+            self.prune();
+        }
+        fresh
+    }
+
+    /// 清理过旧slot的去重记录
+    fn prune(&mut self) {
+        let min_slot = self.highest_slot.saturating_sub(DEDUP_RETAIN_SLOTS);
+        self.first_seen.retain(|(slot, _), _| *slot >= min_slot);
+        self.seen_signatures.retain(|_, slot| *slot >= min_slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_transaction;
+    use solana_sdk::transaction::VersionedTransaction;
+
+    fn deduplicator(endpoints: usize) -> (EntryDeduplicator, SharedEndpointStats) {
+        let stats = Arc::new(StdMutex::new(vec![EndpointStats::default(); endpoints]));
+        (EntryDeduplicator::new(stats.clone()), stats)
+    }
+
+    fn arrival(endpoint: usize, slot: u64, received_at: Instant) -> RawArrival {
+        RawArrival {
+            endpoint,
+            entry: ProtoEntry { slot, entries: vec![] },
+            received_at,
+            received_at_utc: SystemTime::now(),
+        }
+    }
+
+    fn transaction() -> VersionedTransaction {
+        let payer = Keypair::new();
+        system_transaction::transfer(&payer, &payer.pubkey(), 1, Hash::new_unique()).into()
+    }
+
+    fn entry(transactions: Vec<VersionedTransaction>) -> Entry {
+        Entry {
+            num_hashes: 1,
+            hash: Hash::new_unique(),
+            transactions,
+        }
+    }
+
+    #[test]
+    fn later_copies_are_dropped_and_counted_as_lag() {
+        let (mut dedup, stats) = deduplicator(2);
+        let entries = vec![entry(vec![transaction()]), entry(vec![])];
+        let start = Instant::now();
+
+        let fresh = dedup.process(&arrival(0, 10, start), entries.clone());
+        assert_eq!(fresh, entries);
+        let late = dedup.process(&arrival(1, 10, start + Duration::from_millis(3)), entries.clone());
+        assert!(late.is_empty());
+
+        let stats = stats.lock().unwrap();
+        assert_eq!((stats[0].messages, stats[0].wins, stats[0].losses), (1, 2, 0));
+        assert_eq!((stats[1].messages, stats[1].wins, stats[1].losses), (1, 0, 2));
+        assert_eq!(stats[1].max_lag, Duration::from_millis(3));
+        assert_eq!(stats[1].avg_lag_ms(), 3.0);
+        assert_eq!(stats[0].win_rate(), 1.0);
+    }
+
+    #[test]
+    fn same_entry_hash_in_another_slot_is_fresh() {
+        let (mut dedup, _) = deduplicator(1);
+        let tick = entry(vec![]);
+        let now = Instant::now();
+        assert_eq!(dedup.process(&arrival(0, 10, now), vec![tick.clone()]).len(), 1);
+        assert_eq!(dedup.process(&arrival(0, 11, now), vec![tick]).len(), 1);
+    }
+
+    #[test]
+    fn duplicate_transactions_are_released_once() {
+        let (mut dedup, _) = deduplicator(2);
+        let (tx, other) = (transaction(), transaction());
+        let now = Instant::now();
+
+        dedup.process(&arrival(0, 10, now), vec![entry(vec![tx.clone()])]);
+        // 不同的Entry中重复的交易被移除，只剩重复交易的Entry整体丢弃
+        let fresh = dedup.process(&arrival(1, 10, now), vec![entry(vec![tx.clone(), other.clone()]), entry(vec![tx])]);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].transactions, vec![other]);
+    }
+
+    #[test]
+    fn old_slots_are_pruned() {
+        let (mut dedup, _) = deduplicator(1);
+        let tx = transaction();
+        let now = Instant::now();
+
+        dedup.process(&arrival(0, 10, now), vec![entry(vec![tx.clone()])]);
+        dedup.process(&arrival(0, 10 + DEDUP_RETAIN_SLOTS + 1, now), vec![]);
+        assert!(dedup.first_seen.is_empty());
+        assert!(dedup.seen_signatures.is_empty());
+
+        // 清理后同一交易再次出现时放行
+        assert_eq!(dedup.process(&arrival(0, 10, now), vec![entry(vec![tx])]).len(), 1);
+    }
+}
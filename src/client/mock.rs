@@ -0,0 +1,256 @@
+use jito_protos::auth::{
+    auth_service_server::{AuthService, AuthServiceServer},
+    GenerateAuthChallengeRequest, GenerateAuthChallengeResponse, GenerateAuthTokensRequest,
+    GenerateAuthTokensResponse, RefreshAccessTokenRequest, RefreshAccessTokenResponse, Token,
+};
+use jito_protos::shredstream::{
+    shredstream_proxy_server::{ShredstreamProxy, ShredstreamProxyServer},
+    Entry, SubscribeEntriesRequest,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{transport::Server, Request, Response, Status};
+
+/// 在本地随机端口启动gRPC服务的监听器
+async fn local_listener() -> std::io::Result<(TcpListener, String)> {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    Ok((listener, url))
+}
+
+fn to_timestamp(time: SystemTime) -> prost_types::Timestamp {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    prost_types::Timestamp {
+        seconds: since_epoch.as_secs() as i64,
+        nanos: since_epoch.subsec_nanos() as i32,
+    }
+}
+
+#[derive(Default)]
+struct MockAuthState {
+    challenges: HashMap<Pubkey, String>,
+    access_tokens: HashMap<String, SystemTime>,
+    refresh_tokens: HashMap<String, SystemTime>,
+    issued: u64,
+}
+
+/// 本地模拟的AuthService，校验挑战签名并签发带过期时间的令牌
+#[derive(Clone)]
+pub struct MockAuthService {
+    state: Arc<StdMutex<MockAuthState>>,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+}
+
+impl MockAuthService {
+    /// 创建模拟认证服务
+    ///
+    /// # 参数
+    ///
+    /// * `access_ttl` - 访问令牌有效期
+    /// * `refresh_ttl` - 刷新令牌有效期
+    pub fn new(access_ttl: Duration, refresh_ttl: Duration) -> Self {
+        Self {
+            state: Arc::new(StdMutex::new(MockAuthState::default())),
+            access_ttl,
+            refresh_ttl,
+        }
+    }
+
+    /// 判断访问令牌是否有效
+    pub fn is_valid_access_token(&self, token: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        matches!(state.access_tokens.get(token), Some(expiry) if *expiry > SystemTime::now())
+    }
+
+    /// 作废所有已签发的令牌，用于模拟服务端令牌失效
+    pub fn revoke_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.access_tokens.clear();
+        state.refresh_tokens.clear();
+    }
+
+    /// 已签发的访问令牌数量（含刷新）
+    pub fn issued_count(&self) -> u64 {
+        self.state.lock().unwrap().issued
+    }
+
+    fn issue(&self, state: &mut MockAuthState, ttl: Duration, refresh: bool) -> Token {
+        state.issued += 1;
+        let value = format!("{}-token-{}", if refresh { "refresh" } else { "access" }, state.issued);
+        let expiry = SystemTime::now() + ttl;
+        if refresh {
+            state.refresh_tokens.insert(value.clone(), expiry);
+        } else {
+            state.access_tokens.insert(value.clone(), expiry);
+        }
+        Token {
+            value,
+            expires_at_utc: Some(to_timestamp(expiry)),
+        }
+    }
+
+    /// 在本地随机端口启动服务，返回服务地址
+    pub async fn serve(self) -> std::io::Result<(String, JoinHandle<()>)> {
+        let (listener, url) = local_listener().await?;
+        let handle = tokio::spawn(async move {
+            if let Err(e) = Server::builder()
+                .add_service(AuthServiceServer::new(self))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+            {
+                println!("模拟AuthService退出: {:?}", e);
+            }
+        });
+        Ok((url, handle))
+    }
+}
+
+#[tonic::async_trait]
+impl AuthService for MockAuthService {
+    async fn generate_auth_challenge(
+        &self,
+        request: Request<GenerateAuthChallengeRequest>,
+    ) -> Result<Response<GenerateAuthChallengeResponse>, Status> {
+        let request = request.into_inner();
+        let pubkey = Pubkey::try_from(request.pubkey.as_slice())
+            .map_err(|_| Status::invalid_argument("invalid pubkey"))?;
+
+        let mut state = self.state.lock().unwrap();
+        state.issued += 1;
+        let challenge = format!("challenge-{}", state.issued);
+        state.challenges.insert(pubkey, challenge.clone());
+        Ok(Response::new(GenerateAuthChallengeResponse { challenge }))
+    }
+
+    async fn generate_auth_tokens(
+        &self,
+        request: Request<GenerateAuthTokensRequest>,
+    ) -> Result<Response<GenerateAuthTokensResponse>, Status> {
+        let request = request.into_inner();
+        let pubkey = Pubkey::try_from(request.client_pubkey.as_slice())
+            .map_err(|_| Status::invalid_argument("invalid pubkey"))?;
+        let signature = Signature::try_from(request.signed_challenge.as_slice())
+            .map_err(|_| Status::invalid_argument("invalid signature"))?;
+
+        let mut state = self.state.lock().unwrap();
+        let challenge = state
+            .challenges
+            .remove(&pubkey)
+            .ok_or_else(|| Status::permission_denied("no challenge issued"))?;
+        if request.challenge != format!("{}-{}", pubkey, challenge) {
+            return Err(Status::permission_denied("challenge mismatch"));
+        }
+        if !signature.verify(pubkey.as_ref(), request.challenge.as_bytes()) {
+            return Err(Status::permission_denied("bad signature"));
+        }
+
+        let access_token = self.issue(&mut state, self.access_ttl, false);
+        let refresh_token = self.issue(&mut state, self.refresh_ttl, true);
+        Ok(Response::new(GenerateAuthTokensResponse {
+            access_token: Some(access_token),
+            refresh_token: Some(refresh_token),
+        }))
+    }
+
+    async fn refresh_access_token(
+        &self,
+        request: Request<RefreshAccessTokenRequest>,
+    ) -> Result<Response<RefreshAccessTokenResponse>, Status> {
+        let refresh_token = request.into_inner().refresh_token;
+        let mut state = self.state.lock().unwrap();
+        match state.refresh_tokens.get(&refresh_token) {
+            Some(expiry) if *expiry > SystemTime::now() => {}
+            _ => return Err(Status::unauthenticated("invalid refresh token")),
+        }
+        let access_token = self.issue(&mut state, self.access_ttl, false);
+        Ok(Response::new(RefreshAccessTokenResponse {
+            access_token: Some(access_token),
+        }))
+    }
+}
+
+/// Entry订阅的推送端
+type EntrySender = mpsc::Sender<Result<Entry, Status>>;
+
+/// 本地模拟的ShredstreamProxy，可选地校验访问令牌，通过`publish`推送Entry
+#[derive(Clone)]
+pub struct MockShredstreamProxy {
+    auth: Option<MockAuthService>,
+    subscribers: Arc<StdMutex<Vec<EntrySender>>>,
+}
+
+impl MockShredstreamProxy {
+    /// 创建模拟代理
+    ///
+    /// # 参数
+    ///
+    /// * `auth` - 设置后每个订阅请求都必须携带该认证服务签发的有效令牌
+    pub fn new(auth: Option<MockAuthService>) -> Self {
+        Self {
+            auth,
+            subscribers: Arc::new(StdMutex::new(Vec::new())),
+        }
+    }
+
+    /// 向所有订阅者推送一条Entry
+    pub async fn publish(&self, entry: Entry) {
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        for subscriber in subscribers {
+            let _ = subscriber.send(Ok(entry.clone())).await;
+        }
+    }
+
+    /// 当前订阅者数量
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().iter().filter(|s| !s.is_closed()).count()
+    }
+
+    /// 在本地随机端口启动服务，返回服务地址
+    pub async fn serve(self) -> std::io::Result<(String, JoinHandle<()>)> {
+        let (listener, url) = local_listener().await?;
+        let handle = tokio::spawn(async move {
+            if let Err(e) = Server::builder()
+                .add_service(ShredstreamProxyServer::new(self))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+            {
+                println!("模拟ShredstreamProxy退出: {:?}", e);
+            }
+        });
+        Ok((url, handle))
+    }
+}
+
+#[tonic::async_trait]
+impl ShredstreamProxy for MockShredstreamProxy {
+    type SubscribeEntriesStream = ReceiverStream<Result<Entry, Status>>;
+
+    async fn subscribe_entries(
+        &self,
+        request: Request<SubscribeEntriesRequest>,
+    ) -> Result<Response<Self::SubscribeEntriesStream>, Status> {
+        if let Some(auth) = &self.auth {
+            let token = request
+                .metadata()
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .unwrap_or_default();
+            if !auth.is_valid_access_token(token) {
+                return Err(Status::unauthenticated("invalid access token"));
+            }
+        }
+
+        let (tx, rx) = mpsc::channel(1024);
+        self.subscribers.lock().unwrap().push(tx);
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
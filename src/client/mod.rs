@@ -1,50 +1,122 @@
+use jito_protos::auth::Role;
 use jito_protos::shredstream::{
     shredstream_proxy_client::ShredstreamProxyClient, SubscribeEntriesRequest, Entry,
 };
-use tonic::Streaming;
+use tonic::codegen::InterceptedService;
+use tonic::transport::Channel;
+use tonic::{Code, Status, Streaming};
 use crate::config::Config;
-use std::time::Duration;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
 use tokio::time::sleep;
 
+pub mod auth;
+pub mod merge;
+#[cfg(test)]
+mod mock;
+
+use auth::{AuthInterceptor, Authenticator};
+use merge::{EndpointStats, EntryDeduplicator, RawArrival, SharedEndpointStats};
+
+type ProxyClient = ShredstreamProxyClient<InterceptedService<Channel, AuthInterceptor>>;
+
+/// 合并通道的缓冲大小
+const ARRIVAL_CHANNEL_SIZE: usize = 4096;
+
+/// 配置了密钥对时完成认证，并在后台保持令牌刷新
+pub async fn connect_authenticator(config: &Config) -> Result<Option<Arc<Authenticator>>, Box<dyn std::error::Error>> {
+    let keypair = match &config.auth_keypair {
+        Some(keypair) => keypair,
+        None => return Ok(None),
+    };
+    let auth_url = config.auth_url.clone().unwrap_or_else(|| config.server_url.clone());
+    let authenticator = Authenticator::connect(&auth_url, keypair.clone(), Role::ShredstreamSubscriber)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    authenticator.spawn_refresh_task();
+    Ok(Some(authenticator))
+}
+
 pub struct ShredstreamClient {
-    client: ShredstreamProxyClient<tonic::transport::Channel>,
     config: Config,
+    authenticator: Option<Arc<Authenticator>>,
+    stats: SharedEndpointStats,
 }
 
 impl ShredstreamClient {
     pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
-        let client = Self::connect(&config).await?;
-        Ok(Self { client, config })
+        let authenticator = connect_authenticator(&config).await?;
+
+        let stats = config
+            .server_urls
+            .iter()
+            .map(|url| EndpointStats {
+                url: url.clone(),
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(Self {
+            config,
+            authenticator,
+            stats: Arc::new(StdMutex::new(stats)),
+        })
     }
 
-    async fn connect(config: &Config) -> Result<ShredstreamProxyClient<tonic::transport::Channel>, Box<dyn std::error::Error>> {
+    async fn connect(url: &str, interceptor: AuthInterceptor) -> Result<ProxyClient, Box<dyn std::error::Error + Send + Sync>> {
         let mut retries = 0;
         let max_retries = 5;
         let base_delay = Duration::from_secs(1);
 
         loop {
-            match ShredstreamProxyClient::connect(config.server_url.clone()).await {
-                Ok(client) => return Ok(client),
+            match Channel::from_shared(url.to_string())?.connect().await {
+                Ok(channel) => return Ok(ShredstreamProxyClient::with_interceptor(channel, interceptor)),
                 Err(e) => {
                     retries += 1;
                     if retries >= max_retries {
                         return Err(Box::new(e));
                     }
                     let delay = base_delay * retries;
-                    println!("连接失败，{}秒后重试 (第{}次)...", delay.as_secs(), retries);
+                    println!("连接 {} 失败，{}秒后重试 (第{}次)...", url, delay.as_secs(), retries);
                     sleep(delay).await;
                 }
             }
         }
     }
 
-    pub async fn subscribe_entries(&mut self) -> Result<Streaming<Entry>, Box<dyn std::error::Error>> {
+    fn interceptor(authenticator: &Option<Arc<Authenticator>>) -> AuthInterceptor {
+        authenticator
+            .as_ref()
+            .map(|auth| auth.interceptor())
+            .unwrap_or_default()
+    }
+
+    /// 处理流或请求返回的错误状态，认证失效时重新认证
+    async fn handle_status(authenticator: &Option<Arc<Authenticator>>, status: &Status) {
+        if status.code() != Code::Unauthenticated {
+            return;
+        }
+        if let Some(authenticator) = authenticator {
+            println!("访问令牌失效，重新认证...");
+            if let Err(e) = authenticator.authenticate().await {
+                println!("重新认证失败: {:?}", e);
+            }
+        }
+    }
+
+    /// 订阅单个端点的Entry流
+    async fn subscribe_entries(
+        url: &str,
+        authenticator: &Option<Arc<Authenticator>>,
+    ) -> Result<Streaming<Entry>, Box<dyn std::error::Error + Send + Sync>> {
         let mut retries = 0;
         let max_retries = 5;
         let base_delay = Duration::from_secs(1);
+        let mut client = Self::connect(url, Self::interceptor(authenticator)).await?;
 
         loop {
-            match self.client
+            match client
                 .subscribe_entries(SubscribeEntriesRequest {})
                 .await
             {
@@ -54,14 +126,133 @@ impl ShredstreamClient {
                     if retries >= max_retries {
                         return Err(Box::new(e));
                     }
+
+                    // 认证失效时重新认证后立即重试，无需重新连接
+                    if e.code() == Code::Unauthenticated && authenticator.is_some() {
+                        Self::handle_status(authenticator, &e).await;
+                        continue;
+                    }
+
                     let delay = base_delay * retries;
-                    println!("订阅失败，{}秒后重试 (第{}次)...", delay.as_secs(), retries);
+                    println!("订阅 {} 失败，{}秒后重试 (第{}次)...", url, delay.as_secs(), retries);
                     sleep(delay).await;
-                    
+
                     // 尝试重新连接
-                    self.client = Self::connect(&self.config).await?;
+                    client = Self::connect(url, Self::interceptor(authenticator)).await?;
                 }
             }
         }
     }
-} 
\ No newline at end of file
+
+    /// 同时订阅所有配置的端点，将收到的消息合并到同一个通道
+    ///
+    /// 每个端点在独立任务中断线重连，消息附带来源端点和到达时间。
+    pub fn spawn_subscriptions(&self) -> mpsc::Receiver<RawArrival> {
+        let (tx, rx) = mpsc::channel(ARRIVAL_CHANNEL_SIZE);
+
+        for (endpoint, url) in self.config.server_urls.iter().enumerate() {
+            let tx = tx.clone();
+            let url = url.clone();
+            let authenticator = self.authenticator.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    match Self::subscribe_entries(&url, &authenticator).await {
+                        Ok(mut stream) => {
+                            println!("已订阅端点: {}", url);
+                            loop {
+                                match stream.message().await {
+                                    Ok(Some(entry)) => {
+                                        let arrival = RawArrival {
+                                            endpoint,
+                                            entry,
+                                            received_at: Instant::now(),
+                                            received_at_utc: SystemTime::now(),
+                                        };
+                                        if tx.send(arrival).await.is_err() {
+                                            // 接收端已关闭，结束订阅
+                                            return;
+                                        }
+                                    }
+                                    Ok(None) => {
+                                        println!("端点 {} 的数据流已结束", url);
+                                        break;
+                                    }
+                                    Err(e) => {
+                                        println!("端点 {} 获取消息失败: {:?}", url, e);
+                                        Self::handle_status(&authenticator, &e).await;
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            println!("端点 {} 连接断开: {}", url, e);
+                            println!("5秒后重新连接...");
+                            sleep(Duration::from_secs(5)).await;
+                        }
+                    }
+                }
+            });
+        }
+
+        rx
+    }
+
+    /// 创建与本客户端共享统计的去重器
+    pub fn deduplicator(&self) -> EntryDeduplicator {
+        EntryDeduplicator::new(self.stats.clone())
+    }
+
+    /// 各端点的胜率与延迟统计快照
+    pub fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockAuthService, MockShredstreamProxy};
+    use super::*;
+    use solana_sdk::signature::Keypair;
+
+    async fn authenticated_proxy() -> (MockAuthService, MockShredstreamProxy, String, Arc<Authenticator>) {
+        let auth = MockAuthService::new(Duration::from_secs(600), Duration::from_secs(3600));
+        let (auth_url, _) = auth.clone().serve().await.unwrap();
+        let proxy = MockShredstreamProxy::new(Some(auth.clone()));
+        let (proxy_url, _) = proxy.clone().serve().await.unwrap();
+        let authenticator = Authenticator::connect(&auth_url, Arc::new(Keypair::new()), Role::ShredstreamSubscriber)
+            .await
+            .unwrap();
+        (auth, proxy, proxy_url, authenticator)
+    }
+
+    #[tokio::test]
+    async fn authenticated_subscription_receives_entries() {
+        let (_auth, proxy, url, authenticator) = authenticated_proxy().await;
+        let mut stream = ShredstreamClient::subscribe_entries(&url, &Some(authenticator)).await.unwrap();
+        assert_eq!(proxy.subscriber_count(), 1);
+
+        proxy.publish(Entry { slot: 7, entries: vec![1, 2, 3] }).await;
+        let entry = stream.message().await.unwrap().unwrap();
+        assert_eq!(entry.slot, 7);
+        assert_eq!(entry.entries, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn revoked_token_reauthenticates_before_subscribing() {
+        let (auth, proxy, url, authenticator) = authenticated_proxy().await;
+        auth.revoke_all();
+        ShredstreamClient::subscribe_entries(&url, &Some(authenticator)).await.unwrap();
+        assert_eq!(proxy.subscriber_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_rejected() {
+        let auth = MockAuthService::new(Duration::from_secs(600), Duration::from_secs(3600));
+        let (url, _) = MockShredstreamProxy::new(Some(auth)).serve().await.unwrap();
+        let mut client = ShredstreamClient::connect(&url, AuthInterceptor::default()).await.unwrap();
+        let status = client.subscribe_entries(SubscribeEntriesRequest {}).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+}
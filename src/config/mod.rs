@@ -1,12 +1,56 @@
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::env;
 use dotenvy::dotenv;
 
+/// 数据接入方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IngestMode {
+    // 通过shredstream-proxy订阅Entry
+    Proxy,
+    // 发送心跳并在本地接收原始分片
+    Shreds,
+}
+
 #[derive(Clone)]
 pub struct Config {
+    // 主端点，认证服务默认使用该地址
     pub server_url: String,
+    // 所有订阅的端点，SERVER_URL可用逗号分隔多个地址
+    pub server_urls: Vec<String>,
     pub token_creator_pubkey: Pubkey,
+    // 认证用密钥对，未设置时匿名连接
+    pub auth_keypair: Option<Arc<Keypair>>,
+    // 认证服务地址，未设置时使用server_url
+    pub auth_url: Option<String>,
+    // 数据接入方式，INGEST_MODE为shreds时使用原生分片接收
+    pub ingest_mode: IngestMode,
+    // 原生分片接收的本地UDP地址
+    pub shred_listen_addr: SocketAddr,
+    // 心跳中上报的公网IP
+    pub shred_public_ip: Option<String>,
+    // 心跳中上报的端口，未设置时使用监听端口
+    pub shred_public_port: Option<u16>,
+    // 订阅分片的区域
+    pub shred_regions: Vec<String>,
+    // 心跳服务地址，未设置时使用server_url
+    pub shred_heartbeat_url: Option<String>,
+    // 原始分片保存路径
+    pub shred_dump_path: Option<String>,
+    // 地址查找表快照文件，启动时加载并定期保存
+    pub lookup_table_snapshot: Option<String>,
+    // 策略配置文件，未设置时使用按环境变量配置的价格区间策略
+    pub strategy_config: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Config {
@@ -15,11 +59,63 @@ impl Config {
         dotenv().ok();
         
         // 从环境变量获取服务器URL，如果未设置则程序停止
-        let server_url = env::var("SERVER_URL").expect("环境变量SERVER_URL未设置");
+        let server_urls: Vec<String> = env::var("SERVER_URL")
+            .expect("环境变量SERVER_URL未设置")
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        let server_url = server_urls.first().cloned().expect("环境变量SERVER_URL为空");
+        
+        // 认证密钥对可以是密钥文件路径，也可以是Base58私钥
+        let auth_keypair = env::var("AUTH_KEYPAIR").ok().map(|value| {
+            let keypair = parse_keypair(&value).expect("环境变量AUTH_KEYPAIR无效");
+            Arc::new(keypair)
+        });
+        
+        let ingest_mode = match env::var("INGEST_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "shreds" => IngestMode::Shreds,
+            "" | "proxy" => IngestMode::Proxy,
+            other => panic!("环境变量INGEST_MODE无效: {}", other),
+        };
+        
+        let shred_listen_addr = env::var("SHRED_LISTEN_ADDR")
+            .unwrap_or_else(|_| "0.0.0.0:20000".to_string())
+            .parse()
+            .expect("环境变量SHRED_LISTEN_ADDR无效");
+        let shred_regions = env::var("SHRED_REGIONS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|region| region.trim().to_string())
+            .filter(|region| !region.is_empty())
+            .collect();
         
         Self {
             server_url,
+            server_urls,
             token_creator_pubkey: Pubkey::from_str("TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM").unwrap(),
+            auth_keypair,
+            auth_url: env::var("AUTH_URL").ok(),
+            ingest_mode,
+            shred_listen_addr,
+            shred_public_ip: env::var("SHRED_PUBLIC_IP").ok(),
+            shred_public_port: env::var("SHRED_PUBLIC_PORT")
+                .ok()
+                .map(|port| port.parse().expect("环境变量SHRED_PUBLIC_PORT无效")),
+            shred_regions,
+            shred_heartbeat_url: env::var("SHRED_HEARTBEAT_URL").ok(),
+            shred_dump_path: env::var("SHRED_DUMP_PATH").ok(),
+            lookup_table_snapshot: env::var("LOOKUP_TABLE_SNAPSHOT").ok(),
+            strategy_config: env::var("STRATEGY_CONFIG").ok(),
         }
     }
-} 
\ No newline at end of file
+}
+
+// 从密钥文件路径或Base58字符串解析密钥对
+fn parse_keypair(value: &str) -> Result<Keypair, Box<dyn std::error::Error>> {
+    if Path::new(value).exists() {
+        return read_keypair_file(value);
+    }
+    let bytes = solana_sdk::bs58::decode(value).into_vec()?;
+    Ok(Keypair::from_bytes(&bytes)?)
+}
@@ -0,0 +1,463 @@
+use crate::transaction::error::TradeError;
+use solana_entry::entry::Entry;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::clock::MAX_PROCESSING_AGE;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Notify};
+
+/// 单次RPC请求最多查询的签名数量
+const MAX_STATUS_BATCH: usize = 256;
+/// 通过RPC查询交易状态的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(400);
+/// 数据流超过该时间没有新slot时改为通过RPC获取当前slot
+const STALE_SLOT_AFTER: Duration = Duration::from_secs(1);
+/// 区块哈希过期后继续等待的slot数，数据流的slot领先confirmed状态，留出确认延迟
+pub const CONFIRMATION_LAG_SLOTS: u64 = 32;
+/// 数据流停滞时判定交易过期的时间
+pub const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// 触发交易的slot和时刻，用于计算上链延迟
+#[derive(Clone, Copy, Debug)]
+pub struct Trigger {
+    pub slot: u64,
+    pub at: Instant,
+}
+
+impl Trigger {
+    pub fn now(slot: u64) -> Self {
+        Self { slot, at: Instant::now() }
+    }
+}
+
+/// 交易的最终状态
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfirmStatus {
+    /// 已上链并执行成功
+    Landed,
+    /// 已上链但执行失败
+    Failed(String),
+    /// 区块哈希过期前没有上链
+    Expired,
+    /// 数据流或processed状态中出现过，但直到超时都没有查询到confirmed的执行结果，可能所在slot被跳过
+    Unknown,
+}
+
+impl fmt::Display for ConfirmStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfirmStatus::Landed => write!(f, "已上链"),
+            ConfirmStatus::Failed(err) => write!(f, "执行失败: {}", err),
+            ConfirmStatus::Expired => write!(f, "已过期"),
+            ConfirmStatus::Unknown => write!(f, "执行结果未知"),
+        }
+    }
+}
+
+/// 交易的确认结果
+#[derive(Clone, Debug)]
+pub struct Confirmation {
+    pub signature: Signature,
+    pub status: ConfirmStatus,
+    pub landed_slot: Option<u64>,
+    // 从触发到在数据流中看到交易的时间，数据流中未看到时为到得到结果的时间
+    pub latency: Duration,
+    // 从触发slot到上链slot经过的slot数
+    pub slots: Option<u64>,
+}
+
+impl Confirmation {
+    pub fn is_landed(&self) -> bool {
+        self.status == ConfirmStatus::Landed
+    }
+}
+
+impl fmt::Display for Confirmation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.signature, self.status)?;
+        if let Some(slot) = self.landed_slot {
+            write!(f, "，slot {}", slot)?;
+        }
+        write!(f, "，延迟 {}ms", self.latency.as_millis())?;
+        if let Some(slots) = self.slots {
+            write!(f, " ({} slot)", slots)?;
+        }
+        Ok(())
+    }
+}
+
+// 等待确认的交易
+struct Pending {
+    trigger: Trigger,
+    // 超过该slot仍未上链视为过期，已包含确认延迟
+    expiry_slot: u64,
+    submitted: Instant,
+    // 在数据流中或以processed状态看到的slot和时刻
+    seen: Option<(u64, Instant)>,
+    result_tx: oneshot::Sender<Confirmation>,
+}
+
+impl Pending {
+    fn confirmation(&self, signature: Signature, status: ConfirmStatus, landed_slot: Option<u64>) -> Confirmation {
+        let latency = match self.seen {
+            Some((_, at)) => at.duration_since(self.trigger.at),
+            None => self.trigger.at.elapsed(),
+        };
+        Confirmation {
+            signature,
+            status,
+            landed_slot,
+            latency,
+            slots: landed_slot.map(|slot| slot.saturating_sub(self.trigger.slot)),
+        }
+    }
+}
+
+/// 已提交交易的确认跟踪器
+///
+/// 优先在数据流中查找我们的签名，得到上链slot和延迟；执行结果通过`get_signature_statuses`查询，
+/// 只有达到confirmed的状态才算数，数据流中没有看到的交易也通过RPC确认。区块哈希超过有效期并留出确认延迟后仍未出现的交易视为过期；
+/// 数据流中看到或已经processed的交易持续查询到超时，仍没有结果时报告执行结果未知，不按已上链处理。
+#[derive(Clone)]
+pub struct ConfirmationTracker {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    rpc_client: RpcClient,
+    pending: StdMutex<HashMap<Signature, Pending>>,
+    // 数据流中观察到或通过RPC获取的最新slot
+    latest_slot: AtomicU64,
+    // 数据流最近一次带来slot的时刻，从未收到时为None
+    stream_slot_at: StdMutex<Option<Instant>>,
+    // 在数据流中看到交易后立即查询状态
+    notify: Notify,
+    timeout: Duration,
+}
+
+impl ConfirmationTracker {
+    /// 创建跟踪器，在后台通过RPC查询交易状态
+    ///
+    /// # 参数
+    ///
+    /// * `rpc_url` - 用于查询交易状态的RPC地址
+    pub fn new(rpc_url: String) -> Self {
+        Self::with_timeout(rpc_url, DEFAULT_CONFIRM_TIMEOUT)
+    }
+
+    /// 创建跟踪器并指定数据流停滞时的过期时间
+    pub fn with_timeout(rpc_url: String, timeout: Duration) -> Self {
+        let tracker = Self {
+            inner: Arc::new(Inner {
+                rpc_client: RpcClient::new(rpc_url),
+                pending: StdMutex::new(HashMap::new()),
+                latest_slot: AtomicU64::new(0),
+                stream_slot_at: StdMutex::new(None),
+                notify: Notify::new(),
+                timeout,
+            }),
+        };
+        tracker.spawn_poller();
+        tracker
+    }
+
+    /// 开始跟踪交易，返回确认结果
+    ///
+    /// # 参数
+    ///
+    /// * `signature` - 交易签名
+    /// * `trigger` - 触发交易的slot和时刻
+    /// * `blockhash_slot` - 交易使用的区块哈希所属的slot，未知时按当前slot估计
+    pub fn watch(&self, signature: Signature, trigger: Trigger, blockhash_slot: Option<u64>) -> oneshot::Receiver<Confirmation> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let base_slot = blockhash_slot.unwrap_or_else(|| self.latest_slot().max(trigger.slot));
+        self.inner.pending.lock().unwrap().insert(
+            signature,
+            Pending {
+                trigger,
+                expiry_slot: base_slot + MAX_PROCESSING_AGE as u64 + CONFIRMATION_LAG_SLOTS,
+                submitted: Instant::now(),
+                seen: None,
+                result_tx,
+            },
+        );
+        result_rx
+    }
+
+    /// 在数据流的Entry中查找等待确认的交易
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - Entry所属的slot
+    /// * `entries` - 反序列化后的Entry列表
+    pub fn record_entries(&self, slot: u64, entries: &[Entry]) {
+        self.inner.latest_slot.fetch_max(slot, Ordering::Relaxed);
+        *self.inner.stream_slot_at.lock().unwrap() = Some(Instant::now());
+        let mut pending = self.inner.pending.lock().unwrap();
+        if pending.is_empty() {
+            return;
+        }
+        let mut found = false;
+        for transaction in entries.iter().flat_map(|entry| &entry.transactions) {
+            let signature = match transaction.signatures.first() {
+                Some(signature) => signature,
+                None => continue,
+            };
+            if let Some(entry) = pending.get_mut(signature) {
+                if entry.seen.is_none() {
+                    entry.seen = Some((slot, Instant::now()));
+                    println!(
+                        "交易 {} 出现在slot {} (触发后 {}ms)",
+                        signature,
+                        slot,
+                        entry.trigger.at.elapsed().as_millis()
+                    );
+                    found = true;
+                }
+            }
+        }
+        if found {
+            self.inner.notify.notify_one();
+        }
+    }
+
+    /// 数据流中观察到的最新slot，数据流停滞时为通过RPC获取的slot
+    pub fn latest_slot(&self) -> u64 {
+        self.inner.latest_slot.load(Ordering::Relaxed)
+    }
+
+    /// 在节点的完整交易历史中重新查询已过期的交易，重建交易前确认它确实没有上链
+    ///
+    /// 查询到任意确认级别的状态时重新跟踪该交易并返回新的确认结果：已经confirmed的立即给出结果，
+    /// 只是processed的按已上链待确认处理；确实没有处理过时返回`Ok(None)`。
+    ///
+    /// # 参数
+    ///
+    /// * `signature` - 已过期的交易签名
+    /// * `trigger` - 触发交易的slot和时刻
+    pub async fn recheck_expired(&self, signature: Signature, trigger: Trigger) -> Result<Option<oneshot::Receiver<Confirmation>>, TradeError> {
+        let response = self.inner.rpc_client.get_signature_statuses_with_history(&[signature]).await?;
+        let status = match response.value.into_iter().next().flatten() {
+            Some(status) => status,
+            None => return Ok(None),
+        };
+        let (result_tx, result_rx) = oneshot::channel();
+        self.inner.pending.lock().unwrap().insert(
+            signature,
+            Pending {
+                trigger,
+                expiry_slot: status.slot,
+                submitted: Instant::now(),
+                seen: Some((status.slot, Instant::now())),
+                result_tx,
+            },
+        );
+        if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+            let result = match &status.err {
+                Some(err) => ConfirmStatus::Failed(err.to_string()),
+                None => ConfirmStatus::Landed,
+            };
+            self.resolve(&[signature], |_| Some((result.clone(), status.slot)));
+        }
+        Ok(Some(result_rx))
+    }
+
+    // 定期查询所有等待确认的交易，在数据流中看到交易时立即查询
+    fn spawn_poller(&self) {
+        let tracker = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    _ = tracker.inner.notify.notified() => {}
+                }
+                tracker.poll().await;
+            }
+        });
+    }
+
+    async fn poll(&self) {
+        let rpc_client = &self.inner.rpc_client;
+        let signatures: Vec<Signature> = self.inner.pending.lock().unwrap().keys().copied().collect();
+        if signatures.is_empty() {
+            return;
+        }
+
+        // 数据流停滞时按RPC的slot判断区块哈希是否过期
+        let stale = self
+            .inner
+            .stream_slot_at
+            .lock()
+            .unwrap()
+            .is_none_or(|at| at.elapsed() > STALE_SLOT_AFTER);
+        if stale {
+            match rpc_client.get_slot_with_commitment(CommitmentConfig::confirmed()).await {
+                Ok(slot) => {
+                    self.inner.latest_slot.fetch_max(slot, Ordering::Relaxed);
+                }
+                Err(e) => println!("查询当前slot失败: {:?}", e),
+            }
+        }
+
+        let mut statuses = HashMap::new();
+        let mut processed = Vec::new();
+        for chunk in signatures.chunks(MAX_STATUS_BATCH) {
+            match rpc_client.get_signature_statuses(chunk).await {
+                Ok(response) => {
+                    // 只处理过的交易仍可能被回滚，等到confirmed再报告结果，在此之前按已上链待确认处理
+                    for (signature, status) in chunk.iter().zip(response.value) {
+                        match status {
+                            Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+                                statuses.insert(*signature, status);
+                            }
+                            Some(status) => processed.push((*signature, status.slot)),
+                            None => {}
+                        }
+                    }
+                }
+                Err(e) => println!("查询交易状态失败: {:?}", e),
+            }
+        }
+        self.record_processed(&processed);
+        self.resolve(&signatures, |signature| {
+            statuses.get(signature).map(|status| {
+                let result = match &status.err {
+                    Some(err) => ConfirmStatus::Failed(err.to_string()),
+                    None => ConfirmStatus::Landed,
+                };
+                (result, status.slot)
+            })
+        });
+    }
+
+    // 已processed但尚未confirmed的交易与数据流中看到的一样，不再因区块哈希过期而失效
+    fn record_processed(&self, processed: &[(Signature, u64)]) {
+        let mut pending = self.inner.pending.lock().unwrap();
+        for (signature, slot) in processed {
+            if let Some(entry) = pending.get_mut(signature) {
+                if entry.seen.is_none() {
+                    entry.seen = Some((*slot, Instant::now()));
+                    println!("交易 {} 已在slot {} 处理，等待确认", signature, slot);
+                }
+            }
+        }
+    }
+
+    // 按查询到的状态和过期条件结束交易的跟踪
+    fn resolve(&self, signatures: &[Signature], status: impl Fn(&Signature) -> Option<(ConfirmStatus, u64)>) {
+        let latest_slot = self.latest_slot();
+        let mut pending = self.inner.pending.lock().unwrap();
+        for signature in signatures {
+            let entry = match pending.get(signature) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let (result, landed_slot) = match status(signature) {
+                Some((result, slot)) => (result, Some(entry.seen.map_or(slot, |(seen, _)| seen))),
+                None => {
+                    let timed_out = entry.submitted.elapsed() > self.inner.timeout;
+                    match entry.seen {
+                        // 数据流中看到或已processed的交易不会因区块哈希过期而失效，继续查询直到超时
+                        Some((slot, _)) if timed_out => {
+                            println!("交易 {} 已出现在slot {}，但未能通过RPC查询到confirmed的执行结果", signature, slot);
+                            (ConfirmStatus::Unknown, Some(slot))
+                        }
+                        None if timed_out || latest_slot > entry.expiry_slot => (ConfirmStatus::Expired, None),
+                        _ => continue,
+                    }
+                }
+            };
+            if let Some(entry) = pending.remove(signature) {
+                let confirmation = entry.confirmation(*signature, result, landed_slot);
+                let _ = entry.result_tx.send(confirmation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_transaction;
+
+    // 后台查询连接不到RPC，结果只由测试中的resolve决定
+    fn tracker(timeout: Duration) -> ConfirmationTracker {
+        ConfirmationTracker::with_timeout("http://127.0.0.1:1".to_string(), timeout)
+    }
+
+    fn seen_in_stream(tracker: &ConfirmationTracker, slot: u64) -> (Signature, oneshot::Receiver<Confirmation>) {
+        let payer = Keypair::new();
+        let transaction = system_transaction::transfer(&payer, &payer.pubkey(), 1, Hash::default());
+        let signature = transaction.signatures[0];
+        let result = tracker.watch(signature, Trigger::now(slot), Some(slot));
+        let entry = Entry {
+            num_hashes: 0,
+            hash: Hash::default(),
+            transactions: vec![transaction.into()],
+        };
+        tracker.record_entries(slot, &[entry]);
+        (signature, result)
+    }
+
+    #[tokio::test]
+    async fn seen_signature_outlives_blockhash_expiry() {
+        let tracker = tracker(DEFAULT_CONFIRM_TIMEOUT);
+        let (signature, mut result) = seen_in_stream(&tracker, 10);
+        tracker.record_entries(10 + MAX_PROCESSING_AGE as u64 + CONFIRMATION_LAG_SLOTS + 1, &[]);
+        tracker.resolve(&[signature], |_| None);
+        assert!(result.try_recv().is_err());
+
+        tracker.resolve(&[signature], |_| Some((ConfirmStatus::Landed, 11)));
+        let confirmation = result.await.unwrap();
+        assert!(confirmation.is_landed());
+        assert_eq!(confirmation.landed_slot, Some(10));
+    }
+
+    #[tokio::test]
+    async fn seen_signature_without_status_is_unknown() {
+        let tracker = tracker(Duration::ZERO);
+        let (signature, result) = seen_in_stream(&tracker, 10);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        tracker.resolve(&[signature], |_| None);
+        let confirmation = result.await.unwrap();
+        assert_eq!(confirmation.status, ConfirmStatus::Unknown);
+        assert!(!confirmation.is_landed());
+    }
+
+    #[tokio::test]
+    async fn unseen_signature_expires_after_confirmation_lag() {
+        let tracker = tracker(DEFAULT_CONFIRM_TIMEOUT);
+        let signature = Signature::new_unique();
+        let mut result = tracker.watch(signature, Trigger::now(10), Some(10));
+        tracker.record_entries(10 + MAX_PROCESSING_AGE as u64 + 1, &[]);
+        tracker.resolve(&[signature], |_| None);
+        assert!(result.try_recv().is_err());
+
+        tracker.record_entries(10 + MAX_PROCESSING_AGE as u64 + CONFIRMATION_LAG_SLOTS + 1, &[]);
+        tracker.resolve(&[signature], |_| None);
+        assert_eq!(result.await.unwrap().status, ConfirmStatus::Expired);
+    }
+
+    #[tokio::test]
+    async fn processed_signature_waits_for_confirmation() {
+        let tracker = tracker(DEFAULT_CONFIRM_TIMEOUT);
+        let signature = Signature::new_unique();
+        let mut result = tracker.watch(signature, Trigger::now(10), Some(10));
+        tracker.record_processed(&[(signature, 12)]);
+        tracker.record_entries(10 + MAX_PROCESSING_AGE as u64 + CONFIRMATION_LAG_SLOTS + 1, &[]);
+        tracker.resolve(&[signature], |_| None);
+        assert!(result.try_recv().is_err());
+
+        tracker.resolve(&[signature], |_| Some((ConfirmStatus::Landed, 12)));
+        let confirmation = result.await.unwrap();
+        assert!(confirmation.is_landed());
+        assert_eq!(confirmation.landed_slot, Some(12));
+    }
+}
@@ -0,0 +1,535 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+
+use crate::bonding_curve::{fee, with_slippage_min, BondingCurve, BASIS_POINTS};
+use crate::processor::mint_state::MintStates;
+use crate::strategy::{Budget, Intent, Order};
+
+/// 卖出默认滑点 (基点)
+pub const DEFAULT_SELL_SLIPPAGE_BPS: u64 = 1500;
+
+/// 分批止盈的一档
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TakeProfit {
+    // 持仓价值达到成本的倍数
+    pub multiple: f64,
+    // 卖出初始持仓的百分比
+    pub percent: f64,
+}
+
+/// 退出规则
+///
+/// 倍数按剩余持仓的卖出所得(扣除手续费)除以这部分持仓的买入成本(含手续费)计算，
+/// 价格来自数据流中实时更新的内盘曲线。未设置的规则不生效。
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExitRules {
+    // 分批止盈，按倍数从低到高依次触发，每档只触发一次
+    #[serde(default)]
+    pub take_profit: Vec<TakeProfit>,
+    // 亏损达到该百分比时全部卖出
+    #[serde(default)]
+    pub stop_loss_pct: Option<f64>,
+    // 从最高倍数回撤该百分比时全部卖出
+    #[serde(default)]
+    pub trailing_stop_pct: Option<f64>,
+    // 持仓超过该时间后全部卖出
+    #[serde(default)]
+    pub max_hold_ms: Option<u64>,
+    // 卖出滑点，最少接收按当前报价扣除滑点计算
+    #[serde(default = "default_sell_slippage_bps")]
+    pub slippage_bps: u64,
+}
+
+fn default_sell_slippage_bps() -> u64 {
+    DEFAULT_SELL_SLIPPAGE_BPS
+}
+
+impl Default for ExitRules {
+    fn default() -> Self {
+        Self {
+            take_profit: Vec::new(),
+            stop_loss_pct: None,
+            trailing_stop_pct: None,
+            max_hold_ms: None,
+            slippage_bps: DEFAULT_SELL_SLIPPAGE_BPS,
+        }
+    }
+}
+
+impl ExitRules {
+    /// 从环境变量读取退出规则
+    ///
+    /// 未设置MAX_HOLD_MS时使用SELL_DELAY_MS作为最长持仓时间，设置为0表示不限。
+    pub fn from_env() -> Self {
+        let pct_var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<f64>().ok()).filter(|pct| *pct > 0.0);
+        let max_hold_ms = env::var("MAX_HOLD_MS")
+            .or_else(|_| env::var("SELL_DELAY_MS"))
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(5000);
+        let slippage_bps = env::var("SELL_SLIPPAGE_BPS")
+            .or_else(|_| env::var("SLIPPAGE_BPS"))
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SELL_SLIPPAGE_BPS);
+        Self {
+            take_profit: env::var("TAKE_PROFIT")
+                .map(|v| parse_take_profit(&v).expect("环境变量TAKE_PROFIT无效"))
+                .unwrap_or_default(),
+            stop_loss_pct: pct_var("STOP_LOSS_PCT"),
+            trailing_stop_pct: pct_var("TRAILING_STOP_PCT"),
+            max_hold_ms: Some(max_hold_ms).filter(|ms| *ms > 0),
+            slippage_bps,
+        }
+    }
+}
+
+impl fmt::Display for ExitRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        for level in &self.take_profit {
+            parts.push(format!("{}倍卖出{}%", level.multiple, level.percent));
+        }
+        if let Some(pct) = self.stop_loss_pct {
+            parts.push(format!("止损{}%", pct));
+        }
+        if let Some(pct) = self.trailing_stop_pct {
+            parts.push(format!("移动止损{}%", pct));
+        }
+        if let Some(ms) = self.max_hold_ms {
+            parts.push(format!("最长持仓{}ms", ms));
+        }
+        if parts.is_empty() {
+            parts.push("不自动卖出".to_string());
+        }
+        write!(f, "{}，卖出滑点{}%", parts.join("，"), self.slippage_bps as f64 / 100.0)
+    }
+}
+
+/// 解析分批止盈配置，格式为逗号分隔的`倍数:百分比`，如`2:50,4:100`
+pub fn parse_take_profit(value: &str) -> Result<Vec<TakeProfit>, String> {
+    let mut levels = Vec::new();
+    for level in value.split(',').map(str::trim).filter(|level| !level.is_empty()) {
+        let (multiple, percent) = level.split_once(':').ok_or_else(|| format!("缺少百分比: {}", level))?;
+        let multiple: f64 = multiple.trim().parse().map_err(|_| format!("倍数无效: {}", level))?;
+        let percent: f64 = percent.trim().parse().map_err(|_| format!("百分比无效: {}", level))?;
+        levels.push(TakeProfit { multiple, percent });
+    }
+    levels.sort_by(|a, b| a.multiple.total_cmp(&b.multiple));
+    Ok(levels)
+}
+
+/// 触发卖出的原因
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitReason {
+    TakeProfit(f64),
+    StopLoss,
+    TrailingStop,
+    MaxHold,
+    // 策略发出的卖出意图
+    Strategy,
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitReason::TakeProfit(multiple) => write!(f, "{}倍止盈", multiple),
+            ExitReason::StopLoss => write!(f, "止损"),
+            ExitReason::TrailingStop => write!(f, "移动止损"),
+            ExitReason::MaxHold => write!(f, "超过最长持仓时间"),
+            ExitReason::Strategy => write!(f, "策略卖出"),
+        }
+    }
+}
+
+/// 需要发送的卖出
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExitOrder {
+    pub strategy: String,
+    pub mint: Pubkey,
+    pub token_amount: u64,
+    // 按当前曲线扣除手续费后的预计所得 (lamports)
+    pub expected_sol_output: u64,
+    // 预计的协议手续费 (lamports)
+    pub fee_lamports: u64,
+    // 扣除手续费后的最少接收 (lamports)
+    pub min_sol_output: u64,
+    pub reason: ExitReason,
+    // 持仓价值相对成本的倍数
+    pub multiple: f64,
+}
+
+/// 退出引擎中的一笔持仓
+pub struct ExitPosition {
+    pub rules: ExitRules,
+    pub opened_ms: u64,
+    // 买入成本，含手续费 (lamports)
+    pub cost_lamports: u64,
+    pub initial_tokens: u64,
+    // 尚未卖出且没有在途卖出的代币
+    pub remaining: u64,
+    // 已发出但尚未确认结果的卖出
+    pub in_flight: u64,
+    // 出现过的最高倍数
+    pub peak_multiple: f64,
+    // 下一档止盈，止盈卖出成功后才前进
+    next_level: usize,
+    // 在途止盈卖出成功后的下一档，卖出结果返回前不再触发止盈
+    pending_level: Option<usize>,
+    budget: Budget,
+}
+
+impl ExitPosition {
+    // 剩余持仓对应的成本
+    fn remaining_cost(&self) -> u64 {
+        if self.initial_tokens == 0 {
+            return 0;
+        }
+        (self.cost_lamports as u128 * self.remaining as u128 / self.initial_tokens as u128) as u64
+    }
+
+    // 按初始持仓百分比计算的代币数量，不超过剩余持仓
+    fn percent_of_initial(&self, percent: f64) -> u64 {
+        let basis_points = (percent * 100.0).round().max(0.0) as u64;
+        let amount = (self.initial_tokens as u128 * basis_points.min(BASIS_POINTS) as u128 / BASIS_POINTS as u128) as u64;
+        amount.min(self.remaining)
+    }
+}
+
+/// 持仓退出引擎
+///
+/// 每次曲线更新后检查所有持仓，按止盈、止损、移动止损和最长持仓时间发出卖出。
+/// 卖出发出后代币计入在途，结果通过`sell_result`报告；失败的卖出退回持仓，下次检查时重新触发。
+/// 持仓全部卖出后释放策略预算。
+#[derive(Default)]
+pub struct ExitEngine {
+    positions: HashMap<(String, Pubkey), ExitPosition>,
+}
+
+impl ExitEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录买入成交，同一策略重复买入同一代币时合并持仓
+    ///
+    /// # 参数
+    ///
+    /// * `order` - 成交的买入，持仓使用其中策略的预算和退出规则
+    /// * `token_amount` - 买入的代币数量
+    /// * `cost_lamports` - 买入成本，含手续费
+    /// * `now_ms` - 当前时间 (毫秒)
+    pub fn open(&mut self, order: Order, token_amount: u64, cost_lamports: u64, now_ms: u64) {
+        let mint = match order.intent {
+            Intent::Buy { mint, .. } => mint,
+            _ => return,
+        };
+        let Order { strategy, budget, exit, .. } = order;
+        let position = self.positions.entry((strategy, mint)).or_insert_with(|| ExitPosition {
+            rules: exit,
+            opened_ms: now_ms,
+            cost_lamports: 0,
+            initial_tokens: 0,
+            remaining: 0,
+            in_flight: 0,
+            peak_multiple: 0.0,
+            next_level: 0,
+            pending_level: None,
+            budget,
+        });
+        position.cost_lamports += cost_lamports;
+        position.initial_tokens += token_amount;
+        position.remaining += token_amount;
+    }
+
+    pub fn get(&self, strategy: &str, mint: &Pubkey) -> Option<&ExitPosition> {
+        self.positions.get(&(strategy.to_string(), *mint))
+    }
+
+    /// 是否有策略持有该代币
+    pub fn holds(&self, mint: &Pubkey) -> bool {
+        self.positions.keys().any(|(_, held)| held == mint)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// 按当前曲线检查所有持仓，返回需要发送的卖出
+    ///
+    /// # 参数
+    ///
+    /// * `mints` - 代币的曲线状态
+    /// * `fee_basis_points` - 协议手续费
+    /// * `now_ms` - 当前时间 (毫秒)
+    pub fn evaluate(&mut self, mints: &MintStates, fee_basis_points: u64, now_ms: u64) -> Vec<ExitOrder> {
+        let mut orders = Vec::new();
+        for ((strategy, mint), position) in &mut self.positions {
+            if position.remaining == 0 {
+                continue;
+            }
+            let expired = position
+                .rules
+                .max_hold_ms
+                .is_some_and(|ms| now_ms.saturating_sub(position.opened_ms) >= ms);
+            // 曲线未跟踪或已完成时无法报价，只有超过最长持仓时间时仍然卖出
+            let quote = mints.get(mint).and_then(|state| {
+                let proceeds = state.curve.sell_quote_with_fee(position.remaining, fee_basis_points).ok()?;
+                Some((&state.curve, proceeds))
+            });
+            let (curve, proceeds) = match quote {
+                Some(quote) => quote,
+                None => {
+                    if expired {
+                        orders.extend(take_unquoted(strategy, mint, position, ExitReason::MaxHold));
+                    }
+                    continue;
+                }
+            };
+            let multiple = proceeds as f64 / position.remaining_cost().max(1) as f64;
+            position.peak_multiple = position.peak_multiple.max(multiple);
+
+            let rules = &position.rules;
+            let exit_all = if expired {
+                Some(ExitReason::MaxHold)
+            } else if rules.stop_loss_pct.is_some_and(|pct| multiple <= 1.0 - pct / 100.0) {
+                Some(ExitReason::StopLoss)
+            } else if rules
+                .trailing_stop_pct
+                .is_some_and(|pct| multiple <= position.peak_multiple * (1.0 - pct / 100.0))
+            {
+                Some(ExitReason::TrailingStop)
+            } else {
+                None
+            };
+
+            let (token_amount, reason) = match exit_all {
+                Some(reason) => (position.remaining, reason),
+                // 上一笔止盈卖出的结果返回前不再触发
+                None if position.pending_level.is_some() => continue,
+                None => {
+                    // 一次跨过多档时合并卖出
+                    let mut token_amount = 0;
+                    let mut reached = None;
+                    let mut next_level = position.next_level;
+                    while let Some(level) = rules.take_profit.get(next_level) {
+                        if multiple < level.multiple {
+                            break;
+                        }
+                        token_amount += position.percent_of_initial(level.percent);
+                        reached = Some(level.multiple);
+                        next_level += 1;
+                    }
+                    match reached {
+                        Some(level) => {
+                            let token_amount = token_amount.min(position.remaining);
+                            if token_amount == 0 {
+                                // 这几档没有可卖出的数量，直接跳过
+                                position.next_level = next_level;
+                                continue;
+                            }
+                            position.pending_level = Some(next_level);
+                            (token_amount, ExitReason::TakeProfit(level))
+                        }
+                        None => continue,
+                    }
+                }
+            };
+            match take(strategy, mint, position, curve, fee_basis_points, token_amount, reason) {
+                Some(order) => orders.push(order),
+                None if matches!(reason, ExitReason::TakeProfit(_)) => position.pending_level = None,
+                None => {}
+            }
+        }
+        orders
+    }
+
+    /// 按策略意图卖出持仓的一部分
+    ///
+    /// # 参数
+    ///
+    /// * `strategy` - 发出意图的策略
+    /// * `mint` - 代币
+    /// * `basis_points` - 卖出剩余持仓的比例
+    /// * `curve` - 当前曲线
+    /// * `fee_basis_points` - 协议手续费
+    pub fn sell_share(
+        &mut self,
+        strategy: &str,
+        mint: &Pubkey,
+        basis_points: u64,
+        curve: &BondingCurve,
+        fee_basis_points: u64,
+    ) -> Option<ExitOrder> {
+        let position = self.positions.get_mut(&(strategy.to_string(), *mint))?;
+        let token_amount =
+            (position.remaining as u128 * basis_points.min(BASIS_POINTS) as u128 / BASIS_POINTS as u128) as u64;
+        take(strategy, mint, position, curve, fee_basis_points, token_amount, ExitReason::Strategy)
+    }
+
+    /// 报告卖出结果，返回持仓是否已全部卖出
+    ///
+    /// 卖出失败时代币退回持仓；止盈卖出成功后才前进到下一档，失败时同一档会重新触发。
+    pub fn sell_result(&mut self, exit: &ExitOrder, success: bool) -> bool {
+        let key = (exit.strategy.clone(), exit.mint);
+        let mint = &exit.mint;
+        let position = match self.positions.get_mut(&key) {
+            Some(position) => position,
+            None => return false,
+        };
+        if matches!(exit.reason, ExitReason::TakeProfit(_)) {
+            if let Some(level) = position.pending_level.take() {
+                if success {
+                    position.next_level = level;
+                }
+            }
+        }
+        let token_amount = exit.token_amount.min(position.in_flight);
+        position.in_flight -= token_amount;
+        if !success {
+            position.remaining += token_amount;
+            return false;
+        }
+        if position.remaining > 0 || position.in_flight > 0 {
+            return false;
+        }
+        if let Some(position) = self.positions.remove(&key) {
+            position.budget.release(mint);
+        }
+        true
+    }
+}
+
+// 将代币从持仓转入在途并生成卖出
+fn take(
+    strategy: &str,
+    mint: &Pubkey,
+    position: &mut ExitPosition,
+    curve: &BondingCurve,
+    fee_basis_points: u64,
+    token_amount: u64,
+    reason: ExitReason,
+) -> Option<ExitOrder> {
+    if token_amount == 0 {
+        return None;
+    }
+    let proceeds = curve.sell_quote(token_amount).ok()?;
+    let fee_lamports = fee(proceeds, fee_basis_points);
+    let expected = proceeds - fee_lamports;
+    let multiple = curve.sell_quote_with_fee(position.remaining, fee_basis_points).ok()? as f64 / position.remaining_cost().max(1) as f64;
+    position.remaining -= token_amount;
+    position.in_flight += token_amount;
+    Some(ExitOrder {
+        strategy: strategy.to_string(),
+        mint: *mint,
+        token_amount,
+        expected_sol_output: expected,
+        fee_lamports,
+        min_sol_output: with_slippage_min(expected, position.rules.slippage_bps),
+        reason,
+        multiple,
+    })
+}
+
+// 无法报价时按最少接收0卖出全部剩余持仓
+fn take_unquoted(strategy: &str, mint: &Pubkey, position: &mut ExitPosition, reason: ExitReason) -> Option<ExitOrder> {
+    let token_amount = position.remaining;
+    if token_amount == 0 {
+        return None;
+    }
+    position.remaining = 0;
+    position.in_flight += token_amount;
+    Some(ExitOrder {
+        strategy: strategy.to_string(),
+        mint: *mint,
+        token_amount,
+        expected_sol_output: 0,
+        fee_lamports: 0,
+        min_sol_output: 0,
+        reason,
+        multiple: 0.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bonding_curve::GlobalAccount;
+    use crate::processor::mint_state::TradeSide;
+    use solana_sdk::signature::Signature;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn order(mint: Pubkey, rules: ExitRules) -> Order {
+        Order {
+            strategy: "test".to_string(),
+            intent: Intent::Buy { mint, lamports: SOL },
+            budget: Budget::unlimited(),
+            exit: rules,
+        }
+    }
+
+    // 按曲线买入1 SOL后其他交易者推高价格，返回曲线状态和买到的代币数量
+    fn pumped_mint(mint: Pubkey) -> (MintStates, u64) {
+        let mut mints = MintStates::default();
+        let state = mints.track(mint, &GlobalAccount::default(), Pubkey::new_unique(), 1, |_| true);
+        let tokens = state.curve.buy_quote(SOL).unwrap();
+        state.record_trade(1, Signature::default(), TradeSide::Buy, Pubkey::new_unique(), tokens, u64::MAX).unwrap();
+        let pump = state.curve.buy_quote(30 * SOL).unwrap();
+        state.record_trade(2, Signature::default(), TradeSide::Buy, Pubkey::new_unique(), pump, u64::MAX).unwrap();
+        (mints, tokens)
+    }
+
+    #[test]
+    fn failed_take_profit_refires_same_level() {
+        let mint = Pubkey::new_unique();
+        let (mints, tokens) = pumped_mint(mint);
+        let rules = ExitRules {
+            take_profit: vec![TakeProfit { multiple: 2.0, percent: 50.0 }],
+            ..ExitRules::default()
+        };
+        let mut engine = ExitEngine::new();
+        engine.open(order(mint, rules), tokens, SOL, 0);
+
+        let first = engine.evaluate(&mints, 0, 0);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].reason, ExitReason::TakeProfit(2.0));
+        assert_eq!(first[0].token_amount, tokens / 2);
+        // 结果返回前不重复触发
+        assert!(engine.evaluate(&mints, 0, 0).is_empty());
+
+        engine.sell_result(&first[0], false);
+        let retry = engine.evaluate(&mints, 0, 0);
+        assert_eq!(retry, first);
+
+        assert!(!engine.sell_result(&retry[0], true));
+        assert!(engine.evaluate(&mints, 0, 0).is_empty());
+        assert_eq!(engine.get("test", &mint).unwrap().remaining, tokens - tokens / 2);
+    }
+
+    #[test]
+    fn max_hold_fires_without_curve() {
+        let mint = Pubkey::new_unique();
+        let rules = ExitRules {
+            max_hold_ms: Some(1_000),
+            ..ExitRules::default()
+        };
+        let mut engine = ExitEngine::new();
+        engine.open(order(mint, rules), 500, SOL, 0);
+        let mints = MintStates::default();
+
+        assert!(engine.evaluate(&mints, 100, 999).is_empty());
+        let orders = engine.evaluate(&mints, 100, 1_000);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].reason, ExitReason::MaxHold);
+        assert_eq!(orders[0].token_amount, 500);
+        assert_eq!(orders[0].min_sol_output, 0);
+        assert!(engine.sell_result(&orders[0], true));
+        assert!(!engine.holds(&mint));
+    }
+}
@@ -0,0 +1,422 @@
+use solana_sdk::compute_budget;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use crate::message::MessageView;
+use crate::processor::mint_state::TradeSide;
+use crate::transaction::DEFAULT_COMPUTE_UNIT_PRICE;
+
+/// 参与统计的最近slot数
+const FEE_WINDOW_SLOTS: u64 = 50;
+/// 最近slot内最多保留的费用样本
+const MAX_RECENT_SAMPLES: usize = 4096;
+/// 每个代币最多保留的费用样本
+const MAX_MINT_SAMPLES: usize = 64;
+/// 最多跟踪的代币数量
+const MAX_MINTS: usize = 4096;
+/// 按代币统计至少需要的样本数，不足时使用最近slot的所有样本
+const MIN_MINT_SAMPLES: usize = 3;
+/// 每种指令保留的计算单元用量样本
+const MAX_USAGE_SAMPLES: usize = 32;
+/// 单笔交易的计算单元上限
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+// ComputeBudget指令的类型字节
+const SET_COMPUTE_UNIT_LIMIT_TAG: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+
+/// 交易中ComputeBudget指令设置的计算预算
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ComputeBudget {
+    // 优先级费用 (micro-lamports/CU)
+    pub unit_price: Option<u64>,
+    pub unit_limit: Option<u32>,
+}
+
+impl ComputeBudget {
+    /// 解析消息中的ComputeBudget指令
+    pub fn from_message<M: MessageView + ?Sized>(message: &M) -> Self {
+        let keys = message.static_keys();
+        let mut budget = Self::default();
+        for instruction in message.instructions() {
+            if keys.get(instruction.program_id_index as usize) != Some(&compute_budget::id()) {
+                continue;
+            }
+            match instruction.data.split_first() {
+                Some((&SET_COMPUTE_UNIT_PRICE_TAG, rest)) if rest.len() >= 8 => {
+                    budget.unit_price = Some(u64::from_le_bytes(rest[..8].try_into().unwrap()));
+                }
+                Some((&SET_COMPUTE_UNIT_LIMIT_TAG, rest)) if rest.len() >= 4 => {
+                    budget.unit_limit = Some(u32::from_le_bytes(rest[..4].try_into().unwrap()));
+                }
+                _ => {}
+            }
+        }
+        budget
+    }
+}
+
+/// 优先级费用的计算方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeeMode {
+    /// 固定费用 (micro-lamports/CU)
+    Fixed(u64),
+    /// 数据流中同一代币或最近slot的Pump交易费用的百分位数乘以倍数
+    Percentile { percentile: f64, multiplier: f64 },
+}
+
+impl FromStr for FeeMode {
+    type Err = String;
+
+    /// 解析`200000`、`fixed:200000`、`p75`或`p75x1.5`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(rest) = s.strip_prefix('p') {
+            let (percentile, multiplier) = match rest.split_once('x') {
+                Some((percentile, multiplier)) => (percentile, multiplier),
+                None => (rest, "1"),
+            };
+            let percentile: f64 = percentile.parse().map_err(|_| format!("无效的百分位数: {}", s))?;
+            let multiplier: f64 = multiplier.parse().map_err(|_| format!("无效的倍数: {}", s))?;
+            if !(0.0..=100.0).contains(&percentile) || multiplier <= 0.0 {
+                return Err(format!("百分位数应在0到100之间，倍数应大于0: {}", s));
+            }
+            return Ok(FeeMode::Percentile { percentile, multiplier });
+        }
+        let fixed = s.strip_prefix("fixed:").unwrap_or(s);
+        fixed
+            .parse()
+            .map(FeeMode::Fixed)
+            .map_err(|_| format!("无效的优先级费用: {}", s))
+    }
+}
+
+impl fmt::Display for FeeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeMode::Fixed(price) => write!(f, "固定 {}", price),
+            FeeMode::Percentile { percentile, multiplier } if *multiplier == 1.0 => write!(f, "p{}", percentile),
+            FeeMode::Percentile { percentile, multiplier } => write!(f, "p{} x{}", percentile, multiplier),
+        }
+    }
+}
+
+/// 优先级费用和计算单元的设置
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeePolicy {
+    pub mode: FeeMode,
+    // 费用下限 (micro-lamports/CU)
+    pub min: u64,
+    // 费用上限 (micro-lamports/CU)，重建交易提高费用时也不超过该值
+    pub max: u64,
+    // 计算单元上限在实测用量之上预留的百分比
+    pub compute_unit_margin_pct: u64,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            mode: FeeMode::Fixed(DEFAULT_COMPUTE_UNIT_PRICE),
+            min: 0,
+            max: 1_000_000,
+            compute_unit_margin_pct: 20,
+        }
+    }
+}
+
+impl FeePolicy {
+    // 从环境变量读取费用设置，未设置的使用默认值，PRIORITY_FEE无效时返回错误
+    pub fn from_env() -> Result<Self, String> {
+        let default = Self::default();
+        let var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let mode = match env::var("PRIORITY_FEE") {
+            Ok(value) => value.parse().map_err(|e| format!("环境变量PRIORITY_FEE: {}", e))?,
+            Err(_) => default.mode,
+        };
+        Ok(Self {
+            mode,
+            min: var("PRIORITY_FEE_MIN").unwrap_or(default.min),
+            max: var("MAX_COMPUTE_UNIT_PRICE").unwrap_or(default.max),
+            compute_unit_margin_pct: var("COMPUTE_UNIT_MARGIN_PCT").unwrap_or(default.compute_unit_margin_pct),
+        })
+    }
+}
+
+impl fmt::Display for FeePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}，范围 {} - {} micro-lamports/CU", self.mode, self.min, self.max)
+    }
+}
+
+/// 需要分别估算计算单元的指令
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ComputeKind {
+    /// 创建ATA并买入
+    Buy,
+    Sell,
+}
+
+impl ComputeKind {
+    /// 没有实测用量时的计算单元上限
+    pub fn default_limit(&self) -> u32 {
+        match self {
+            ComputeKind::Buy => 150_000,
+            ComputeKind::Sell => 120_000,
+        }
+    }
+}
+
+impl From<TradeSide> for ComputeKind {
+    fn from(side: TradeSide) -> Self {
+        match side {
+            TradeSide::Buy => ComputeKind::Buy,
+            TradeSide::Sell => ComputeKind::Sell,
+        }
+    }
+}
+
+/// 优先级费用预言机
+///
+/// 从数据流中Pump交易的ComputeBudget指令学习当前有竞争力的优先级费用：同一代币的样本足够时使用该代币的，
+/// 否则使用最近若干slot内的所有样本，按策略取百分位数并限制在上下限之间。
+/// 计算单元上限按每种指令最近的实测用量加上预留比例计算，没有实测时使用默认值。
+/// 克隆共享样本和费用设置。
+#[derive(Clone)]
+pub struct FeeOracle {
+    state: Arc<StdMutex<FeeState>>,
+}
+
+#[derive(Default)]
+struct FeeState {
+    policy: FeePolicy,
+    latest_slot: u64,
+    // 最近slot内的 (slot, 费用)
+    recent: VecDeque<(u64, u64)>,
+    mints: HashMap<Pubkey, MintFees>,
+    // 每种指令最近的计算单元用量
+    usage: HashMap<ComputeKind, VecDeque<u32>>,
+}
+
+struct MintFees {
+    last_slot: u64,
+    prices: VecDeque<u64>,
+}
+
+impl FeeOracle {
+    pub fn new(policy: FeePolicy) -> Self {
+        Self {
+            state: Arc::new(StdMutex::new(FeeState {
+                policy,
+                ..FeeState::default()
+            })),
+        }
+    }
+
+    /// 修改费用设置，已有的样本保留，所有克隆同时生效
+    pub fn set_policy(&self, policy: FeePolicy) {
+        self.state.lock().unwrap().policy = policy;
+    }
+
+    /// 记录数据流中一笔Pump交易的优先级费用
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - 交易所在的slot
+    /// * `mint` - 交易的代币
+    /// * `unit_price` - 交易设置的优先级费用 (micro-lamports/CU)
+    pub fn record(&self, slot: u64, mint: &Pubkey, unit_price: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.latest_slot = state.latest_slot.max(slot);
+        let oldest = state.latest_slot.saturating_sub(FEE_WINDOW_SLOTS);
+
+        state.recent.push_back((slot, unit_price));
+        while state
+            .recent
+            .front()
+            .is_some_and(|(slot, _)| *slot < oldest)
+            || state.recent.len() > MAX_RECENT_SAMPLES
+        {
+            state.recent.pop_front();
+        }
+
+        if state.mints.len() >= MAX_MINTS && !state.mints.contains_key(mint) {
+            state.mints.retain(|_, fees| fees.last_slot >= oldest);
+        }
+        let fees = state.mints.entry(*mint).or_insert_with(|| MintFees {
+            last_slot: slot,
+            prices: VecDeque::with_capacity(MAX_MINT_SAMPLES),
+        });
+        fees.last_slot = fees.last_slot.max(slot);
+        if fees.prices.len() == MAX_MINT_SAMPLES {
+            fees.prices.pop_front();
+        }
+        fees.prices.push_back(unit_price);
+    }
+
+    /// 同一代币或最近slot内优先级费用的百分位数，没有样本时返回None
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 要交易的代币，为None时只使用最近slot的样本
+    /// * `percentile` - 百分位数，0到100
+    pub fn percentile(&self, mint: Option<&Pubkey>, percentile: f64) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        let mint_prices = mint
+            .and_then(|mint| state.mints.get(mint))
+            .filter(|fees| fees.prices.len() >= MIN_MINT_SAMPLES);
+        let mut prices: Vec<u64> = match mint_prices {
+            Some(fees) => fees.prices.iter().copied().collect(),
+            None => state.recent.iter().map(|(_, price)| *price).collect(),
+        };
+        if prices.is_empty() {
+            return None;
+        }
+        prices.sort_unstable();
+        // 最近秩法
+        let rank = ((percentile / 100.0) * prices.len() as f64).ceil() as usize;
+        Some(prices[rank.clamp(1, prices.len()) - 1])
+    }
+
+    /// 按策略计算交易使用的优先级费用 (micro-lamports/CU)
+    ///
+    /// 按百分位数计算但还没有样本时使用默认费用。
+    pub fn compute_unit_price(&self, mint: Option<&Pubkey>) -> u64 {
+        let policy = self.state.lock().unwrap().policy;
+        let price = match policy.mode {
+            FeeMode::Fixed(price) => price,
+            FeeMode::Percentile { percentile, multiplier } => match self.percentile(mint, percentile) {
+                Some(price) => (price as f64 * multiplier).round() as u64,
+                None => DEFAULT_COMPUTE_UNIT_PRICE,
+            },
+        };
+        price.clamp(policy.min, policy.max.max(policy.min))
+    }
+
+    /// 记录一笔已上链交易实际消耗的计算单元
+    pub fn record_compute_units(&self, kind: ComputeKind, units: u32) {
+        let mut state = self.state.lock().unwrap();
+        let usage = state.usage.entry(kind).or_default();
+        if usage.len() == MAX_USAGE_SAMPLES {
+            usage.pop_front();
+        }
+        usage.push_back(units);
+    }
+
+    /// 按最近的最大实测用量加上预留比例计算计算单元上限
+    pub fn compute_unit_limit(&self, kind: ComputeKind) -> u32 {
+        let state = self.state.lock().unwrap();
+        let measured = state.usage.get(&kind).and_then(|usage| usage.iter().max().copied());
+        match measured {
+            Some(units) => {
+                let limit = units as u64 * (100 + state.policy.compute_unit_margin_pct) / 100;
+                limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+            }
+            None => kind.default_limit(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oracle(mode: FeeMode) -> FeeOracle {
+        FeeOracle::new(FeePolicy {
+            mode,
+            ..FeePolicy::default()
+        })
+    }
+
+    #[test]
+    fn parses_fee_modes() {
+        assert_eq!("200000".parse::<FeeMode>(), Ok(FeeMode::Fixed(200_000)));
+        assert_eq!("fixed:5".parse::<FeeMode>(), Ok(FeeMode::Fixed(5)));
+        assert_eq!("p75".parse::<FeeMode>(), Ok(FeeMode::Percentile { percentile: 75.0, multiplier: 1.0 }));
+        assert_eq!("p90x1.5".parse::<FeeMode>(), Ok(FeeMode::Percentile { percentile: 90.0, multiplier: 1.5 }));
+        assert!("p101".parse::<FeeMode>().is_err());
+        assert!("p50x0".parse::<FeeMode>().is_err());
+        assert!("fast".parse::<FeeMode>().is_err());
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let fees = oracle(FeeMode::Fixed(0));
+        assert_eq!(fees.percentile(None, 50.0), None);
+        for price in (1..=10).rev() {
+            fees.record(100, &Pubkey::new_unique(), price * 10);
+        }
+        assert_eq!(fees.percentile(None, 0.0), Some(10));
+        assert_eq!(fees.percentile(None, 50.0), Some(50));
+        assert_eq!(fees.percentile(None, 75.0), Some(80));
+        assert_eq!(fees.percentile(None, 100.0), Some(100));
+    }
+
+    #[test]
+    fn percentile_prefers_mint_samples_and_drops_old_slots() {
+        let fees = oracle(FeeMode::Fixed(0));
+        let mint = Pubkey::new_unique();
+        fees.record(100, &Pubkey::new_unique(), 1_000);
+        fees.record(100, &mint, 10);
+        fees.record(100, &mint, 20);
+        // 同一代币的样本不足时使用最近slot的所有样本
+        assert_eq!(fees.percentile(Some(&mint), 100.0), Some(1_000));
+        fees.record(101, &mint, 30);
+        assert_eq!(fees.percentile(Some(&mint), 100.0), Some(30));
+
+        // 超出窗口的样本不再参与最近slot的统计
+        fees.record(100 + FEE_WINDOW_SLOTS + 1, &Pubkey::new_unique(), 5);
+        assert_eq!(fees.percentile(None, 100.0), Some(30));
+    }
+
+    #[test]
+    fn compute_unit_price_applies_multiplier_and_bounds() {
+        let fees = oracle(FeeMode::Percentile { percentile: 50.0, multiplier: 1.5 });
+        assert_eq!(fees.compute_unit_price(None), DEFAULT_COMPUTE_UNIT_PRICE);
+        fees.record(1, &Pubkey::new_unique(), 1_000);
+        assert_eq!(fees.compute_unit_price(None), 1_500);
+
+        fees.set_policy(FeePolicy {
+            mode: FeeMode::Percentile { percentile: 50.0, multiplier: 1.5 },
+            min: 2_000,
+            max: 3_000,
+            compute_unit_margin_pct: 20,
+        });
+        assert_eq!(fees.compute_unit_price(None), 2_000);
+        fees.record(1, &Pubkey::new_unique(), 9_000);
+        fees.record(1, &Pubkey::new_unique(), 9_000);
+        assert_eq!(fees.compute_unit_price(None), 3_000);
+    }
+
+    #[test]
+    fn clones_share_policy() {
+        let fees = oracle(FeeMode::Fixed(100));
+        let clone = fees.clone();
+        fees.set_policy(FeePolicy {
+            mode: FeeMode::Fixed(700),
+            ..FeePolicy::default()
+        });
+        assert_eq!(clone.compute_unit_price(None), 700);
+    }
+
+    #[test]
+    fn compute_unit_limit_adds_margin_to_recent_max() {
+        let fees = oracle(FeeMode::Fixed(0));
+        assert_eq!(fees.compute_unit_limit(ComputeKind::Buy), ComputeKind::Buy.default_limit());
+        fees.record_compute_units(ComputeKind::Buy, 60_000);
+        fees.record_compute_units(ComputeKind::Buy, 50_000);
+        assert_eq!(fees.compute_unit_limit(ComputeKind::Buy), 72_000);
+        assert_eq!(fees.compute_unit_limit(ComputeKind::Sell), ComputeKind::Sell.default_limit());
+
+        fees.record_compute_units(ComputeKind::Buy, 1_300_000);
+        assert_eq!(fees.compute_unit_limit(ComputeKind::Buy), MAX_COMPUTE_UNIT_LIMIT);
+        // 只保留最近的样本
+        for _ in 0..MAX_USAGE_SAMPLES {
+            fees.record_compute_units(ComputeKind::Buy, 10_000);
+        }
+        assert_eq!(fees.compute_unit_limit(ComputeKind::Buy), 12_000);
+    }
+}
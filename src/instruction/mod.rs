@@ -1,209 +1,674 @@
+use solana_sdk::message::AccountKeys;
 use solana_sdk::pubkey::Pubkey;
-use std::error::Error;
 use borsh::BorshDeserialize;
+use std::fmt;
 
-// 定义CreateEvent参数结构，用于Borsh反序列化
-#[derive(BorshDeserialize, Debug)]
-struct CreateArgs {
-    name: String,
-    symbol: String,
-    uri: String,
+use crate::bonding_curve::bonding_curve_pda;
+
+// 创建代币的指令识别字节
+const CREATE_DISCRIMINATOR: [u8; 8] = [0x18, 0x1e, 0xc8, 0x28, 0x05, 0x1c, 0x07, 0x77];
+// 买入的指令识别字节
+pub const BUY_DISCRIMINATOR: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
+// 卖出的指令识别字节
+pub const SELL_DISCRIMINATOR: [u8; 8] = [0x33, 0xe6, 0x85, 0xa4, 0x01, 0x7f, 0x83, 0xad];
+// 提取迁移资金的指令识别字节
+const WITHDRAW_DISCRIMINATOR: [u8; 8] = [0xb7, 0x12, 0x46, 0x9c, 0x94, 0x6d, 0xa1, 0x22];
+// 设置Global参数的指令识别字节
+const SET_PARAMS_DISCRIMINATOR: [u8; 8] = [0x1b, 0xea, 0xb2, 0x34, 0x93, 0x02, 0xbb, 0x8d];
+// 初始化Global账户的指令识别字节
+const INITIALIZE_DISCRIMINATOR: [u8; 8] = [0xaf, 0xaf, 0x6d, 0x1f, 0x0d, 0x98, 0x9b, 0xed];
+// 扩展账户空间的指令识别字节
+const EXTEND_ACCOUNT_DISCRIMINATOR: [u8; 8] = [0xea, 0x66, 0xc2, 0xcb, 0x96, 0x48, 0x3e, 0xe5];
+// 迁移到AMM的指令识别字节
+const MIGRATE_DISCRIMINATOR: [u8; 8] = [0x9b, 0xea, 0xe7, 0x92, 0xec, 0x9e, 0xa2, 0x1e];
+
+const DISCRIMINATOR_SIZE: usize = 8;
+// Buy/Sell参数长度：两个u64
+const TRADE_ARGS_SIZE: usize = 16;
+// SetParams参数长度：公钥和五个u64
+const SET_PARAMS_ARGS_SIZE: usize = 72;
+
+/// 指令解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstructionError {
+    /// 指令数据长度不足
+    DataTooShort { expected: usize, actual: usize },
+    /// 未知的指令识别字节
+    UnknownDiscriminator([u8; 8]),
+    /// 字符串字段不是有效的UTF-8
+    InvalidUtf8(&'static str),
+    /// 参数无法反序列化
+    InvalidArgs(String),
+    /// 指令的账户数量不足
+    NotEnoughAccounts { expected: usize, actual: usize },
+    /// 账户与按种子推导的地址不符
+    AccountMismatch { name: &'static str, expected: Pubkey, actual: Pubkey },
+    /// 账户来自尚未加载的地址查找表
+    AccountNotLoaded { index: u8 },
 }
 
-// 定义Buy参数结构，用于Borsh反序列化
-#[derive(BorshDeserialize, Debug)]
-struct BuyArgs {
-    amount: u64,
-    max_sol_cost: u64,
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::DataTooShort { expected, actual } => {
+                write!(f, "指令数据长度不足: 需要{}字节，实际{}字节", expected, actual)
+            }
+            InstructionError::UnknownDiscriminator(discriminator) => {
+                write!(f, "未知的指令识别字节: {:02x?}", discriminator)
+            }
+            InstructionError::InvalidUtf8(field) => write!(f, "{}不是有效的UTF-8", field),
+            InstructionError::InvalidArgs(msg) => write!(f, "指令参数无效: {}", msg),
+            InstructionError::NotEnoughAccounts { expected, actual } => {
+                write!(f, "指令账户数量不足: 需要{}个，实际{}个", expected, actual)
+            }
+            InstructionError::AccountMismatch { name, expected, actual } => {
+                write!(f, "{}账户不符: 应为{}，实际为{}", name, expected, actual)
+            }
+            InstructionError::AccountNotLoaded { index } => {
+                write!(f, "账户索引{}来自尚未加载的查找表", index)
+            }
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct CreateEventInstruction {
+impl std::error::Error for InstructionError {}
+
+/// Create指令参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateArgs {
     pub name: String,
     pub symbol: String,
     pub uri: String,
-    pub user: Pubkey,
+    // 新版本指令在末尾携带创建者公钥
+    pub creator: Option<Pubkey>,
 }
 
-#[derive(Debug)]
-pub struct BuyInstruction {
+/// Buy指令参数
+#[derive(BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuyArgs {
     pub amount: u64,
     pub max_sol_cost: u64,
 }
 
-// 创建事件的指令识别字节
-const CREATE_EVENT_DISCRIMINATOR: [u8; 8] = [0x18, 0x1e, 0xc8, 0x28, 0x05, 0x1c, 0x07, 0x77];
-// 购买事件的指令识别字节
-const BUY_EVENT_DISCRIMINATOR: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
-
-pub fn parse_instruction_data(data: &[u8]) -> Result<(String, Option<CreateEventInstruction>, Option<BuyInstruction>), Box<dyn Error>> {
-    if data.len() < 8 {
-        return Err("Instruction data too short".into());
-    }
-
-    let discriminator = &data[0..8];
-    
-    match discriminator {
-        // CreateEvent 指令 [0x18, 0x1e, 0xc8, 0x28, 0x05, 0x1c, 0x07, 0x77]
-        discriminator if discriminator == CREATE_EVENT_DISCRIMINATOR => {
-            // 尝试使用Borsh结构解析
-            if let Ok(args) = CreateArgs::try_from_slice(&data[8..]) {
-                // 从指令数据末尾提取用户公钥
-                let user_offset = data.len() - 32;
-                let user = if user_offset >= 8 && user_offset + 32 <= data.len() {
-                    Pubkey::new_from_array(data[user_offset..user_offset + 32].try_into().unwrap())
-                } else {
-                    Pubkey::default()
-                };
-
-                let instruction = CreateEventInstruction {
-                    name: args.name,
-                    symbol: args.symbol,
-                    uri: args.uri,
-                    user,
-                };
-                return Ok(("CreateEvent".to_string(), Some(instruction), None));
-            }
+/// Sell指令参数
+#[derive(BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SellArgs {
+    pub amount: u64,
+    pub min_sol_output: u64,
+}
 
-            // 如果Borsh解析失败，回退到手动解析
-            let mut offset = 8;
-            
-            // 解析名称
-            if offset + 4 > data.len() {
-                return Err("Insufficient data for name length".into());
-            }
-            let name_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            if offset + name_len > data.len() {
-                return Err("Insufficient data for name".into());
-            }
-            let name = String::from_utf8(data[offset..offset + name_len].to_vec())?;
-            offset += name_len;
+/// SetParams指令参数
+#[derive(BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SetParamsArgs {
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+}
 
-            // 解析符号
-            if offset + 4 > data.len() {
-                return Err("Insufficient data for symbol length".into());
-            }
-            let symbol_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            if offset + symbol_len > data.len() {
-                return Err("Insufficient data for symbol".into());
-            }
-            let symbol = String::from_utf8(data[offset..offset + symbol_len].to_vec())?;
-            offset += symbol_len;
+/// Create指令账户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateAccounts {
+    pub mint: Pubkey,
+    pub mint_authority: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub global: Pubkey,
+    pub metadata: Pubkey,
+    pub user: Pubkey,
+}
 
-            // 解析URI
-            if offset + 4 > data.len() {
-                return Err("Insufficient data for URI length".into());
-            }
-            let uri_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            if offset + uri_len > data.len() {
-                return Err("Insufficient data for URI".into());
-            }
-            let uri = String::from_utf8(data[offset..offset + uri_len].to_vec())?;
-            offset += uri_len;
+/// Buy和Sell指令共用的账户布局
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeAccounts {
+    pub global: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub associated_user: Pubkey,
+    pub user: Pubkey,
+}
 
-            // 解析用户公钥
-            if offset + 32 > data.len() {
-                return Err("Insufficient data for user pubkey".into());
-            }
-            let user = Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+/// Withdraw指令账户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawAccounts {
+    pub global: Pubkey,
+    pub last_withdraw: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub associated_user: Pubkey,
+    pub user: Pubkey,
+}
 
-            let instruction = CreateEventInstruction { name, symbol, uri, user };
-            Ok(("CreateEvent".to_string(), Some(instruction), None))
-        }
+/// Initialize和SetParams指令账户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalAccounts {
+    pub global: Pubkey,
+    pub user: Pubkey,
+}
 
-        // Buy 指令 [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea]
-        discriminator if discriminator == BUY_EVENT_DISCRIMINATOR => {
-            // 尝试使用Borsh结构解析
-            if let Ok(args) = BuyArgs::try_from_slice(&data[8..]) {
-                let instruction = BuyInstruction {
-                    amount: args.amount,
-                    max_sol_cost: args.max_sol_cost,
-                };
-                return Ok(("Buy".to_string(), None, Some(instruction)));
-            }
+/// ExtendAccount指令账户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendAccountAccounts {
+    pub account: Pubkey,
+    pub user: Pubkey,
+}
 
-            // 如果Borsh解析失败，回退到手动解析
-            if data.len() < 24 {
-                return Err("Insufficient data for Buy instruction".into());
-            }
-            
-            let amount = u64::from_le_bytes(data[8..16].try_into().unwrap());
-            let max_sol_cost = u64::from_le_bytes(data[16..24].try_into().unwrap());
+/// Migrate指令账户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateAccounts {
+    pub global: Pubkey,
+    pub withdraw_authority: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub user: Pubkey,
+    pub pool: Pubkey,
+}
+
+/// 解析后的Pump程序指令，包含参数和按位置命名的账户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PumpInstruction {
+    Create { args: CreateArgs, accounts: CreateAccounts },
+    Buy { args: BuyArgs, accounts: TradeAccounts },
+    Sell { args: SellArgs, accounts: TradeAccounts },
+    Withdraw { accounts: WithdrawAccounts },
+    SetParams { args: SetParamsArgs, accounts: GlobalAccounts },
+    Initialize { accounts: GlobalAccounts },
+    ExtendAccount { accounts: ExtendAccountAccounts },
+    Migrate { accounts: MigrateAccounts },
+}
 
-            let instruction = BuyInstruction { amount, max_sol_cost };
-            Ok(("Buy".to_string(), None, Some(instruction)))
+impl PumpInstruction {
+    /// 解析指令数据和账户
+    ///
+    /// # 参数
+    ///
+    /// * `data` - 指令数据，以8字节识别字节开头
+    /// * `accounts` - 按指令账户顺序排列的公钥
+    pub fn decode(data: &[u8], accounts: &[Pubkey]) -> Result<Self, InstructionError> {
+        if data.len() < DISCRIMINATOR_SIZE {
+            return Err(InstructionError::DataTooShort {
+                expected: DISCRIMINATOR_SIZE,
+                actual: data.len(),
+            });
         }
+        let discriminator: [u8; 8] = data[..DISCRIMINATOR_SIZE].try_into().unwrap();
+        let args = &data[DISCRIMINATOR_SIZE..];
+        let accounts = AccountList(accounts);
 
-        // 创建代币的另一种可能的指令格式
-        // 如果是CreateCoin指令(24)
-        [24, _, _, _, _, _, _, _] => {
-            // 尝试解析CreateCoin指令
-            let mut offset = 8;
-            
-            // 解析名称
-            if offset + 4 > data.len() {
-                return Err("Insufficient data for name length".into());
+        match discriminator {
+            CREATE_DISCRIMINATOR => {
+                accounts.require(8)?;
+                Ok(PumpInstruction::Create {
+                    args: decode_create_args(args)?,
+                    accounts: CreateAccounts {
+                        mint: accounts.get(0),
+                        mint_authority: accounts.get(1),
+                        bonding_curve: accounts.get(2),
+                        associated_bonding_curve: accounts.get(3),
+                        global: accounts.get(4),
+                        metadata: accounts.get(6),
+                        user: accounts.get(7),
+                    },
+                })
             }
-            let name_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            if offset + name_len > data.len() {
-                return Err("Insufficient data for name".into());
+            BUY_DISCRIMINATOR => Ok(PumpInstruction::Buy {
+                args: decode_args(args, TRADE_ARGS_SIZE)?,
+                accounts: accounts.trade()?,
+            }),
+            SELL_DISCRIMINATOR => Ok(PumpInstruction::Sell {
+                args: decode_args(args, TRADE_ARGS_SIZE)?,
+                accounts: accounts.trade()?,
+            }),
+            WITHDRAW_DISCRIMINATOR => {
+                accounts.require(7)?;
+                Ok(PumpInstruction::Withdraw {
+                    accounts: WithdrawAccounts {
+                        global: accounts.get(0),
+                        last_withdraw: accounts.get(1),
+                        mint: accounts.get(2),
+                        bonding_curve: accounts.get(3),
+                        associated_bonding_curve: accounts.get(4),
+                        associated_user: accounts.get(5),
+                        user: accounts.get(6),
+                    },
+                })
             }
-            let name = String::from_utf8(data[offset..offset + name_len].to_vec())?;
-            offset += name_len;
-
-            // 解析符号
-            if offset + 4 > data.len() {
-                return Err("Insufficient data for symbol length".into());
+            SET_PARAMS_DISCRIMINATOR => Ok(PumpInstruction::SetParams {
+                args: decode_args(args, SET_PARAMS_ARGS_SIZE)?,
+                accounts: accounts.global()?,
+            }),
+            INITIALIZE_DISCRIMINATOR => Ok(PumpInstruction::Initialize {
+                accounts: accounts.global()?,
+            }),
+            EXTEND_ACCOUNT_DISCRIMINATOR => {
+                accounts.require(2)?;
+                Ok(PumpInstruction::ExtendAccount {
+                    accounts: ExtendAccountAccounts {
+                        account: accounts.get(0),
+                        user: accounts.get(1),
+                    },
+                })
             }
-            let symbol_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            if offset + symbol_len > data.len() {
-                return Err("Insufficient data for symbol".into());
+            MIGRATE_DISCRIMINATOR => {
+                accounts.require(10)?;
+                Ok(PumpInstruction::Migrate {
+                    accounts: MigrateAccounts {
+                        global: accounts.get(0),
+                        withdraw_authority: accounts.get(1),
+                        mint: accounts.get(2),
+                        bonding_curve: accounts.get(3),
+                        associated_bonding_curve: accounts.get(4),
+                        user: accounts.get(5),
+                        pool: accounts.get(9),
+                    },
+                })
             }
-            let symbol = String::from_utf8(data[offset..offset + symbol_len].to_vec())?;
-            offset += symbol_len;
+            other => Err(InstructionError::UnknownDiscriminator(other)),
+        }
+    }
 
-            // 解析URI
-            if offset + 4 > data.len() {
-                return Err("Insufficient data for URI length".into());
+    // 指令中的代币和内盘曲线账户
+    fn mint_and_bonding_curve(&self) -> Option<(Pubkey, Pubkey)> {
+        match self {
+            PumpInstruction::Create { accounts, .. } => Some((accounts.mint, accounts.bonding_curve)),
+            PumpInstruction::Buy { accounts, .. } | PumpInstruction::Sell { accounts, .. } => {
+                Some((accounts.mint, accounts.bonding_curve))
             }
-            let uri_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            if offset + uri_len > data.len() {
-                return Err("Insufficient data for URI".into());
+            PumpInstruction::Withdraw { accounts } => Some((accounts.mint, accounts.bonding_curve)),
+            PumpInstruction::Migrate { accounts } => Some((accounts.mint, accounts.bonding_curve)),
+            _ => None,
+        }
+    }
+
+    /// 校验内盘曲线账户是否为`["bonding-curve", mint]`推导出的PDA
+    ///
+    /// 账户索引错位或经过路由程序包装时，按位置取出的账户不再对应，此时返回错误。
+    pub fn validate_accounts(&self) -> Result<(), InstructionError> {
+        self.validate_accounts_with(bonding_curve_pda)
+    }
+
+    /// 使用给定的方式取得代币的内盘曲线PDA并校验
+    ///
+    /// # 参数
+    ///
+    /// * `bonding_curve_of` - 返回代币的内盘曲线PDA，可以查询缓存以避免每条指令都搜索PDA
+    pub fn validate_accounts_with(&self, bonding_curve_of: impl FnOnce(&Pubkey) -> Pubkey) -> Result<(), InstructionError> {
+        if let Some((mint, bonding_curve)) = self.mint_and_bonding_curve() {
+            let expected = bonding_curve_of(&mint);
+            if bonding_curve != expected {
+                return Err(InstructionError::AccountMismatch {
+                    name: "bonding_curve",
+                    expected,
+                    actual: bonding_curve,
+                });
             }
-            let uri = String::from_utf8(data[offset..offset + uri_len].to_vec())?;
+        }
+        Ok(())
+    }
+}
+
+/// 将编译后指令的账户索引解析为公钥
+///
+/// 未能解析的查找表账户以默认公钥占位，Pump指令在解析前会先检查索引是否都已加载。
+///
+/// # 参数
+///
+/// * `indices` - 指令的账户索引
+/// * `account_keys` - 消息的完整账户列表
+pub fn resolve_accounts(indices: &[u8], account_keys: &AccountKeys) -> Vec<Pubkey> {
+    indices
+        .iter()
+        .map(|index| account_keys.get(*index as usize).copied().unwrap_or_default())
+        .collect()
+}
 
-            // 使用默认的用户公钥，在processor中将使用账户列表中的值
-            let user = Pubkey::default();
+// 按位置读取指令账户
+struct AccountList<'a>(&'a [Pubkey]);
 
-            let instruction = CreateEventInstruction { name, symbol, uri, user };
-            Ok(("CreateEvent".to_string(), Some(instruction), None))
+impl AccountList<'_> {
+    fn require(&self, expected: usize) -> Result<(), InstructionError> {
+        if self.0.len() < expected {
+            return Err(InstructionError::NotEnoughAccounts {
+                expected,
+                actual: self.0.len(),
+            });
         }
+        Ok(())
+    }
 
-        // BuyTokens指令(102)
-        [102, _, _, _, _, _, _, _] => {
-            if data.len() < 24 {
-                return Err("Insufficient data for BuyTokens instruction".into());
-            }
-            
-            let amount = u64::from_le_bytes(data[8..16].try_into().unwrap());
-            let max_sol_cost = u64::from_le_bytes(data[16..24].try_into().unwrap());
+    fn get(&self, index: usize) -> Pubkey {
+        self.0[index]
+    }
+
+    fn trade(&self) -> Result<TradeAccounts, InstructionError> {
+        self.require(7)?;
+        Ok(TradeAccounts {
+            global: self.get(0),
+            fee_recipient: self.get(1),
+            mint: self.get(2),
+            bonding_curve: self.get(3),
+            associated_bonding_curve: self.get(4),
+            associated_user: self.get(5),
+            user: self.get(6),
+        })
+    }
+
+    fn global(&self) -> Result<GlobalAccounts, InstructionError> {
+        self.require(2)?;
+        Ok(GlobalAccounts {
+            global: self.get(0),
+            user: self.get(1),
+        })
+    }
+}
+
+// 解析固定长度的Borsh参数，允许末尾存在新增字段
+fn decode_args<T: BorshDeserialize>(mut data: &[u8], size: usize) -> Result<T, InstructionError> {
+    if data.len() < size {
+        return Err(InstructionError::DataTooShort {
+            expected: DISCRIMINATOR_SIZE + size,
+            actual: DISCRIMINATOR_SIZE + data.len(),
+        });
+    }
+    T::deserialize(&mut data).map_err(|e| InstructionError::InvalidArgs(e.to_string()))
+}
 
-            let instruction = BuyInstruction { amount, max_sol_cost };
-            Ok(("Buy".to_string(), None, Some(instruction)))
+// 读取u32长度前缀的字符串
+fn read_string(data: &[u8], offset: &mut usize, field: &'static str) -> Result<String, InstructionError> {
+    let len_end = *offset + 4;
+    let len_bytes = data.get(*offset..len_end).ok_or(InstructionError::DataTooShort {
+        expected: DISCRIMINATOR_SIZE + len_end,
+        actual: DISCRIMINATOR_SIZE + data.len(),
+    })?;
+    let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+    let end = len_end + len;
+    let bytes = data.get(len_end..end).ok_or(InstructionError::DataTooShort {
+        expected: DISCRIMINATOR_SIZE + end,
+        actual: DISCRIMINATOR_SIZE + data.len(),
+    })?;
+    *offset = end;
+    String::from_utf8(bytes.to_vec()).map_err(|_| InstructionError::InvalidUtf8(field))
+}
+
+// 解析Create参数：名称、符号、URI，以及可选的创建者公钥
+fn decode_create_args(data: &[u8]) -> Result<CreateArgs, InstructionError> {
+    let mut offset = 0;
+    let name = read_string(data, &mut offset, "name")?;
+    let symbol = read_string(data, &mut offset, "symbol")?;
+    let uri = read_string(data, &mut offset, "uri")?;
+    let creator = data
+        .get(offset..offset + 32)
+        .map(|bytes| Pubkey::new_from_array(bytes.try_into().unwrap()));
+    Ok(CreateArgs { name, symbol, uri, creator })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::transaction::{EVENT_AUTHORITY, FEE_RECIPIENT, GLOBAL_ACCOUNT, PUMP_PROGRAM_ID, SYSVAR_RENT_PUBKEY};
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use solana_sdk::system_program;
+
+    fn string_arg(data: &mut Vec<u8>, value: &str) {
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+    }
+
+    /// Pump程序的Create指令，账户顺序与链上一致
+    pub(crate) fn create_instruction(mint: &Pubkey, user: &Pubkey) -> Instruction {
+        let mut data = CREATE_DISCRIMINATOR.to_vec();
+        string_arg(&mut data, "Test");
+        string_arg(&mut data, "TST");
+        string_arg(&mut data, "https://example.com/test.json");
+        data.extend_from_slice(user.as_ref());
+        Instruction::new_with_bytes(
+            PUMP_PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(*mint, true),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                AccountMeta::new(bonding_curve_pda(mint), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(GLOBAL_ACCOUNT, false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(*user, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(SYSVAR_RENT_PUBKEY, false),
+                AccountMeta::new_readonly(EVENT_AUTHORITY, false),
+                AccountMeta::new_readonly(PUMP_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    fn trade_instruction(discriminator: [u8; 8], mint: &Pubkey, user: &Pubkey, amount: u64, sol_limit: u64) -> Instruction {
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&sol_limit.to_le_bytes());
+        Instruction::new_with_bytes(
+            PUMP_PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new_readonly(GLOBAL_ACCOUNT, false),
+                AccountMeta::new(FEE_RECIPIENT, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new(bonding_curve_pda(mint), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(*user, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(SYSVAR_RENT_PUBKEY, false),
+                AccountMeta::new_readonly(EVENT_AUTHORITY, false),
+                AccountMeta::new_readonly(PUMP_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    /// Pump程序的Buy指令
+    pub(crate) fn buy_instruction(mint: &Pubkey, user: &Pubkey, amount: u64, max_sol_cost: u64) -> Instruction {
+        trade_instruction(BUY_DISCRIMINATOR, mint, user, amount, max_sol_cost)
+    }
+
+    /// Pump程序的Sell指令
+    pub(crate) fn sell_instruction(mint: &Pubkey, user: &Pubkey, amount: u64, min_sol_output: u64) -> Instruction {
+        trade_instruction(SELL_DISCRIMINATOR, mint, user, amount, min_sol_output)
+    }
+
+    fn decode(instruction: &Instruction) -> Result<PumpInstruction, InstructionError> {
+        let accounts: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        PumpInstruction::decode(&instruction.data, &accounts)
+    }
+
+    #[test]
+    fn decodes_create() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = create_instruction(&mint, &user);
+        let PumpInstruction::Create { args, accounts } = decode(&instruction).unwrap() else {
+            panic!("应解析为Create");
+        };
+        assert_eq!(args.name, "Test");
+        assert_eq!(args.symbol, "TST");
+        assert_eq!(args.uri, "https://example.com/test.json");
+        assert_eq!(args.creator, Some(user));
+        assert_eq!(accounts.mint, mint);
+        assert_eq!(accounts.bonding_curve, bonding_curve_pda(&mint));
+        assert_eq!(accounts.global, GLOBAL_ACCOUNT);
+        assert_eq!(accounts.user, user);
+    }
+
+    #[test]
+    fn decodes_create_without_creator() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut instruction = create_instruction(&mint, &user);
+        // 旧版本指令不携带创建者公钥
+        instruction.data.truncate(instruction.data.len() - 32);
+        let PumpInstruction::Create { args, .. } = decode(&instruction).unwrap() else {
+            panic!("应解析为Create");
+        };
+        assert_eq!(args.creator, None);
+    }
+
+    #[test]
+    fn decodes_buy_and_sell() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let buy = decode(&buy_instruction(&mint, &user, 1_000, 2_000)).unwrap();
+        let PumpInstruction::Buy { args, accounts } = &buy else {
+            panic!("应解析为Buy");
+        };
+        assert_eq!(*args, BuyArgs { amount: 1_000, max_sol_cost: 2_000 });
+        assert_eq!(accounts.fee_recipient, FEE_RECIPIENT);
+        assert_eq!(accounts.mint, mint);
+        assert_eq!(accounts.user, user);
+        assert_eq!(buy.validate_accounts(), Ok(()));
+
+        let sell = decode(&sell_instruction(&mint, &user, 3_000, 4_000)).unwrap();
+        let PumpInstruction::Sell { args, accounts } = &sell else {
+            panic!("应解析为Sell");
+        };
+        assert_eq!(*args, SellArgs { amount: 3_000, min_sol_output: 4_000 });
+        assert_eq!(accounts.bonding_curve, bonding_curve_pda(&mint));
+        assert_eq!(sell.validate_accounts(), Ok(()));
+    }
+
+    #[test]
+    fn decodes_admin_instructions() {
+        let keys: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+
+        let decoded = PumpInstruction::decode(&WITHDRAW_DISCRIMINATOR, &keys).unwrap();
+        let PumpInstruction::Withdraw { accounts } = decoded else {
+            panic!("应解析为Withdraw");
+        };
+        assert_eq!((accounts.mint, accounts.bonding_curve, accounts.user), (keys[2], keys[3], keys[6]));
+
+        let mut data = SET_PARAMS_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(keys[9].as_ref());
+        for value in 1..=5u64 {
+            data.extend_from_slice(&value.to_le_bytes());
         }
+        let PumpInstruction::SetParams { args, accounts } = PumpInstruction::decode(&data, &keys).unwrap() else {
+            panic!("应解析为SetParams");
+        };
+        assert_eq!(args.fee_recipient, keys[9]);
+        assert_eq!(
+            (
+                args.initial_virtual_token_reserves,
+                args.initial_virtual_sol_reserves,
+                args.initial_real_token_reserves,
+                args.token_total_supply,
+                args.fee_basis_points
+            ),
+            (1, 2, 3, 4, 5)
+        );
+        assert_eq!(accounts, GlobalAccounts { global: keys[0], user: keys[1] });
+
+        assert_eq!(
+            PumpInstruction::decode(&INITIALIZE_DISCRIMINATOR, &keys).unwrap(),
+            PumpInstruction::Initialize { accounts: GlobalAccounts { global: keys[0], user: keys[1] } }
+        );
+        assert_eq!(
+            PumpInstruction::decode(&EXTEND_ACCOUNT_DISCRIMINATOR, &keys).unwrap(),
+            PumpInstruction::ExtendAccount { accounts: ExtendAccountAccounts { account: keys[0], user: keys[1] } }
+        );
+
+        let PumpInstruction::Migrate { accounts } = PumpInstruction::decode(&MIGRATE_DISCRIMINATOR, &keys).unwrap() else {
+            panic!("应解析为Migrate");
+        };
+        assert_eq!((accounts.mint, accounts.user, accounts.pool), (keys[2], keys[5], keys[9]));
+    }
 
-        _ => Err("Unknown instruction data".into()),
+    #[test]
+    fn rejects_truncated_data() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts: Vec<Pubkey> = buy_instruction(&mint, &user, 1, 1).accounts.iter().map(|meta| meta.pubkey).collect();
+
+        assert_eq!(
+            PumpInstruction::decode(&BUY_DISCRIMINATOR[..5], &accounts),
+            Err(InstructionError::DataTooShort { expected: 8, actual: 5 })
+        );
+        let buy = buy_instruction(&mint, &user, 1, 1);
+        assert_eq!(
+            PumpInstruction::decode(&buy.data[..20], &accounts),
+            Err(InstructionError::DataTooShort { expected: 24, actual: 20 })
+        );
+        let mut data = SET_PARAMS_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[0u8; 40]);
+        assert_eq!(
+            PumpInstruction::decode(&data, &accounts),
+            Err(InstructionError::DataTooShort { expected: 80, actual: 48 })
+        );
+
+        // 字符串长度前缀超出数据
+        let create = create_instruction(&mint, &user);
+        assert!(matches!(
+            PumpInstruction::decode(&create.data[..14], &accounts),
+            Err(InstructionError::DataTooShort { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_utf8_and_unknown_discriminator() {
+        let accounts: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        let mut data = CREATE_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0xff, 0xfe]);
+        assert_eq!(PumpInstruction::decode(&data, &accounts), Err(InstructionError::InvalidUtf8("name")));
+
+        let unknown = [1u8; 8];
+        assert_eq!(
+            PumpInstruction::decode(&unknown, &accounts),
+            Err(InstructionError::UnknownDiscriminator(unknown))
+        );
+    }
+
+    #[test]
+    fn rejects_missing_accounts() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let buy = buy_instruction(&mint, &user, 1, 1);
+        let accounts: Vec<Pubkey> = buy.accounts.iter().take(6).map(|meta| meta.pubkey).collect();
+        assert_eq!(
+            PumpInstruction::decode(&buy.data, &accounts),
+            Err(InstructionError::NotEnoughAccounts { expected: 7, actual: 6 })
+        );
+        assert_eq!(
+            PumpInstruction::decode(&MIGRATE_DISCRIMINATOR, &accounts),
+            Err(InstructionError::NotEnoughAccounts { expected: 10, actual: 6 })
+        );
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn rejects_shifted_accounts() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let sell = sell_instruction(&mint, &user, 1, 1);
+        // 路由程序多插入一个账户时，按位置取出的曲线账户不再是PDA
+        let mut accounts: Vec<Pubkey> = sell.accounts.iter().map(|meta| meta.pubkey).collect();
+        accounts.insert(0, Pubkey::new_unique());
+        let parsed = PumpInstruction::decode(&sell.data, &accounts).unwrap();
+        assert!(matches!(
+            parsed.validate_accounts(),
+            Err(InstructionError::AccountMismatch { name: "bonding_curve", .. })
+        ));
+    }
+
+    #[test]
+    fn validates_against_supplied_pda() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let parsed = decode(&buy_instruction(&mint, &user, 1, 1)).unwrap();
+        let curve = bonding_curve_pda(&mint);
+        assert_eq!(parsed.validate_accounts_with(|_| curve), Ok(()));
+
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            parsed.validate_accounts_with(|_| other),
+            Err(InstructionError::AccountMismatch { name: "bonding_curve", expected: other, actual: curve })
+        );
+    }
+}
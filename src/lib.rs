@@ -3,4 +3,16 @@ pub mod client;
 pub mod processor;
 pub mod utils;
 pub mod instruction; 
-pub mod transaction;
\ No newline at end of file
+pub mod transaction;
+pub mod bundle;
+pub mod shred;
+pub mod capture;
+pub mod backtest;
+pub mod bonding_curve;
+pub mod lookup_table;
+pub mod message;
+pub mod strategy;
+pub mod exit;
+pub mod position;
+pub mod confirm;
+pub mod fee;
@@ -0,0 +1,325 @@
+use solana_program::address_lookup_table::instruction::ProgramInstruction;
+use solana_program::address_lookup_table::program::ID as LOOKUP_TABLE_PROGRAM_ID;
+use solana_program::address_lookup_table::state::AddressLookupTable;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::message::v0::LoadedAddresses;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::message::{MessageView, ResolvedMessage};
+
+/// 单次RPC请求最多加载的查找表数量
+const MAX_FETCH_BATCH: usize = 100;
+
+/// 地址查找表缓存
+///
+/// 解析v0消息时把查找表加载的可写和只读地址依次追加到静态账户之后，
+/// 得到与链上一致的完整账户列表。缓存未命中时返回None并在后台通过RPC加载。
+/// 数据流中的交易不带执行结果，扩展查找表的指令只触发重新加载，不直接追加地址，
+/// 避免执行失败的扩展污染缓存。
+#[derive(Clone)]
+pub struct LookupTableCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    tables: StdRwLock<HashMap<Pubkey, Vec<Pubkey>>>,
+    // 已请求加载但尚未返回的查找表
+    pending: StdMutex<HashSet<Pubkey>>,
+    fetch_tx: Option<mpsc::UnboundedSender<Pubkey>>,
+    // 自上次保存快照后是否有变化
+    dirty: AtomicBool,
+}
+
+impl LookupTableCache {
+    /// 创建只使用本地数据的缓存，未命中时不会加载
+    pub fn offline() -> Self {
+        Self::with_fetcher(None)
+    }
+
+    /// 创建缓存，未命中时通过RPC在后台加载查找表
+    ///
+    /// # 参数
+    ///
+    /// * `rpc_url` - 用于加载查找表账户的RPC地址
+    pub fn with_rpc(rpc_url: String) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cache = Self::with_fetcher(Some(tx));
+        cache.spawn_fetcher(rpc_url, rx);
+        cache
+    }
+
+    fn with_fetcher(fetch_tx: Option<mpsc::UnboundedSender<Pubkey>>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                tables: StdRwLock::new(HashMap::new()),
+                pending: StdMutex::new(HashSet::new()),
+                fetch_tx,
+                dirty: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    // 批量加载请求的查找表
+    fn spawn_fetcher(&self, rpc_url: String, mut rx: mpsc::UnboundedReceiver<Pubkey>) {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            // 以confirmed读取，扩展交易确认后即可加载到新地址
+            let rpc_client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+            while let Some(first) = rx.recv().await {
+                let mut keys = vec![first];
+                while keys.len() < MAX_FETCH_BATCH {
+                    match rx.try_recv() {
+                        Ok(key) => keys.push(key),
+                        Err(_) => break,
+                    }
+                }
+
+                match rpc_client.get_multiple_accounts(&keys).await {
+                    Ok(accounts) => {
+                        for (key, account) in keys.iter().zip(accounts) {
+                            let table = account
+                                .filter(|account| account.owner == LOOKUP_TABLE_PROGRAM_ID)
+                                .and_then(|account| {
+                                    AddressLookupTable::deserialize(&account.data)
+                                        .ok()
+                                        .map(|table| table.addresses.to_vec())
+                                });
+                            match table {
+                                Some(addresses) => cache.insert(*key, addresses),
+                                None => println!("查找表 {} 不存在或数据无效", key),
+                            }
+                        }
+                    }
+                    Err(e) => println!("加载查找表失败: {:?}", e),
+                }
+
+                let mut pending = cache.inner.pending.lock().unwrap();
+                for key in &keys {
+                    pending.remove(key);
+                }
+            }
+        });
+    }
+
+    /// 请求加载查找表，已在加载中的不会重复请求
+    pub fn request(&self, key: Pubkey) {
+        if let Some(tx) = &self.inner.fetch_tx {
+            if self.inner.pending.lock().unwrap().insert(key) {
+                let _ = tx.send(key);
+            }
+        }
+    }
+
+    /// 写入或替换查找表
+    pub fn insert(&self, key: Pubkey, addresses: Vec<Pubkey>) {
+        self.inner.tables.write().unwrap().insert(key, addresses);
+        self.inner.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// 解析消息的查找表，得到完整账户列表：静态账户、所有可写加载地址、所有只读加载地址
+    ///
+    /// 任何一个查找表未缓存或索引超出缓存长度时返回None，并请求重新加载该表。
+    pub fn resolve<'a, M: MessageView + ?Sized>(&self, message: &'a M) -> Option<ResolvedMessage<'a, M>> {
+        let lookups = message.table_lookups();
+        if lookups.is_empty() {
+            return Some(ResolvedMessage::unresolved(message));
+        }
+
+        let tables = self.inner.tables.read().unwrap();
+        let mut missing = Vec::new();
+        let mut loaded = LoadedAddresses::default();
+
+        for lookup in lookups {
+            let table = match tables.get(&lookup.account_key) {
+                Some(table) => table,
+                None => {
+                    missing.push(lookup.account_key);
+                    continue;
+                }
+            };
+            let load = |indexes: &[u8], out: &mut Vec<Pubkey>| {
+                for index in indexes {
+                    out.push(*table.get(*index as usize)?);
+                }
+                Some(())
+            };
+            // 索引超出缓存长度说明查找表在缓存后被扩展过
+            if load(&lookup.writable_indexes, &mut loaded.writable).is_none()
+                || load(&lookup.readonly_indexes, &mut loaded.readonly).is_none()
+            {
+                missing.push(lookup.account_key);
+            }
+        }
+        drop(tables);
+
+        if !missing.is_empty() {
+            for key in missing {
+                self.request(key);
+            }
+            return None;
+        }
+        Some(ResolvedMessage::new(message, loaded))
+    }
+
+    /// 应用消息中对查找表的修改
+    pub fn observe(&self, message: &(impl MessageView + ?Sized)) {
+        let account_keys = message.static_keys();
+        for instruction in message.instructions() {
+            if account_keys.get(instruction.program_id_index as usize) != Some(&LOOKUP_TABLE_PROGRAM_ID) {
+                continue;
+            }
+            // 查找表账户必须可写，不会来自其他查找表
+            let table_key = match instruction.accounts.first().and_then(|i| account_keys.get(*i as usize)) {
+                Some(key) => *key,
+                None => continue,
+            };
+            let program_instruction = match bincode::deserialize::<ProgramInstruction>(&instruction.data) {
+                Ok(program_instruction) => program_instruction,
+                Err(_) => continue,
+            };
+
+            // 创建和关闭即使执行失败，也只会让该表在下次使用时重新加载
+            match program_instruction {
+                ProgramInstruction::CreateLookupTable { .. } => {
+                    self.inner.tables.write().unwrap().entry(table_key).or_default();
+                    self.inner.dirty.store(true, Ordering::Relaxed);
+                }
+                // 交易可能执行失败，以链上状态为准；查找表只追加，已缓存的索引仍然有效
+                ProgramInstruction::ExtendLookupTable { .. } => self.request(table_key),
+                ProgramInstruction::CloseLookupTable => {
+                    self.inner.tables.write().unwrap().remove(&table_key);
+                    self.inner.dirty.store(true, Ordering::Relaxed);
+                }
+                ProgramInstruction::FreezeLookupTable | ProgramInstruction::DeactivateLookupTable => {}
+            }
+        }
+    }
+
+    /// 从快照文件加载查找表，返回加载的数量
+    ///
+    /// 快照为JSON对象，键为查找表地址，值为其中的地址列表。
+    pub fn load_snapshot(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let snapshot: HashMap<String, Vec<String>> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, format!("快照中的地址无效: {:?}", e));
+
+        let mut tables = self.inner.tables.write().unwrap();
+        for (key, addresses) in &snapshot {
+            let addresses = addresses
+                .iter()
+                .map(|address| Pubkey::from_str(address))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(invalid)?;
+            tables.insert(Pubkey::from_str(key).map_err(invalid)?, addresses);
+        }
+        Ok(snapshot.len())
+    }
+
+    /// 将所有查找表写入快照文件，返回写入的数量
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        // 读取前清除标记，读取之后的修改会在下次保存；写入失败时恢复标记以便重试
+        self.inner.dirty.store(false, Ordering::Relaxed);
+        let snapshot: HashMap<String, Vec<String>> = {
+            let tables = self.inner.tables.read().unwrap();
+            tables
+                .iter()
+                .map(|(key, addresses)| (key.to_string(), addresses.iter().map(|a| a.to_string()).collect()))
+                .collect()
+        };
+
+        let result = Self::write_snapshot(path.as_ref(), &snapshot);
+        if result.is_err() {
+            self.inner.dirty.store(true, Ordering::Relaxed);
+        }
+        result.map(|()| snapshot.len())
+    }
+
+    // 先写临时文件再替换，避免中断时留下不完整的快照
+    fn write_snapshot(path: &Path, snapshot: &HashMap<String, Vec<String>>) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, snapshot)?;
+        writer.flush()?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// 定期将有变化的缓存写入快照文件
+    pub fn spawn_snapshot_saver(&self, path: PathBuf, interval: Duration) -> JoinHandle<()> {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if !cache.inner.dirty.load(Ordering::Relaxed) {
+                    continue;
+                }
+                if let Err(e) = cache.save_snapshot(&path) {
+                    println!("保存查找表快照失败: {:?}", e);
+                }
+            }
+        })
+    }
+}
+
+impl Default for LookupTableCache {
+    fn default() -> Self {
+        Self::offline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::address_lookup_table::instruction::{create_lookup_table, extend_lookup_table};
+    use solana_sdk::message::legacy::Message;
+
+    fn cache_with_requests() -> (LookupTableCache, mpsc::UnboundedReceiver<Pubkey>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (LookupTableCache::with_fetcher(Some(tx)), rx)
+    }
+
+    #[test]
+    fn extend_requests_refetch_instead_of_appending() {
+        let (cache, mut requests) = cache_with_requests();
+        let authority = Pubkey::new_unique();
+        let (create, table) = create_lookup_table(authority, authority, 1);
+        let cached = vec![Pubkey::new_unique()];
+        cache.insert(table, cached.clone());
+
+        let extend = extend_lookup_table(table, authority, Some(authority), vec![Pubkey::new_unique()]);
+        cache.observe(&Message::new(&[create, extend], Some(&authority)));
+
+        assert_eq!(cache.inner.tables.read().unwrap()[&table], cached);
+        assert_eq!(requests.try_recv().unwrap(), table);
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[test]
+    fn failed_snapshot_write_keeps_dirty() {
+        let cache = LookupTableCache::offline();
+        cache.insert(Pubkey::new_unique(), vec![Pubkey::new_unique()]);
+
+        let missing_dir = std::env::temp_dir().join(format!("alt-missing-{}", std::process::id()));
+        assert!(cache.save_snapshot(missing_dir.join("snapshot.json")).is_err());
+        assert!(cache.inner.dirty.load(Ordering::Relaxed));
+
+        let path = std::env::temp_dir().join(format!("alt-snapshot-{}.json", std::process::id()));
+        assert_eq!(cache.save_snapshot(&path).unwrap(), 1);
+        assert!(!cache.inner.dirty.load(Ordering::Relaxed));
+
+        let restored = LookupTableCache::offline();
+        assert_eq!(restored.load_snapshot(&path).unwrap(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
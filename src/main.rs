@@ -4,35 +4,170 @@ mod processor;
 mod utils;
 mod instruction;
 mod transaction;
+mod bundle;
+mod shred;
+mod capture;
+mod backtest;
+mod bonding_curve;
+mod lookup_table;
+mod message;
+mod strategy;
+mod exit;
+mod position;
+mod confirm;
+mod fee;
 
-use config::Config;
-use client::ShredstreamClient;
+use config::{Config, IngestMode};
+use client::{connect_authenticator, ShredstreamClient};
 use processor::TransactionProcessor;
 use utils::deserialize_entries;
 use utils::redis::RedisClient;
-use utils::auto_trader::AutoTrader;
-use std::error::Error;
+use utils::auto_trader::{AutoTrader, SnipeParams};
+use bundle::BundleSender;
+use shred::receiver::{ShredReceiver, ShredReceiverConfig};
+use utils::blockhash_cache::BlockhashCache;
+use capture::{CaptureRecord, CaptureWriter, ReplaySpeed, CAPTURE_FLUSH_INTERVAL};
+use backtest::{BacktestConfig, Backtester, Latency};
+use lookup_table::LookupTableCache;
+use strategy::StrategySet;
+use exit::ExitRules;
+use position::{PnlSummary, PositionLedger};
+use confirm::ConfirmationTracker;
+use transaction::resend::ResendPolicy;
+use fee::FeePolicy;
+use transaction::sender::{SendEndpoint, TransactionSender};
+use transaction::trader::{MintAccounts, PumpTrader};
+use transaction::PumpRoute;
+use fee::{ComputeKind, FeeOracle};
+use bonding_curve::{BondingCurve, GlobalAccount};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use std::collections::HashMap;
+use clap::{Parser, Subcommand};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::env;
 use dotenvy::dotenv;
 
+#[derive(Parser)]
+#[command(name = "jito-shredstream-client", about = "基于Jito Shredstream的Pumpfun狙击客户端")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 订阅数据流并自动交易（默认）
+    Run {
+        /// 同时将收到的数据写入该目录下的捕获文件
+        #[arg(long)]
+        record: Option<String>,
+        /// 单个捕获文件的最大大小 (MB)
+        #[arg(long, default_value_t = 512)]
+        rotate_mb: u64,
+    },
+    /// 只订阅数据流并写入捕获文件，不进行交易
+    Record {
+        /// 捕获文件目录
+        #[arg(long, default_value = "captures")]
+        dir: String,
+        /// 单个捕获文件的最大大小 (MB)
+        #[arg(long, default_value_t = 512)]
+        rotate_mb: u64,
+    },
+    /// 将捕获文件回放给处理器，不进行交易
+    Replay {
+        /// 捕获文件或包含捕获文件的目录
+        path: String,
+        /// 回放速度：original、max 或倍数（如 10x）
+        #[arg(long, default_value = "max")]
+        speed: ReplaySpeed,
+    },
+    /// 使用捕获文件回测策略，策略和参数从配置文件和环境变量读取
+    Backtest {
+        /// 捕获文件或包含捕获文件的目录
+        path: String,
+        /// 信号到成交的延迟 (slot)
+        #[arg(long, default_value_t = 1)]
+        latency_slots: u64,
+        /// 信号到成交的延迟 (毫秒)，设置后替代按slot计算的延迟
+        #[arg(long)]
+        latency_ms: Option<u64>,
+        /// 协议手续费 (基点)
+        #[arg(long, default_value_t = 100)]
+        fee_bps: u64,
+        /// 每笔交易的网络费用和小费 (lamports)
+        #[arg(long, default_value_t = 5_000)]
+        tx_fee_lamports: u64,
+        /// JSON报告输出路径
+        #[arg(long, default_value = "backtest.json")]
+        json: String,
+        /// CSV逐笔交易输出路径
+        #[arg(long, default_value = "backtest.csv")]
+        csv: String,
+    },
+    /// 查看持仓账本中的持仓和盈亏，未实现盈亏按链上内盘曲线计算
+    Positions {
+        /// 同时显示已平仓的持仓
+        #[arg(long)]
+        all: bool,
+    },
+    /// 测量从触发到签好买入交易的延迟，比较使用交易上下文和每次解析私钥、搜索PDA的耗时
+    Bench {
+        /// 每种方式的测量次数
+        #[arg(long, default_value_t = 10_000)]
+        iterations: usize,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     // 加载环境变量
     dotenv().ok();
+    let cli = Cli::parse();
     
     // 获取配置
     let config = Config::new();
-    let client_result = ShredstreamClient::new(config.clone()).await;
-    let mut client = match client_result {
-        Ok(client) => client,
-        Err(e) => {
-            println!("创建客户端失败: {:?}", e);
-            return;
-        }
-    };
     
+    match cli.command.unwrap_or(Command::Run { record: None, rotate_mb: 512 }) {
+        Command::Run { record, rotate_mb } => {
+            let capture = match record.map(|dir| CaptureWriter::new(dir, rotate_mb * 1024 * 1024)).transpose() {
+                Ok(capture) => capture,
+                Err(e) => {
+                    println!("创建捕获目录失败: {:?}", e);
+                    return;
+                }
+            };
+            run(config, capture).await
+        }
+        Command::Record { dir, rotate_mb } => record(config, dir, rotate_mb).await,
+        Command::Replay { path, speed } => replay(config, path, speed).await,
+        Command::Backtest { path, latency_slots, latency_ms, fee_bps, tx_fee_lamports, json, csv } => {
+            let latency = match latency_ms {
+                Some(ms) => Latency::Millis(ms),
+                None => Latency::Slots(latency_slots),
+            };
+            let backtest_config = BacktestConfig {
+                params: SnipeParams::from_env(),
+                latency,
+                fee_bps,
+                tx_fee_lamports,
+            };
+            let strategies = match load_strategies(&config, &backtest_config.params) {
+                Some(strategies) => strategies,
+                None => return,
+            };
+            backtest(backtest_config, strategies, offline_lookup_tables(&config), path, json, csv).await
+        }
+        Command::Positions { all } => positions(all).await,
+        Command::Bench { iterations } => bench(iterations),
+    }
+}
+
+// 订阅数据流并自动交易
+async fn run(config: Config, capture: Option<CaptureWriter>) {
     let mut processor = TransactionProcessor::new(config.token_creator_pubkey);
     
     // 获取Redis配置
@@ -48,6 +183,22 @@ async fn main() {
             return;
         }
     };
+    // 启动时解析私钥，交易时不再解析
+    let signer = match transaction::parse_keypair(&private_key) {
+        Ok(signer) => signer,
+        Err(e) => {
+            println!("环境变量PRIVATE_KEY: {}", e);
+            return;
+        }
+    };
+    // 启动时检查交易设置，无效时不连接任何服务
+    let (simulate, route, buy_templates, fee_policy) = match trade_settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
     
     // 初始化Redis客户端
     let redis_client_result = RedisClient::new(&redis_url).await;
@@ -66,77 +217,637 @@ async fn main() {
     let auto_trader = AutoTrader::new(
         redis_client.clone(),
         rpc_url.clone(),
-        private_key.clone()
+        signer
     ).await;
     
-    // 从环境变量读取狙击价格范围
-    let min_sol_str = env::var("MIN_SOL_PRICE").unwrap_or_else(|_| "0.5".to_string());
-    let max_sol_str = env::var("MAX_SOL_PRICE").unwrap_or_else(|_| "3.0".to_string());
-    let buy_sol_str = env::var("BUY_SOL_AMOUNT").unwrap_or_else(|_| "0.1".to_string());
-    let sell_delay_ms = env::var("SELL_DELAY_MS").unwrap_or_else(|_| "5000".to_string());
+    // 从环境变量读取狙击参数，并按配置加载策略
+    let params = SnipeParams::from_env();
+    let strategies = match load_strategies(&config, &params) {
+        Some(strategies) => strategies,
+        None => return,
+    };
+    
+    // 通过RPC发送时并行发送到所有配置的节点，确认前持续重发
+    let mut auto_trader = auto_trader;
+    let send_endpoints = env::var("SEND_RPC_URLS")
+        .map(|urls| SendEndpoint::parse_list(&urls))
+        .unwrap_or_default();
+    let send_endpoints = if send_endpoints.is_empty() { vec![SendEndpoint::parse(&rpc_url)] } else { send_endpoints };
+    auto_trader.set_send_endpoints(&send_endpoints, ResendPolicy::from_env(), simulate);
+    spawn_sender_stats(auto_trader.transaction_sender());
+    auto_trader.set_fee_policy(fee_policy);
+    auto_trader.set_pump_route(route);
     
-    // 将SOL单位的浮点数转换为lamports整数
-    let min_sol = (min_sol_str.parse::<f64>().unwrap_or(0.5) * 1_000_000_000.0) as u64;
-    let max_sol = (max_sol_str.parse::<f64>().unwrap_or(3.0) * 1_000_000_000.0) as u64;
-    let buy_sol = (buy_sol_str.parse::<f64>().unwrap_or(0.1) * 1_000_000_000.0) as u64;
-    let sell_delay = sell_delay_ms.parse::<u64>().unwrap_or(5000);
+    // 如果配置了区块引擎地址，则通过Jito捆绑发送交易
+    if let Ok(block_engine_url) = env::var("BLOCK_ENGINE_URL") {
+        let tip_lamports = env::var("BUNDLE_TIP_LAMPORTS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10_000);
+        match BundleSender::connect(&block_engine_url).await {
+            Ok(sender) => auto_trader.set_bundle_sender(sender, tip_lamports),
+            Err(e) => println!("连接区块引擎失败: {}，将通过RPC发送交易", e),
+        }
+    }
     
     // 创建自动交易器的互斥锁
     let auto_trader = Arc::new(Mutex::new(auto_trader));
     
-    // 设置交易器参数和启动
-    {
-        let mut trader = auto_trader.lock().await;
-        trader.set_price_range(min_sol, max_sol).await;
-        trader.set_buy_amount(buy_sol).await;
-        trader.set_sell_delay(sell_delay).await;
-        trader.start();
-    }
+    // 设置交易器参数
+    auto_trader.lock().await.set_slippage(params.slippage_bps).await;
     
-    // 为处理器设置自动交易器
+    // 区块哈希缓存和交易确认由数据流中的Entry直接更新
+    let blockhash_cache = auto_trader.lock().await.blockhash_cache();
+    let confirmations = auto_trader.lock().await.confirmation_tracker();
+    
+    // 为处理器设置自动交易器和策略
     processor.set_auto_trader(Arc::clone(&auto_trader));
+    processor.set_strategies(strategies);
+    processor.set_fee_oracle(auto_trader.lock().await.fee_oracle());
+    processor.set_mint_accounts(auto_trader.lock().await.mint_accounts());
+    
+    // 为新代币预先签好默认买入金额的买入交易，策略决定买入时直接发送
+    if buy_templates {
+        processor.set_buy_templates(auto_trader.lock().await.enable_buy_templates(params.buy_amount));
+    }
+    
+    // 从Redis恢复持仓账本，未平仓的持仓继续由退出引擎管理
+    match auto_trader.lock().await.load_positions().await {
+        Ok(count) => println!("从Redis恢复了 {} 笔成交", count),
+        Err(e) => println!("恢复持仓账本失败: {:?}", e),
+    }
+    processor.restore_positions(auto_trader.lock().await.ledger());
+    
+    // 读取链上Global账户，新代币的曲线参数和手续费以链上为准
+    match bonding_curve::fetch_global_account(&rpc_url).await {
+        Ok(global) => {
+            println!("Global账户: 手续费 {} bps", global.fee_basis_points);
+            processor.set_global(global);
+        }
+        Err(e) => println!("读取Global账户失败: {:?}，使用默认曲线参数", e),
+    }
+    
+    // 地址查找表缓存，未命中时通过RPC加载
+    let lookup_tables = LookupTableCache::with_rpc(rpc_url.clone());
+    if let Some(path) = &config.lookup_table_snapshot {
+        load_lookup_tables(&lookup_tables, path);
+        lookup_tables.spawn_snapshot_saver(path.into(), std::time::Duration::from_secs(60));
+    }
+    processor.set_lookup_tables(lookup_tables);
     
     println!("开始监听Jito Shredstream数据...");
-    println!("将自动狙击价格在 {} - {} SOL 的新代币", min_sol_str, max_sol_str);
-    println!("每次将投入 {} SOL 进行购买", buy_sol_str);
-    println!("并在 {}ms 后自动卖出", sell_delay);
     println!("---------------------------");
 
-    // 主循环 - 持续监听Shredstream数据
+    match config.ingest_mode {
+        IngestMode::Proxy => run_proxy(config, processor, blockhash_cache, confirmations, capture).await,
+        IngestMode::Shreds => run_shreds(config, processor, blockhash_cache, confirmations, capture).await,
+    }
+}
+
+// 通过shredstream-proxy订阅Entry的主循环
+async fn run_proxy(
+    config: Config,
+    mut processor: TransactionProcessor,
+    blockhash_cache: Arc<BlockhashCache>,
+    confirmations: ConfirmationTracker,
+    mut capture: Option<CaptureWriter>,
+) {
+    let client = match ShredstreamClient::new(config.clone()).await {
+        Ok(client) => client,
+        Err(e) => {
+            println!("创建客户端失败: {:?}", e);
+            return;
+        }
+    };
+
+    // 主循环 - 同时订阅所有端点，合并去重后处理
+    let mut arrivals = client.spawn_subscriptions();
+    let mut deduplicator = client.deduplicator();
+    let mut stats_interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+    stats_interval.tick().await;
+    let mut flush_interval = tokio::time::interval(CAPTURE_FLUSH_INTERVAL);
+
     loop {
-        match client.subscribe_entries().await {
-            Ok(mut stream) => {
-                let process_result = async {
-                    while let Some(entry) = match stream.message().await {
-                        Ok(entry) => entry,
-                        Err(e) => {
-                            println!("获取消息失败: {:?}", e);
-                            return Ok(());
+        tokio::select! {
+            arrival = arrivals.recv() => {
+                let arrival = match arrival {
+                    Some(arrival) => arrival,
+                    None => {
+                        println!("所有端点订阅已结束");
+                        break;
+                    }
+                };
+                // 去重前记录，保留所有端点的原始到达
+                write_capture(&mut capture, &CaptureRecord::from_arrival(&arrival));
+                let slot = arrival.entry.slot;
+                match deserialize_entries(&arrival.entry.entries) {
+                    Ok(entries) => {
+                        // 只处理首次到达的Entry
+                        let entries = deduplicator.process(&arrival, entries);
+                        if entries.is_empty() {
+                            continue;
                         }
-                    } {
-                        match deserialize_entries(&entry.entries) {
-                            Ok(entries) => {
-                                if let Err(e) = processor.process_entries(entries, entry.slot) {
-                                    println!("处理条目失败: {:?}", e);
-                                }
-                            },
-                            Err(e) => {
-                                println!("反序列化失败: {e}");
-                            }
+                        blockhash_cache.record_entries(slot, &entries);
+                        confirmations.record_entries(slot, &entries);
+                        if let Err(e) = processor.process_entries(entries, slot) {
+                            println!("处理条目失败: {:?}", e);
                         }
+                    },
+                    Err(e) => {
+                        println!("反序列化失败: {e}");
+                    }
+                }
+            }
+            _ = flush_interval.tick() => flush_capture(&mut capture),
+            _ = stats_interval.tick() => {
+                if config.server_urls.len() > 1 {
+                    for stats in client.endpoint_stats() {
+                        println!("端点统计: {}", stats);
                     }
-                    Ok::<(), ()>(())
-                }.await;
-                
-                if let Err(_) = process_result {
-                    println!("处理消息循环发生致命错误");
                 }
             }
+        }
+    }
+}
+
+// 发送心跳并在本地接收原始分片的主循环
+async fn run_shreds(
+    config: Config,
+    mut processor: TransactionProcessor,
+    blockhash_cache: Arc<BlockhashCache>,
+    confirmations: ConfirmationTracker,
+    mut capture: Option<CaptureWriter>,
+) {
+    let receiver_config = match ShredReceiverConfig::from_config(&config) {
+        Some(receiver_config) => receiver_config,
+        None => {
+            println!("分片接收模式需要设置SHRED_PUBLIC_IP环境变量");
+            return;
+        }
+    };
+    let authenticator = match connect_authenticator(&config).await {
+        Ok(authenticator) => authenticator,
+        Err(e) => {
+            println!("认证失败: {:?}", e);
+            return;
+        }
+    };
+    let receiver = ShredReceiver::new(receiver_config, authenticator);
+    let mut assembled = match receiver.spawn().await {
+        Ok(assembled) => assembled,
+        Err(e) => {
+            println!("启动分片接收失败: {:?}", e);
+            return;
+        }
+    };
+
+    let mut flush_interval = tokio::time::interval(CAPTURE_FLUSH_INTERVAL);
+    loop {
+        let (slot, entries) = tokio::select! {
+            assembled = assembled.recv() => match assembled {
+                Some(assembled) => assembled,
+                None => break,
+            },
+            _ = flush_interval.tick() => {
+                flush_capture(&mut capture);
+                continue;
+            }
+        };
+        if capture.is_some() {
+            // 分片模式下记录组装后的Entry
+            match bincode::serialize(&entries) {
+                Ok(bytes) => {
+                    let record = CaptureRecord {
+                        slot,
+                        received_at_ns: unix_nanos(),
+                        endpoint: 0,
+                        entries: bytes,
+                    };
+                    write_capture(&mut capture, &record);
+                }
+                Err(e) => println!("序列化Entry失败: {:?}", e),
+            }
+        }
+        blockhash_cache.record_entries(slot, &entries);
+        confirmations.record_entries(slot, &entries);
+        if let Err(e) = processor.process_entries(entries, slot) {
+            println!("处理条目失败: {:?}", e);
+        }
+    }
+    println!("分片接收已结束");
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+// 写入捕获记录，写入失败时停止记录
+fn write_capture(capture: &mut Option<CaptureWriter>, record: &CaptureRecord) {
+    if let Some(writer) = capture.as_mut() {
+        if let Err(e) = writer.write(record) {
+            println!("写入捕获文件失败: {:?}，停止记录", e);
+            *capture = None;
+        }
+    }
+}
+
+// 将缓冲的捕获记录刷新到磁盘，失败时停止记录
+fn flush_capture(capture: &mut Option<CaptureWriter>) {
+    if let Some(writer) = capture.as_mut() {
+        if let Err(e) = writer.flush() {
+            println!("刷新捕获文件失败: {:?}，停止记录", e);
+            *capture = None;
+        }
+    }
+}
+
+// 只订阅数据流并写入捕获文件
+async fn record(config: Config, dir: String, rotate_mb: u64) {
+    if config.ingest_mode != IngestMode::Proxy {
+        println!("record命令只支持proxy接入方式，分片模式请使用 run --record");
+        return;
+    }
+    let mut capture = match CaptureWriter::new(&dir, rotate_mb * 1024 * 1024) {
+        Ok(capture) => Some(capture),
+        Err(e) => {
+            println!("创建捕获目录失败: {:?}", e);
+            return;
+        }
+    };
+    let client = match ShredstreamClient::new(config).await {
+        Ok(client) => client,
+        Err(e) => {
+            println!("创建客户端失败: {:?}", e);
+            return;
+        }
+    };
+
+    println!("开始记录数据流到 {}", dir);
+    let mut arrivals = client.spawn_subscriptions();
+    let mut flush_interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
+    loop {
+        tokio::select! {
+            arrival = arrivals.recv() => {
+                match arrival {
+                    Some(arrival) => write_capture(&mut capture, &CaptureRecord::from_arrival(&arrival)),
+                    None => {
+                        println!("所有端点订阅已结束");
+                        break;
+                    }
+                }
+            }
+            _ = flush_interval.tick() => {
+                if let Some(writer) = capture.as_mut() {
+                    if let Err(e) = writer.flush() {
+                        println!("刷新捕获文件失败: {:?}", e);
+                    }
+                    println!("已记录 {} 条消息", writer.records());
+                }
+            }
+        }
+    }
+}
+
+// 从快照文件加载地址查找表，文件不存在时跳过
+fn load_lookup_tables(lookup_tables: &LookupTableCache, path: &str) {
+    if !std::path::Path::new(path).exists() {
+        return;
+    }
+    match lookup_tables.load_snapshot(path) {
+        Ok(count) => println!("从 {} 加载了 {} 个查找表", path, count),
+        Err(e) => println!("加载查找表快照失败: {:?}", e),
+    }
+}
+
+// 按STRATEGY_CONFIG加载策略，未配置时使用环境变量中的价格区间策略
+// 没有单独配置退出规则的策略使用环境变量中的退出规则
+fn load_strategies(config: &Config, params: &SnipeParams) -> Option<StrategySet> {
+    let exit = ExitRules::from_env();
+    println!("默认退出规则: {}", exit);
+    let strategies = match &config.strategy_config {
+        Some(path) => match StrategySet::load(path, &exit) {
+            Ok(strategies) => strategies,
             Err(e) => {
-                println!("连接断开: {e}");
-                println!("5秒后重新连接...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                println!("加载策略配置 {} 失败: {}", path, e);
+                return None;
+            }
+        },
+        None => StrategySet::from_params(params, &exit),
+    };
+    for (name, budget) in strategies.budgets() {
+        if budget.limit() == u64::MAX {
+            println!("启用策略: {} (不限预算)", name);
+        } else {
+            println!("启用策略: {} (预算 {} SOL)", name, budget.limit() as f64 / 1_000_000_000.0);
+        }
+    }
+    Some(strategies)
+}
+
+// 从环境变量读取交易设置：发送前模拟、交易路径、预签名买入模板和优先级费用
+fn trade_settings() -> Result<(bool, PumpRoute, bool, FeePolicy), String> {
+    Ok((
+        env_or("SIMULATE_BEFORE_SEND", false)?,
+        env_or("PUMP_ROUTE", PumpRoute::default())?,
+        env_or("BUY_TEMPLATES", true)?,
+        FeePolicy::from_env()?,
+    ))
+}
+
+// 读取并解析环境变量，未设置时使用默认值，无效时返回错误
+fn env_or<T>(name: &str, default: T) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value.parse().map_err(|e| format!("环境变量{}无效: {}", name, e)),
+        Err(_) => Ok(default),
+    }
+}
+
+// 定期输出各发送节点的统计，按最先提交且上链的次数排名
+fn spawn_sender_stats(sender: TransactionSender) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300));
+        interval.tick().await;
+        let mut last_sends = 0;
+        loop {
+            interval.tick().await;
+            let sends = sender.total_sends();
+            if sends == last_sends {
+                continue;
+            }
+            last_sends = sends;
+            println!("发送节点排名:");
+            for stats in sender.stats() {
+                println!("  {}", stats);
             }
         }
+    });
+}
+
+// 离线使用的查找表缓存，只从快照加载
+fn offline_lookup_tables(config: &Config) -> LookupTableCache {
+    let lookup_tables = LookupTableCache::offline();
+    if let Some(path) = &config.lookup_table_snapshot {
+        load_lookup_tables(&lookup_tables, path);
+    }
+    lookup_tables
+}
+
+// 将捕获文件回放给处理器，与实时运行一样先按首次到达去重
+async fn replay(config: Config, path: String, speed: ReplaySpeed) {
+    let mut processor = TransactionProcessor::new(config.token_creator_pubkey);
+    processor.set_lookup_tables(offline_lookup_tables(&config));
+    // 回放时不交易，只打印策略的决定
+    if let Some(strategies) = load_strategies(&config, &SnipeParams::from_env()) {
+        processor.set_strategies(strategies);
+    }
+
+    println!("开始回放: {} (速度: {:?})", path, speed);
+    let result = capture::replay_entries(&path, speed, |slot, _, entries| {
+        if let Err(e) = processor.process_entries(entries, slot) {
+            println!("处理条目失败: {:?}", e);
+        }
+    })
+    .await;
+
+    match result {
+        Ok(count) => println!("回放完成，共 {} 条消息", count),
+        Err(e) => println!("回放失败: {:?}", e),
+    }
+}
+
+// 回测捕获文件并输出报告
+async fn backtest(
+    backtest_config: BacktestConfig,
+    strategies: StrategySet,
+    lookup_tables: LookupTableCache,
+    path: String,
+    json: String,
+    csv: String,
+) {
+    println!("开始回测: {} (延迟: {:?})", path, backtest_config.latency);
+    let report = match Backtester::run_capture(backtest_config, strategies, lookup_tables, &path).await {
+        Ok(report) => report,
+        Err(e) => {
+            println!("回测失败: {:?}", e);
+            return;
+        }
+    };
+
+    let summary = &report.summary;
+    println!("---------------------------");
+    println!("新代币: {}  信号: {}  成交: {}", summary.mints_created, summary.signals, summary.filled);
+    println!("盈利: {}  亏损: {}  胜率: {:.1}%", summary.wins, summary.losses, summary.win_rate * 100.0);
+    println!("总收益: {:.6} SOL", summary.total_pnl_lamports as f64 / 1_000_000_000.0);
+    println!("最大回撤: {:.6} SOL", summary.max_drawdown_lamports as f64 / 1_000_000_000.0);
+    println!("平均持仓: {:.0}ms", summary.avg_hold_ms);
+
+    if let Err(e) = report.write_json(&json) {
+        println!("写入JSON报告失败: {:?}", e);
+    }
+    if let Err(e) = report.write_csv(&csv) {
+        println!("写入CSV报告失败: {:?}", e);
     }
+    println!("报告已写入 {} 和 {}", json, csv);
+}
+
+// 打印持仓账本中的持仓和按策略、按代币汇总的盈亏
+async fn positions(all: bool) {
+    let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    let rpc_url = env::var("RPC_URL").unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+    let redis_client = match RedisClient::new(&redis_url).await {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            println!("Redis连接失败: {:?}", e);
+            return;
+        }
+    };
+    let mut ledger = PositionLedger::with_redis(redis_client);
+    match ledger.load().await {
+        Ok(count) => println!("共 {} 笔成交", count),
+        Err(e) => {
+            println!("读取持仓账本失败: {:?}", e);
+            return;
+        }
+    }
+
+    // 读取未平仓代币的内盘曲线用于估值，读取失败的持仓不计入未实现盈亏
+    let fee_basis_points = match bonding_curve::fetch_global_account(&rpc_url).await {
+        Ok(global) => global.fee_basis_points,
+        Err(e) => {
+            println!("读取Global账户失败: {:?}，使用默认手续费", e);
+            GlobalAccount::default().fee_basis_points
+        }
+    };
+    let mut curves: HashMap<Pubkey, BondingCurve> = HashMap::new();
+    for (_, mint, _) in ledger.open_positions() {
+        if curves.contains_key(mint) {
+            continue;
+        }
+        match bonding_curve::fetch_bonding_curve(&rpc_url, mint).await {
+            Ok(curve) => {
+                curves.insert(*mint, curve);
+            }
+            Err(e) => println!("读取 {} 的内盘曲线失败: {:?}", mint, e),
+        }
+    }
+    let value = |mint: &Pubkey, token_amount: u64| {
+        curves.get(mint)?.sell_quote_with_fee(token_amount, fee_basis_points).ok()
+    };
+
+    let mut rows: Vec<_> = ledger.positions().filter(|(_, _, position)| all || position.is_open()).collect();
+    rows.sort_by_key(|(_, _, position)| position.opened_ms);
+    println!("---------------------------");
+    for (strategy, mint, position) in rows {
+        let unrealized = match value(mint, position.token_amount) {
+            Some(value) if position.is_open() => format!("{:+.6}", position.unrealized_pnl(value) as f64 / 1_000_000_000.0),
+            _ => "-".to_string(),
+        };
+        let unreconciled = if position.unreconciled > 0 {
+            format!("  ({} 笔成交未核对)", position.unreconciled)
+        } else {
+            String::new()
+        };
+        println!(
+            "[{}] {}  持仓: {}  均价: {:.9} SOL  成本: {:.6} SOL  已实现: {:+.6}  未实现: {}{}",
+            strategy,
+            mint,
+            position.token_amount,
+            // 按6位精度换算为每个代币的SOL价格
+            position.avg_cost() * 1_000_000.0 / 1_000_000_000.0,
+            position.cost_basis as f64 / 1_000_000_000.0,
+            position.realized_pnl as f64 / 1_000_000_000.0,
+            unrealized,
+            unreconciled
+        );
+    }
+
+    println!("---------------------------");
+    println!("按策略:");
+    for (strategy, summary) in ledger.by_strategy(value) {
+        print_pnl_summary(&strategy, &summary);
+    }
+    println!("按代币:");
+    for (mint, summary) in ledger.by_mint(value) {
+        print_pnl_summary(&mint, &summary);
+    }
+    print_pnl_summary("合计", &ledger.total(value));
+}
+
+// 测量从触发到签好买入交易的延迟，不发送交易
+// 每次测量使用新的代币：上下文方式在测量前按Create指令缓存地址，与实盘一致
+fn bench(iterations: usize) {
+    let iterations = iterations.max(1);
+    let keypair = solana_sdk::signature::Keypair::new();
+    let private_key = keypair.to_base58_string();
+    let rpc_url = "http://127.0.0.1:8899".to_string();
+    let trader = PumpTrader::new(
+        keypair,
+        TransactionSender::new(&[SendEndpoint::parse(&rpc_url)]),
+        Arc::new(solana_rpc_client::nonblocking::rpc_client::RpcClient::new(rpc_url)),
+    );
+    let params = SnipeParams::from_env();
+    let global = GlobalAccount::default();
+    let curve = global.new_curve();
+    let fees = match FeePolicy::from_env() {
+        Ok(policy) => FeeOracle::new(policy),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let blockhash = solana_sdk::hash::Hash::new_unique();
+
+    // 按曲线计算数量、计算费用并签名，两种方式共用
+    let sign = |signer: &solana_sdk::signature::Keypair, accounts: &MintAccounts| {
+        let token_amount = params.token_amount(params.buy_amount, &curve, global.fee_basis_points).unwrap_or(0);
+        let instructions = transaction::buy_instructions(
+            trader.route(),
+            &signer.pubkey(),
+            accounts,
+            token_amount,
+            params.buy_amount,
+            fees.compute_unit_price(Some(&accounts.mint)),
+            fees.compute_unit_limit(ComputeKind::Buy),
+        );
+        transaction::sign_transaction(signer, &instructions, blockhash)
+    };
+
+    let mut cached = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let mint = Pubkey::new_unique();
+        let created = MintAccounts::derive(&Pubkey::new_unique(), mint);
+        trader.mint_accounts().insert_created(mint, created.bonding_curve, created.associated_bonding_curve);
+        let start = std::time::Instant::now();
+        let transaction = sign(trader.signer(), &trader.accounts(&mint));
+        cached.push(start.elapsed());
+        std::hint::black_box(transaction);
+    }
+
+    let mut uncached = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let mint = Pubkey::new_unique();
+        let start = std::time::Instant::now();
+        let transaction = match transaction::parse_keypair(&private_key) {
+            Ok(signer) => sign(&signer, &MintAccounts::derive(&signer.pubkey(), mint)),
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        uncached.push(start.elapsed());
+        std::hint::black_box(transaction);
+    }
+
+    println!("从触发到签好买入交易的延迟 ({} 次):", iterations);
+    print_latency("交易上下文", &mut cached);
+    print_latency("解析私钥并搜索PDA", &mut uncached);
+}
+
+fn print_latency(name: &str, samples: &mut [std::time::Duration]) {
+    samples.sort_unstable();
+    let micros = |index: usize| samples[index.min(samples.len() - 1)].as_secs_f64() * 1_000_000.0;
+    let mean = samples.iter().map(|sample| sample.as_secs_f64()).sum::<f64>() * 1_000_000.0 / samples.len() as f64;
+    println!(
+        "{:<18} 平均 {:>8.1}us  p50 {:>8.1}us  p99 {:>8.1}us  最大 {:>8.1}us",
+        name,
+        mean,
+        micros(samples.len() / 2),
+        micros(samples.len() * 99 / 100),
+        micros(samples.len() - 1)
+    );
+}
+
+// 打印一组持仓的盈亏汇总
+fn print_pnl_summary(name: &str, summary: &PnlSummary) {
+    let unpriced = if summary.unpriced > 0 {
+        format!(" ({} 个未估值)", summary.unpriced)
+    } else {
+        String::new()
+    };
+    let unreconciled = if summary.unreconciled > 0 {
+        format!("  {} 个持仓含未核对成交", summary.unreconciled)
+    } else {
+        String::new()
+    };
+    println!(
+        "  {}  持仓: {}/{}  成本: {:.6} SOL  已实现: {:+.6}  未实现: {:+.6}{}  合计: {:+.6}  手续费: {:.6}  小费: {:.6}{}",
+        name,
+        summary.open,
+        summary.positions,
+        summary.cost_basis as f64 / 1_000_000_000.0,
+        summary.realized_pnl as f64 / 1_000_000_000.0,
+        summary.unrealized_pnl as f64 / 1_000_000_000.0,
+        unpriced,
+        summary.total_pnl() as f64 / 1_000_000_000.0,
+        summary.fees as f64 / 1_000_000_000.0,
+        summary.tips as f64 / 1_000_000_000.0,
+        unreconciled
+    );
 }
@@ -0,0 +1,229 @@
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::v0::{LoadedAddresses, MessageAddressTableLookup};
+use solana_sdk::message::{legacy, v0, AccountKeys, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::instruction::{resolve_accounts, InstructionError, PumpInstruction};
+use crate::transaction::trader::MintAccountCache;
+use crate::transaction::PUMP_PROGRAM_ID;
+
+/// legacy和v0消息的统一只读视图
+///
+/// 处理器、回测器等只依赖这个视图，新的解码和策略逻辑不需要区分消息版本。
+pub trait MessageView {
+    /// 消息中直接列出的账户
+    fn static_keys(&self) -> &[Pubkey];
+
+    /// 地址查找表引用，legacy消息为空
+    fn table_lookups(&self) -> &[MessageAddressTableLookup];
+
+    fn instructions(&self) -> &[CompiledInstruction];
+}
+
+impl MessageView for legacy::Message {
+    fn static_keys(&self) -> &[Pubkey] {
+        &self.account_keys
+    }
+
+    fn table_lookups(&self) -> &[MessageAddressTableLookup] {
+        &[]
+    }
+
+    fn instructions(&self) -> &[CompiledInstruction] {
+        &self.instructions
+    }
+}
+
+impl MessageView for v0::Message {
+    fn static_keys(&self) -> &[Pubkey] {
+        &self.account_keys
+    }
+
+    fn table_lookups(&self) -> &[MessageAddressTableLookup] {
+        &self.address_table_lookups
+    }
+
+    fn instructions(&self) -> &[CompiledInstruction] {
+        &self.instructions
+    }
+}
+
+impl MessageView for VersionedMessage {
+    fn static_keys(&self) -> &[Pubkey] {
+        self.static_account_keys()
+    }
+
+    fn table_lookups(&self) -> &[MessageAddressTableLookup] {
+        self.address_table_lookups().unwrap_or_default()
+    }
+
+    fn instructions(&self) -> &[CompiledInstruction] {
+        VersionedMessage::instructions(self)
+    }
+}
+
+/// 已解析查找表的消息
+///
+/// 完整账户列表为静态账户、所有可写加载地址、所有只读加载地址，与链上的账户索引一致。
+pub struct ResolvedMessage<'a, M: MessageView + ?Sized = VersionedMessage> {
+    pub message: &'a M,
+    pub loaded: LoadedAddresses,
+}
+
+impl<'a, M: MessageView + ?Sized> ResolvedMessage<'a, M> {
+    /// 使用查找表加载的地址构建
+    pub fn new(message: &'a M, loaded: LoadedAddresses) -> Self {
+        Self { message, loaded }
+    }
+
+    /// 只包含静态账户，查找表中的账户解析为默认公钥
+    pub fn unresolved(message: &'a M) -> Self {
+        Self::new(message, LoadedAddresses::default())
+    }
+
+    /// 完整账户列表
+    pub fn account_keys(&self) -> AccountKeys<'_> {
+        AccountKeys::new(self.message.static_keys(), Some(&self.loaded))
+    }
+
+    /// 消息是否引用了指定账户
+    pub fn contains_key(&self, key: &Pubkey) -> bool {
+        self.account_keys().iter().any(|k| k == key)
+    }
+
+    /// 指令调用的程序
+    pub fn program_id(&self, instruction: &CompiledInstruction) -> Option<&Pubkey> {
+        self.message.static_keys().get(instruction.program_id_index as usize)
+    }
+
+    /// 按指令的账户索引取出账户公钥
+    pub fn instruction_accounts(&self, instruction: &CompiledInstruction) -> Vec<Pubkey> {
+        resolve_accounts(&instruction.accounts, &self.account_keys())
+    }
+
+    /// 解析消息中所有直接调用Pump程序的指令
+    ///
+    /// 无法解析的指令返回错误，内盘曲线账户与PDA不符的指令同样返回错误。
+    pub fn pump_instructions(&self) -> impl Iterator<Item = Result<PumpInstruction, InstructionError>> + '_ {
+        self.pump_instructions_with(None)
+    }
+
+    /// 解析Pump指令，内盘曲线PDA从代币地址缓存中读取
+    ///
+    /// 查找表未加载时，账户全部来自静态账户的指令照常解析，其余指令返回错误。
+    ///
+    /// # 参数
+    ///
+    /// * `mint_accounts` - 代币交易地址缓存，未设置时每条指令都搜索PDA
+    pub fn pump_instructions_with<'b>(
+        &'b self,
+        mint_accounts: Option<&'b MintAccountCache>,
+    ) -> impl Iterator<Item = Result<PumpInstruction, InstructionError>> + 'b {
+        self.message
+            .instructions()
+            .iter()
+            .filter(|instruction| self.program_id(instruction) == Some(&PUMP_PROGRAM_ID))
+            .map(move |instruction| {
+                let loaded = self.message.static_keys().len() + self.loaded.len();
+                if let Some(index) = instruction.accounts.iter().find(|index| **index as usize >= loaded) {
+                    return Err(InstructionError::AccountNotLoaded { index: *index });
+                }
+                let accounts = self.instruction_accounts(instruction);
+                let parsed = PumpInstruction::decode(&instruction.data, &accounts)?;
+                match mint_accounts {
+                    Some(cache) => parsed.validate_accounts_with(|mint| cache.bonding_curve(mint))?,
+                    None => parsed.validate_accounts()?,
+                }
+                Ok(parsed)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::tests::{buy_instruction, create_instruction, sell_instruction};
+    use crate::lookup_table::LookupTableCache;
+    use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::system_instruction;
+
+    // 把指定账户放进查找表编译v0消息
+    fn v0_message(payer: &Pubkey, instructions: &[Instruction], table: &AddressLookupTableAccount) -> VersionedMessage {
+        VersionedMessage::V0(v0::Message::try_compile(payer, instructions, std::slice::from_ref(table), Hash::new_unique()).unwrap())
+    }
+
+    #[test]
+    fn decodes_static_instructions_before_table_loads() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let buy = buy_instruction(&mint, &user, 1_000, 2_000);
+        let recipient = Pubkey::new_unique();
+        let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: vec![recipient] };
+        // 只有转账的接收方来自查找表
+        let message = v0_message(&user, &[system_instruction::transfer(&user, &recipient, 1), buy.clone()], &table);
+
+        let cache = LookupTableCache::offline();
+        assert!(cache.resolve(&message).is_none());
+        let resolved = ResolvedMessage::unresolved(&message);
+        let parsed: Vec<_> = resolved.pump_instructions().collect();
+        assert_eq!(parsed.len(), 1);
+        let PumpInstruction::Buy { accounts, .. } = parsed[0].as_ref().unwrap() else {
+            panic!("应解析为Buy");
+        };
+        assert_eq!(accounts.associated_user, buy.accounts[5].pubkey);
+    }
+
+    #[test]
+    fn instructions_using_unloaded_table_wait_for_it() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let buy = buy_instruction(&mint, &user, 1_000, 2_000);
+        let associated_user = buy.accounts[5].pubkey;
+        let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: vec![associated_user] };
+        let message = v0_message(&user, std::slice::from_ref(&buy), &table);
+
+        let unresolved = ResolvedMessage::unresolved(&message);
+        let parsed: Vec<_> = unresolved.pump_instructions().collect();
+        assert!(matches!(parsed[..], [Err(InstructionError::AccountNotLoaded { .. })]));
+
+        let cache = LookupTableCache::offline();
+        cache.insert(table.key, table.addresses.clone());
+        let resolved = cache.resolve(&message).unwrap();
+        let PumpInstruction::Buy { accounts, .. } = resolved.pump_instructions().next().unwrap().unwrap() else {
+            panic!("应解析为Buy");
+        };
+        assert_eq!(accounts.associated_user, associated_user);
+    }
+
+    #[test]
+    fn legacy_and_resolved_v0_decode_the_same() {
+        let (mint, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let create = create_instruction(&mint, &user);
+        let buy = buy_instruction(&mint, &user, 1_000, 2_000);
+        let sell = sell_instruction(&mint, &user, 500, 400);
+        let instructions = [create, buy.clone(), sell];
+        let legacy = legacy::Message::new(&instructions, Some(&user));
+
+        // 关联账户（可写）和事件账户（只读）来自查找表
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![buy.accounts[4].pubkey, buy.accounts[10].pubkey, buy.accounts[5].pubkey],
+        };
+        let v0 = v0_message(&user, &instructions, &table);
+        assert_eq!(v0.table_lookups().len(), 1);
+        assert!(v0.static_keys().len() < legacy.static_keys().len());
+
+        let cache = LookupTableCache::offline();
+        cache.insert(table.key, table.addresses.clone());
+        let legacy_resolved = cache.resolve(&legacy).unwrap();
+        let v0_resolved = cache.resolve(&v0).unwrap();
+        assert_eq!(v0_resolved.account_keys().len(), legacy_resolved.account_keys().len());
+        assert_eq!(v0_resolved.loaded.writable.len(), 2);
+        assert_eq!(v0_resolved.loaded.readonly, vec![buy.accounts[10].pubkey]);
+
+        let from_legacy: Vec<_> = legacy_resolved.pump_instructions().collect::<Result<_, _>>().unwrap();
+        let from_v0: Vec<_> = v0_resolved.pump_instructions().collect::<Result<_, _>>().unwrap();
+        assert_eq!(from_legacy.len(), 3);
+        assert_eq!(from_legacy, from_v0);
+    }
+}
@@ -0,0 +1,382 @@
+use redis::RedisError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::processor::mint_state::TradeSide;
+use crate::utils::redis::RedisClient;
+
+/// 一笔成交
+///
+/// 交易确认上链后记录，数量、金额和协议手续费取自上链交易的余额变化。查询不到交易时使用提交时
+/// 按内盘曲线计算的值，并标记为未核对。协议手续费已计入`lamports`，小费另行记录，网络费用不计入。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub strategy: String,
+    #[serde(serialize_with = "serialize_pubkey", deserialize_with = "deserialize_pubkey")]
+    pub mint: Pubkey,
+    pub signature: String,
+    // 上链的slot
+    pub slot: Option<u64>,
+    pub side: TradeSide,
+    pub token_amount: u64,
+    // 买入为花费，卖出为所得 (lamports)
+    pub lamports: u64,
+    // 协议手续费 (lamports)
+    pub fee_lamports: u64,
+    // 捆绑小费 (lamports)
+    pub tip_lamports: u64,
+    pub timestamp_ms: u64,
+    // 查询不到上链交易，数量和金额为提交时的报价
+    #[serde(default)]
+    pub unreconciled: bool,
+}
+
+fn serialize_pubkey<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+fn deserialize_pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Pubkey::from_str(&value).map_err(serde::de::Error::custom)
+}
+
+/// 一个策略在一个代币上的持仓
+///
+/// 按平均成本计算：买入的花费和小费计入成本，卖出时按卖出比例结转成本，
+/// 所得扣除小费和结转成本后计入已实现盈亏。
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Position {
+    // 当前持有的代币数量
+    pub token_amount: u64,
+    // 当前持仓的成本 (lamports)
+    pub cost_basis: u64,
+    pub bought_tokens: u64,
+    pub bought_lamports: u64,
+    pub sold_tokens: u64,
+    pub sold_lamports: u64,
+    pub realized_pnl: i64,
+    pub fees: u64,
+    pub tips: u64,
+    pub fills: usize,
+    // 按报价记录、未经核对的成交数量
+    pub unreconciled: usize,
+    pub opened_ms: u64,
+    pub last_ms: u64,
+}
+
+impl Position {
+    /// 应用一笔成交
+    pub fn apply(&mut self, fill: &Fill) {
+        if self.token_amount == 0 && fill.side == TradeSide::Buy {
+            self.opened_ms = fill.timestamp_ms;
+        }
+        match fill.side {
+            TradeSide::Buy => {
+                self.token_amount += fill.token_amount;
+                self.cost_basis += fill.lamports + fill.tip_lamports;
+                self.bought_tokens += fill.token_amount;
+                self.bought_lamports += fill.lamports;
+            }
+            TradeSide::Sell => {
+                // 卖出超过账本中的持仓时只结转已有的成本
+                let sold = fill.token_amount.min(self.token_amount);
+                let released = match self.token_amount {
+                    0 => 0,
+                    held => (self.cost_basis as u128 * sold as u128 / held as u128) as u64,
+                };
+                self.token_amount -= sold;
+                self.cost_basis -= released;
+                self.sold_tokens += fill.token_amount;
+                self.sold_lamports += fill.lamports;
+                self.realized_pnl += fill.lamports as i64 - fill.tip_lamports as i64 - released as i64;
+            }
+        }
+        self.fees += fill.fee_lamports;
+        self.tips += fill.tip_lamports;
+        self.fills += 1;
+        if fill.unreconciled {
+            self.unreconciled += 1;
+        }
+        self.last_ms = fill.timestamp_ms;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.token_amount > 0
+    }
+
+    /// 平均成本 (lamports/代币，含精度)
+    pub fn avg_cost(&self) -> f64 {
+        if self.token_amount == 0 {
+            return 0.0;
+        }
+        self.cost_basis as f64 / self.token_amount as f64
+    }
+
+    /// 按卖出当前持仓的所得计算的未实现盈亏
+    ///
+    /// # 参数
+    ///
+    /// * `value` - 卖出当前持仓扣除手续费后的所得 (lamports)
+    pub fn unrealized_pnl(&self, value: u64) -> i64 {
+        value as i64 - self.cost_basis as i64
+    }
+}
+
+/// 一组持仓的盈亏汇总
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PnlSummary {
+    pub positions: usize,
+    pub open: usize,
+    // 无法估值的未平仓持仓，未计入未实现盈亏
+    pub unpriced: usize,
+    // 含有未核对成交的持仓
+    pub unreconciled: usize,
+    pub cost_basis: u64,
+    pub bought_lamports: u64,
+    pub sold_lamports: u64,
+    pub realized_pnl: i64,
+    pub unrealized_pnl: i64,
+    pub fees: u64,
+    pub tips: u64,
+}
+
+impl PnlSummary {
+    fn add(&mut self, position: &Position, value: Option<u64>) {
+        self.positions += 1;
+        if position.unreconciled > 0 {
+            self.unreconciled += 1;
+        }
+        if position.is_open() {
+            self.open += 1;
+            match value {
+                Some(value) => self.unrealized_pnl += position.unrealized_pnl(value),
+                None => self.unpriced += 1,
+            }
+        }
+        self.cost_basis += position.cost_basis;
+        self.bought_lamports += position.bought_lamports;
+        self.sold_lamports += position.sold_lamports;
+        self.realized_pnl += position.realized_pnl;
+        self.fees += position.fees;
+        self.tips += position.tips;
+    }
+
+    /// 已实现和未实现盈亏之和
+    pub fn total_pnl(&self) -> i64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+/// 持仓账本
+///
+/// 记录每一笔成交并按策略和代币维护持仓。配置了Redis时成交追加写入列表`position_fills`，
+/// 启动时按顺序重放即可恢复所有持仓和盈亏。
+#[derive(Default)]
+pub struct PositionLedger {
+    positions: HashMap<(String, Pubkey), Position>,
+    fills: Vec<Fill>,
+    redis: Option<Arc<RedisClient>>,
+}
+
+impl PositionLedger {
+    /// 创建写入Redis的账本，需要调用`load`恢复已有的成交
+    pub fn with_redis(redis: Arc<RedisClient>) -> Self {
+        Self {
+            redis: Some(redis),
+            ..Self::default()
+        }
+    }
+
+    /// 从Redis重放已保存的成交，返回成交数量
+    ///
+    /// 无法解析的成交会被跳过。
+    pub async fn load(&mut self) -> Result<usize, RedisError> {
+        let redis = match &self.redis {
+            Some(redis) => Arc::clone(redis),
+            None => return Ok(0),
+        };
+        self.positions.clear();
+        self.fills.clear();
+        for value in redis.load_fills().await? {
+            match serde_json::from_str::<Fill>(&value) {
+                Ok(fill) => self.apply(fill),
+                Err(e) => println!("跳过无效的成交记录: {} ({})", value, e),
+            }
+        }
+        Ok(self.fills.len())
+    }
+
+    /// 记录成交，配置了Redis时同时保存
+    ///
+    /// 保存失败时成交仍然计入内存中的持仓。
+    pub async fn record(&mut self, fill: Fill) -> Result<(), RedisError> {
+        let value = serde_json::to_string(&fill).expect("成交记录序列化失败");
+        self.apply(fill);
+        if let Some(redis) = &self.redis {
+            redis.push_fill(&value).await?;
+        }
+        Ok(())
+    }
+
+    /// 只在内存中应用成交
+    pub fn apply(&mut self, fill: Fill) {
+        self.positions
+            .entry((fill.strategy.clone(), fill.mint))
+            .or_default()
+            .apply(&fill);
+        self.fills.push(fill);
+    }
+
+    /// 所有持仓，包括已平仓的
+    pub fn positions(&self) -> impl Iterator<Item = (&str, &Pubkey, &Position)> + '_ {
+        self.positions.iter().map(|((strategy, mint), position)| (strategy.as_str(), mint, position))
+    }
+
+    /// 未平仓的持仓
+    pub fn open_positions(&self) -> impl Iterator<Item = (&str, &Pubkey, &Position)> + '_ {
+        self.positions().filter(|(_, _, position)| position.is_open())
+    }
+
+    /// 按策略汇总盈亏
+    ///
+    /// # 参数
+    ///
+    /// * `value` - 卖出给定数量代币扣除手续费后的所得，无法估值时返回None
+    pub fn by_strategy(&self, value: impl Fn(&Pubkey, u64) -> Option<u64>) -> BTreeMap<String, PnlSummary> {
+        let mut summaries: BTreeMap<String, PnlSummary> = BTreeMap::new();
+        for (strategy, mint, position) in self.positions() {
+            summaries
+                .entry(strategy.to_string())
+                .or_default()
+                .add(position, value(mint, position.token_amount));
+        }
+        summaries
+    }
+
+    /// 按代币汇总所有策略的盈亏
+    pub fn by_mint(&self, value: impl Fn(&Pubkey, u64) -> Option<u64>) -> BTreeMap<String, PnlSummary> {
+        let mut summaries: BTreeMap<String, PnlSummary> = BTreeMap::new();
+        for (_, mint, position) in self.positions() {
+            summaries
+                .entry(mint.to_string())
+                .or_default()
+                .add(position, value(mint, position.token_amount));
+        }
+        summaries
+    }
+
+    /// 汇总所有持仓的盈亏
+    pub fn total(&self, value: impl Fn(&Pubkey, u64) -> Option<u64>) -> PnlSummary {
+        let mut summary = PnlSummary::default();
+        for (_, mint, position) in self.positions() {
+            summary.add(position, value(mint, position.token_amount));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn fill(strategy: &str, mint: Pubkey, side: TradeSide, token_amount: u64, lamports: u64) -> Fill {
+        Fill {
+            strategy: strategy.to_string(),
+            mint,
+            signature: String::new(),
+            slot: Some(1),
+            side,
+            token_amount,
+            lamports,
+            fee_lamports: lamports / 100,
+            tip_lamports: 0,
+            timestamp_ms: 1_000,
+            unreconciled: false,
+        }
+    }
+
+    #[test]
+    fn partial_sell_releases_average_cost() {
+        let mint = Pubkey::new_unique();
+        let mut position = Position::default();
+        position.apply(&Fill { tip_lamports: 10_000, ..fill("a", mint, TradeSide::Buy, 1_000, SOL) });
+        position.apply(&fill("a", mint, TradeSide::Buy, 1_000, 3 * SOL));
+        assert_eq!(position.cost_basis, 4 * SOL + 10_000);
+        assert_eq!(position.avg_cost(), (4 * SOL + 10_000) as f64 / 2_000.0);
+
+        // 卖出一半持仓结转一半成本
+        position.apply(&fill("a", mint, TradeSide::Sell, 1_000, 3 * SOL));
+        assert_eq!(position.token_amount, 1_000);
+        assert_eq!(position.cost_basis, 2 * SOL + 5_000);
+        assert_eq!(position.realized_pnl, (SOL - 5_000) as i64);
+        assert_eq!(position.unrealized_pnl(SOL), -((SOL + 5_000) as i64));
+        assert_eq!(position.fees, (SOL + 3 * SOL + 3 * SOL) / 100);
+        assert_eq!(position.tips, 10_000);
+        assert_eq!(position.fills, 3);
+        assert!(position.is_open());
+    }
+
+    #[test]
+    fn overselling_only_releases_held_cost() {
+        let mint = Pubkey::new_unique();
+        let mut position = Position::default();
+        position.apply(&fill("a", mint, TradeSide::Buy, 1_000, SOL));
+        position.apply(&fill("a", mint, TradeSide::Sell, 1_500, 2 * SOL));
+        assert_eq!(position.token_amount, 0);
+        assert_eq!(position.cost_basis, 0);
+        assert_eq!(position.sold_tokens, 1_500);
+        assert_eq!(position.realized_pnl, SOL as i64);
+        assert!(!position.is_open());
+        assert_eq!(position.avg_cost(), 0.0);
+    }
+
+    #[test]
+    fn summaries_group_positions_and_count_unreconciled() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut ledger = PositionLedger::default();
+        ledger.apply(fill("x", a, TradeSide::Buy, 1_000, SOL));
+        ledger.apply(Fill { unreconciled: true, ..fill("x", b, TradeSide::Buy, 1_000, SOL) });
+        ledger.apply(fill("y", a, TradeSide::Buy, 1_000, 2 * SOL));
+        ledger.apply(fill("y", a, TradeSide::Sell, 1_000, 3 * SOL));
+
+        let open: Vec<_> = ledger.open_positions().map(|(strategy, mint, _)| (strategy.to_string(), *mint)).collect();
+        assert_eq!(open.len(), 2);
+        assert!(!open.contains(&("y".to_string(), a)));
+
+        // b无法估值，a按1.5 SOL估值
+        let value = |mint: &Pubkey, _: u64| (*mint == a).then_some(3 * SOL / 2);
+        let by_strategy = ledger.by_strategy(value);
+        let x = &by_strategy["x"];
+        assert_eq!((x.positions, x.open, x.unpriced, x.unreconciled), (2, 2, 1, 1));
+        assert_eq!(x.unrealized_pnl, (SOL / 2) as i64);
+        assert_eq!(by_strategy["y"].realized_pnl, SOL as i64);
+
+        let by_mint = ledger.by_mint(value);
+        assert_eq!(by_mint[&a.to_string()].positions, 2);
+        assert_eq!(by_mint[&b.to_string()].unreconciled, 1);
+
+        let total = ledger.total(value);
+        assert_eq!(total.positions, 3);
+        assert_eq!(total.total_pnl(), (SOL + SOL / 2) as i64);
+        assert_eq!(total.bought_lamports, 4 * SOL);
+        assert_eq!(total.sold_lamports, 3 * SOL);
+    }
+
+    #[test]
+    fn fills_round_trip_and_old_records_are_reconciled() {
+        let original = Fill { unreconciled: true, ..fill("x", Pubkey::new_unique(), TradeSide::Sell, 5, 6) };
+        let json = serde_json::to_string(&original).unwrap();
+        assert_eq!(serde_json::from_str::<Fill>(&json).unwrap(), original);
+
+        // 旧版本记录没有unreconciled字段
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value.as_object_mut().unwrap().remove("unreconciled");
+        let old: Fill = serde_json::from_value(value).unwrap();
+        assert!(!old.unreconciled);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::bonding_curve::{fee, BondingCurve, CurveError, GlobalAccount};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+/// 每个代币默认保留的成交记录数量
+pub const DEFAULT_TAPE_LEN: usize = 256;
+/// 默认最多跟踪的代币数量
+pub const DEFAULT_MAX_MINTS: usize = 10_000;
+
+/// 成交方向
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+/// 成交记录中的一笔交易
+#[derive(Clone, Copy, Debug)]
+pub struct TapeEntry {
+    pub slot: u64,
+    pub signature: Signature,
+    pub side: TradeSide,
+    pub trader: Pubkey,
+    // 实际成交的代币数量
+    pub token_amount: u64,
+    // 指令中的SOL限制，买入为max_sol_cost，卖出为min_sol_output
+    pub sol_bound: u64,
+    // 按曲线计算的SOL数量 (不含手续费)
+    pub sol_amount: u64,
+}
+
+impl fmt::Display for TapeEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:.6} 代币, {:.6} SOL (限制 {:.6} SOL), 交易者 {}, slot {}, 签名 {}",
+            self.side,
+            self.token_amount as f64 / 1_000_000.0,
+            self.sol_amount as f64 / 1_000_000_000.0,
+            self.sol_bound as f64 / 1_000_000_000.0,
+            self.trader,
+            self.slot,
+            self.signature
+        )
+    }
+}
+
+/// 单个代币的曲线状态和最近的成交记录
+#[derive(Clone, Debug)]
+pub struct MintState {
+    pub curve: BondingCurve,
+    // Create指令的发起者，视为开发者
+    pub creator: Pubkey,
+    pub last_slot: u64,
+    // 创建时Global参数中的手续费基点，用于判断观察到的交易是否超出滑点
+    fee_basis_points: u64,
+    tape: VecDeque<TapeEntry>,
+    max_tape_len: usize,
+}
+
+impl MintState {
+    /// 按Global参数创建新代币的状态
+    ///
+    /// # 参数
+    ///
+    /// * `global` - 决定初始曲线和手续费的Global参数
+    /// * `creator` - Create指令的发起者
+    /// * `slot` - 创建所在slot
+    /// * `max_tape_len` - 最多保留的成交记录数量
+    pub fn new(global: &GlobalAccount, creator: Pubkey, slot: u64, max_tape_len: usize) -> Self {
+        Self {
+            curve: global.new_curve(),
+            creator,
+            last_slot: slot,
+            fee_basis_points: global.fee_basis_points,
+            tape: VecDeque::with_capacity(max_tape_len.min(DEFAULT_TAPE_LEN)),
+            max_tape_len,
+        }
+    }
+
+    /// 应用一笔交易到曲线并写入成交记录，返回写入的记录
+    ///
+    /// 含手续费的花费超过买入的max_sol_cost，或扣除手续费的所得低于卖出的min_sol_output时，
+    /// 这笔交易在链上会失败，曲线和成交记录都保持不变。
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - 交易所在slot
+    /// * `signature` - 交易签名
+    /// * `side` - 买入或卖出
+    /// * `trader` - 交易者
+    /// * `token_amount` - 指令中的代币数量
+    /// * `sol_bound` - 指令中的SOL限制
+    pub fn record_trade(
+        &mut self,
+        slot: u64,
+        signature: Signature,
+        side: TradeSide,
+        trader: Pubkey,
+        token_amount: u64,
+        sol_bound: u64,
+    ) -> Result<TapeEntry, CurveError> {
+        let fee_basis_points = self.fee_basis_points;
+        let (token_amount, sol_amount) = match side {
+            TradeSide::Buy => {
+                let token_amount = token_amount.min(self.curve.real_token_reserves);
+                let cost = self.curve.buy_cost(token_amount)?;
+                if cost.saturating_add(fee(cost, fee_basis_points)) > sol_bound {
+                    return Err(CurveError::SlippageExceeded);
+                }
+                self.curve.apply_buy(token_amount)?
+            }
+            TradeSide::Sell => {
+                if self.curve.sell_quote_with_fee(token_amount, fee_basis_points)? < sol_bound {
+                    return Err(CurveError::SlippageExceeded);
+                }
+                (token_amount, self.curve.apply_sell(token_amount)?)
+            }
+        };
+        if self.tape.len() >= self.max_tape_len {
+            self.tape.pop_front();
+        }
+        let entry = TapeEntry {
+            slot,
+            signature,
+            side,
+            trader,
+            token_amount,
+            sol_bound,
+            sol_amount,
+        };
+        self.tape.push_back(entry);
+        self.last_slot = self.last_slot.max(slot);
+        Ok(entry)
+    }
+
+    /// 最近的成交记录，按时间从旧到新
+    pub fn tape(&self) -> &VecDeque<TapeEntry> {
+        &self.tape
+    }
+}
+
+/// 所有跟踪中的代币状态
+///
+/// 代币数量超过上限时淘汰最久没有成交的持仓以外的代币。
+pub struct MintStates {
+    mints: HashMap<Pubkey, MintState>,
+    // 按加入索引时的最后成交slot排序，成交后不立即更新，淘汰时再按当前状态校正
+    by_slot: BTreeSet<(u64, Pubkey)>,
+    max_mints: usize,
+    max_tape_len: usize,
+}
+
+impl Default for MintStates {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_MINTS, DEFAULT_TAPE_LEN)
+    }
+}
+
+impl MintStates {
+    pub fn new(max_mints: usize, max_tape_len: usize) -> Self {
+        Self {
+            mints: HashMap::new(),
+            by_slot: BTreeSet::new(),
+            max_mints,
+            max_tape_len,
+        }
+    }
+
+    /// 开始跟踪新代币，已跟踪的代币保持不变
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 新代币
+    /// * `global` - 决定初始曲线和手续费的Global参数
+    /// * `creator` - Create指令的发起者
+    /// * `slot` - 创建所在slot
+    /// * `held` - 判断代币是否有持仓，有持仓的代币不会被淘汰
+    pub fn track(
+        &mut self,
+        mint: Pubkey,
+        global: &GlobalAccount,
+        creator: Pubkey,
+        slot: u64,
+        held: impl Fn(&Pubkey) -> bool,
+    ) -> &mut MintState {
+        if !self.mints.contains_key(&mint) && self.mints.len() >= self.max_mints {
+            self.evict_oldest(held);
+        }
+        let max_tape_len = self.max_tape_len;
+        let by_slot = &mut self.by_slot;
+        self.mints.entry(mint).or_insert_with(|| {
+            by_slot.insert((slot, mint));
+            MintState::new(global, creator, slot, max_tape_len)
+        })
+    }
+
+    // 淘汰最久没有成交且没有持仓的代币，全部有持仓时不淘汰
+    fn evict_oldest(&mut self, held: impl Fn(&Pubkey) -> bool) {
+        let mut skipped = Vec::new();
+        while let Some((slot, mint)) = self.by_slot.pop_first() {
+            let last_slot = match self.mints.get(&mint) {
+                Some(state) => state.last_slot,
+                None => continue,
+            };
+            if last_slot != slot {
+                // 索引之后又有成交，按当前slot重新排序
+                self.by_slot.insert((last_slot, mint));
+            } else if held(&mint) {
+                skipped.push((slot, mint));
+            } else {
+                self.mints.remove(&mint);
+                break;
+            }
+        }
+        self.by_slot.extend(skipped);
+    }
+
+    pub fn get(&self, mint: &Pubkey) -> Option<&MintState> {
+        self.mints.get(mint)
+    }
+
+    pub fn get_mut(&mut self, mint: &Pubkey) -> Option<&mut MintState> {
+        self.mints.get_mut(mint)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global() -> GlobalAccount {
+        GlobalAccount::default()
+    }
+
+    fn buy(state: &mut MintState, slot: u64, trader: Pubkey) {
+        state
+            .record_trade(slot, Signature::default(), TradeSide::Buy, trader, 1_000_000, u64::MAX)
+            .unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_traded() {
+        let mut mints = MintStates::new(2, 4);
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        mints.track(a, &global(), Pubkey::new_unique(), 1, |_| false);
+        mints.track(b, &global(), Pubkey::new_unique(), 2, |_| false);
+        // a在b之后仍有成交，应淘汰b
+        buy(mints.get_mut(&a).unwrap(), 3, Pubkey::new_unique());
+        mints.track(c, &global(), Pubkey::new_unique(), 4, |_| false);
+        assert!(mints.get(&a).is_some());
+        assert!(mints.get(&b).is_none());
+        assert!(mints.get(&c).is_some());
+    }
+
+    #[test]
+    fn held_mints_are_not_evicted() {
+        let mut mints = MintStates::new(2, 4);
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        mints.track(a, &global(), Pubkey::new_unique(), 1, |_| false);
+        mints.track(b, &global(), Pubkey::new_unique(), 2, |_| false);
+        mints.track(c, &global(), Pubkey::new_unique(), 3, |mint| *mint == a);
+        assert!(mints.get(&a).is_some());
+        assert!(mints.get(&b).is_none());
+
+        // 全部有持仓时超出上限也不淘汰
+        let d = Pubkey::new_unique();
+        mints.track(d, &global(), Pubkey::new_unique(), 4, |_| true);
+        assert!([a, c, d].iter().all(|mint| mints.get(mint).is_some()));
+    }
+
+    #[test]
+    fn tape_is_bounded() {
+        let mut state = MintState::new(&global(), Pubkey::new_unique(), 1, 2);
+        let traders: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        for (slot, trader) in traders.iter().enumerate() {
+            buy(&mut state, slot as u64 + 1, *trader);
+        }
+        let tape: Vec<Pubkey> = state.tape().iter().map(|entry| entry.trader).collect();
+        assert_eq!(tape, traders[1..]);
+        assert_eq!(state.last_slot, 3);
+    }
+
+    #[test]
+    fn slippage_failures_leave_curve_unchanged() {
+        let mut state = MintState::new(&global(), Pubkey::new_unique(), 1, 4);
+        let trader = Pubkey::new_unique();
+        let fee_basis_points = global().fee_basis_points;
+        let cost = state.curve.buy_cost(1_000_000).unwrap();
+        let with_fee = cost + fee(cost, fee_basis_points);
+
+        // 花费加手续费超过max_sol_cost
+        let before = state.curve.clone();
+        let result = state.record_trade(1, Signature::default(), TradeSide::Buy, trader, 1_000_000, with_fee - 1);
+        assert_eq!(result.unwrap_err(), CurveError::SlippageExceeded);
+        assert_eq!(state.curve, before);
+        assert!(state.tape().is_empty());
+
+        let entry = state
+            .record_trade(2, Signature::default(), TradeSide::Buy, trader, 1_000_000, with_fee)
+            .unwrap();
+        assert_eq!((entry.token_amount, entry.sol_amount), (1_000_000, cost));
+
+        // 扣除手续费后的所得低于min_sol_output
+        let proceeds = state.curve.sell_quote_with_fee(500_000, fee_basis_points).unwrap();
+        let before = state.curve.clone();
+        let result = state.record_trade(3, Signature::default(), TradeSide::Sell, trader, 500_000, proceeds + 1);
+        assert_eq!(result.unwrap_err(), CurveError::SlippageExceeded);
+        assert_eq!(state.curve, before);
+        assert_eq!(state.tape().len(), 1);
+        assert_eq!(state.last_slot, 2);
+
+        state
+            .record_trade(4, Signature::default(), TradeSide::Sell, trader, 500_000, proceeds)
+            .unwrap();
+        assert_eq!(state.tape().len(), 2);
+    }
+}
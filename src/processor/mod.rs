@@ -1,36 +1,129 @@
+pub mod mint_state;
+
 use chrono::Local;
-use solana_sdk::{message::VersionedMessage, pubkey::Pubkey, transaction::VersionedTransaction};
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
 use solana_entry::entry::Entry;
-use crate::instruction::parse_instruction_data;
+use crate::instruction::{BuyArgs, CreateAccounts, CreateArgs, InstructionError, PumpInstruction, SellArgs, TradeAccounts};
+use crate::message::{MessageView, ResolvedMessage};
+use crate::transaction::PUMP_PROGRAM_ID;
+use crate::transaction::error::TradeError;
+use crate::transaction::trader::MintAccountCache;
 use std::error::Error;
-use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use crate::utils::auto_trader::AutoTrader;
+use crate::utils::buy_template::BuyTemplates;
+use crate::bonding_curve::GlobalAccount;
+use crate::lookup_table::LookupTableCache;
+use crate::strategy::{EventContext, Intent, Order, StrategyEvent, StrategySet};
+use crate::exit::{ExitEngine, ExitOrder};
+use crate::position::PositionLedger;
+use crate::confirm::Trigger;
+use crate::fee::{ComputeBudget, FeeOracle, FeePolicy};
+use tokio::sync::mpsc;
+use mint_state::{MintState, MintStates, TradeSide};
 
-// 用于存储代币的虚拟储备信息
-struct TokenReserves {
-    virtual_sol_reserves: u64,    // 虚拟SOL储备
-    virtual_token_reserves: u64,  // 虚拟代币储备
+// 后台交易任务报告给处理器的结果
+enum PositionEvent {
+    Bought { order: Order, token_amount: u64, cost_lamports: u64 },
+    Sold { exit: ExitOrder, success: bool },
+    // 买入失败或被回滚，预算已释放
+    BuyFailed { strategy: String, mint: Pubkey },
 }
 
 pub struct TransactionProcessor {
     token_creator_pubkey: Pubkey,
-    // 使用HashMap跟踪各个代币的虚拟储备状态
-    token_reserves: HashMap<String, TokenReserves>,
+    // 各个代币的内盘曲线状态和成交记录
+    mints: MintStates,
+    // 新曲线的初始参数和手续费
+    global: GlobalAccount,
     // 自动交易器
     auto_trader: Option<Arc<Mutex<AutoTrader>>>,
+    // 决定买卖的策略
+    strategies: StrategySet,
+    // 持仓的止盈止损
+    exits: ExitEngine,
+    position_tx: mpsc::UnboundedSender<PositionEvent>,
+    position_rx: mpsc::UnboundedReceiver<PositionEvent>,
+    // 地址查找表缓存，用于解析v0消息加载的账户
+    lookup_tables: LookupTableCache,
+    // 从Pump交易中学习优先级费用
+    fees: FeeOracle,
+    // 新代币的交易地址，与自动交易器共用，买入时无需再搜索PDA
+    mint_accounts: Option<MintAccountCache>,
+    // 新代币的预签名买入交易，看到Create指令时准备，内盘完成或迁移时丢弃
+    templates: Option<BuyTemplates>,
 }
 
 impl TransactionProcessor {
     pub fn new(token_creator_pubkey: Pubkey) -> Self {
+        let (position_tx, position_rx) = mpsc::unbounded_channel();
         Self { 
             token_creator_pubkey,
-            token_reserves: HashMap::new(),
+            mints: MintStates::default(),
+            global: GlobalAccount::default(),
             auto_trader: None,
+            strategies: StrategySet::new(),
+            exits: ExitEngine::new(),
+            position_tx,
+            position_rx,
+            lookup_tables: LookupTableCache::offline(),
+            fees: FeeOracle::new(FeePolicy::default()),
+            mint_accounts: None,
+            templates: None,
+        }
+    }
+    
+    // 设置Global账户参数
+    pub fn set_global(&mut self, global: GlobalAccount) {
+        self.global = global;
+    }
+    
+    // 设置地址查找表缓存
+    pub fn set_lookup_tables(&mut self, lookup_tables: LookupTableCache) {
+        self.lookup_tables = lookup_tables;
+    }
+    
+    // 设置策略，未设置自动交易器时只打印策略的决定
+    pub fn set_strategies(&mut self, strategies: StrategySet) {
+        self.strategies = strategies;
+    }
+    
+    // 将账本中未平仓的持仓交给退出引擎，需要在设置策略之后调用
+    // 恢复的持仓从当前数量重新计算止盈档位，最长持仓时间从首次买入算起
+    pub fn restore_positions(&mut self, ledger: &PositionLedger) {
+        for (strategy, mint, position) in ledger.open_positions() {
+            match self.strategies.resume(strategy, *mint, position.cost_basis) {
+                Some(order) => {
+                    println!(
+                        "恢复策略 {} 的 {} 持仓: {} 代币，成本 {:.6} SOL",
+                        strategy,
+                        mint,
+                        position.token_amount,
+                        position.cost_basis as f64 / 1_000_000_000.0
+                    );
+                    self.exits.open(order, position.token_amount, position.cost_basis, position.opened_ms);
+                }
+                None => println!("策略 {} 已不在配置中，{} 持仓需要手动处理", strategy, mint),
+            }
         }
     }
     
+    // 设置费用预言机，与自动交易器共用以便按数据流中的费用出价
+    pub fn set_fee_oracle(&mut self, fees: FeeOracle) {
+        self.fees = fees;
+    }
+    
+    // 设置代币交易地址缓存，看到Create指令时加入新代币
+    pub fn set_mint_accounts(&mut self, mint_accounts: MintAccountCache) {
+        self.mint_accounts = Some(mint_accounts);
+    }
+    
+    // 设置预签名买入模板，与自动交易器共用
+    pub fn set_buy_templates(&mut self, templates: BuyTemplates) {
+        self.templates = Some(templates);
+    }
+    
     // 设置自动交易器
     pub fn set_auto_trader(&mut self, auto_trader: Arc<Mutex<AutoTrader>>) {
         self.auto_trader = Some(auto_trader);
@@ -38,320 +131,341 @@ impl TransactionProcessor {
     }
 
     pub fn process_entries(&mut self, entries: Vec<Entry>, slot: u64) -> Result<(), Box<dyn Error>> {
+        self.apply_position_events();
         for entry in entries {
-            for tx_data in entry.transactions {
-                let transaction = tx_data;
-                
-                match &transaction.message {
-                    VersionedMessage::V0(message) => self.process_message_v0(message, &transaction, slot)?,
-                    VersionedMessage::Legacy(message) => self.process_message_legacy(message, &transaction, slot)?,
-                }
+            for transaction in &entry.transactions {
+                self.process_transaction(transaction, slot)?;
             }
         }
+        self.check_exits(slot);
         Ok(())
     }
 
-    fn process_message_v0(&mut self, message: &solana_sdk::message::v0::Message, transaction: &VersionedTransaction, slot: u64) -> Result<(), Box<dyn Error>> {
-        if message.account_keys.contains(&self.token_creator_pubkey) {
-            println!("\n{}", "-".repeat(80));
-            println!("[{}] Pumpfun内盘创建代币事件:", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"));
-            println!("Slot: {}", slot);
-            println!("Signatures: {}", transaction.signatures[0]);
-            
-            // 提取关键账户地址
-            let mint_address = message.account_keys[1].to_string();
-            let bonding_curve = message.account_keys[2].to_string();
-            
-            println!("Mint: {}", mint_address);
-            println!("Bonding_Curve: {}", bonding_curve);
+    // 解析查找表后逐条处理交易中的Pump指令，legacy和v0消息共用同一流程
+    fn process_transaction(&mut self, transaction: &VersionedTransaction, slot: u64) -> Result<(), Box<dyn Error>> {
+        let message = &transaction.message;
+        self.lookup_tables.observe(message);
+        
+        // 程序ID只能来自静态账户，不涉及Pump程序的交易无需解析查找表
+        if !message.static_keys().contains(&PUMP_PROGRAM_ID) {
+            return Ok(());
+        }
+        // 查找表加载已在后台发起，加载完成前只解析账户全部来自静态账户的指令
+        let resolved = self
+            .lookup_tables
+            .resolve(message)
+            .unwrap_or_else(|| ResolvedMessage::unresolved(message));
+        // 只打印创建代币的交易，其他交易只用于更新曲线状态
+        let in_create_tx = resolved.contains_key(&self.token_creator_pubkey);
+        let signature = transaction.signatures[0];
+        let mut header_printed = false;
+        // 交易设置的优先级费用，用于学习同一代币和最近slot的竞争费用
+        let unit_price = ComputeBudget::from_message(message).unit_price;
 
-            // 检查交易中的所有指令
-            for instruction in &message.instructions {
-                let program_id = message.account_keys[instruction.program_id_index as usize].to_string();
-                
-                // 如果指令是针对目标程序的
-                if program_id == self.token_creator_pubkey.to_string() || program_id == "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P" {
-                    // 尝试解析指令
-                    if let Ok((instruction_type, create_event, buy_event)) = parse_instruction_data(&instruction.data) {
-                        match instruction_type.as_str() {
-                            "CreateEvent" => {
-                                if let Some(event) = create_event {
-                                    println!("Token_Metadata:");
-                                    println!("  Name: {}", event.name);
-                                    println!("  Symbol: {}", event.symbol);
-                                    println!("  URI: {}", event.uri);
-                                    println!("  Creator: {}", event.user);
-                                    
-                                    // 为新代币初始化虚拟储备
-                                    if !self.token_reserves.contains_key(&mint_address) {
-                                        // 初始化虚拟储备值 - 根据交易记录调整为更准确的值
-                                        let virtual_sol_reserves = 30_000_000_000;             // 30 SOL (lamports)
-                                        let virtual_token_reserves = 1_073_000_000_000_000;    // 约10.73亿代币（精度为6）
-                                        
-                                        self.token_reserves.insert(mint_address.clone(), TokenReserves {
-                                            virtual_sol_reserves,
-                                            virtual_token_reserves,
-                                        });
-                                    }
-                                }
-                            }
-                            "Buy" => {
-                                if let Some(event) = buy_event {
-                                    // 直接使用原始值，保留精度
-                                    let token_amount = event.amount;
-                                    let sol_amount = event.max_sol_cost;
-                                    
-                                    // 简化显示的打印输出
-                                    let token_amount_display = token_amount as f64 / 1_000_000.0; // 考虑6位小数精度
-                                    let sol_amount_display = sol_amount as f64 / 1_000_000_000.0;
-                                    
-                                    println!("Buy_Event:");
-                                    println!("  User: {}", message.account_keys[0]);
-                                    println!("  SOL_Amount: {:.6}", sol_amount_display);
-                                    println!("  Token_Amount: {:.6}", token_amount_display);
-                                    
-                                    // 检查是否满足狙击条件
-                                    if let Some(auto_trader) = &self.auto_trader {
-                                        // 使用克隆的mint_address和auto_trader，以便在异步闭包中使用
-                                        let mint = mint_address.clone();
-                                        let trader_clone = Arc::clone(auto_trader);
-                                        
-                                        // 使用tokio::spawn启动异步任务，检查是否需要狙击
-                                        let sol_amount_copy = sol_amount;
-                                        let sol_display = sol_amount_display;
-                                        
-                                        // 获取当前代币价格
-                                        let token_price = if let Some(reserves) = self.token_reserves.get(&mint_address) {
-                                            let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                            let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                            virtual_sol / virtual_token
-                                        } else {
-                                            0.000000033 // 默认估计值，如果无法获取实际价格
-                                        };
-                                        
-                                        // 传递slot，以便用于获取合适的区块哈希
-                                        let current_slot = slot;
-                                        
-                                        // 使用tokio::spawn来执行异步代码
-                                        tokio::spawn(async move {
-                                            // 记录开始检查的时间，用于监控处理延迟
-                                            let start_time = std::time::Instant::now();
-                                            
-                                            let should_snipe = {
-                                                let trader = trader_clone.lock().await;
-                                                trader.should_snipe(sol_amount_copy)
-                                            };
-                                            
-                                            if should_snipe {
-                                                println!("检测到符合条件的购买，准备狙击: {} SOL", sol_display);
-                                                println!("使用slot: {}, 当前时间: {}", current_slot, Local::now().format("%H:%M:%S%.3f"));
-                                                println!("从检测到需要狙击到准备狙击的延迟: {:.3}ms", start_time.elapsed().as_millis());
-                                                
-                                                // 获取锁以执行狙击，传递slot
-                                                let trader = trader_clone.lock().await;
-                                                if let Err(e) = trader.snipe_token(&mint, token_price, Some(current_slot)).await {
-                                                    println!("狙击失败: {:?}", e);
-                                                }
-                                            }
-                                        });
-                                    }
-                                    
-                                    // 更新虚拟储备（仅用于内部计算，不作为真实值显示）
-                                    if let Some(reserves) = self.token_reserves.get_mut(&mint_address) {
-                                        // 更新前的状态
-                                        let old_virtual_token = reserves.virtual_token_reserves;
-                                        
-                                        // 更新虚拟储备，添加溢出检查
-                                        reserves.virtual_sol_reserves = reserves.virtual_sol_reserves.saturating_add(sol_amount);
-                                        
-                                        // 使用saturating_sub避免溢出
-                                        if token_amount <= reserves.virtual_token_reserves {
-                                            reserves.virtual_token_reserves = reserves.virtual_token_reserves.saturating_sub(token_amount);
-                                        }
-                                        
-                                        // 计算价格（使用虚拟储备）
-                                        let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                        let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                        let price = virtual_sol / virtual_token;
-                                        
-                                        // realSolReserves和realTokenReserves实际上只是从交易中提取的数据，而不是真实的储备状态
-                                        // realSolReserves通常就是交易中投入的SOL
-                                        let real_sol_reserves = sol_amount_display;
-                                        
-                                        // realTokenReserves是基于交易前的代币储备减去获得的代币数量
-                                        // 使用checked_sub避免溢出，如果溢出就使用0
-                                        let real_token_reserves = if old_virtual_token >= token_amount {
-                                            (old_virtual_token - token_amount) as f64 / 1_000_000.0
-                                        } else {
-                                            0.0 // 如果发生溢出，则显示0
-                                        };
-                                        
-                                        println!("  realSolReserves: {:.6}", real_sol_reserves);
-                                        println!("  realTokenReserves: {:.6}", real_token_reserves);
-                                        println!("  Price: {:.9}", price);
-                                    }
-                                }
-                            }
-                            _ => {
-                                // 其他指令类型暂不处理
-                            }
-                        }
+        // 检查交易中的所有Pump指令，账户按每条指令自己的账户索引解析
+        // 内盘曲线PDA从代币地址缓存读取，同一代币的买卖不再重复搜索
+        let mint_accounts = self.mint_accounts.clone();
+        for parsed in resolved.pump_instructions_with(mint_accounts.as_ref()) {
+            let parsed = match parsed {
+                Ok(parsed) => parsed,
+                // 内盘曲线账户与PDA不符的指令直接丢弃
+                Err(e @ InstructionError::AccountMismatch { .. }) => {
+                    if in_create_tx {
+                        println!("丢弃账户无效的指令 ({}): {}", signature, e);
                     }
+                    continue;
                 }
+                Err(_) => continue,
+            };
+
+            if in_create_tx && !header_printed {
+                println!("\n{}", "-".repeat(80));
+                println!("[{}] Pumpfun内盘创建代币事件:", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"));
+                println!("Slot: {}", slot);
+                println!("Signatures: {}", signature);
+                header_printed = true;
+            }
+
+            if let (Some(unit_price), PumpInstruction::Buy { accounts, .. } | PumpInstruction::Sell { accounts, .. }) = (unit_price, &parsed) {
+                self.fees.record(slot, &accounts.mint, unit_price);
             }
+
+            // 先更新代币状态，策略看到的是应用该指令之后的状态
+            match &parsed {
+                PumpInstruction::Create { args, accounts } => self.handle_create(args, accounts, slot),
+                PumpInstruction::Buy { args, accounts } => self.handle_buy(args, accounts, slot, signature, in_create_tx),
+                PumpInstruction::Sell { args, accounts } => self.handle_sell(args, accounts, slot, signature, in_create_tx),
+                PumpInstruction::Withdraw { accounts } => self.handle_graduated(&accounts.mint),
+                PumpInstruction::Migrate { accounts } => self.handle_graduated(&accounts.mint),
+                _ => {
+                    // 其他指令类型暂不处理
+                }
+            }
+            self.run_strategies(&parsed, slot, in_create_tx);
         }
         Ok(())
     }
 
-    fn process_message_legacy(&mut self, message: &solana_sdk::message::Message, transaction: &VersionedTransaction, slot: u64) -> Result<(), Box<dyn Error>> {
-        if message.account_keys.contains(&self.token_creator_pubkey) {
-            println!("\n{}", "-".repeat(80));
-            println!("[{}] Pumpfun内盘创建代币事件:", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"));
-            println!("Slot: {}", slot);
-            println!("Signatures: {}", transaction.signatures[0]);
-            
-            // 提取关键账户地址
-            let mint_address = message.account_keys[1].to_string();
-            let bonding_curve = message.account_keys[2].to_string();
-            
-            println!("Mint: {}", mint_address);
-            println!("Bonding_Curve: {}", bonding_curve);
+    fn handle_create(&mut self, event: &CreateArgs, accounts: &CreateAccounts, slot: u64) {
+        let mint_address = accounts.mint.to_string();
+        println!("Mint: {}", mint_address);
+        println!("Bonding_Curve: {}", accounts.bonding_curve);
+        println!("Token_Metadata:");
+        println!("  Name: {}", event.name);
+        println!("  Symbol: {}", event.symbol);
+        println!("  URI: {}", event.uri);
+        println!("  Creator: {}", event.creator.unwrap_or(accounts.user));
+        
+        // 按Global参数为新代币初始化内盘曲线
+        let exits = &self.exits;
+        self.mints.track(accounts.mint, &self.global, accounts.user, slot, |mint| exits.holds(mint));
+        if let Some(mint_accounts) = &self.mint_accounts {
+            mint_accounts.insert_created(accounts.mint, accounts.bonding_curve, accounts.associated_bonding_curve);
+        }
+        if let Some(templates) = &self.templates {
+            templates.prepare(accounts.mint, slot, &self.global.new_curve(), self.global.fee_basis_points);
+        }
+    }
 
-            // 检查交易中的所有指令
-            for instruction in &message.instructions {
-                let program_id = message.account_keys[instruction.program_id_index as usize].to_string();
+    // 流动性已从内盘取出，代币不再能在内盘买入
+    fn handle_graduated(&mut self, mint: &Pubkey) {
+        // 标记内盘完成，之后的报价和模板都按已完成处理
+        if let Some(state) = self.mints.get_mut(mint) {
+            state.curve.complete = true;
+        }
+        if let Some(templates) = &self.templates {
+            templates.evict(mint);
+        }
+    }
+
+    fn handle_buy(&mut self, event: &BuyArgs, accounts: &TradeAccounts, slot: u64, signature: Signature, in_create_tx: bool) {
+        if in_create_tx {
+            // 直接使用原始值，保留精度，打印时按6位小数和9位小数显示
+            println!("Buy_Event:");
+            println!("  Mint: {}", accounts.mint);
+            println!("  User: {}", accounts.user);
+            println!("  SOL_Amount: {:.6}", event.max_sol_cost as f64 / 1_000_000_000.0);
+            println!("  Token_Amount: {:.6}", event.amount as f64 / 1_000_000.0);
+        }
+        
+        // 按买入的代币数量更新内盘曲线，花费由曲线精确计算
+        if let Some(state) = self.mints.get_mut(&accounts.mint) {
+            let result = state.record_trade(slot, signature, TradeSide::Buy, accounts.user, event.amount, event.max_sol_cost);
+            if let Some(templates) = &self.templates {
+                templates.observe(&accounts.mint, slot, &state.curve);
+            }
+            if in_create_tx {
+                match result {
+                    Ok(entry) => {
+                        println!("  Trade: {}", entry);
+                        print_curve(state);
+                    }
+                    Err(e) => println!("  更新内盘曲线失败: {}", e),
+                }
+            }
+        }
+    }
+
+    fn handle_sell(&mut self, event: &SellArgs, accounts: &TradeAccounts, slot: u64, signature: Signature, in_create_tx: bool) {
+        if in_create_tx {
+            println!("Sell_Event:");
+            println!("  Mint: {}", accounts.mint);
+            println!("  User: {}", accounts.user);
+            println!("  Token_Amount: {:.6}", event.amount as f64 / 1_000_000.0);
+            println!("  Min_SOL_Output: {:.6}", event.min_sol_output as f64 / 1_000_000_000.0);
+        }
+        
+        // 按卖出的代币数量更新内盘曲线
+        if let Some(state) = self.mints.get_mut(&accounts.mint) {
+            let result = state.record_trade(slot, signature, TradeSide::Sell, accounts.user, event.amount, event.min_sol_output);
+            if let Some(templates) = &self.templates {
+                templates.observe(&accounts.mint, slot, &state.curve);
+            }
+            if in_create_tx {
+                match result {
+                    Ok(entry) => {
+                        println!("  Trade: {}", entry);
+                        print_curve(state);
+                    }
+                    Err(e) => println!("  更新内盘曲线失败: {}", e),
+                }
+            }
+        }
+    }
+
+    // 将事件交给所有策略并执行通过预算检查的意图
+    fn run_strategies(&mut self, instruction: &PumpInstruction, slot: u64, in_create_tx: bool) {
+        let event = match StrategyEvent::from_instruction(instruction) {
+            Some(event) => event,
+            None => return,
+        };
+        let ctx = EventContext {
+            in_create_tx,
+            state: self.mints.get(&event.mint()),
+        };
+        let orders = self.strategies.on_event(&event, &ctx);
+        for order in orders {
+            self.execute(order, slot);
+        }
+    }
+
+    fn execute(&mut self, order: Order, slot: u64) {
+        let auto_trader = match &self.auto_trader {
+            Some(auto_trader) => Arc::clone(auto_trader),
+            None => {
+                println!("策略 {} 的决定: {:?} (未启用自动交易)", order.strategy, order.intent);
+                if let Intent::Buy { mint, .. } = order.intent {
+                    order.budget.release(&mint);
+                    self.strategies.closed(&order.strategy, &mint);
+                }
+                return;
+            }
+        };
+        
+        match order.intent {
+            Intent::Buy { mint, lamports } => {
+                println!("策略 {} 决定买入 {}: {} SOL", order.strategy, mint, lamports as f64 / 1_000_000_000.0);
                 
-                // 如果指令是针对目标程序的
-                if program_id == self.token_creator_pubkey.to_string() || program_id == "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P" {
-                    // 尝试解析指令
-                    if let Ok((instruction_type, create_event, buy_event)) = parse_instruction_data(&instruction.data) {
-                        match instruction_type.as_str() {
-                            "CreateEvent" => {
-                                if let Some(event) = create_event {
-                                    println!("Token_Metadata:");
-                                    println!("  Name: {}", event.name);
-                                    println!("  Symbol: {}", event.symbol);
-                                    println!("  URI: {}", event.uri);
-                                    println!("  Creator: {}", event.user);
-                                    
-                                    // 为新代币初始化虚拟储备
-                                    if !self.token_reserves.contains_key(&mint_address) {
-                                        // 初始化虚拟储备值 - 根据交易记录调整为更准确的值
-                                        let virtual_sol_reserves = 30_000_000_000;             // 30 SOL (lamports)
-                                        let virtual_token_reserves = 1_073_000_000_000_000;    // 约10.73亿代币（精度为6）
-                                        
-                                        self.token_reserves.insert(mint_address.clone(), TokenReserves {
-                                            virtual_sol_reserves,
-                                            virtual_token_reserves,
-                                        });
-                                    }
-                                }
-                            }
-                            "Buy" => {
-                                if let Some(event) = buy_event {
-                                    // 直接使用原始值，保留精度
-                                    let token_amount = event.amount;
-                                    let sol_amount = event.max_sol_cost;
-                                    
-                                    // 简化显示的打印输出
-                                    let token_amount_display = token_amount as f64 / 1_000_000.0; // 考虑6位小数精度
-                                    let sol_amount_display = sol_amount as f64 / 1_000_000_000.0;
-                                    
-                                    println!("Buy_Event:");
-                                    println!("  User: {}", message.account_keys[0]);
-                                    println!("  SOL_Amount: {:.6} SOL", sol_amount_display);
-                                    println!("  Token_Amount: {:.6} ", token_amount_display);
-                                    
-                                    // 检查是否满足狙击条件
-                                    if let Some(auto_trader) = &self.auto_trader {
-                                        // 使用克隆的mint_address和auto_trader，以便在异步闭包中使用
-                                        let mint = mint_address.clone();
-                                        let trader_clone = Arc::clone(auto_trader);
-                                        
-                                        // 使用tokio::spawn启动异步任务，检查是否需要狙击
-                                        let sol_amount_copy = sol_amount;
-                                        let sol_display = sol_amount_display;
-                                        
-                                        // 获取当前代币价格
-                                        let token_price = if let Some(reserves) = self.token_reserves.get(&mint_address) {
-                                            let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                            let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                            virtual_sol / virtual_token
-                                        } else {
-                                            0.000000033 // 默认估计值，如果无法获取实际价格
-                                        };
-                                        
-                                        // 传递slot，以便用于获取合适的区块哈希
-                                        let current_slot = slot;
-                                        
-                                        // 使用tokio::spawn来执行异步代码
-                                        tokio::spawn(async move {
-                                            // 记录开始检查的时间，用于监控处理延迟
-                                            let start_time = std::time::Instant::now();
-                                            
-                                            let should_snipe = {
-                                                let trader = trader_clone.lock().await;
-                                                trader.should_snipe(sol_amount_copy)
-                                            };
-                                            
-                                            if should_snipe {
-                                                println!("检测到符合条件的购买，准备狙击: {} SOL", sol_display);
-                                                println!("使用slot: {}, 当前时间: {}", current_slot, Local::now().format("%H:%M:%S%.3f"));
-                                                println!("从检测到需要狙击到准备狙击的延迟: {:.3}ms", start_time.elapsed().as_millis());
-                                                
-                                                // 获取锁以执行狙击，传递slot
-                                                let trader = trader_clone.lock().await;
-                                                if let Err(e) = trader.snipe_token(&mint, token_price, Some(current_slot)).await {
-                                                    println!("狙击失败: {:?}", e);
-                                                }
-                                            }
-                                        });
-                                    }
-                                    
-                                    // 更新虚拟储备（仅用于内部计算，不作为真实值显示）
-                                    if let Some(reserves) = self.token_reserves.get_mut(&mint_address) {
-                                        // 更新前的状态
-                                        let old_virtual_token = reserves.virtual_token_reserves;
-                                        
-                                        // 更新虚拟储备，添加溢出检查
-                                        reserves.virtual_sol_reserves = reserves.virtual_sol_reserves.saturating_add(sol_amount);
-                                        
-                                        // 使用saturating_sub避免溢出
-                                        if token_amount <= reserves.virtual_token_reserves {
-                                            reserves.virtual_token_reserves = reserves.virtual_token_reserves.saturating_sub(token_amount);
-                                        }
-                                        
-                                        // 计算价格（使用虚拟储备）
-                                        let virtual_sol = reserves.virtual_sol_reserves as f64 / 1_000_000_000.0;
-                                        let virtual_token = reserves.virtual_token_reserves as f64 / 1_000_000.0;
-                                        let price = virtual_sol / virtual_token;
-                                        
-                                        // realSolReserves和realTokenReserves实际上只是从交易中提取的数据，而不是真实的储备状态
-                                        // realSolReserves通常就是交易中投入的SOL
-                                        let real_sol_reserves = sol_amount_display;
-                                        
-                                        // realTokenReserves是基于交易前的代币储备减去获得的代币数量
-                                        // 使用checked_sub避免溢出，如果溢出就使用0
-                                        let real_token_reserves = if old_virtual_token >= token_amount {
-                                            (old_virtual_token - token_amount) as f64 / 1_000_000.0
-                                        } else {
-                                            0.0 // 如果发生溢出，则显示0
-                                        };
-                                        
-                                        println!("  realSolReserves: {:.6}", real_sol_reserves);
-                                        println!("  realTokenReserves: {:.6}", real_token_reserves);
-                                        println!("  Price: {:.9}", price);
-                                    }
-                                }
-                            }
-                            _ => {
-                                // 其他指令类型暂不处理
-                            }
+                // 应用触发指令之后的内盘曲线快照，用于精确计算买入数量
+                let curve = self.mints
+                    .get(&mint)
+                    .map(|state| state.curve.clone())
+                    .unwrap_or_else(|| self.global.new_curve());
+                let fee_basis_points = self.global.fee_basis_points;
+                let position_tx = self.position_tx.clone();
+                let trigger = Trigger::now(slot);
+                
+                // 使用tokio::spawn来执行异步代码，传递slot以便用于获取合适的区块哈希
+                tokio::spawn(async move {
+                    // 记录开始的时间，用于监控处理延迟
+                    let start_time = std::time::Instant::now();
+                    // 只在克隆发送句柄时持有锁，发送期间其他买卖和结算不必等待
+                    let handles = auto_trader.lock().await.handles();
+                    println!("使用slot: {}, 当前时间: {}", slot, Local::now().format("%H:%M:%S%.3f"));
+                    println!("从检测到需要狙击到准备狙击的延迟: {:.3}ms", start_time.elapsed().as_millis());
+                    
+                    let result = handles
+                        .snipe_token(&order.strategy, &mint.to_string(), lamports, &curve, fee_basis_points, trigger)
+                        .await;
+                    let pending = match result {
+                        Ok(pending) => pending,
+                        Err(e) => {
+                            println!("狙击失败 ({}): {}", failure_class(&e), e);
+                            order.budget.release(&mint);
+                            let _ = position_tx.send(PositionEvent::BuyFailed { strategy: order.strategy, mint });
+                            return;
+                        }
+                    };
+                    
+                    // 确认上链后才交给退出引擎，全部卖出后释放预算；失败或过期的买入直接释放预算
+                    let (fill, confirmation) = pending.wait().await;
+                    match AutoTrader::settle(&auto_trader, fill, &confirmation).await {
+                        Some(fill) => {
+                            let (token_amount, cost_lamports) = (fill.token_amount, fill.lamports);
+                            let _ = position_tx.send(PositionEvent::Bought { order, token_amount, cost_lamports });
+                        }
+                        None => {
+                            order.budget.release(&mint);
+                            let _ = position_tx.send(PositionEvent::BuyFailed { strategy: order.strategy, mint });
                         }
                     }
+                });
+            }
+            Intent::Sell { mint, basis_points } => {
+                let exit = self.mints.get(&mint).and_then(|state| {
+                    self.exits.sell_share(&order.strategy, &mint, basis_points, &state.curve, self.global.fee_basis_points)
+                });
+                match exit {
+                    Some(exit) => self.send_exit(auto_trader, exit, slot),
+                    None => println!("策略 {} 没有可卖出的 {} 持仓", order.strategy, mint),
                 }
             }
+            Intent::Ignore => {}
+        }
+    }
+
+    // 应用后台任务报告的买入和卖出结果
+    fn apply_position_events(&mut self) {
+        while let Ok(event) = self.position_rx.try_recv() {
+            match event {
+                PositionEvent::Bought { order, token_amount, cost_lamports } => {
+                    self.exits.open(order, token_amount, cost_lamports, now_ms());
+                }
+                PositionEvent::Sold { exit, success } => {
+                    if self.exits.sell_result(&exit, success) {
+                        println!("策略 {} 的 {} 持仓已全部卖出", exit.strategy, exit.mint);
+                        self.strategies.closed(&exit.strategy, &exit.mint);
+                    }
+                }
+                PositionEvent::BuyFailed { strategy, mint } => self.strategies.closed(&strategy, &mint),
+            }
         }
-        Ok(())
     }
-} 
\ No newline at end of file
+
+    // 按最新的曲线检查所有持仓的退出条件
+    fn check_exits(&mut self, slot: u64) {
+        if self.exits.is_empty() {
+            return;
+        }
+        let auto_trader = match &self.auto_trader {
+            Some(auto_trader) => Arc::clone(auto_trader),
+            None => return,
+        };
+        for exit in self.exits.evaluate(&self.mints, self.global.fee_basis_points, now_ms()) {
+            self.send_exit(Arc::clone(&auto_trader), exit, slot);
+        }
+    }
+
+    // 发送卖出，确认上链后才算卖出成功，失败或过期的代币退回持仓
+    fn send_exit(&self, auto_trader: Arc<Mutex<AutoTrader>>, exit: ExitOrder, slot: u64) {
+        println!(
+            "策略 {} 卖出 {} ({}，当前 {:.2}倍): {} 代币，最少接收 {:.6} SOL",
+            exit.strategy,
+            exit.mint,
+            exit.reason,
+            exit.multiple,
+            exit.token_amount,
+            exit.min_sol_output as f64 / 1_000_000_000.0
+        );
+        let position_tx = self.position_tx.clone();
+        let trigger = Trigger::now(slot);
+        tokio::spawn(async move {
+            let handles = auto_trader.lock().await.handles();
+            let result = handles.sell_token(&exit, trigger).await;
+            let success = match result {
+                Ok(pending) => {
+                    println!("卖出已提交: {}", pending.signature);
+                    let (fill, confirmation) = pending.wait().await;
+                    AutoTrader::settle(&auto_trader, fill, &confirmation).await.is_some()
+                }
+                Err(e) => {
+                    println!("卖出失败 ({}): {}", failure_class(&e), e);
+                    false
+                }
+            };
+            let _ = position_tx.send(PositionEvent::Sold { exit, success });
+        });
+    }
+}
+
+// 交易失败的类别：重试可能成功的临时故障，或应当放弃的失败
+fn failure_class(e: &TradeError) -> &'static str {
+    if e.is_retryable() {
+        "临时故障"
+    } else {
+        "放弃"
+    }
+}
+
+// 当前时间 (毫秒)
+fn now_ms() -> u64 {
+    Local::now().timestamp_millis() as u64
+}
+
+// 打印代币当前的曲线状态
+fn print_curve(state: &MintState) {
+    let curve = &state.curve;
+    println!("  realSolReserves: {:.6}", curve.real_sol_reserves as f64 / 1_000_000_000.0);
+    println!("  realTokenReserves: {:.6}", curve.real_token_reserves as f64 / 1_000_000.0);
+    println!("  Price: {:.9}", curve.price_sol());
+}
@@ -0,0 +1,333 @@
+use super::{Shred, ShredError, ShredKind, ShredVariant, DATA_COMPLETE_SHRED, LAST_SHRED_IN_SLOT};
+use crate::utils::deserialize_entries;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use solana_entry::entry::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// 落后于最新slot多少个slot后丢弃未完成的分片
+const MAX_SLOT_LAG: u64 = 32;
+
+/// 单个FEC集合的编码分片
+#[derive(Default)]
+struct FecSet {
+    // 由编码分片头部得到的数据分片和编码分片数量
+    num_data: usize,
+    num_coding: usize,
+    // 对应数据分片的类型，用于从恢复出的纠删码分片重建数据分片
+    data_variant: Option<ShredVariant>,
+    coding: HashMap<usize, Vec<u8>>,
+    // 数据分片已全部到齐或已完成恢复
+    complete: bool,
+}
+
+/// 单个slot的分片状态
+#[derive(Default)]
+struct SlotShreds {
+    data: BTreeMap<u32, Shred>,
+    fec_sets: HashMap<u32, FecSet>,
+    // 下一个待组装数据集的起始索引
+    next_index: u32,
+    // 已收到slot的最后一个分片并组装完成
+    completed: bool,
+}
+
+/// 分片组装统计
+#[derive(Clone, Debug, Default)]
+pub struct AssemblerStats {
+    pub packets: u64,
+    pub invalid: u64,
+    pub duplicates: u64,
+    pub recovered: u64,
+    pub data_sets: u64,
+    pub deserialize_errors: u64,
+}
+
+impl fmt::Display for AssemblerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "数据包: {} 无效: {} 重复: {} 恢复: {} 数据集: {} 反序列化失败: {}",
+            self.packets, self.invalid, self.duplicates, self.recovered, self.data_sets, self.deserialize_errors
+        )
+    }
+}
+
+/// 将原始分片按slot重新组装为Entry
+///
+/// 数据分片按索引收集，遇到DATA_COMPLETE标志时将连续的数据分片拼接并反序列化；
+/// 数据分片缺失时使用同一FEC集合的编码分片进行Reed-Solomon恢复。
+/// 分片签名不做校验，数据源的可信度由心跳订阅的区块引擎保证。
+pub struct ShredAssembler {
+    slots: HashMap<u64, SlotShreds>,
+    highest_slot: u64,
+    stats: AssemblerStats,
+}
+
+impl Default for ShredAssembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShredAssembler {
+    pub fn new() -> Self {
+        Self {
+            slots: HashMap::new(),
+            highest_slot: 0,
+            stats: AssemblerStats::default(),
+        }
+    }
+
+    /// 处理一个UDP数据包，返回因此完成组装的Entry
+    ///
+    /// # 参数
+    ///
+    /// * `packet` - 收到的原始分片数据包
+    pub fn insert_packet(&mut self, packet: &[u8]) -> Vec<(u64, Vec<Entry>)> {
+        self.stats.packets += 1;
+        match Shred::parse(packet) {
+            Ok(shred) => self.insert(shred),
+            Err(_) => {
+                self.stats.invalid += 1;
+                Vec::new()
+            }
+        }
+    }
+
+    /// 插入一个已解析的分片，返回因此完成组装的Entry
+    pub fn insert(&mut self, shred: Shred) -> Vec<(u64, Vec<Entry>)> {
+        let slot = shred.slot;
+        if slot.saturating_add(MAX_SLOT_LAG) < self.highest_slot {
+            return Vec::new();
+        }
+        if slot > self.highest_slot {
+            self.highest_slot = slot;
+            self.prune();
+        }
+
+        let state = self.slots.entry(slot).or_default();
+        if state.completed {
+            self.stats.duplicates += 1;
+            return Vec::new();
+        }
+
+        let fec_set_index = shred.fec_set_index;
+        match shred.kind {
+            ShredKind::Data { .. } => {
+                if shred.index < state.next_index || state.data.contains_key(&shred.index) {
+                    self.stats.duplicates += 1;
+                    return Vec::new();
+                }
+                state.fec_sets.entry(fec_set_index).or_default();
+                state.data.insert(shred.index, shred);
+            }
+            ShredKind::Code { num_data_shreds, num_coding_shreds, position } => {
+                let fec_set = state.fec_sets.entry(fec_set_index).or_default();
+                if fec_set.complete || fec_set.coding.contains_key(&(position as usize)) {
+                    self.stats.duplicates += 1;
+                    return Vec::new();
+                }
+                fec_set.num_data = num_data_shreds as usize;
+                fec_set.num_coding = num_coding_shreds as usize;
+                fec_set.data_variant = Some(match shred.variant {
+                    ShredVariant::MerkleCode { proof_size, chained, resigned } => {
+                        ShredVariant::MerkleData { proof_size, chained, resigned }
+                    }
+                    _ => ShredVariant::LegacyData,
+                });
+                fec_set.coding.insert(position as usize, shred.erasure_shard().to_vec());
+            }
+        }
+
+        match Self::try_recover(state, slot, fec_set_index) {
+            Ok(recovered) => self.stats.recovered += recovered,
+            Err(e) => println!("slot {} FEC集合 {} 恢复失败: {}", slot, fec_set_index, e),
+        }
+        Self::assemble(state, slot, &mut self.stats)
+    }
+
+    /// 尝试用编码分片恢复FEC集合中缺失的数据分片，返回恢复的分片数量
+    fn try_recover(state: &mut SlotShreds, slot: u64, fec_set_index: u32) -> Result<u64, Box<dyn std::error::Error>> {
+        let fec_set = match state.fec_sets.get_mut(&fec_set_index) {
+            Some(fec_set) if !fec_set.complete && fec_set.num_data > 0 => fec_set,
+            _ => return Ok(0),
+        };
+        let data_variant = fec_set.data_variant.ok_or("缺少数据分片类型")?;
+
+        let start = fec_set_index;
+        let end = fec_set_index
+            .checked_add(fec_set.num_data as u32)
+            .ok_or(ShredError::InvalidHeader("FEC集合索引溢出"))?;
+        let present = state.data.range(start..end).count();
+        if present == fec_set.num_data {
+            fec_set.complete = true;
+            return Ok(0);
+        }
+        if present + fec_set.coding.len() < fec_set.num_data {
+            return Ok(0);
+        }
+
+        // 数据分片在前，编码分片按position排在后面
+        let mut shards: Vec<Option<Vec<u8>>> = (start..end)
+            .map(|index| state.data.get(&index).map(|shred| shred.erasure_shard().to_vec()))
+            .collect();
+        shards.extend((0..fec_set.num_coding).map(|position| fec_set.coding.get(&position).cloned()));
+
+        let rs = ReedSolomon::new(fec_set.num_data, fec_set.num_coding)?;
+        rs.reconstruct_data(&mut shards)?;
+        fec_set.complete = true;
+        fec_set.coding.clear();
+
+        let mut recovered = 0;
+        for (offset, shard) in shards.into_iter().take(end as usize - start as usize).enumerate() {
+            let index = start + offset as u32;
+            if state.data.contains_key(&index) || index < state.next_index {
+                continue;
+            }
+            let shard = shard.ok_or("恢复结果缺少数据分片")?;
+            let shred = Shred::from_recovered_data_shard(data_variant, &shard)?;
+            if shred.slot != slot || shred.index != index {
+                return Err(Box::new(ShredError::InvalidHeader("恢复出的分片与FEC集合不匹配")));
+            }
+            state.data.insert(index, shred);
+            recovered += 1;
+        }
+        Ok(recovered)
+    }
+
+    /// 组装所有已连续到达且以DATA_COMPLETE结尾的数据集
+    fn assemble(state: &mut SlotShreds, slot: u64, stats: &mut AssemblerStats) -> Vec<(u64, Vec<Entry>)> {
+        let mut output = Vec::new();
+        loop {
+            // 从next_index开始查找连续分片中的第一个数据集结尾
+            let mut end = None;
+            for ((&index, shred), expected) in state.data.range(state.next_index..).zip(state.next_index..) {
+                if index != expected {
+                    break;
+                }
+                if shred.flags() & DATA_COMPLETE_SHRED != 0 {
+                    end = Some(index);
+                    break;
+                }
+            }
+            let end = match end {
+                Some(end) => end,
+                None => break,
+            };
+
+            let mut buffer = Vec::new();
+            let mut last_in_slot = false;
+            for index in state.next_index..=end {
+                // 分片保留到slot完成，以便后续FEC恢复仍能使用
+                if let Some(shred) = state.data.get(&index) {
+                    buffer.extend_from_slice(shred.data());
+                    last_in_slot |= shred.flags() & LAST_SHRED_IN_SLOT == LAST_SHRED_IN_SLOT;
+                }
+            }
+            state.next_index = end + 1;
+            stats.data_sets += 1;
+
+            match deserialize_entries(&buffer) {
+                Ok(entries) => output.push((slot, entries)),
+                Err(e) => {
+                    stats.deserialize_errors += 1;
+                    println!("slot {} 数据集反序列化失败: {}", slot, e);
+                }
+            }
+
+            if last_in_slot {
+                state.completed = true;
+                state.data.clear();
+                state.fec_sets.clear();
+                break;
+            }
+        }
+        output
+    }
+
+    /// 丢弃过旧slot的分片
+    fn prune(&mut self) {
+        let min_slot = self.highest_slot.saturating_sub(MAX_SLOT_LAG);
+        self.slots.retain(|slot, _| *slot >= min_slot);
+    }
+
+    /// 组装统计快照
+    pub fn stats(&self) -> AssemblerStats {
+        self.stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shred::tests::{data_shred, slot_packets, test_entries};
+
+    fn assemble_all(assembler: &mut ShredAssembler, packets: &[Vec<u8>]) -> Vec<(u64, Vec<Entry>)> {
+        packets.iter().flat_map(|packet| assembler.insert_packet(packet)).collect()
+    }
+
+    #[test]
+    fn assembles_slot_in_order() {
+        let entries = test_entries(100);
+        let (data, _) = slot_packets(10, &entries, 2);
+        assert!(data.len() > 1);
+
+        let mut assembler = ShredAssembler::new();
+        assert_eq!(assemble_all(&mut assembler, &data), vec![(10, entries)]);
+        assert_eq!(assembler.stats().data_sets, 1);
+    }
+
+    #[test]
+    fn assembles_out_of_order_and_ignores_duplicates() {
+        let entries = test_entries(100);
+        let (mut data, _) = slot_packets(10, &entries, 2);
+        data.reverse();
+        data.push(data[0].clone());
+
+        let mut assembler = ShredAssembler::new();
+        assert_eq!(assemble_all(&mut assembler, &data), vec![(10, entries)]);
+        assert_eq!(assembler.stats().duplicates, 1);
+    }
+
+    #[test]
+    fn recovers_missing_data_shreds_from_coding_shreds() {
+        let entries = test_entries(100);
+        let (data, coding) = slot_packets(10, &entries, 3);
+        assert!(data.len() > 2);
+
+        // 丢弃前两个数据分片，由编码分片补齐
+        let mut packets: Vec<Vec<u8>> = data.into_iter().skip(2).collect();
+        packets.extend(coding);
+
+        let mut assembler = ShredAssembler::new();
+        assert_eq!(assemble_all(&mut assembler, &packets), vec![(10, entries)]);
+        assert_eq!(assembler.stats().recovered, 2);
+    }
+
+    #[test]
+    fn drops_shreds_from_stale_slots() {
+        let entries = test_entries(10);
+        let (newer, _) = slot_packets(100, &entries, 1);
+        let (stale, _) = slot_packets(100 - MAX_SLOT_LAG - 1, &entries, 1);
+
+        let mut assembler = ShredAssembler::new();
+        assert_eq!(assemble_all(&mut assembler, &newer).len(), 1);
+        assert!(assemble_all(&mut assembler, &stale).is_empty());
+    }
+
+    #[test]
+    fn extreme_header_values_do_not_panic() {
+        let mut assembler = ShredAssembler::new();
+        let packet = data_shred(u64::MAX, 0, 0, 0, b"x");
+        assert!(assembler.insert_packet(&packet).is_empty());
+
+        let (_, coding) = slot_packets(u64::MAX, &test_entries(10), 1);
+        let mut packet = coding[0].clone();
+        // FEC集合起始索引加数据分片数量超出u32范围
+        packet[73..77].copy_from_slice(&u32::MAX.to_le_bytes());
+        packet[79..83].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+        packet[83..85].copy_from_slice(&10u16.to_le_bytes());
+        assert!(assembler.insert_packet(&packet).is_empty());
+    }
+}
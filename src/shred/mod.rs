@@ -0,0 +1,402 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub mod assembler;
+pub mod receiver;
+
+// 分片布局常量，与Solana验证者保持一致
+pub const SIZE_OF_SIGNATURE: usize = 64;
+pub const SIZE_OF_COMMON_SHRED_HEADER: usize = 83;
+pub const SIZE_OF_DATA_SHRED_HEADERS: usize = 88;
+pub const SIZE_OF_CODING_SHRED_HEADERS: usize = 89;
+// 编码分片的负载大小（PACKET_DATA_SIZE减去4字节nonce）
+pub const CODE_SHRED_PAYLOAD_SIZE: usize = 1228;
+// 数据分片的负载大小
+pub const DATA_SHRED_PAYLOAD_SIZE: usize = CODE_SHRED_PAYLOAD_SIZE - SIZE_OF_CODING_SHRED_HEADERS + SIZE_OF_SIGNATURE;
+// 默克尔证明中每个节点的大小
+const SIZE_OF_MERKLE_PROOF_ENTRY: usize = 20;
+const SIZE_OF_MERKLE_ROOT: usize = 32;
+
+// 数据分片标志位
+pub const DATA_COMPLETE_SHRED: u8 = 0b0100_0000;
+pub const LAST_SHRED_IN_SLOT: u8 = 0b1100_0000;
+
+/// 分片解析错误
+#[derive(Debug)]
+pub enum ShredError {
+    /// 数据包长度不足
+    TooShort(usize),
+    /// 未知的分片类型字节
+    InvalidVariant(u8),
+    /// 头部字段不合法
+    InvalidHeader(&'static str),
+}
+
+impl fmt::Display for ShredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShredError::TooShort(len) => write!(f, "分片长度不足: {} 字节", len),
+            ShredError::InvalidVariant(byte) => write!(f, "未知的分片类型: {:#010b}", byte),
+            ShredError::InvalidHeader(msg) => write!(f, "分片头部无效: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShredError {}
+
+/// 分片类型，对应分片公共头部中的shred_variant字节
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShredVariant {
+    LegacyCode,
+    LegacyData,
+    MerkleCode { proof_size: u8, chained: bool, resigned: bool },
+    MerkleData { proof_size: u8, chained: bool, resigned: bool },
+}
+
+impl ShredVariant {
+    fn from_byte(byte: u8) -> Result<Self, ShredError> {
+        let proof_size = byte & 0x0F;
+        match byte {
+            0b0101_1010 => Ok(ShredVariant::LegacyCode),
+            0b1010_0101 => Ok(ShredVariant::LegacyData),
+            _ => match byte & 0xF0 {
+                0x40 => Ok(ShredVariant::MerkleCode { proof_size, chained: false, resigned: false }),
+                0x60 => Ok(ShredVariant::MerkleCode { proof_size, chained: true, resigned: false }),
+                0x70 => Ok(ShredVariant::MerkleCode { proof_size, chained: true, resigned: true }),
+                0x80 => Ok(ShredVariant::MerkleData { proof_size, chained: false, resigned: false }),
+                0x90 => Ok(ShredVariant::MerkleData { proof_size, chained: true, resigned: false }),
+                0xB0 => Ok(ShredVariant::MerkleData { proof_size, chained: true, resigned: true }),
+                _ => Err(ShredError::InvalidVariant(byte)),
+            },
+        }
+    }
+
+    pub fn is_data(&self) -> bool {
+        matches!(self, ShredVariant::LegacyData | ShredVariant::MerkleData { .. })
+    }
+
+    /// 纠删码分片的长度，同一FEC集合内的数据分片与编码分片相同
+    pub fn erasure_shard_size(&self) -> usize {
+        match *self {
+            ShredVariant::LegacyCode | ShredVariant::LegacyData => {
+                CODE_SHRED_PAYLOAD_SIZE - SIZE_OF_CODING_SHRED_HEADERS
+            }
+            ShredVariant::MerkleCode { proof_size, chained, resigned }
+            | ShredVariant::MerkleData { proof_size, chained, resigned } => {
+                CODE_SHRED_PAYLOAD_SIZE
+                    - SIZE_OF_CODING_SHRED_HEADERS
+                    - proof_size as usize * SIZE_OF_MERKLE_PROOF_ENTRY
+                    - if chained { SIZE_OF_MERKLE_ROOT } else { 0 }
+                    - if resigned { SIZE_OF_SIGNATURE } else { 0 }
+            }
+        }
+    }
+
+    /// 纠删码分片在数据分片负载中的起始偏移
+    fn data_shard_offset(&self) -> usize {
+        match self {
+            // 旧格式的纠删码分片包含签名
+            ShredVariant::LegacyData | ShredVariant::LegacyCode => 0,
+            _ => SIZE_OF_SIGNATURE,
+        }
+    }
+}
+
+/// 数据分片或编码分片的类型相关头部
+#[derive(Clone, Copy, Debug)]
+pub enum ShredKind {
+    Data { flags: u8, size: u16 },
+    Code { num_data_shreds: u16, num_coding_shreds: u16, position: u16 },
+}
+
+/// 解析后的分片
+#[derive(Clone, Debug)]
+pub struct Shred {
+    pub variant: ShredVariant,
+    pub slot: u64,
+    pub index: u32,
+    pub fec_set_index: u32,
+    pub kind: ShredKind,
+    payload: Vec<u8>,
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+impl Shred {
+    /// 从UDP数据包解析分片
+    pub fn parse(packet: &[u8]) -> Result<Self, ShredError> {
+        if packet.len() < SIZE_OF_CODING_SHRED_HEADERS {
+            return Err(ShredError::TooShort(packet.len()));
+        }
+        let variant = ShredVariant::from_byte(packet[SIZE_OF_SIGNATURE])?;
+        let slot = read_u64(packet, 65);
+        let index = read_u32(packet, 73);
+        let fec_set_index = read_u32(packet, 79);
+
+        let (kind, payload_size) = if variant.is_data() {
+            // 公共头部之后依次为parent_offset(u16)、flags(u8)、size(u16)
+            let size = read_u16(packet, SIZE_OF_COMMON_SHRED_HEADER + 3);
+            let size_bytes = size as usize;
+            if size_bytes < SIZE_OF_DATA_SHRED_HEADERS
+                || size_bytes > packet.len()
+                || size_bytes > DATA_SHRED_PAYLOAD_SIZE
+            {
+                return Err(ShredError::InvalidHeader("数据长度超出分片"));
+            }
+            let kind = ShredKind::Data {
+                flags: packet[SIZE_OF_COMMON_SHRED_HEADER + 2],
+                size,
+            };
+            (kind, DATA_SHRED_PAYLOAD_SIZE)
+        } else {
+            let num_data_shreds = read_u16(packet, SIZE_OF_COMMON_SHRED_HEADER);
+            let num_coding_shreds = read_u16(packet, SIZE_OF_COMMON_SHRED_HEADER + 2);
+            let position = read_u16(packet, SIZE_OF_COMMON_SHRED_HEADER + 4);
+            if num_data_shreds == 0 || num_coding_shreds == 0 || position >= num_coding_shreds {
+                return Err(ShredError::InvalidHeader("编码分片参数无效"));
+            }
+            let kind = ShredKind::Code { num_data_shreds, num_coding_shreds, position };
+            (kind, CODE_SHRED_PAYLOAD_SIZE)
+        };
+
+        if index < fec_set_index {
+            return Err(ShredError::InvalidHeader("分片索引小于FEC集合起始索引"));
+        }
+
+        // 数据分片负载可能被截断，补零到完整长度以便计算纠删码分片
+        let mut payload = packet[..packet.len().min(payload_size)].to_vec();
+        payload.resize(payload_size, 0);
+
+        Ok(Self { variant, slot, index, fec_set_index, kind, payload })
+    }
+
+    /// 从恢复出的纠删码分片重建数据分片
+    pub fn from_recovered_data_shard(variant: ShredVariant, shard: &[u8]) -> Result<Self, ShredError> {
+        let mut payload = vec![0u8; DATA_SHRED_PAYLOAD_SIZE];
+        let offset = variant.data_shard_offset();
+        payload[offset..offset + shard.len()].copy_from_slice(shard);
+        Self::parse(&payload)
+    }
+
+    /// 数据分片中携带的Entry数据
+    pub fn data(&self) -> &[u8] {
+        match self.kind {
+            ShredKind::Data { size, .. } => &self.payload[SIZE_OF_DATA_SHRED_HEADERS..size as usize],
+            ShredKind::Code { .. } => &[],
+        }
+    }
+
+    /// 数据分片标志位
+    pub fn flags(&self) -> u8 {
+        match self.kind {
+            ShredKind::Data { flags, .. } => flags,
+            ShredKind::Code { .. } => 0,
+        }
+    }
+
+    /// 参与纠删码计算的分片内容
+    pub fn erasure_shard(&self) -> &[u8] {
+        let size = self.variant.erasure_shard_size();
+        match self.kind {
+            ShredKind::Data { .. } => {
+                let offset = self.variant.data_shard_offset();
+                &self.payload[offset..offset + size]
+            }
+            ShredKind::Code { .. } => {
+                &self.payload[SIZE_OF_CODING_SHRED_HEADERS..SIZE_OF_CODING_SHRED_HEADERS + size]
+            }
+        }
+    }
+}
+
+/// 将收到的分片数据包写入文件，格式为重复的 [u16小端长度][数据包]
+pub struct ShredPacketWriter {
+    writer: BufWriter<File>,
+}
+
+impl ShredPacketWriter {
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self { writer: BufWriter::new(File::create(path)?) })
+    }
+
+    pub fn write_packet(&mut self, packet: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(&(packet.len() as u16).to_le_bytes())?;
+        self.writer.write_all(packet)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reed_solomon_erasure::galois_8::ReedSolomon;
+    use solana_entry::entry::Entry;
+    use solana_sdk::hash::hash;
+    use std::io::{BufReader, Read};
+
+    // 每个数据分片携带的数据量，保证数据位于纠删码分片范围内
+    const DATA_PER_SHRED: usize = 1000;
+
+    /// 读取`ShredPacketWriter`写入的分片数据包
+    pub(super) fn read_shred_packets(path: impl AsRef<Path>) -> std::io::Result<Vec<Vec<u8>>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut packets = Vec::new();
+        let mut len_buf = [0u8; 2];
+        loop {
+            match reader.read_exact(&mut len_buf) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let mut packet = vec![0u8; u16::from_le_bytes(len_buf) as usize];
+            reader.read_exact(&mut packet)?;
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    pub(super) fn test_entries(count: u64) -> Vec<Entry> {
+        (0..count)
+            .map(|i| Entry {
+                num_hashes: i,
+                hash: hash(&i.to_le_bytes()),
+                transactions: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// 构造旧格式的数据分片数据包
+    pub(super) fn data_shred(slot: u64, index: u32, fec_set_index: u32, flags: u8, data: &[u8]) -> Vec<u8> {
+        let size = SIZE_OF_DATA_SHRED_HEADERS + data.len();
+        let mut packet = vec![0u8; DATA_SHRED_PAYLOAD_SIZE];
+        packet[SIZE_OF_SIGNATURE] = 0b1010_0101;
+        packet[65..73].copy_from_slice(&slot.to_le_bytes());
+        packet[73..77].copy_from_slice(&index.to_le_bytes());
+        packet[79..83].copy_from_slice(&fec_set_index.to_le_bytes());
+        packet[83..85].copy_from_slice(&1u16.to_le_bytes());
+        packet[85] = flags;
+        packet[86..88].copy_from_slice(&(size as u16).to_le_bytes());
+        packet[SIZE_OF_DATA_SHRED_HEADERS..size].copy_from_slice(data);
+        packet
+    }
+
+    /// 构造旧格式的编码分片数据包
+    fn code_shred(slot: u64, fec_set_index: u32, num_data: u16, num_coding: u16, position: u16, shard: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0u8; CODE_SHRED_PAYLOAD_SIZE];
+        packet[SIZE_OF_SIGNATURE] = 0b0101_1010;
+        packet[65..73].copy_from_slice(&slot.to_le_bytes());
+        packet[73..77].copy_from_slice(&(fec_set_index + num_data as u32 + position as u32).to_le_bytes());
+        packet[79..83].copy_from_slice(&fec_set_index.to_le_bytes());
+        packet[83..85].copy_from_slice(&num_data.to_le_bytes());
+        packet[85..87].copy_from_slice(&num_coding.to_le_bytes());
+        packet[87..89].copy_from_slice(&position.to_le_bytes());
+        packet[SIZE_OF_CODING_SHRED_HEADERS..SIZE_OF_CODING_SHRED_HEADERS + shard.len()].copy_from_slice(shard);
+        packet
+    }
+
+    /// 将一个slot的Entry切分为单个FEC集合的数据分片和编码分片
+    pub(super) fn slot_packets(slot: u64, entries: &[Entry], num_coding: usize) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let buffer = bincode::serialize(entries).unwrap();
+        let chunks: Vec<&[u8]> = buffer.chunks(DATA_PER_SHRED).collect();
+        let data: Vec<Vec<u8>> = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let flags = if index + 1 == chunks.len() { LAST_SHRED_IN_SLOT } else { 0 };
+                data_shred(slot, index as u32, 0, flags, chunk)
+            })
+            .collect();
+
+        let mut shards: Vec<Vec<u8>> = data
+            .iter()
+            .map(|packet| Shred::parse(packet).unwrap().erasure_shard().to_vec())
+            .collect();
+        let shard_size = shards[0].len();
+        shards.extend((0..num_coding).map(|_| vec![0u8; shard_size]));
+        ReedSolomon::new(data.len(), num_coding).unwrap().encode(&mut shards).unwrap();
+
+        let coding = shards[data.len()..]
+            .iter()
+            .enumerate()
+            .map(|(position, shard)| {
+                code_shred(slot, 0, data.len() as u16, num_coding as u16, position as u16, shard)
+            })
+            .collect();
+        (data, coding)
+    }
+
+    #[test]
+    fn parses_data_shred_headers() {
+        let packet = data_shred(42, 3, 0, DATA_COMPLETE_SHRED, b"hello");
+        let shred = Shred::parse(&packet).unwrap();
+        assert_eq!(shred.variant, ShredVariant::LegacyData);
+        assert_eq!((shred.slot, shred.index, shred.fec_set_index), (42, 3, 0));
+        assert_eq!(shred.flags(), DATA_COMPLETE_SHRED);
+        assert_eq!(shred.data(), b"hello");
+    }
+
+    #[test]
+    fn parses_code_shred_headers() {
+        let (_, coding) = slot_packets(9, &test_entries(30), 2);
+        let shred = Shred::parse(&coding[1]).unwrap();
+        assert!(!shred.variant.is_data());
+        assert!(matches!(
+            shred.kind,
+            ShredKind::Code { num_data_shreds: 2, num_coding_shreds: 2, position: 1 }
+        ));
+        assert!(shred.data().is_empty());
+    }
+
+    #[test]
+    fn rejects_size_beyond_data_payload() {
+        // 完整UDP数据包长度大于数据分片负载，声明的长度不能超过负载大小
+        let mut packet = data_shred(1, 0, 0, 0, &[]);
+        packet.resize(1280, 0);
+        packet[86..88].copy_from_slice(&(DATA_SHRED_PAYLOAD_SIZE as u16 + 1).to_le_bytes());
+        assert!(matches!(Shred::parse(&packet), Err(ShredError::InvalidHeader(_))));
+
+        packet[86..88].copy_from_slice(&(DATA_SHRED_PAYLOAD_SIZE as u16).to_le_bytes());
+        assert_eq!(Shred::parse(&packet).unwrap().data().len(), DATA_SHRED_PAYLOAD_SIZE - SIZE_OF_DATA_SHRED_HEADERS);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        assert!(matches!(Shred::parse(&[0u8; 10]), Err(ShredError::TooShort(10))));
+
+        let mut packet = data_shred(1, 0, 0, 0, b"x");
+        packet[SIZE_OF_SIGNATURE] = 0x00;
+        assert!(matches!(Shred::parse(&packet), Err(ShredError::InvalidVariant(0))));
+
+        let packet = data_shred(1, 0, 5, 0, b"x");
+        assert!(matches!(Shred::parse(&packet), Err(ShredError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn packet_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("shred-packets-{}.bin", std::process::id()));
+        let packets = vec![data_shred(1, 0, 0, 0, b"a"), vec![1, 2, 3], Vec::new()];
+        let mut writer = ShredPacketWriter::create(&path).unwrap();
+        for packet in &packets {
+            writer.write_packet(packet).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(read_shred_packets(&path).unwrap(), packets);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
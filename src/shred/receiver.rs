@@ -0,0 +1,264 @@
+use super::assembler::ShredAssembler;
+use super::ShredPacketWriter;
+use crate::client::auth::{AuthInterceptor, Authenticator};
+use crate::config::Config;
+use jito_protos::shared::Socket;
+use jito_protos::shredstream::{shredstream_client::ShredstreamClient as HeartbeatClient, Heartbeat};
+use solana_entry::entry::Entry;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tonic::transport::Channel;
+use tonic::Code;
+
+/// UDP接收缓冲区大小，大于最大分片数据包
+const PACKET_BUFFER_SIZE: usize = 1280;
+/// 组装结果通道的缓冲大小
+const ENTRY_CHANNEL_SIZE: usize = 4096;
+/// 心跳失败后的重试间隔
+const HEARTBEAT_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// 服务端未返回有效ttl时使用的心跳间隔
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+/// 定时将保存的数据包刷新到磁盘，避免进程崩溃时丢失缓冲区中的数据
+const DUMP_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// 打印组装统计的间隔
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 原生分片接收配置
+#[derive(Clone)]
+pub struct ShredReceiverConfig {
+    // 接收分片的本地UDP地址
+    pub listen_addr: SocketAddr,
+    // 心跳中上报的公网IP，必须与发出心跳的IP一致
+    pub public_ip: String,
+    // 心跳中上报的端口，默认与监听端口相同
+    pub public_port: u16,
+    // 订阅的区域
+    pub regions: Vec<String>,
+    // 接收心跳的区块引擎地址
+    pub heartbeat_url: String,
+    // 可选，将收到的原始数据包保存到文件
+    pub dump_path: Option<String>,
+}
+
+impl ShredReceiverConfig {
+    /// 从全局配置构建，未设置公网IP时返回None
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let public_ip = config.shred_public_ip.clone()?;
+        Some(Self {
+            listen_addr: config.shred_listen_addr,
+            public_ip,
+            public_port: config.shred_public_port.unwrap_or(config.shred_listen_addr.port()),
+            regions: config.shred_regions.clone(),
+            heartbeat_url: config.shred_heartbeat_url.clone().unwrap_or_else(|| config.server_url.clone()),
+            dump_path: config.shred_dump_path.clone(),
+        })
+    }
+}
+
+/// 原生分片接收器
+///
+/// 定时向区块引擎发送心跳以保持分片推送，在本地UDP端口接收原始分片，
+/// 组装为Entry后通过通道输出，不再依赖外部的shredstream-proxy。
+pub struct ShredReceiver {
+    config: ShredReceiverConfig,
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl ShredReceiver {
+    /// 创建分片接收器
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 接收配置
+    /// * `authenticator` - 可选的认证器，心跳请求会携带其访问令牌
+    pub fn new(config: ShredReceiverConfig, authenticator: Option<Arc<Authenticator>>) -> Self {
+        Self { config, authenticator }
+    }
+
+    fn interceptor(&self) -> AuthInterceptor {
+        self.authenticator
+            .as_ref()
+            .map(|auth| auth.interceptor())
+            .unwrap_or_default()
+    }
+
+    /// 启动心跳任务
+    pub fn spawn_heartbeat(&self) -> JoinHandle<()> {
+        let url = self.config.heartbeat_url.clone();
+        let heartbeat = Heartbeat {
+            socket: Some(Socket {
+                ip: self.config.public_ip.clone(),
+                port: self.config.public_port as i64,
+            }),
+            regions: self.config.regions.clone(),
+        };
+        let interceptor = self.interceptor();
+        let authenticator = self.authenticator.clone();
+
+        tokio::spawn(async move {
+            let mut client = None;
+            let mut last_ttl = None;
+            loop {
+                if client.is_none() {
+                    match Channel::from_shared(url.clone()) {
+                        Ok(endpoint) => match endpoint.connect().await {
+                            Ok(channel) => {
+                                client = Some(HeartbeatClient::with_interceptor(channel, interceptor.clone()));
+                            }
+                            Err(e) => {
+                                println!("连接心跳服务 {} 失败: {:?}", url, e);
+                                sleep(HEARTBEAT_RETRY_DELAY).await;
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            println!("心跳服务地址无效 {}: {:?}", url, e);
+                            return;
+                        }
+                    }
+                }
+
+                let result = client.as_mut().unwrap().send_heartbeat(heartbeat.clone()).await;
+                match result {
+                    Ok(response) => {
+                        let ttl_ms = response.into_inner().ttl_ms;
+                        if last_ttl != Some(ttl_ms) {
+                            println!("心跳成功，ttl: {}ms", ttl_ms);
+                            last_ttl = Some(ttl_ms);
+                        }
+                        // 在ttl过半时发送下一次心跳
+                        let interval = if ttl_ms > 0 {
+                            Duration::from_millis(ttl_ms as u64 / 2)
+                        } else {
+                            DEFAULT_HEARTBEAT_INTERVAL
+                        };
+                        sleep(interval).await;
+                    }
+                    Err(e) => {
+                        println!("发送心跳失败: {:?}", e);
+                        last_ttl = None;
+                        if e.code() == Code::Unauthenticated {
+                            if let Some(authenticator) = &authenticator {
+                                if let Err(e) = authenticator.authenticate().await {
+                                    println!("重新认证失败: {:?}", e);
+                                }
+                            }
+                        } else if e.code() == Code::Unavailable {
+                            client = None;
+                        }
+                        sleep(HEARTBEAT_RETRY_DELAY).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// 启动心跳和UDP接收任务，返回按slot组装好的Entry
+    pub async fn spawn(&self) -> Result<mpsc::Receiver<(u64, Vec<Entry>)>, Box<dyn Error + Send + Sync>> {
+        let socket = UdpSocket::bind(self.config.listen_addr).await?;
+        println!("分片接收地址: {}", socket.local_addr()?);
+
+        let mut writer = match &self.config.dump_path {
+            Some(path) => Some(ShredPacketWriter::create(path)?),
+            None => None,
+        };
+
+        self.spawn_heartbeat();
+
+        let (tx, rx) = mpsc::channel(ENTRY_CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let mut assembler = ShredAssembler::new();
+            let mut buf = [0u8; PACKET_BUFFER_SIZE];
+            let mut flush_interval = tokio::time::interval(DUMP_FLUSH_INTERVAL);
+            let mut stats_interval = tokio::time::interval(STATS_INTERVAL);
+            stats_interval.tick().await;
+            loop {
+                let received = tokio::select! {
+                    received = socket.recv_from(&mut buf) => received,
+                    _ = flush_interval.tick() => {
+                        if let Some(w) = writer.as_mut() {
+                            if let Err(e) = w.flush() {
+                                println!("刷新分片保存文件失败: {:?}，停止保存", e);
+                                writer = None;
+                            }
+                        }
+                        continue;
+                    }
+                    _ = stats_interval.tick() => {
+                        println!("分片统计: {}", assembler.stats());
+                        continue;
+                    }
+                };
+                let len = match received {
+                    Ok((len, _)) => len,
+                    Err(e) => {
+                        println!("接收分片失败: {:?}", e);
+                        continue;
+                    }
+                };
+                let packet = &buf[..len];
+
+                if let Some(w) = writer.as_mut() {
+                    if let Err(e) = w.write_packet(packet) {
+                        println!("保存分片失败: {:?}，停止保存", e);
+                        writer = None;
+                    }
+                }
+
+                for assembled in assembler.insert_packet(packet) {
+                    if tx.send(assembled).await.is_err() {
+                        // 接收端已关闭
+                        if let Some(w) = writer.as_mut() {
+                            let _ = w.flush();
+                        }
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shred::tests::{read_shred_packets, slot_packets, test_entries};
+
+    // 将捕获文件中的分片数据包依次送入组装器
+    fn assemble_capture_file(path: &std::path::Path) -> std::io::Result<Vec<(u64, Vec<Entry>)>> {
+        let mut assembler = ShredAssembler::new();
+        Ok(read_shred_packets(path)?
+            .iter()
+            .flat_map(|packet| assembler.insert_packet(packet))
+            .collect())
+    }
+
+    #[test]
+    fn assembles_captured_packets_from_disk() {
+        let path = std::env::temp_dir().join(format!("shred-capture-{}.bin", std::process::id()));
+        let first = test_entries(60);
+        let second = test_entries(5);
+        let (first_data, first_coding) = slot_packets(20, &first, 2);
+        let (second_data, _) = slot_packets(21, &second, 1);
+
+        // 捕获中丢失了一个数据分片，并夹杂了无效数据包
+        let mut writer = ShredPacketWriter::create(&path).unwrap();
+        for packet in first_data.iter().skip(1).chain(&first_coding).chain(&second_data) {
+            writer.write_packet(packet).unwrap();
+        }
+        writer.write_packet(&[0u8; 16]).unwrap();
+        writer.flush().unwrap();
+
+        let assembled = assemble_capture_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(assembled, vec![(20, first), (21, second)]);
+    }
+}
@@ -0,0 +1,568 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use crate::exit::ExitRules;
+use crate::instruction::{BuyArgs, CreateAccounts, MigrateAccounts, PumpInstruction, TradeAccounts};
+use crate::processor::mint_state::{MintState, TradeSide};
+use crate::utils::auto_trader::SnipeParams;
+
+/// 全部卖出对应的基点
+pub const SELL_ALL_BPS: u64 = 10_000;
+
+/// 策略配置错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StrategyError {
+    /// 没有该类型的策略
+    UnknownKind(String),
+    /// 策略参数无法解析
+    InvalidParams { name: String, reason: String },
+    /// 策略名称重复
+    DuplicateName(String),
+}
+
+impl fmt::Display for StrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategyError::UnknownKind(kind) => write!(f, "未知的策略类型: {}", kind),
+            StrategyError::InvalidParams { name, reason } => write!(f, "策略 {} 参数无效: {}", name, reason),
+            StrategyError::DuplicateName(name) => write!(f, "策略名称重复: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for StrategyError {}
+
+/// 策略收到的事件，由解析后的Pump指令得到
+#[derive(Debug, Clone, Copy)]
+pub enum StrategyEvent<'a> {
+    Create { accounts: &'a CreateAccounts },
+    Buy { args: &'a BuyArgs, accounts: &'a TradeAccounts },
+    Sell { accounts: &'a TradeAccounts },
+    // 内盘完成后迁移到外盘
+    Migrate { accounts: &'a MigrateAccounts },
+}
+
+impl<'a> StrategyEvent<'a> {
+    /// 从Pump指令构建事件，策略不关心的指令返回None
+    pub fn from_instruction(instruction: &'a PumpInstruction) -> Option<Self> {
+        match instruction {
+            PumpInstruction::Create { accounts, .. } => Some(StrategyEvent::Create { accounts }),
+            PumpInstruction::Buy { args, accounts } => Some(StrategyEvent::Buy { args, accounts }),
+            PumpInstruction::Sell { accounts, .. } => Some(StrategyEvent::Sell { accounts }),
+            PumpInstruction::Migrate { accounts } => Some(StrategyEvent::Migrate { accounts }),
+            _ => None,
+        }
+    }
+
+    /// 事件涉及的代币
+    pub fn mint(&self) -> Pubkey {
+        match self {
+            StrategyEvent::Create { accounts } => accounts.mint,
+            StrategyEvent::Buy { accounts, .. } | StrategyEvent::Sell { accounts } => accounts.mint,
+            StrategyEvent::Migrate { accounts } => accounts.mint,
+        }
+    }
+}
+
+/// 事件发生时的上下文
+pub struct EventContext<'a> {
+    // 事件是否来自创建代币的交易
+    pub in_create_tx: bool,
+    // 应用该事件之后的代币状态，开始跟踪之前创建的代币为None
+    pub state: Option<&'a MintState>,
+}
+
+/// 策略对事件的决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    Ignore,
+    // 以不超过lamports的花费(含手续费)买入
+    Buy { mint: Pubkey, lamports: u64 },
+    // 卖出当前持仓的basis_points/10000
+    Sell { mint: Pubkey, basis_points: u64 },
+}
+
+/// 交易策略
+///
+/// 策略只做决定，不发送交易。实盘和回测把同一组策略接到各自的事件源和执行器上，
+/// 每个策略的买入都受它自己的预算限制。
+pub trait Strategy: Send {
+    /// 策略名称，用于日志和预算
+    fn name(&self) -> &str;
+
+    /// 处理一个事件，返回交易意图
+    ///
+    /// # 参数
+    ///
+    /// * `event` - 解析后的事件
+    /// * `ctx` - 事件所在交易和代币状态
+    fn on_event(&mut self, event: &StrategyEvent<'_>, ctx: &EventContext<'_>) -> Intent;
+
+    /// 该策略在代币上的买入被拒绝、失败或回滚，或持仓已全部卖出
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 不再持有的代币
+    fn on_closed(&mut self, _mint: &Pubkey) {}
+}
+
+/// 价格区间策略
+///
+/// 在创建代币的交易中观察到买入，且买入者的max_sol_cost落在区间内时按固定金额买入。
+/// 设置了跟随卖出时，开发者在内盘卖出后卖出该代币持仓的一定比例。
+pub struct PriceRangeStrategy {
+    name: String,
+    min_sol_price: u64,
+    max_sol_price: u64,
+    buy_amount: u64,
+    // 开发者卖出时跟随卖出的基点
+    creator_sell_bps: Option<u64>,
+    // 发出过买入、尚未跟随卖出的代币
+    bought: HashSet<Pubkey>,
+}
+
+/// 价格区间策略在配置文件中的参数，单位为SOL
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PriceRangeConfig {
+    min_sol_price: f64,
+    max_sol_price: f64,
+    buy_sol_amount: f64,
+    // 开发者卖出时跟随卖出持仓的百分比
+    #[serde(default)]
+    creator_sell_pct: Option<f64>,
+}
+
+impl PriceRangeStrategy {
+    pub fn new(name: impl Into<String>, min_sol_price: u64, max_sol_price: u64, buy_amount: u64) -> Self {
+        Self {
+            name: name.into(),
+            min_sol_price,
+            max_sol_price,
+            buy_amount,
+            creator_sell_bps: None,
+            bought: HashSet::new(),
+        }
+    }
+
+    /// 开发者卖出时跟随卖出持仓的`percent`%
+    pub fn with_creator_sell(mut self, percent: f64) -> Self {
+        self.creator_sell_bps = Some(((percent * 100.0).round().max(0.0) as u64).min(SELL_ALL_BPS));
+        self
+    }
+
+    /// 使用环境变量中的狙击参数
+    pub fn from_params(name: impl Into<String>, params: &SnipeParams) -> Self {
+        Self::new(name, params.min_sol_price, params.max_sol_price, params.buy_amount)
+    }
+}
+
+impl Strategy for PriceRangeStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_event(&mut self, event: &StrategyEvent<'_>, ctx: &EventContext<'_>) -> Intent {
+        match event {
+            StrategyEvent::Buy { args, accounts } if ctx.in_create_tx => {
+                if args.max_sol_cost >= self.min_sol_price && args.max_sol_cost <= self.max_sol_price {
+                    if self.creator_sell_bps.is_some() {
+                        self.bought.insert(accounts.mint);
+                    }
+                    Intent::Buy {
+                        mint: accounts.mint,
+                        lamports: self.buy_amount,
+                    }
+                } else {
+                    Intent::Ignore
+                }
+            }
+            StrategyEvent::Sell { accounts } => match (self.creator_sell_bps, ctx.state) {
+                (Some(basis_points), Some(state))
+                    if creator_sold(state, &accounts.user) && self.bought.remove(&accounts.mint) =>
+                {
+                    Intent::Sell {
+                        mint: accounts.mint,
+                        basis_points,
+                    }
+                }
+                _ => Intent::Ignore,
+            },
+            _ => Intent::Ignore,
+        }
+    }
+
+    fn on_closed(&mut self, mint: &Pubkey) {
+        self.bought.remove(mint);
+    }
+}
+
+// 开发者的卖出已应用到曲线；超出滑点的卖出在链上会失败，不会写入成交记录
+fn creator_sold(state: &MintState, user: &Pubkey) -> bool {
+    state.creator == *user
+        && state
+            .tape()
+            .back()
+            .is_some_and(|entry| entry.side == TradeSide::Sell && entry.trader == *user)
+}
+
+/// 策略预算，限制同时占用的买入金额
+///
+/// 买入前按代币预留，买入失败或仓位全部卖出后释放。克隆得到的句柄共享同一份预算，
+/// 可以在执行交易的异步任务中释放。
+#[derive(Clone)]
+pub struct Budget {
+    limit: u64,
+    reserved: Arc<StdMutex<HashMap<Pubkey, u64>>>,
+}
+
+impl Budget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            reserved: Arc::new(StdMutex::new(HashMap::new())),
+        }
+    }
+
+    /// 不限金额的预算
+    pub fn unlimited() -> Self {
+        Self::new(u64::MAX)
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// 已占用的金额
+    pub fn used(&self) -> u64 {
+        self.reserved.lock().unwrap().values().sum()
+    }
+
+    /// 为代币预留买入金额，超出预算或该代币已有仓位时返回false
+    pub fn try_reserve(&self, mint: Pubkey, lamports: u64) -> bool {
+        let mut reserved = self.reserved.lock().unwrap();
+        if reserved.contains_key(&mint) {
+            return false;
+        }
+        let used: u64 = reserved.values().sum();
+        if used.saturating_add(lamports) > self.limit {
+            return false;
+        }
+        reserved.insert(mint, lamports);
+        true
+    }
+
+    /// 释放代币占用的金额
+    pub fn release(&self, mint: &Pubkey) {
+        self.reserved.lock().unwrap().remove(mint);
+    }
+}
+
+/// 通过预算检查的交易意图
+#[derive(Clone)]
+pub struct Order {
+    pub strategy: String,
+    pub intent: Intent,
+    // 发出该意图的策略的预算，执行失败或平仓后释放
+    pub budget: Budget,
+    // 买入成交后持仓使用的退出规则
+    pub exit: ExitRules,
+}
+
+struct Slot {
+    strategy: Box<dyn Strategy>,
+    budget: Budget,
+    exit: ExitRules,
+}
+
+/// 配置文件中的一个策略
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StrategyConfig {
+    // 策略名称，同一类型可以用不同名称配置多次
+    pub name: String,
+    // 策略类型，如price_range
+    pub kind: String,
+    // 同时占用的买入金额上限 (SOL)，未设置时不限
+    #[serde(default)]
+    pub budget_sol: Option<f64>,
+    #[serde(default)]
+    pub params: serde_json::Value,
+    // 该策略持仓的退出规则，未设置时使用环境变量中的规则
+    #[serde(default)]
+    pub exit: Option<ExitRules>,
+}
+
+impl StrategyConfig {
+    /// 按类型名称构建策略
+    pub fn build(&self) -> Result<Box<dyn Strategy>, StrategyError> {
+        let invalid = |e: serde_json::Error| StrategyError::InvalidParams {
+            name: self.name.clone(),
+            reason: e.to_string(),
+        };
+        match self.kind.as_str() {
+            "price_range" => {
+                let params: PriceRangeConfig = serde_json::from_value(self.params.clone()).map_err(invalid)?;
+                let strategy = PriceRangeStrategy::new(
+                    self.name.clone(),
+                    sol_to_lamports(params.min_sol_price),
+                    sol_to_lamports(params.max_sol_price),
+                    sol_to_lamports(params.buy_sol_amount),
+                );
+                Ok(Box::new(match params.creator_sell_pct {
+                    Some(percent) => strategy.with_creator_sell(percent),
+                    None => strategy,
+                }))
+            }
+            other => Err(StrategyError::UnknownKind(other.to_string())),
+        }
+    }
+
+    fn budget(&self) -> Budget {
+        match self.budget_sol {
+            Some(sol) => Budget::new(sol_to_lamports(sol)),
+            None => Budget::unlimited(),
+        }
+    }
+}
+
+fn sol_to_lamports(sol: f64) -> u64 {
+    (sol * 1_000_000_000.0) as u64
+}
+
+/// 同时运行的一组策略
+///
+/// 每个事件依次交给所有策略，买入意图在对应策略的预算内预留成功后才会返回。
+#[derive(Default)]
+pub struct StrategySet {
+    slots: Vec<Slot>,
+}
+
+impl StrategySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从JSON配置文件加载策略
+    ///
+    /// 配置文件为策略数组，每项包含name、kind、可选的budget_sol和exit，以及该类型的params。
+    pub fn load(path: impl AsRef<Path>, default_exit: &ExitRules) -> Result<Self, Box<dyn Error>> {
+        let configs: Vec<StrategyConfig> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(Self::from_configs(&configs, default_exit)?)
+    }
+
+    /// 按配置构建策略，没有配置退出规则的策略使用`default_exit`
+    pub fn from_configs(configs: &[StrategyConfig], default_exit: &ExitRules) -> Result<Self, StrategyError> {
+        let mut names = HashSet::new();
+        let mut set = Self::new();
+        for config in configs {
+            if !names.insert(config.name.as_str()) {
+                return Err(StrategyError::DuplicateName(config.name.clone()));
+            }
+            let exit = config.exit.clone().unwrap_or_else(|| default_exit.clone());
+            set.add(config.build()?, config.budget(), exit);
+        }
+        Ok(set)
+    }
+
+    /// 只包含按环境变量配置的价格区间策略，不限预算
+    pub fn from_params(params: &SnipeParams, exit: &ExitRules) -> Self {
+        let mut set = Self::new();
+        let strategy = PriceRangeStrategy::from_params("price_range", params);
+        set.add(Box::new(strategy), Budget::unlimited(), exit.clone());
+        set
+    }
+
+    pub fn add(&mut self, strategy: Box<dyn Strategy>, budget: Budget, exit: ExitRules) {
+        self.slots.push(Slot { strategy, budget, exit });
+    }
+
+    /// 策略名称和预算
+    pub fn budgets(&self) -> impl Iterator<Item = (&str, &Budget)> + '_ {
+        self.slots.iter().map(|slot| (slot.strategy.name(), &slot.budget))
+    }
+
+    /// 为重启前的持仓生成买入，交给退出引擎继续管理
+    ///
+    /// 持仓成本计入策略预算，超出预算时仍恢复持仓但不占用预算。策略已不在配置中时返回None。
+    pub fn resume(&self, strategy: &str, mint: Pubkey, lamports: u64) -> Option<Order> {
+        let slot = self.slots.iter().find(|slot| slot.strategy.name() == strategy)?;
+        if !slot.budget.try_reserve(mint, lamports) {
+            println!(
+                "策略 {} 预算不足或已持有 {}，恢复的持仓不占用预算 (已用 {:.3} / {:.3} SOL)",
+                strategy,
+                mint,
+                slot.budget.used() as f64 / 1_000_000_000.0,
+                slot.budget.limit() as f64 / 1_000_000_000.0
+            );
+        }
+        Some(Order {
+            strategy: strategy.to_string(),
+            intent: Intent::Buy { mint, lamports },
+            budget: slot.budget.clone(),
+            exit: slot.exit.clone(),
+        })
+    }
+
+    /// 通知策略不再持有代币：买入失败或回滚，或持仓已全部卖出
+    ///
+    /// # 参数
+    ///
+    /// * `strategy` - 发出买入的策略名称
+    /// * `mint` - 代币
+    pub fn closed(&mut self, strategy: &str, mint: &Pubkey) {
+        if let Some(slot) = self.slots.iter_mut().find(|slot| slot.strategy.name() == strategy) {
+            slot.strategy.on_closed(mint);
+        }
+    }
+
+    /// 将事件交给所有策略，返回需要执行的意图
+    pub fn on_event(&mut self, event: &StrategyEvent<'_>, ctx: &EventContext<'_>) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for slot in &mut self.slots {
+            let intent = slot.strategy.on_event(event, ctx);
+            match intent {
+                Intent::Ignore => continue,
+                Intent::Buy { mint, lamports } => {
+                    if !slot.budget.try_reserve(mint, lamports) {
+                        println!(
+                            "策略 {} 预算不足或已持有 {}，跳过买入 (已用 {:.3} / {:.3} SOL)",
+                            slot.strategy.name(),
+                            mint,
+                            slot.budget.used() as f64 / 1_000_000_000.0,
+                            slot.budget.limit() as f64 / 1_000_000_000.0
+                        );
+                        slot.strategy.on_closed(&mint);
+                        continue;
+                    }
+                }
+                Intent::Sell { .. } => {}
+            }
+            orders.push(Order {
+                strategy: slot.strategy.name().to_string(),
+                intent,
+                budget: slot.budget.clone(),
+                exit: slot.exit.clone(),
+            });
+        }
+        orders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bonding_curve::GlobalAccount;
+    use crate::instruction::SellArgs;
+    use solana_sdk::signature::Signature;
+
+    fn accounts(mint: Pubkey, user: Pubkey) -> TradeAccounts {
+        TradeAccounts {
+            global: Pubkey::default(),
+            fee_recipient: Pubkey::default(),
+            mint,
+            bonding_curve: Pubkey::default(),
+            associated_bonding_curve: Pubkey::default(),
+            associated_user: Pubkey::default(),
+            user,
+        }
+    }
+
+    fn ctx(state: &MintState, in_create_tx: bool) -> EventContext<'_> {
+        EventContext {
+            in_create_tx,
+            state: Some(state),
+        }
+    }
+
+    #[test]
+    fn follows_creator_sell_from_tape() {
+        let (mint, creator, other) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut strategy = PriceRangeStrategy::new("test", 0, u64::MAX, 1_000).with_creator_sell(50.0);
+        let mut state = MintState::new(&GlobalAccount::default(), creator, 10, 16);
+
+        let buy = BuyArgs { amount: 1_000_000_000, max_sol_cost: u64::MAX };
+        state.record_trade(10, Signature::default(), TradeSide::Buy, creator, buy.amount, buy.max_sol_cost).unwrap();
+        let buy_accounts = accounts(mint, creator);
+        let intent = strategy.on_event(&StrategyEvent::Buy { args: &buy, accounts: &buy_accounts }, &ctx(&state, true));
+        assert_eq!(intent, Intent::Buy { mint, lamports: 1_000 });
+
+        // 其他交易者卖出不触发
+        let sell = SellArgs { amount: 1_000, min_sol_output: 0 };
+        let other_accounts = accounts(mint, other);
+        state.record_trade(11, Signature::default(), TradeSide::Sell, other, sell.amount, 0).unwrap();
+        let intent = strategy.on_event(&StrategyEvent::Sell { accounts: &other_accounts }, &ctx(&state, false));
+        assert_eq!(intent, Intent::Ignore);
+
+        // 开发者卖出时只跟随一次
+        let creator_accounts = accounts(mint, creator);
+        state.record_trade(12, Signature::default(), TradeSide::Sell, creator, sell.amount, 0).unwrap();
+        let event = StrategyEvent::Sell { accounts: &creator_accounts };
+        assert_eq!(strategy.on_event(&event, &ctx(&state, false)), Intent::Sell { mint, basis_points: 5_000 });
+        assert_eq!(strategy.on_event(&event, &ctx(&state, false)), Intent::Ignore);
+    }
+
+    #[test]
+    fn ignores_creator_sell_that_fails_slippage() {
+        let (mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut strategy = PriceRangeStrategy::new("test", 0, u64::MAX, 1_000).with_creator_sell(100.0);
+        let mut state = MintState::new(&GlobalAccount::default(), creator, 10, 16);
+
+        let buy = BuyArgs { amount: 1_000_000_000, max_sol_cost: u64::MAX };
+        state.record_trade(10, Signature::default(), TradeSide::Buy, creator, buy.amount, buy.max_sol_cost).unwrap();
+        let creator_accounts = accounts(mint, creator);
+        strategy.on_event(&StrategyEvent::Buy { args: &buy, accounts: &creator_accounts }, &ctx(&state, true));
+
+        // 开发者的卖出因滑点失败，曲线和成交记录都没有变化
+        let failed = SellArgs { amount: 1_000, min_sol_output: u64::MAX };
+        assert!(state.record_trade(11, Signature::default(), TradeSide::Sell, creator, failed.amount, failed.min_sol_output).is_err());
+        let event = StrategyEvent::Sell { accounts: &creator_accounts };
+        assert_eq!(strategy.on_event(&event, &ctx(&state, false)), Intent::Ignore);
+
+        let sell = SellArgs { amount: 1_000, min_sol_output: 0 };
+        state.record_trade(12, Signature::default(), TradeSide::Sell, creator, sell.amount, 0).unwrap();
+        let event = StrategyEvent::Sell { accounts: &creator_accounts };
+        assert_eq!(strategy.on_event(&event, &ctx(&state, false)), Intent::Sell { mint, basis_points: SELL_ALL_BPS });
+    }
+
+    #[test]
+    fn closed_buys_are_not_followed() {
+        let (mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut set = StrategySet::new();
+        let strategy = PriceRangeStrategy::new("test", 0, u64::MAX, 1_000).with_creator_sell(100.0);
+        set.add(Box::new(strategy), Budget::new(1_000), ExitRules::default());
+        let mut state = MintState::new(&GlobalAccount::default(), creator, 10, 16);
+
+        let buy = BuyArgs { amount: 1_000_000_000, max_sol_cost: u64::MAX };
+        state.record_trade(10, Signature::default(), TradeSide::Buy, creator, buy.amount, buy.max_sol_cost).unwrap();
+        let creator_accounts = accounts(mint, creator);
+        let orders = set.on_event(&StrategyEvent::Buy { args: &buy, accounts: &creator_accounts }, &ctx(&state, true));
+        assert_eq!(orders.len(), 1);
+
+        // 买入失败后释放预算并通知策略
+        orders[0].budget.release(&mint);
+        set.closed("test", &mint);
+        let sell = SellArgs { amount: 1_000, min_sol_output: 0 };
+        state.record_trade(11, Signature::default(), TradeSide::Sell, creator, sell.amount, 0).unwrap();
+        let sell_event = StrategyEvent::Sell { accounts: &creator_accounts };
+        assert!(set.on_event(&sell_event, &ctx(&state, false)).is_empty());
+
+        // 预算不足被拒绝的买入同样不再跟随
+        let other = Pubkey::new_unique();
+        let other_accounts = accounts(other, creator);
+        let mut other_state = MintState::new(&GlobalAccount::default(), creator, 12, 16);
+        assert!(set.budgets().all(|(_, budget)| budget.try_reserve(Pubkey::new_unique(), 1_000)));
+        let event = StrategyEvent::Buy { args: &buy, accounts: &other_accounts };
+        assert!(set.on_event(&event, &ctx(&other_state, true)).is_empty());
+        other_state.record_trade(13, Signature::default(), TradeSide::Sell, creator, sell.amount, 0).unwrap();
+        let sell_event = StrategyEvent::Sell { accounts: &other_accounts };
+        assert!(set.on_event(&sell_event, &ctx(&other_state, false)).is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+use solana_rpc_client_api::client_error::{Error as ClientError, ErrorKind};
+use solana_rpc_client_api::request::RpcError;
+use std::fmt;
+
+use super::simulate::SimulationError;
+use crate::bonding_curve::CurveError;
+use crate::bundle::BundleError;
+
+// 可重试的RPC响应错误码
+const RPC_NODE_UNHEALTHY: i64 = -32005;
+const RPC_MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+const RPC_TOO_MANY_REQUESTS: i64 = 429;
+
+/// 买入和卖出失败的原因
+///
+/// 调用方按`is_retryable`区分可以立即重试的临时故障和应当放弃的失败。
+#[derive(Clone, Debug, PartialEq)]
+pub enum TradeError {
+    /// 私钥无效
+    InvalidKey(String),
+    /// 交易参数无效，如代币地址无效、买入金额不足以购买代币
+    InvalidOrder(String),
+    /// 无法获取区块哈希
+    BlockhashUnavailable(String),
+    /// 与节点的连接失败
+    RpcTransport(String),
+    /// 节点返回了错误
+    RpcResponse { code: i64, message: String },
+    /// 发送前模拟失败，交易没有发送
+    Simulation(SimulationError),
+    /// 请求超时
+    Timeout(String),
+    /// Jito捆绑提交失败
+    Bundle(BundleError),
+}
+
+impl TradeError {
+    /// 是否为临时故障，重试可能成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            TradeError::BlockhashUnavailable(_) | TradeError::RpcTransport(_) | TradeError::Timeout(_) => true,
+            TradeError::RpcResponse { code, .. } => matches!(
+                *code,
+                RPC_NODE_UNHEALTHY | RPC_MIN_CONTEXT_SLOT_NOT_REACHED | RPC_TOO_MANY_REQUESTS
+            ),
+            TradeError::Simulation(SimulationError::Rpc(_)) => true,
+            TradeError::Bundle(e) => e.is_retryable(),
+            TradeError::InvalidKey(_) | TradeError::InvalidOrder(_) | TradeError::Simulation(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for TradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeError::InvalidKey(e) => write!(f, "私钥无效: {}", e),
+            TradeError::InvalidOrder(e) => write!(f, "交易参数无效: {}", e),
+            TradeError::BlockhashUnavailable(e) => write!(f, "获取区块哈希失败: {}", e),
+            TradeError::RpcTransport(e) => write!(f, "RPC连接失败: {}", e),
+            TradeError::RpcResponse { code, message } => write!(f, "RPC返回错误 {}: {}", code, message),
+            TradeError::Simulation(e) => write!(f, "模拟失败，未发送: {}", e),
+            TradeError::Timeout(e) => write!(f, "请求超时: {}", e),
+            TradeError::Bundle(e) => write!(f, "捆绑提交失败: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TradeError {}
+
+impl From<ClientError> for TradeError {
+    fn from(e: ClientError) -> Self {
+        match e.kind() {
+            ErrorKind::Reqwest(reqwest) if reqwest.is_timeout() => TradeError::Timeout(e.to_string()),
+            ErrorKind::Reqwest(reqwest) => match reqwest.status() {
+                Some(status) => TradeError::RpcResponse {
+                    code: status.as_u16() as i64,
+                    message: e.to_string(),
+                },
+                None => TradeError::RpcTransport(e.to_string()),
+            },
+            ErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. }) => TradeError::RpcResponse {
+                code: *code,
+                message: message.clone(),
+            },
+            _ => TradeError::RpcTransport(e.to_string()),
+        }
+    }
+}
+
+impl From<SimulationError> for TradeError {
+    fn from(e: SimulationError) -> Self {
+        TradeError::Simulation(e)
+    }
+}
+
+impl From<BundleError> for TradeError {
+    fn from(e: BundleError) -> Self {
+        TradeError::Bundle(e)
+    }
+}
+
+impl From<CurveError> for TradeError {
+    fn from(e: CurveError) -> Self {
+        TradeError::InvalidOrder(e.to_string())
+    }
+}
@@ -0,0 +1,310 @@
+use base64::Engine;
+use serde_json::{json, Value};
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// 本地模拟的JSON-RPC节点，用于在不连接真实节点的情况下验证发送、重发和确认流程
+///
+/// 支持sendTransaction、simulateTransaction、getSignatureStatuses、getLatestBlockhash、getSlot和getVersion。
+/// 每次sendTransaction按丢弃比例随机丢弃，与真实节点一样仍然返回签名；没有被丢弃的交易在当前slot上链。
+/// simulateTransaction返回预设的模拟结果，默认为成功；与真实节点一样，没有设置`replaceRecentBlockhash`且
+/// 交易的区块哈希不是节点的最新区块哈希时返回BlockhashNotFound。通过`land_in_history`上链的交易只有在
+/// getSignatureStatuses带`searchTransactionHistory`时才能查到，模拟状态缓存中缺失的迟到交易。
+#[derive(Clone)]
+pub struct MockRpcServer {
+    inner: Arc<MockState>,
+}
+
+struct MockState {
+    drop_rate: f64,
+    rng: AtomicU64,
+    slot: AtomicU64,
+    blockhash: StdMutex<Hash>,
+    sends: AtomicU64,
+    dropped: AtomicU64,
+    // 已上链的交易和上链的slot
+    landed: StdMutex<HashMap<Signature, u64>>,
+    // 只能在完整交易历史中查到的交易和上链的slot
+    history: StdMutex<HashMap<Signature, u64>>,
+    // simulateTransaction返回的value
+    simulation: StdMutex<Value>,
+    simulations: AtomicU64,
+}
+
+impl MockRpcServer {
+    /// 创建模拟节点
+    ///
+    /// # 参数
+    ///
+    /// * `drop_rate` - 丢弃sendTransaction的比例，0到1之间
+    pub fn new(drop_rate: f64) -> Self {
+        Self::with_seed(drop_rate, 0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// 使用固定的随机种子创建模拟节点，丢弃顺序可以重现
+    pub fn with_seed(drop_rate: f64, seed: u64) -> Self {
+        Self {
+            inner: Arc::new(MockState {
+                drop_rate,
+                rng: AtomicU64::new(seed.max(1)),
+                slot: AtomicU64::new(1),
+                blockhash: StdMutex::new(Hash::new_unique()),
+                sends: AtomicU64::new(0),
+                dropped: AtomicU64::new(0),
+                landed: StdMutex::new(HashMap::new()),
+                history: StdMutex::new(HashMap::new()),
+                simulation: StdMutex::new(json!({
+                    "err": null,
+                    "logs": [
+                        "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+                        "Program ComputeBudget111111111111111111111111111111 success",
+                    ],
+                    "unitsConsumed": 150,
+                })),
+                simulations: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// 设置simulateTransaction返回的value，格式与RPC节点相同
+    ///
+    /// # 参数
+    ///
+    /// * `value` - 如`{"err": {"InstructionError": [3, {"Custom": 6002}]}, "logs": [...], "unitsConsumed": 41000}`
+    pub fn set_simulation(&self, value: Value) {
+        *self.inner.simulation.lock().unwrap() = value;
+    }
+
+    /// 收到的simulateTransaction次数
+    pub fn simulations(&self) -> u64 {
+        self.inner.simulations.load(Ordering::Relaxed)
+    }
+
+    /// 当前slot
+    pub fn slot(&self) -> u64 {
+        self.inner.slot.load(Ordering::Relaxed)
+    }
+
+    /// 前进若干slot，同时更换最新区块哈希
+    pub fn advance_slots(&self, slots: u64) {
+        self.inner.slot.fetch_add(slots, Ordering::Relaxed);
+        *self.inner.blockhash.lock().unwrap() = Hash::new_unique();
+    }
+
+    /// 收到的sendTransaction次数
+    pub fn sends(&self) -> u64 {
+        self.inner.sends.load(Ordering::Relaxed)
+    }
+
+    /// 被丢弃的sendTransaction次数
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 交易上链的slot，未上链时返回None
+    pub fn landed_slot(&self, signature: &Signature) -> Option<u64> {
+        self.inner.landed.lock().unwrap().get(signature).copied()
+    }
+
+    /// 让交易在当前slot上链，但只能通过完整交易历史查到，返回上链的slot
+    pub fn land_in_history(&self, signature: Signature) -> u64 {
+        let slot = self.slot();
+        self.inner.history.lock().unwrap().insert(signature, slot);
+        slot
+    }
+
+    /// 在本地随机端口启动服务，返回可直接用于`RpcClient::new`的地址
+    pub async fn serve(self) -> std::io::Result<(String, JoinHandle<()>)> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let addr = listener.local_addr()?;
+        let handle = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let server = self.clone();
+                        tokio::spawn(async move { server.handle_connection(stream).await });
+                    }
+                    Err(e) => {
+                        println!("模拟RPC节点退出: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok((format!("http://{}", addr), handle))
+    }
+
+    // 处理一个HTTP/1.1连接上的所有请求
+    async fn handle_connection(&self, stream: TcpStream) {
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return;
+            }
+            let mut content_length = 0;
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                let header = line.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            if reader.read_exact(&mut body).await.is_err() {
+                return;
+            }
+
+            let response = match serde_json::from_slice::<Value>(&body) {
+                Ok(request) => self.handle_request(&request),
+                Err(e) => json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32700, "message": e.to_string() } }),
+            };
+            let body = response.to_string();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+            let stream = reader.get_mut();
+            if stream.write_all(head.as_bytes()).await.is_err() || stream.write_all(body.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn handle_request(&self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let slot = self.slot();
+        let result = match request.get("method").and_then(Value::as_str).unwrap_or_default() {
+            "sendTransaction" => self.send_transaction(&params),
+            "simulateTransaction" => self.simulate_transaction(&params),
+            "getSignatureStatuses" => Ok(self.signature_statuses(&params)),
+            "getLatestBlockhash" => Ok(json!({
+                "context": { "slot": slot },
+                "value": {
+                    "blockhash": self.inner.blockhash.lock().unwrap().to_string(),
+                    "lastValidBlockHeight": slot + 150,
+                },
+            })),
+            "getSlot" => Ok(json!(slot)),
+            "getVersion" => Ok(json!({ "solana-core": "1.18.26", "feature-set": 0 })),
+            method => Err((-32601, format!("Method not found: {}", method))),
+        };
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        }
+    }
+
+    fn simulate_transaction(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let transaction = decode_transaction(params)?;
+        self.inner.simulations.fetch_add(1, Ordering::Relaxed);
+        let replace_blockhash = params
+            .get(1)
+            .and_then(|config| config.get("replaceRecentBlockhash"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let value = if replace_blockhash || *transaction.message.recent_blockhash() == *self.inner.blockhash.lock().unwrap() {
+            self.inner.simulation.lock().unwrap().clone()
+        } else {
+            json!({ "err": "BlockhashNotFound", "logs": [], "accounts": null, "unitsConsumed": 0 })
+        };
+        Ok(json!({ "context": { "slot": self.slot() }, "value": value }))
+    }
+
+    fn send_transaction(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let invalid = |message: String| (-32602, message);
+        let transaction = decode_transaction(params)?;
+        let signature = *transaction.signatures.first().ok_or_else(|| invalid("交易没有签名".to_string()))?;
+
+        self.inner.sends.fetch_add(1, Ordering::Relaxed);
+        if self.next_random() < self.inner.drop_rate {
+            self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.inner.landed.lock().unwrap().entry(signature).or_insert(self.slot());
+        }
+        Ok(json!(signature.to_string()))
+    }
+
+    fn signature_statuses(&self, params: &Value) -> Value {
+        let landed = self.inner.landed.lock().unwrap();
+        let history = self.inner.history.lock().unwrap();
+        let search_history = params
+            .get(1)
+            .and_then(|config| config.get("searchTransactionHistory"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let statuses: Vec<Value> = params
+            .get(0)
+            .and_then(Value::as_array)
+            .map(|signatures| signatures.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|signature| {
+                let signature = signature.as_str().and_then(|signature| signature.parse::<Signature>().ok());
+                let recent = signature.and_then(|signature| landed.get(&signature));
+                let searched = signature
+                    .filter(|_| search_history)
+                    .and_then(|signature| history.get(&signature));
+                match (recent, searched) {
+                    (Some(slot), _) => json!({
+                        "slot": slot,
+                        "confirmations": 1,
+                        "err": null,
+                        "status": { "Ok": null },
+                        "confirmationStatus": "confirmed",
+                    }),
+                    (None, Some(slot)) => json!({
+                        "slot": slot,
+                        "confirmations": null,
+                        "err": null,
+                        "status": { "Ok": null },
+                        "confirmationStatus": "finalized",
+                    }),
+                    (None, None) => Value::Null,
+                }
+            })
+            .collect();
+        json!({ "context": { "slot": self.slot() }, "value": statuses })
+    }
+
+    // xorshift64*，返回[0, 1)之间的随机数
+    fn next_random(&self) -> f64 {
+        let mut x = self.inner.rng.load(Ordering::Relaxed);
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.inner.rng.store(x, Ordering::Relaxed);
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// 解析请求参数中按配置编码的交易
+fn decode_transaction(params: &Value) -> Result<VersionedTransaction, (i64, String)> {
+    let invalid = |message: String| (-32602, message);
+    let encoded = params.get(0).and_then(Value::as_str).ok_or_else(|| invalid("缺少交易".to_string()))?;
+    let encoding = params.get(1).and_then(|config| config.get("encoding")).and_then(Value::as_str);
+    let bytes = match encoding {
+        Some("base64") => base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| invalid(e.to_string()))?,
+        _ => solana_sdk::bs58::decode(encoded).into_vec().map_err(|e| invalid(e.to_string()))?,
+    };
+    bincode::deserialize(&bytes).map_err(|e| invalid(e.to_string()))
+}
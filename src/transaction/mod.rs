@@ -1,17 +1,30 @@
-use std::fmt::Error;
+pub mod error;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod resend;
+pub mod sender;
+pub mod simulate;
+pub mod trader;
+
+use std::fmt;
+use std::str::FromStr;
 
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_rpc_client_api::config::RpcSendTransactionConfig;
 use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
+    commitment_config::CommitmentLevel,
     hash::Hash,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
+    signature::{Keypair, Signature},
     signer::Signer,
     system_program,
-    transaction::Transaction,
+    transaction::{Transaction, VersionedTransaction},
 };
-use spl_associated_token_account::get_associated_token_address;
+
+use crate::bundle::BundleSender;
+use crate::instruction::{BUY_DISCRIMINATOR, SELL_DISCRIMINATOR};
+use error::TradeError;
+use trader::MintAccounts;
 
 // Pump协议相关常量
 pub const GLOBAL_ACCOUNT: Pubkey =
@@ -26,274 +39,427 @@ pub const PROXY_PROGRAM: Pubkey = solana_sdk::pubkey!("AmXoSVCLjsfKrwCUqvkMFXYcD
 // 系统账户
 pub const SYSVAR_RENT_PUBKEY: Pubkey = solana_sdk::pubkey!("SysvarRent111111111111111111111111111111111");
 
-// 指令鉴别器
+// 代理程序的指令鉴别器
 pub const PUMP_BUY_SELECTOR: &[u8; 8] = &[82, 225, 119, 231, 78, 29, 45, 70];  // 内盘买入鉴别器
 pub const PUMP_SELL_SELECTOR: &[u8; 8] = &[83, 225, 119, 231, 78, 29, 45, 70]; // 内盘卖出鉴别器
 pub const ATA_SELECTOR: &[u8; 8] = &[22, 51, 53, 97, 247, 184, 54, 78];        // 创建ATA鉴别器
 
-const BONDING_CURVE_SEED: &[u8] = b"bonding-curve";
+/// 默认优先级费用 (micro-lamports/CU)
+pub const DEFAULT_COMPUTE_UNIT_PRICE: u64 = 200_000;
+
+/// 买入和卖出调用的程序
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PumpRoute {
+    /// 直接调用Pump程序，使用Anchor指令鉴别器，通过关联代币账户程序幂等创建ATA
+    #[default]
+    Direct,
+    /// 经由代理程序`PROXY_PROGRAM`调用，使用代理程序的鉴别器
+    Proxy,
+}
+
+impl PumpRoute {
+    // 买入和卖出指令调用的程序
+    fn program_id(&self) -> Pubkey {
+        match self {
+            PumpRoute::Direct => PUMP_PROGRAM_ID,
+            PumpRoute::Proxy => PROXY_PROGRAM,
+        }
+    }
+
+    fn buy_discriminator(&self) -> &'static [u8; 8] {
+        match self {
+            PumpRoute::Direct => &BUY_DISCRIMINATOR,
+            PumpRoute::Proxy => PUMP_BUY_SELECTOR,
+        }
+    }
+
+    fn sell_discriminator(&self) -> &'static [u8; 8] {
+        match self {
+            PumpRoute::Direct => &SELL_DISCRIMINATOR,
+            PumpRoute::Proxy => PUMP_SELL_SELECTOR,
+        }
+    }
+
+    // 创建用户的代币关联账户，账户已存在时不失败
+    fn create_ata_instruction(&self, payer: &Pubkey, accounts: &MintAccounts) -> Instruction {
+        match self {
+            PumpRoute::Direct => spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                payer,
+                payer,
+                &accounts.mint,
+                &spl_token::id(),
+            ),
+            PumpRoute::Proxy => {
+                let mut data = Vec::with_capacity(9);
+                data.extend_from_slice(ATA_SELECTOR);
+                data.extend_from_slice(&[0]);
+                Instruction::new_with_bytes(
+                    PROXY_PROGRAM,
+                    &data,
+                    vec![
+                        AccountMeta::new(*payer, true),
+                        AccountMeta::new(accounts.associated_user, false),
+                        AccountMeta::new_readonly(accounts.mint, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                        AccountMeta::new_readonly(spl_token::id(), false),
+                        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                    ],
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for PumpRoute {
+    type Err = String;
+
+    /// 解析`direct`或`proxy`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "direct" => Ok(PumpRoute::Direct),
+            "proxy" => Ok(PumpRoute::Proxy),
+            _ => Err(format!("无效的交易路径: {}，应为direct或proxy", s)),
+        }
+    }
+}
+
+impl fmt::Display for PumpRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PumpRoute::Direct => write!(f, "直接调用Pump程序"),
+            PumpRoute::Proxy => write!(f, "经由代理程序 {}", PROXY_PROGRAM),
+        }
+    }
+}
+
+/// 发送交易的设置：跳过预检且节点不重试，重发由调用方控制
+pub fn send_config() -> RpcSendTransactionConfig {
+    RpcSendTransactionConfig {
+        skip_preflight: true,
+        preflight_commitment: Some(CommitmentLevel::Processed), // 使用Processed级别以最快返回
+        max_retries: Some(0), // 不重试，因为我们需要立即知道结果
+        ..Default::default()
+    }
+}
+
+/// 解析base58编码的私钥
+///
+/// # 参数
+///
+/// * `private_key` - 64字节密钥对的base58编码
+pub fn parse_keypair(private_key: &str) -> Result<Keypair, TradeError> {
+    let bytes = solana_sdk::bs58::decode(private_key.trim())
+        .into_vec()
+        .map_err(|e| TradeError::InvalidKey(e.to_string()))?;
+    Keypair::from_bytes(&bytes).map_err(|e| TradeError::InvalidKey(e.to_string()))
+}
+
+/// 按指令构造并签名交易，签名者同时支付手续费
+///
+/// # 参数
+///
+/// * `signer` - 交易用户
+/// * `instructions` - 买入或卖出的全部指令
+/// * `blockhash` - 交易使用的区块哈希
+pub fn sign_transaction(signer: &Keypair, instructions: &[Instruction], blockhash: Hash) -> Transaction {
+    Transaction::new_signed_with_payer(instructions, Some(&signer.pubkey()), &[signer], blockhash)
+}
 
-/// Pump协议代币买入交易
-/// 
+/// Pump买入指令，账户顺序与Pump程序的buy指令一致，代理程序按相同顺序转发
+///
 /// # 参数
-/// 
-/// * `rpc_url` - RPC节点URL
-/// * `private_key` - 用户私钥
-/// * `token_mint` - 代币Mint地址
+///
+/// * `route` - 直接调用Pump程序或经由代理程序
+/// * `payer` - 买入用户
+/// * `accounts` - 代币的交易地址，用户关联账户应属于`payer`
 /// * `token_amount` - 要购买的代币数量
 /// * `max_sol_cost` - 最大SOL花费(lamports)
-/// * `slot` - 可选的槽号，用于记录日志
-/// * `cached_blockhash` - 可选的缓存区块哈希，如果提供则不会查询RPC
-pub async fn pump_buy(
-    rpc_url: &str, 
-    private_key: &str, 
-    token_mint: Pubkey, 
-    token_amount: u64, 
-    max_sol_cost: u64,
-    slot: Option<u64>,
-    cached_blockhash: Option<Hash>
-) -> Result<String, Error> {
-    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
-
-    // 构造买入指令数据
+pub fn pump_buy(route: PumpRoute, payer: &Pubkey, accounts: &MintAccounts, token_amount: u64, max_sol_cost: u64) -> Instruction {
     let mut data = Vec::with_capacity(24);
-    data.extend_from_slice(PUMP_BUY_SELECTOR);
+    data.extend_from_slice(route.buy_discriminator());
     data.extend_from_slice(&token_amount.to_le_bytes());
     data.extend_from_slice(&max_sol_cost.to_le_bytes());
 
-    let signer = solana_sdk::signature::Keypair::from_base58_string(private_key);
-
-    // 计算Bonding Curve地址
-    let bonding_curve_address =
-        Pubkey::find_program_address(&[BONDING_CURVE_SEED, token_mint.as_ref()], &PUMP_PROGRAM_ID);
-
-    // 用户代币关联账户
-    let associated_user = get_associated_token_address(&signer.pubkey(), &token_mint);
-
-    // Bonding Curve关联代币账户
-    let associated_bonding_curve =
-        get_associated_token_address(&bonding_curve_address.0, &token_mint);
-
-    // 构造买入指令
-    let buy_instruction = Instruction::new_with_bytes(
-        PROXY_PROGRAM,
+    Instruction::new_with_bytes(
+        route.program_id(),
         &data,
         vec![
             AccountMeta::new_readonly(GLOBAL_ACCOUNT, false),
             AccountMeta::new(FEE_RECIPIENT, false),
-            AccountMeta::new_readonly(token_mint, false),
-            AccountMeta::new(bonding_curve_address.0, false),
-            AccountMeta::new(associated_bonding_curve, false),
-            AccountMeta::new(associated_user, false),
-            AccountMeta::new(signer.pubkey(), true),
+            AccountMeta::new_readonly(accounts.mint, false),
+            AccountMeta::new(accounts.bonding_curve, false),
+            AccountMeta::new(accounts.associated_bonding_curve, false),
+            AccountMeta::new(accounts.associated_user, false),
+            AccountMeta::new(*payer, true),
             AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
             AccountMeta::new_readonly(SYSVAR_RENT_PUBKEY, false), // 修正Rent Sysvar地址
             AccountMeta::new_readonly(EVENT_AUTHORITY, false),
             AccountMeta::new_readonly(PUMP_PROGRAM_ID, false),
         ],
-    );
-
-    // 创建ATA指令数据
-    let mut ata_data = Vec::with_capacity(9);
-    ata_data.extend_from_slice(ATA_SELECTOR);
-    ata_data.extend_from_slice(&[0]);
-
-    // 构造创建ATA指令
-    let ata_instruction = Instruction::new_with_bytes(
-        PROXY_PROGRAM,
-        &ata_data,
-        vec![
-            AccountMeta::new(signer.pubkey(), true),
-            AccountMeta::new(associated_user, false),
-            AccountMeta::new_readonly(token_mint, false),
-            AccountMeta::new_readonly(system_program::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
-        ],
-    );
-
-    // 添加优先级费用指令 - 提高优先级费用到200000以确保快速处理
-    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(200000);
-    
-    // 增加最大计算单元以确保交易不会因为计算资源不足而失败
-    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(200000);
-
-    // 获取区块哈希
-    let blockhash = if let Some(hash) = cached_blockhash {
-        // 使用提供的缓存区块哈希
-        if let Some(slot_num) = slot {
-            println!("买入使用相关slot: {} 和缓存区块哈希", slot_num);
-        } else {
-            println!("买入使用缓存区块哈希");
-        }
-        hash
-    } else {
-        // 直接获取最新区块哈希
-        if let Some(slot_num) = slot {
-            println!("买入使用相关slot: {} 和新获取的区块哈希", slot_num);
-        }
-        
-        rpc_client
-            .get_latest_blockhash_with_commitment(CommitmentConfig {
-                commitment: CommitmentLevel::Confirmed,
-            })
-            .await
-            .unwrap()
-            .0
-    };
-
-    // 创建交易
-    let transaction = Transaction::new_signed_with_payer(
-        &[compute_unit_price_ix, compute_unit_limit_ix, ata_instruction, buy_instruction], // 添加两个优先级指令
-        Some(&signer.pubkey()),
-        &[&signer],
-        blockhash,
-    );
-
-    // 发送交易 - 使用最优的交易设置
-    match rpc_client
-        .send_transaction_with_config(
-            &transaction,
-            RpcSendTransactionConfig {
-                skip_preflight: true,
-                preflight_commitment: Some(CommitmentLevel::Processed), // 使用Processed级别以最快返回
-                max_retries: Some(0), // 不重试，因为我们需要立即知道结果
-                ..Default::default()
-            },
-        )
-        .await
-    {
-        Ok(signature) => {
-            println!("买入交易已提交: {}", signature);
-            Ok(signature.to_string())
-        },
-        Err(e) => {
-            println!("买入交易失败: {:?}", e);
-            Err(Error)
-        }
-    }
+    )
 }
 
-/// Pump协议代币卖出交易
-/// 
+/// Pump卖出指令，账户顺序与Pump程序的sell指令一致
+///
 /// # 参数
-/// 
-/// * `rpc_url` - RPC节点URL
-/// * `private_key` - 用户私钥
-/// * `token_mint` - 代币Mint地址
+///
+/// * `route` - 直接调用Pump程序或经由代理程序
+/// * `payer` - 卖出用户
+/// * `accounts` - 代币的交易地址，用户关联账户应属于`payer`
 /// * `token_amount` - 要卖出的代币数量
 /// * `min_sol_receive` - 最小SOL收益(lamports)
-/// * `slot` - 可选的槽号，用于记录日志
-/// * `cached_blockhash` - 可选的缓存区块哈希，如果提供则不会查询RPC
-pub async fn pump_sell(
-    rpc_url: &str, 
-    private_key: &str, 
-    token_mint: Pubkey, 
-    token_amount: u64, 
-    min_sol_receive: u64,
-    slot: Option<u64>,
-    cached_blockhash: Option<Hash>
-) -> Result<String, Error> {
-    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
-
-    // 构造卖出指令数据
+pub fn pump_sell(route: PumpRoute, payer: &Pubkey, accounts: &MintAccounts, token_amount: u64, min_sol_receive: u64) -> Instruction {
     let mut data = Vec::with_capacity(24);
-    data.extend_from_slice(PUMP_SELL_SELECTOR); // 使用内部选择器 PUMPFUN_SELL_SELECTOR
+    data.extend_from_slice(route.sell_discriminator());
     data.extend_from_slice(&token_amount.to_le_bytes());
     data.extend_from_slice(&min_sol_receive.to_le_bytes());
 
-    let signer = solana_sdk::signature::Keypair::from_base58_string(private_key);
-
-    // 计算Bonding Curve地址
-    let bonding_curve_address =
-        Pubkey::find_program_address(&[BONDING_CURVE_SEED, token_mint.as_ref()], &PUMP_PROGRAM_ID);
-
-    // 用户代币关联账户
-    let associated_user = get_associated_token_address(&signer.pubkey(), &token_mint);
-
-    // Bonding Curve关联代币账户
-    let associated_bonding_curve =
-        get_associated_token_address(&bonding_curve_address.0, &token_mint);
-
-    // 添加优先级费用指令 - 提高优先级费用到200000以确保快速处理
-    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(200000);
-    
-    // 增加最大计算单元以确保交易不会因为计算资源不足而失败
-    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(200000);
-
-    // 构造卖出指令
-    let sell_instruction = Instruction::new_with_bytes(
-        PROXY_PROGRAM,
+    Instruction::new_with_bytes(
+        route.program_id(),
         &data,
         vec![
             AccountMeta::new_readonly(GLOBAL_ACCOUNT, false),
             AccountMeta::new(FEE_RECIPIENT, false),
-            AccountMeta::new_readonly(token_mint, false),
-            AccountMeta::new(bonding_curve_address.0, false),
-            AccountMeta::new(associated_bonding_curve, false),
-            AccountMeta::new(associated_user, false),
-            AccountMeta::new(signer.pubkey(), true),
+            AccountMeta::new_readonly(accounts.mint, false),
+            AccountMeta::new(accounts.bonding_curve, false),
+            AccountMeta::new(accounts.associated_bonding_curve, false),
+            AccountMeta::new(accounts.associated_user, false),
+            AccountMeta::new(*payer, true),
             AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(spl_associated_token_account::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
             AccountMeta::new_readonly(EVENT_AUTHORITY, false),
             AccountMeta::new_readonly(PUMP_PROGRAM_ID, false),
         ],
-    );
+    )
+}
+
+/// 构造Pump买入所需的全部指令（优先级费用、创建ATA、买入）
+///
+/// # 参数
+///
+/// * `route` - 直接调用Pump程序或经由代理程序
+/// * `payer` - 买入用户
+/// * `accounts` - 代币的交易地址，用户关联账户应属于`payer`
+/// * `token_amount` - 要购买的代币数量
+/// * `max_sol_cost` - 最大SOL花费(lamports)
+/// * `compute_unit_price` - 优先级费用 (micro-lamports/CU)
+/// * `compute_unit_limit` - 计算单元上限
+pub fn buy_instructions(
+    route: PumpRoute,
+    payer: &Pubkey,
+    accounts: &MintAccounts,
+    token_amount: u64,
+    max_sol_cost: u64,
+    compute_unit_price: u64,
+    compute_unit_limit: u32,
+) -> Vec<Instruction> {
+    let buy_instruction = pump_buy(route, payer, accounts, token_amount, max_sol_cost);
+
+    // 创建用户的代币关联账户
+    let ata_instruction = route.create_ata_instruction(payer, accounts);
+
+    // 添加优先级费用指令
+    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price);
+    
+    // 计算单元上限按实测用量设置
+    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit);
+
+    vec![compute_unit_price_ix, compute_unit_limit_ix, ata_instruction, buy_instruction]
+}
+
+/// 构造Pump卖出所需的全部指令（优先级费用、卖出）
+///
+/// # 参数
+///
+/// * `route` - 直接调用Pump程序或经由代理程序
+/// * `payer` - 卖出用户
+/// * `accounts` - 代币的交易地址，用户关联账户应属于`payer`
+/// * `token_amount` - 要卖出的代币数量
+/// * `min_sol_receive` - 最小SOL收益(lamports)
+/// * `compute_unit_price` - 优先级费用 (micro-lamports/CU)
+/// * `compute_unit_limit` - 计算单元上限
+pub fn sell_instructions(
+    route: PumpRoute,
+    payer: &Pubkey,
+    accounts: &MintAccounts,
+    token_amount: u64,
+    min_sol_receive: u64,
+    compute_unit_price: u64,
+    compute_unit_limit: u32,
+) -> Vec<Instruction> {
+    // 添加优先级费用指令
+    let compute_unit_price_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price);
+    
+    // 计算单元上限按实测用量设置
+    let compute_unit_limit_ix = solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit);
+
+    let sell_instruction = pump_sell(route, payer, accounts, token_amount, min_sol_receive);
+
+    vec![compute_unit_price_ix, compute_unit_limit_ix, sell_instruction]
+}
+
+/// 通过Jito捆绑发送Pump交易，交易末尾附加小费转账，返回捆绑ID和交易签名
+///
+/// # 参数
+///
+/// * `bundle_sender` - 捆绑发送器
+/// * `signer` - 用户
+/// * `instructions` - 买入或卖出的全部指令
+/// * `tip_lamports` - 捆绑小费(lamports)
+/// * `blockhash` - 交易使用的区块哈希
+pub async fn pump_bundle(
+    bundle_sender: &BundleSender,
+    signer: &Keypair,
+    mut instructions: Vec<Instruction>,
+    tip_lamports: u64,
+    blockhash: Hash,
+) -> Result<(String, Signature), TradeError> {
+    instructions.push(bundle_sender.tip_instruction(&signer.pubkey(), tip_lamports).await?);
 
-    // 获取区块哈希
-    let blockhash = if let Some(hash) = cached_blockhash {
-        // 使用提供的缓存区块哈希
-        if let Some(slot_num) = slot {
-            println!("卖出使用相关slot: {} 和缓存区块哈希", slot_num);
-        } else {
-            println!("卖出使用缓存区块哈希");
-        }
-        hash
-    } else {
-        // 直接获取最新区块哈希
-        if let Some(slot_num) = slot {
-            println!("卖出使用相关slot: {} 和新获取的区块哈希", slot_num);
-        }
-        
-        rpc_client
-            .get_latest_blockhash_with_commitment(CommitmentConfig {
-                commitment: CommitmentLevel::Confirmed,
-            })
-            .await
-            .unwrap()
-            .0
-    };
-
-    // 创建交易
     let transaction = Transaction::new_signed_with_payer(
-        &[compute_unit_price_ix, compute_unit_limit_ix, sell_instruction], // 添加两个优先级指令
+        &instructions,
         Some(&signer.pubkey()),
-        &[&signer],
+        &[signer],
         blockhash,
     );
+    send_bundle_transaction(bundle_sender, &transaction).await
+}
+
+/// 将已签名且包含小费转账的交易作为捆绑发送，返回捆绑ID和交易签名
+///
+/// # 参数
+///
+/// * `bundle_sender` - 捆绑发送器
+/// * `transaction` - 已签名的交易
+pub async fn send_bundle_transaction(
+    bundle_sender: &BundleSender,
+    transaction: &Transaction,
+) -> Result<(String, Signature), TradeError> {
+    let bundle_id = bundle_sender.send_bundle(&[VersionedTransaction::from(transaction.clone())]).await?;
+    Ok((bundle_id, transaction.signatures[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::hash;
+
+    // Anchor指令鉴别器：sha256("global:<指令名>")的前8字节
+    fn anchor_discriminator(name: &str) -> Vec<u8> {
+        hash(format!("global:{}", name).as_bytes()).to_bytes()[..8].to_vec()
+    }
+
+    fn metas(instruction: &Instruction) -> Vec<(Pubkey, bool, bool)> {
+        instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+            .collect()
+    }
+
+    #[test]
+    fn direct_buy_calls_pump_program() {
+        let payer = Pubkey::new_unique();
+        let accounts = MintAccounts::derive(&payer, Pubkey::new_unique());
+        let instruction = pump_buy(PumpRoute::Direct, &payer, &accounts, 1_000, 2_000);
+
+        assert_eq!(instruction.program_id, PUMP_PROGRAM_ID);
+        assert_eq!(&instruction.data[..8], anchor_discriminator("buy").as_slice());
+        assert_eq!(&instruction.data[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[16..], &2_000u64.to_le_bytes());
+        assert_eq!(
+            metas(&instruction),
+            vec![
+                (GLOBAL_ACCOUNT, false, false),
+                (FEE_RECIPIENT, false, true),
+                (accounts.mint, false, false),
+                (accounts.bonding_curve, false, true),
+                (accounts.associated_bonding_curve, false, true),
+                (accounts.associated_user, false, true),
+                (payer, true, true),
+                (system_program::id(), false, false),
+                (spl_token::id(), false, false),
+                (solana_sdk::sysvar::rent::id(), false, false),
+                (EVENT_AUTHORITY, false, false),
+                (PUMP_PROGRAM_ID, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn direct_sell_calls_pump_program() {
+        let payer = Pubkey::new_unique();
+        let accounts = MintAccounts::derive(&payer, Pubkey::new_unique());
+        let instruction = pump_sell(PumpRoute::Direct, &payer, &accounts, 1_000, 2_000);
+
+        assert_eq!(instruction.program_id, PUMP_PROGRAM_ID);
+        assert_eq!(&instruction.data[..8], anchor_discriminator("sell").as_slice());
+        assert_eq!(&instruction.data[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[16..], &2_000u64.to_le_bytes());
+        assert_eq!(
+            metas(&instruction),
+            vec![
+                (GLOBAL_ACCOUNT, false, false),
+                (FEE_RECIPIENT, false, true),
+                (accounts.mint, false, false),
+                (accounts.bonding_curve, false, true),
+                (accounts.associated_bonding_curve, false, true),
+                (accounts.associated_user, false, true),
+                (payer, true, true),
+                (system_program::id(), false, false),
+                (spl_associated_token_account::id(), false, false),
+                (spl_token::id(), false, false),
+                (EVENT_AUTHORITY, false, false),
+                (PUMP_PROGRAM_ID, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn direct_ata_is_created_idempotently() {
+        let payer = Pubkey::new_unique();
+        let accounts = MintAccounts::derive(&payer, Pubkey::new_unique());
+        let instruction = PumpRoute::Direct.create_ata_instruction(&payer, &accounts);
+
+        assert_eq!(instruction.program_id, spl_associated_token_account::id());
+        // 关联代币账户程序的CreateIdempotent指令
+        assert_eq!(instruction.data, vec![1]);
+        assert_eq!(
+            metas(&instruction),
+            vec![
+                (payer, true, true),
+                (accounts.associated_user, false, true),
+                (payer, false, false),
+                (accounts.mint, false, false),
+                (system_program::id(), false, false),
+                (spl_token::id(), false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn direct_is_the_default_route() {
+        assert_eq!(PumpRoute::default(), PumpRoute::Direct);
+        assert_eq!("proxy".parse::<PumpRoute>(), Ok(PumpRoute::Proxy));
+        assert_eq!(" Direct ".parse::<PumpRoute>(), Ok(PumpRoute::Direct));
+        assert!("jupiter".parse::<PumpRoute>().is_err());
+    }
+
+    #[test]
+    fn buy_and_sell_instructions_end_with_the_route_instruction() {
+        let payer = Pubkey::new_unique();
+        let accounts = MintAccounts::derive(&payer, Pubkey::new_unique());
+        for route in [PumpRoute::Direct, PumpRoute::Proxy] {
+            let buy = buy_instructions(route, &payer, &accounts, 1_000, 2_000, 5, 100_000);
+            assert_eq!(buy.len(), 4);
+            assert_eq!(buy[2], route.create_ata_instruction(&payer, &accounts));
+            assert_eq!(buy[3], pump_buy(route, &payer, &accounts, 1_000, 2_000));
 
-    // 发送交易 - 使用最优的交易设置
-    match rpc_client
-        .send_transaction_with_config(
-            &transaction,
-            RpcSendTransactionConfig {
-                skip_preflight: true,
-                preflight_commitment: Some(CommitmentLevel::Processed), // 使用Processed级别以最快返回
-                max_retries: Some(0), // 不重试，因为我们需要立即知道结果
-                ..Default::default()
-            },
-        )
-        .await
-    {
-        Ok(signature) => {
-            println!("卖出交易已提交: {}", signature);
-            Ok(signature.to_string())
-        },
-        Err(e) => {
-            println!("卖出交易失败: {:?}", e);
-            Err(Error)
+            let sell = sell_instructions(route, &payer, &accounts, 1_000, 2_000, 5, 100_000);
+            assert_eq!(sell.len(), 3);
+            assert_eq!(sell[2], pump_sell(route, &payer, &accounts, 1_000, 2_000));
         }
     }
 }
@@ -0,0 +1,425 @@
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use super::sender::TransactionSender;
+use super::error::TradeError;
+use super::simulate::Simulation;
+use crate::confirm::{ConfirmStatus, Confirmation, ConfirmationTracker, Trigger};
+use crate::utils::blockhash_cache::BlockhashCache;
+
+/// 重发和重建策略
+#[derive(Clone, Copy, Debug)]
+pub struct ResendPolicy {
+    // 两次重发同一笔交易的间隔
+    pub interval: Duration,
+    // 区块哈希过期后使用新区块哈希重建交易的最多次数，0表示不重建
+    pub max_rebuilds: u32,
+    // 每次重建提高优先级费用的百分比
+    pub fee_bump_pct: u64,
+    // 优先级费用上限 (micro-lamports/CU)
+    pub max_compute_unit_price: u64,
+}
+
+impl Default for ResendPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            max_rebuilds: 0,
+            fee_bump_pct: 50,
+            max_compute_unit_price: 1_000_000,
+        }
+    }
+}
+
+impl ResendPolicy {
+    // 从环境变量读取重发策略，未设置的使用默认值
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        Self {
+            interval: var("RESEND_INTERVAL_MS").map(Duration::from_millis).unwrap_or(default.interval),
+            max_rebuilds: var("RESEND_MAX_REBUILDS").map(|v| v as u32).unwrap_or(default.max_rebuilds),
+            fee_bump_pct: var("PRIORITY_FEE_BUMP_PCT").unwrap_or(default.fee_bump_pct),
+            max_compute_unit_price: var("MAX_COMPUTE_UNIT_PRICE").unwrap_or(default.max_compute_unit_price),
+        }
+    }
+
+    /// 重建时使用的优先级费用，不超过上限
+    pub fn bump(&self, compute_unit_price: u64) -> u64 {
+        let bumped = compute_unit_price.saturating_add(compute_unit_price.saturating_mul(self.fee_bump_pct) / 100);
+        bumped.min(self.max_compute_unit_price).max(compute_unit_price)
+    }
+}
+
+/// 已提交的交易
+pub struct Submission {
+    // 第一次提交的交易签名，重建后的交易签名见最终的确认结果
+    pub signature: Signature,
+    // 发送前模拟的结果，未启用模拟或发送预签名交易时为None
+    pub simulation: Option<Simulation>,
+    pub confirmation: oneshot::Receiver<Confirmation>,
+}
+
+/// 通过RPC发送交易并在确认前持续重发
+///
+/// 同一笔已签名的交易按间隔通过发送器重复发送到所有节点，直到确认跟踪器给出结果。
+/// 区块哈希过期仍未上链时，可按策略使用新的区块哈希和更高的优先级费用重建交易。
+#[derive(Clone)]
+pub struct Resender {
+    sender: TransactionSender,
+    blockhash_cache: Arc<BlockhashCache>,
+    confirmations: ConfirmationTracker,
+    policy: ResendPolicy,
+}
+
+impl Resender {
+    /// 创建重发器
+    ///
+    /// # 参数
+    ///
+    /// * `sender` - 发送交易的节点
+    /// * `blockhash_cache` - 重建交易时获取新区块哈希
+    /// * `confirmations` - 确认跟踪器
+    /// * `policy` - 重发和重建策略
+    pub fn new(
+        sender: TransactionSender,
+        blockhash_cache: Arc<BlockhashCache>,
+        confirmations: ConfirmationTracker,
+        policy: ResendPolicy,
+    ) -> Self {
+        Self {
+            sender,
+            blockhash_cache,
+            confirmations,
+            policy,
+        }
+    }
+
+    /// 签名并发送交易，返回第一次提交的签名和最终的确认结果
+    ///
+    /// 发送器启用了模拟时先模拟第一笔交易，模拟失败或所有节点都发送失败时返回错误；
+    /// 之后的重发和重建在后台进行，重建的交易不再模拟。
+    ///
+    /// # 参数
+    ///
+    /// * `build` - 按区块哈希和优先级费用构造并签名交易
+    /// * `blockhash` - 第一次提交使用的区块哈希，未知时从RPC获取
+    /// * `compute_unit_price` - 第一次提交的优先级费用 (micro-lamports/CU)
+    /// * `trigger` - 触发交易的slot和时刻
+    pub async fn submit<F>(
+        &self,
+        build: F,
+        blockhash: Option<Hash>,
+        compute_unit_price: u64,
+        trigger: Trigger,
+    ) -> Result<Submission, TradeError>
+    where
+        F: Fn(Hash, u64) -> Transaction + Send + Sync + 'static,
+    {
+        let blockhash = match blockhash {
+            Some(blockhash) => blockhash,
+            None => self.sender.latest_blockhash().await?,
+        };
+        let transaction = Arc::new(build(blockhash, compute_unit_price));
+        let simulation = self.sender.preflight(&transaction).await?;
+        self.send_tracked(transaction, simulation, build, compute_unit_price, trigger).await
+    }
+
+    /// 发送已签名的交易，如预先签好的买入模板，之后与`submit`一样重发和重建
+    ///
+    /// 预签名是为了让发送路径上只有网络写入，因此即使发送器启用了模拟也不模拟，
+    /// 失败原因只能在上链后得知。
+    ///
+    /// # 参数
+    ///
+    /// * `transaction` - 已签名的交易
+    /// * `build` - 区块哈希过期时按新区块哈希和优先级费用重建交易
+    /// * `compute_unit_price` - 已签名交易的优先级费用 (micro-lamports/CU)
+    /// * `trigger` - 触发交易的slot和时刻
+    pub async fn submit_signed<F>(
+        &self,
+        transaction: Arc<Transaction>,
+        build: F,
+        compute_unit_price: u64,
+        trigger: Trigger,
+    ) -> Result<Submission, TradeError>
+    where
+        F: Fn(Hash, u64) -> Transaction + Send + Sync + 'static,
+    {
+        self.send_tracked(transaction, None, build, compute_unit_price, trigger).await
+    }
+
+    // 发送第一笔交易并在后台重发和重建
+    async fn send_tracked<F>(
+        &self,
+        transaction: Arc<Transaction>,
+        simulation: Option<Simulation>,
+        build: F,
+        compute_unit_price: u64,
+        trigger: Trigger,
+    ) -> Result<Submission, TradeError>
+    where
+        F: Fn(Hash, u64) -> Transaction + Send + Sync + 'static,
+    {
+        let signature = transaction.signatures[0];
+        self.sender.send(&transaction).await?;
+
+        let confirmation = self.watch(signature, trigger, transaction.message.recent_blockhash);
+        let (result_tx, result_rx) = oneshot::channel();
+        let resender = self.clone();
+        tokio::spawn(async move {
+            let confirmation = resender
+                .resend_until_resolved(build, transaction, compute_unit_price, trigger, confirmation)
+                .await;
+            let _ = result_tx.send(confirmation);
+        });
+        Ok(Submission {
+            signature,
+            simulation,
+            confirmation: result_rx,
+        })
+    }
+
+    // 重发直到有结果，过期时按策略重建
+    async fn resend_until_resolved<F>(
+        &self,
+        build: F,
+        mut transaction: Arc<Transaction>,
+        mut compute_unit_price: u64,
+        trigger: Trigger,
+        mut confirmation: oneshot::Receiver<Confirmation>,
+    ) -> Confirmation
+    where
+        F: Fn(Hash, u64) -> Transaction,
+    {
+        let mut rebuilds = 0;
+        loop {
+            let mut result = self.resend(&transaction, &mut confirmation).await;
+            let mut rebuild = result.status == ConfirmStatus::Expired && rebuilds < self.policy.max_rebuilds;
+            if rebuild {
+                // 过期按slot判断，交易可能已经处理但状态没有及时查询到，在完整的交易历史中确认没有上链后才重建
+                match self.confirmations.recheck_expired(result.signature, trigger).await {
+                    Ok(None) => {}
+                    Ok(Some(rechecked)) => {
+                        println!("交易 {} 已过期但在交易历史中查询到，不再重建", result.signature);
+                        rebuild = false;
+                        result = rechecked.await.unwrap_or(result);
+                    }
+                    Err(e) => {
+                        println!("查询过期交易 {} 的历史状态失败，不再重建: {}", result.signature, e);
+                        rebuild = false;
+                    }
+                }
+            }
+            let first = self.sender.finish(&result.signature, result.is_landed());
+            if result.is_landed() {
+                if let Some(name) = first {
+                    println!("交易 {} 最先由 {} 提交", result.signature, name);
+                }
+            }
+            if !rebuild {
+                return result;
+            }
+
+            let blockhash = match self.blockhash_cache.get_latest_blockhash().await {
+                Ok(blockhash) => blockhash,
+                Err(e) => {
+                    println!("重建交易获取区块哈希失败: {}", e);
+                    return result;
+                }
+            };
+            rebuilds += 1;
+            compute_unit_price = self.policy.bump(compute_unit_price);
+            transaction = Arc::new(build(blockhash, compute_unit_price));
+            let signature = transaction.signatures[0];
+            println!(
+                "交易 {} 已过期，重建为 {} (第{}次，优先级费用 {})",
+                result.signature, signature, rebuilds, compute_unit_price
+            );
+            if let Err(e) = self.sender.send(&transaction).await {
+                println!("发送重建的交易失败: {}", e);
+            }
+            confirmation = self.watch(signature, trigger, blockhash);
+        }
+    }
+
+    // 按间隔重发同一笔交易，直到确认跟踪器给出结果
+    async fn resend(&self, transaction: &Arc<Transaction>, confirmation: &mut oneshot::Receiver<Confirmation>) -> Confirmation {
+        let signature = transaction.signatures[0];
+        let mut ticker = tokio::time::interval(self.policy.interval);
+        // 第一次发送已经完成
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                result = &mut *confirmation => {
+                    return result.unwrap_or(Confirmation {
+                        signature,
+                        status: ConfirmStatus::Expired,
+                        landed_slot: None,
+                        latency: Duration::ZERO,
+                        slots: None,
+                    });
+                }
+                _ = ticker.tick() => {
+                    let _ = self.sender.send(transaction).await;
+                }
+            }
+        }
+    }
+
+    fn watch(&self, signature: Signature, trigger: Trigger, blockhash: Hash) -> oneshot::Receiver<Confirmation> {
+        let blockhash_slot = self.blockhash_cache.blockhash_slot(&blockhash);
+        self.confirmations.watch(signature, trigger, blockhash_slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::mock::MockRpcServer;
+    use crate::transaction::sender::SendEndpoint;
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+
+    // 超过区块哈希有效期和确认延迟的slot数
+    const MAX_PROCESSING_AGE_SLOTS: u64 = solana_sdk::clock::MAX_PROCESSING_AGE as u64 + crate::confirm::CONFIRMATION_LAG_SLOTS + 1;
+
+    async fn resender(mock: &MockRpcServer, policy: ResendPolicy) -> Resender {
+        let (url, _) = mock.clone().serve().await.unwrap();
+        Resender::new(
+            TransactionSender::new(&[SendEndpoint::parse(&url)]),
+            Arc::new(BlockhashCache::new(&url, 0)),
+            ConfirmationTracker::new(url),
+            policy,
+        )
+    }
+
+    fn build(payer: Keypair) -> impl Fn(Hash, u64) -> Transaction + Send + Sync + 'static {
+        move |blockhash, compute_unit_price| {
+            let instructions = [
+                ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price),
+                system_instruction::transfer(&payer.pubkey(), &payer.pubkey(), 1),
+            ];
+            Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &[&payer], blockhash)
+        }
+    }
+
+    fn policy(max_rebuilds: u32) -> ResendPolicy {
+        ResendPolicy {
+            interval: Duration::from_millis(20),
+            max_rebuilds,
+            ..ResendPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn resends_until_a_send_gets_through() {
+        let mock = MockRpcServer::new(0.8);
+        let resender = resender(&mock, policy(0)).await;
+        let submission = resender
+            .submit(build(Keypair::new()), None, 1_000, Trigger::now(mock.slot()))
+            .await
+            .unwrap();
+
+        let confirmation = tokio::time::timeout(Duration::from_secs(10), submission.confirmation)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(confirmation.is_landed());
+        assert_eq!(confirmation.signature, submission.signature);
+        assert_eq!(mock.landed_slot(&submission.signature), confirmation.landed_slot);
+        assert!(mock.dropped() > 0);
+        assert!(mock.sends() > mock.dropped());
+    }
+
+    #[tokio::test]
+    async fn signed_transaction_is_sent_without_simulation() {
+        let mock = MockRpcServer::new(0.0);
+        let (url, _) = mock.clone().serve().await.unwrap();
+        let resender = Resender::new(
+            TransactionSender::with_simulator(&[SendEndpoint::parse(&url)], &url),
+            Arc::new(BlockhashCache::new(&url, 0)),
+            ConfirmationTracker::new(url),
+            policy(0),
+        );
+        let submission = resender
+            .submit(build(Keypair::new()), None, 1_000, Trigger::now(mock.slot()))
+            .await
+            .unwrap();
+        assert!(submission.simulation.is_some());
+        assert_eq!(mock.simulations(), 1);
+
+        let build = build(Keypair::new());
+        let transaction = Arc::new(build(Hash::new_unique(), 1_000));
+        let submission = resender
+            .submit_signed(transaction, build, 1_000, Trigger::now(mock.slot()))
+            .await
+            .unwrap();
+        assert!(submission.simulation.is_none());
+        assert_eq!(mock.simulations(), 1);
+    }
+
+    #[tokio::test]
+    async fn rebuilds_after_expiry_from_rpc_slot() {
+        // 没有数据流，区块哈希是否过期只能按RPC的slot判断
+        let mock = MockRpcServer::new(1.0);
+        let resender = resender(&mock, policy(1)).await;
+        let submission = resender
+            .submit(build(Keypair::new()), None, 1_000, Trigger::now(mock.slot()))
+            .await
+            .unwrap();
+        let mut confirmation = submission.confirmation;
+
+        mock.advance_slots(MAX_PROCESSING_AGE_SLOTS);
+        let sends = mock.sends();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(confirmation.try_recv().is_err(), "重建的交易应继续等待");
+        assert!(mock.sends() > sends);
+
+        mock.advance_slots(MAX_PROCESSING_AGE_SLOTS);
+        let result = tokio::time::timeout(Duration::from_secs(10), confirmation).await.unwrap().unwrap();
+        assert_eq!(result.status, ConfirmStatus::Expired);
+        assert_ne!(result.signature, submission.signature);
+        assert_eq!(mock.dropped(), mock.sends());
+    }
+
+    #[tokio::test]
+    async fn expired_transaction_found_in_history_is_not_rebuilt() {
+        // 交易在过期前处理，但只有查询完整交易历史才能查到
+        let mock = MockRpcServer::new(1.0);
+        let resender = resender(&mock, policy(1)).await;
+        let submission = resender
+            .submit(build(Keypair::new()), None, 1_000, Trigger::now(mock.slot()))
+            .await
+            .unwrap();
+        let landed_slot = mock.land_in_history(submission.signature);
+
+        mock.advance_slots(MAX_PROCESSING_AGE_SLOTS);
+        let result = tokio::time::timeout(Duration::from_secs(10), submission.confirmation)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_landed());
+        assert_eq!(result.signature, submission.signature);
+        assert_eq!(result.landed_slot, Some(landed_slot));
+        assert_eq!(mock.landed_slot(&submission.signature), None);
+    }
+
+    #[test]
+    fn bump_is_capped() {
+        let policy = ResendPolicy {
+            fee_bump_pct: 50,
+            max_compute_unit_price: 1_200,
+            ..ResendPolicy::default()
+        };
+        assert_eq!(policy.bump(600), 900);
+        assert_eq!(policy.bump(1_000), 1_200);
+        assert_eq!(policy.bump(2_000), 2_000);
+    }
+}
@@ -0,0 +1,394 @@
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+use super::error::TradeError;
+use super::send_config;
+use super::simulate::{simulate, Simulation, SimulationError};
+
+/// 最多保留的交易记录数量，超过后丢弃过期的记录
+const MAX_TRACKED_SIGNATURES: usize = 4096;
+/// 交易记录的保留时间，超过区块哈希的有效期
+const FIRST_ACK_TTL: Duration = Duration::from_secs(120);
+
+/// 发送节点，RPC节点、区块引擎的JSON-RPC地址或质押中继
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SendEndpoint {
+    pub name: String,
+    pub url: String,
+}
+
+impl SendEndpoint {
+    /// 解析`名称=地址`或只有地址的配置，未指定名称时使用地址的主机名
+    ///
+    /// # 参数
+    ///
+    /// * `spec` - 节点配置，如`jito=https://mainnet.block-engine.jito.wtf/api/v1/transactions`
+    pub fn parse(spec: &str) -> Self {
+        let spec = spec.trim();
+        match spec.split_once('=') {
+            Some((name, url)) if !name.contains("://") => Self {
+                name: name.trim().to_string(),
+                url: url.trim().to_string(),
+            },
+            _ => Self {
+                name: host(spec).to_string(),
+                url: spec.to_string(),
+            },
+        }
+    }
+
+    /// 解析逗号分隔的节点列表
+    pub fn parse_list(specs: &str) -> Vec<Self> {
+        specs
+            .split(',')
+            .filter(|spec| !spec.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+}
+
+// 地址中的主机名
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+/// 单个发送节点的统计
+#[derive(Clone, Debug, Default)]
+pub struct EndpointStats {
+    pub name: String,
+    pub sends: u64,
+    pub errors: u64,
+    // 成功提交的响应时间之和
+    pub ack_latency: Duration,
+    // 最先成功提交交易的次数
+    pub first_acks: u64,
+    // 最先成功提交且交易最终上链的次数，按提交响应计，不代表上链的正是该节点转发的副本
+    pub acked_first_landed: u64,
+}
+
+impl EndpointStats {
+    /// 成功提交的平均响应时间 (毫秒)
+    pub fn avg_ack_ms(&self) -> f64 {
+        let acks = self.sends - self.errors;
+        if acks == 0 {
+            return 0.0;
+        }
+        self.ack_latency.as_secs_f64() * 1000.0 / acks as f64
+    }
+}
+
+impl fmt::Display for EndpointStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<16} 发送 {:>6}  失败 {:>5}  平均响应 {:>8.1}ms  最先提交 {:>5}  最先提交且上链 {:>5}",
+            self.name,
+            self.sends,
+            self.errors,
+            self.avg_ack_ms(),
+            self.first_acks,
+            self.acked_first_landed
+        )
+    }
+}
+
+/// 并行发送交易到多个节点
+///
+/// 每个节点使用长期保持的RPC连接。同一笔交易同时发送到所有节点，记录每个节点的响应时间，
+/// 以及每笔交易最先成功提交的节点；交易上链后计入该节点的`acked_first_landed`。
+/// 同一笔交易的所有副本签名相同，无法得知上链的是哪个节点转发的，该计数只反映节点的响应速度。
+/// 设置了模拟节点时，调用方可在发送前通过`preflight`模拟交易。
+#[derive(Clone)]
+pub struct TransactionSender {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    endpoints: Vec<Endpoint>,
+    // 发送前模拟交易的节点，发送节点可能是不支持simulateTransaction的中继
+    simulator: Option<RpcClient>,
+    // 每笔交易最先成功提交的节点
+    first_acks: StdMutex<HashMap<Signature, FirstAck>>,
+}
+
+struct FirstAck {
+    endpoint: usize,
+    at: Instant,
+    // 已结束记录，之后的重发不再计入
+    finished: bool,
+}
+
+struct Endpoint {
+    client: RpcClient,
+    stats: StdMutex<EndpointStats>,
+}
+
+impl TransactionSender {
+    /// 创建发送器，为每个节点建立RPC连接
+    ///
+    /// # 参数
+    ///
+    /// * `endpoints` - 发送节点，至少一个
+    pub fn new(endpoints: &[SendEndpoint]) -> Self {
+        Self::build(endpoints, None)
+    }
+
+    /// 创建发送前模拟交易的发送器
+    ///
+    /// # 参数
+    ///
+    /// * `endpoints` - 发送节点，至少一个
+    /// * `simulator_url` - 模拟交易的RPC节点
+    pub fn with_simulator(endpoints: &[SendEndpoint], simulator_url: &str) -> Self {
+        Self::build(endpoints, Some(RpcClient::new(simulator_url.to_string())))
+    }
+
+    fn build(endpoints: &[SendEndpoint], simulator: Option<RpcClient>) -> Self {
+        let endpoints = endpoints
+            .iter()
+            .map(|endpoint| Endpoint {
+                client: RpcClient::new(endpoint.url.clone()),
+                stats: StdMutex::new(EndpointStats {
+                    name: endpoint.name.clone(),
+                    ..EndpointStats::default()
+                }),
+            })
+            .collect();
+        Self {
+            inner: Arc::new(Inner {
+                endpoints,
+                simulator,
+                first_acks: StdMutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// 设置了模拟节点时模拟交易，未设置时返回`Ok(None)`
+    ///
+    /// 模拟失败时返回解析后的原因，调用方不应再发送该交易。
+    pub async fn preflight(&self, transaction: &Transaction) -> Result<Option<Simulation>, SimulationError> {
+        match &self.inner.simulator {
+            Some(simulator) => simulate(simulator, transaction).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// 从第一个节点获取最新区块哈希
+    pub async fn latest_blockhash(&self) -> Result<Hash, TradeError> {
+        let endpoint = self
+            .inner
+            .endpoints
+            .first()
+            .ok_or_else(|| TradeError::BlockhashUnavailable("没有配置发送节点".to_string()))?;
+        endpoint
+            .client
+            .get_latest_blockhash()
+            .await
+            .map_err(|e| TradeError::BlockhashUnavailable(e.to_string()))
+    }
+
+    /// 并行发送交易到所有节点，任一节点成功提交即返回，其余节点在后台继续发送
+    ///
+    /// 所有节点都失败时返回配置中第一个节点的错误。
+    pub async fn send(&self, transaction: &Arc<Transaction>) -> Result<Signature, TradeError> {
+        let signature = transaction.signatures[0];
+        let mut sends = JoinSet::new();
+        for index in 0..self.inner.endpoints.len() {
+            let inner = Arc::clone(&self.inner);
+            let transaction = Arc::clone(transaction);
+            sends.spawn(async move { inner.send_to(index, &transaction).await });
+        }
+        let mut errors = Vec::new();
+        while let Some(result) = sends.join_next().await {
+            match result {
+                Ok(Ok(())) => {
+                    sends.detach_all();
+                    return Ok(signature);
+                }
+                Ok(Err(e)) => errors.push(e),
+                Err(e) => errors.push((usize::MAX, TradeError::RpcTransport(e.to_string()))),
+            }
+        }
+        match errors.into_iter().min_by_key(|(index, _)| *index) {
+            Some((_, e)) => Err(e),
+            None => Err(TradeError::RpcTransport("没有配置发送节点".to_string())),
+        }
+    }
+
+    /// 结束对交易的记录，返回最先成功提交该交易的节点名称
+    ///
+    /// # 参数
+    ///
+    /// * `signature` - 交易签名
+    /// * `landed` - 交易是否上链，上链时计入最先提交节点的`acked_first_landed`
+    pub fn finish(&self, signature: &Signature, landed: bool) -> Option<String> {
+        let endpoint = {
+            let mut first_acks = self.inner.first_acks.lock().unwrap();
+            let first_ack = first_acks.get_mut(signature).filter(|first_ack| !first_ack.finished)?;
+            first_ack.finished = true;
+            first_ack.endpoint
+        };
+        let mut stats = self.inner.endpoints[endpoint].stats.lock().unwrap();
+        if landed {
+            stats.acked_first_landed += 1;
+        }
+        Some(stats.name.clone())
+    }
+
+    /// 各节点的统计，按最先提交且上链的次数和平均响应时间排序
+    pub fn stats(&self) -> Vec<EndpointStats> {
+        let mut stats: Vec<EndpointStats> = self
+            .inner
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.stats.lock().unwrap().clone())
+            .collect();
+        stats.sort_by(|a, b| {
+            b.acked_first_landed
+                .cmp(&a.acked_first_landed)
+                .then(a.avg_ack_ms().total_cmp(&b.avg_ack_ms()))
+        });
+        stats
+    }
+
+    /// 所有节点的发送次数
+    pub fn total_sends(&self) -> u64 {
+        self.inner
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.stats.lock().unwrap().sends)
+            .sum()
+    }
+}
+
+impl Inner {
+    async fn send_to(&self, index: usize, transaction: &Transaction) -> Result<(), (usize, TradeError)> {
+        let endpoint = &self.endpoints[index];
+        let start = Instant::now();
+        let result = endpoint.client.send_transaction_with_config(transaction, send_config()).await;
+        let elapsed = start.elapsed();
+
+        let mut stats = endpoint.stats.lock().unwrap();
+        stats.sends += 1;
+        match result {
+            Ok(signature) => {
+                stats.ack_latency += elapsed;
+                if self.record_first_ack(signature, index) {
+                    stats.first_acks += 1;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                stats.errors += 1;
+                Err((index, TradeError::from(e)))
+            }
+        }
+    }
+
+    // 记录最先成功提交交易的节点，已有记录时返回false
+    fn record_first_ack(&self, signature: Signature, endpoint: usize) -> bool {
+        let mut first_acks = self.first_acks.lock().unwrap();
+        if first_acks.contains_key(&signature) {
+            return false;
+        }
+        if first_acks.len() >= MAX_TRACKED_SIGNATURES {
+            first_acks.retain(|_, first_ack| first_ack.at.elapsed() < FIRST_ACK_TTL);
+        }
+        if first_acks.len() >= MAX_TRACKED_SIGNATURES {
+            first_acks.retain(|_, first_ack| !first_ack.finished);
+        }
+        first_acks.insert(
+            signature,
+            FirstAck {
+                endpoint,
+                at: Instant::now(),
+                finished: false,
+            },
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::mock::MockRpcServer;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_transaction;
+
+    fn transfer() -> Arc<Transaction> {
+        let payer = Keypair::new();
+        Arc::new(system_transaction::transfer(&payer, &payer.pubkey(), 1, Hash::new_unique()))
+    }
+
+    async fn endpoint(name: &str, mock: &MockRpcServer) -> SendEndpoint {
+        let (url, _) = mock.clone().serve().await.unwrap();
+        SendEndpoint::parse(&format!("{}={}", name, url))
+    }
+
+    #[test]
+    fn parses_named_and_bare_endpoints() {
+        let endpoints = SendEndpoint::parse_list(" jito=https://mainnet.block-engine.jito.wtf/api/v1/transactions, http://127.0.0.1:8899/?key=1 ,");
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints[0].name, "jito");
+        assert_eq!(endpoints[0].url, "https://mainnet.block-engine.jito.wtf/api/v1/transactions");
+        assert_eq!(endpoints[1].name, "127.0.0.1:8899");
+        assert_eq!(endpoints[1].url, "http://127.0.0.1:8899/?key=1");
+    }
+
+    #[tokio::test]
+    async fn fans_out_to_every_endpoint() {
+        let (first, second) = (MockRpcServer::new(0.0), MockRpcServer::new(0.0));
+        let sender = TransactionSender::new(&[endpoint("a", &first).await, endpoint("b", &second).await]);
+        let transaction = transfer();
+        let signature = sender.send(&transaction).await.unwrap();
+        assert_eq!(signature, transaction.signatures[0]);
+
+        // 其余节点在后台继续发送
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(first.sends(), 1);
+        assert_eq!(second.sends(), 1);
+        assert_eq!(sender.total_sends(), 2);
+        let stats = sender.stats();
+        assert_eq!(stats.iter().map(|stats| stats.first_acks).sum::<u64>(), 1);
+        assert!(stats.iter().all(|stats| stats.errors == 0 && stats.avg_ack_ms() > 0.0));
+    }
+
+    #[tokio::test]
+    async fn failed_endpoint_is_counted_and_first_ack_credited_once() {
+        let mock = MockRpcServer::new(0.0);
+        let sender = TransactionSender::new(&[SendEndpoint::parse("down=http://127.0.0.1:1"), endpoint("up", &mock).await]);
+        let transaction = transfer();
+        sender.send(&transaction).await.unwrap();
+        // 重发不会改变最先提交的节点
+        sender.send(&transaction).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let signature = transaction.signatures[0];
+        assert_eq!(sender.finish(&signature, true).as_deref(), Some("up"));
+        assert_eq!(sender.finish(&signature, true), None);
+
+        let stats = sender.stats();
+        assert_eq!(stats[0].name, "up");
+        assert_eq!((stats[0].sends, stats[0].errors, stats[0].first_acks, stats[0].acked_first_landed), (2, 0, 1, 1));
+        assert_eq!(stats[1].name, "down");
+        assert_eq!((stats[1].sends, stats[1].errors, stats[1].first_acks, stats[1].acked_first_landed), (2, 2, 0, 0));
+        assert_eq!(stats[1].avg_ack_ms(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn fails_when_every_endpoint_fails() {
+        let sender = TransactionSender::new(&[SendEndpoint::parse("down=http://127.0.0.1:1")]);
+        let transaction = transfer();
+        assert!(sender.send(&transaction).await.is_err());
+        assert_eq!(sender.finish(&transaction.signatures[0], false), None);
+    }
+}
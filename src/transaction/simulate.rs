@@ -0,0 +1,375 @@
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::config::RpcSimulateTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use std::fmt;
+
+use super::{PROXY_PROGRAM, PUMP_PROGRAM_ID};
+
+// Anchor用户自定义错误码的起始值，小于该值的是Anchor框架的错误
+const ANCHOR_ERROR_CODE_OFFSET: u32 = 6000;
+
+/// Pump程序的错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PumpError {
+    /// 6000 没有权限执行该指令
+    NotAuthorized,
+    /// 6001 程序已初始化
+    AlreadyInitialized,
+    /// 6002 买入需要的SOL超过最大花费
+    TooMuchSolRequired,
+    /// 6003 卖出得到的SOL低于最少接收
+    TooLittleSolReceived,
+    /// 6004 Mint与内盘曲线不符
+    MintDoesNotMatchBondingCurve,
+    /// 6005 内盘已完成，流动性已迁移
+    BondingCurveComplete,
+    /// 6006 内盘尚未完成
+    BondingCurveNotComplete,
+    /// 6007 程序未初始化
+    NotInitialized,
+    /// Anchor框架的错误，如账户约束不满足
+    Anchor { code: u32, name: Option<String> },
+    /// 未知的程序错误
+    Other { code: u32, name: Option<String> },
+}
+
+impl PumpError {
+    /// 按错误码解析，日志中的错误名称用于未知的错误码
+    ///
+    /// # 参数
+    ///
+    /// * `code` - 自定义错误码
+    /// * `name` - 日志中AnchorError的错误名称
+    pub fn from_code(code: u32, name: Option<String>) -> Self {
+        match code {
+            6000 => PumpError::NotAuthorized,
+            6001 => PumpError::AlreadyInitialized,
+            6002 => PumpError::TooMuchSolRequired,
+            6003 => PumpError::TooLittleSolReceived,
+            6004 => PumpError::MintDoesNotMatchBondingCurve,
+            6005 => PumpError::BondingCurveComplete,
+            6006 => PumpError::BondingCurveNotComplete,
+            6007 => PumpError::NotInitialized,
+            code if code < ANCHOR_ERROR_CODE_OFFSET => PumpError::Anchor { code, name },
+            code => PumpError::Other { code, name },
+        }
+    }
+}
+
+impl fmt::Display for PumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PumpError::NotAuthorized => write!(f, "没有权限执行该指令"),
+            PumpError::AlreadyInitialized => write!(f, "程序已初始化"),
+            PumpError::TooMuchSolRequired => write!(f, "滑点超出: 买入需要的SOL超过最大花费"),
+            PumpError::TooLittleSolReceived => write!(f, "滑点超出: 卖出得到的SOL低于最少接收"),
+            PumpError::MintDoesNotMatchBondingCurve => write!(f, "Mint与内盘曲线不符"),
+            PumpError::BondingCurveComplete => write!(f, "内盘已完成，流动性已迁移"),
+            PumpError::BondingCurveNotComplete => write!(f, "内盘尚未完成"),
+            PumpError::NotInitialized => write!(f, "程序未初始化"),
+            PumpError::Anchor { code, name } => write!(f, "Anchor错误 {} ({})", code, name.as_deref().unwrap_or("未知")),
+            PumpError::Other { code, name } => write!(f, "程序错误 {} ({})", code, name.as_deref().unwrap_or("未知")),
+        }
+    }
+}
+
+/// 模拟交易的失败原因
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimulationError {
+    /// 模拟请求失败
+    Rpc(String),
+    /// Pump指令执行失败
+    Pump {
+        instruction: u8,
+        error: PumpError,
+        units_consumed: Option<u64>,
+    },
+    /// 其他指令执行失败
+    Instruction {
+        instruction: u8,
+        error: String,
+        units_consumed: Option<u64>,
+    },
+    /// 交易本身无效，如区块哈希不存在、余额不足以支付手续费
+    Transaction(String),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::Rpc(e) => write!(f, "模拟请求失败: {}", e),
+            SimulationError::Pump { instruction, error, .. } => write!(f, "第{}条指令失败: {}", instruction, error),
+            SimulationError::Instruction { instruction, error, .. } => write!(f, "第{}条指令失败: {}", instruction, error),
+            SimulationError::Transaction(e) => write!(f, "交易无效: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+/// 模拟成功的结果
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Simulation {
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+/// 模拟交易，失败时按Pump程序的错误码和日志解析原因
+///
+/// 交易可能使用数据流推导的区块哈希，模拟节点还没有看到该区块时会报告BlockhashNotFound，
+/// 因此模拟时由节点替换为最新的区块哈希，只检查指令能否执行。
+///
+/// # 参数
+///
+/// * `rpc_client` - 支持simulateTransaction的RPC节点
+/// * `transaction` - 已签名的交易
+pub async fn simulate(rpc_client: &RpcClient, transaction: &Transaction) -> Result<Simulation, SimulationError> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    };
+    let result = rpc_client
+        .simulate_transaction_with_config(transaction, config)
+        .await
+        .map_err(|e| SimulationError::Rpc(e.to_string()))?
+        .value;
+
+    let logs = result.logs.unwrap_or_default();
+    let units_consumed = result.units_consumed.or_else(|| units_from_logs(&logs));
+    match result.err {
+        None => Ok(Simulation { units_consumed, logs }),
+        Some(err) => Err(decode_error(transaction, err, &logs, units_consumed)),
+    }
+}
+
+// 按失败的指令和日志解析错误
+fn decode_error(transaction: &Transaction, err: TransactionError, logs: &[String], units_consumed: Option<u64>) -> SimulationError {
+    let (instruction, error) = match err {
+        TransactionError::InstructionError(instruction, error) => (instruction, error),
+        err => return SimulationError::Transaction(err.to_string()),
+    };
+    let message = &transaction.message;
+    let program_id = message
+        .instructions
+        .get(instruction as usize)
+        .and_then(|ix| message.account_keys.get(ix.program_id_index as usize));
+    // 买入和卖出经由代理程序调用Pump程序，Pump的错误码原样返回
+    let is_pump = matches!(program_id, Some(id) if *id == PUMP_PROGRAM_ID || *id == PROXY_PROGRAM);
+    match error {
+        InstructionError::Custom(code) if is_pump => SimulationError::Pump {
+            instruction,
+            error: PumpError::from_code(code, anchor_error_name(logs, code)),
+            units_consumed,
+        },
+        error => SimulationError::Instruction {
+            instruction,
+            error: error.to_string(),
+            units_consumed,
+        },
+    }
+}
+
+/// 日志中AnchorError的错误名称
+///
+/// 日志格式: `Program log: AnchorError ... Error Code: TooMuchSolRequired. Error Number: 6002. Error Message: ...`
+pub fn anchor_error_name(logs: &[String], code: u32) -> Option<String> {
+    let number = format!("Error Number: {}.", code);
+    logs.iter()
+        .filter(|line| line.contains("AnchorError") && line.contains(&number))
+        .find_map(|line| {
+            let start = line.find("Error Code: ")? + "Error Code: ".len();
+            let name = &line[start..];
+            Some(name[..name.find('.')?].to_string())
+        })
+}
+
+/// 按日志统计顶层指令消耗的计算单元
+///
+/// 日志格式: `Program <id> invoke [1]`、`Program <id> consumed 12345 of 200000 compute units`
+pub fn units_from_logs(logs: &[String]) -> Option<u64> {
+    let mut depth = 0usize;
+    let mut total = None;
+    for line in logs {
+        let rest = match line.strip_prefix("Program ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        if rest.contains(" invoke [") {
+            depth += 1;
+        } else if rest.ends_with(" success") || rest.contains(" failed: ") {
+            depth = depth.saturating_sub(1);
+        } else if let Some((_, consumed)) = rest.split_once(" consumed ") {
+            // 内层调用的用量已计入外层
+            if depth == 1 {
+                let units = consumed.split_whitespace().next().and_then(|units| units.parse::<u64>().ok());
+                if let Some(units) = units {
+                    total = Some(total.unwrap_or(0) + units);
+                }
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::mock::MockRpcServer;
+    use serde_json::json;
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+
+    const PUMP_LOGS: [&str; 6] = [
+        "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+        "Program ComputeBudget111111111111111111111111111111 success",
+        "Program 6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P invoke [1]",
+        "Program log: AnchorError thrown in programs/pump/src/lib.rs:175. Error Code: TooMuchSolRequired. Error Number: 6002. Error Message: slippage: Too much SOL required to buy the given amount of tokens..",
+        "Program 6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P consumed 40850 of 199700 compute units",
+        "Program 6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P failed: custom program error: 0x1772",
+    ];
+
+    // 计算单元价格、计算单元上限、转账和Pump指令，Pump指令位于第3条
+    fn transaction(program_id: Pubkey) -> Transaction {
+        let payer = Keypair::new();
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_price(1_000),
+            ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+            system_instruction::transfer(&payer.pubkey(), &payer.pubkey(), 1),
+            Instruction::new_with_bytes(program_id, &[0; 8], vec![]),
+        ];
+        Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &[&payer], Hash::new_unique())
+    }
+
+    async fn simulate_with(value: serde_json::Value, transaction: &Transaction) -> Result<Simulation, SimulationError> {
+        let mock = MockRpcServer::new(0.0);
+        mock.set_simulation(value);
+        let (url, _) = mock.clone().serve().await.unwrap();
+        let result = simulate(&RpcClient::new(url), transaction).await;
+        assert_eq!(mock.simulations(), 1);
+        result
+    }
+
+    #[tokio::test]
+    async fn decodes_pump_slippage_error() {
+        let result = simulate_with(
+            json!({
+                "err": { "InstructionError": [3, { "Custom": 6002 }] },
+                "logs": PUMP_LOGS,
+                "unitsConsumed": 41000,
+            }),
+            &transaction(PUMP_PROGRAM_ID),
+        )
+        .await;
+
+        assert_eq!(
+            result,
+            Err(SimulationError::Pump {
+                instruction: 3,
+                error: PumpError::TooMuchSolRequired,
+                units_consumed: Some(41000),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_errors_decode_as_pump_with_units_from_logs() {
+        let result = simulate_with(
+            json!({
+                "err": { "InstructionError": [3, { "Custom": 6005 }] },
+                "logs": PUMP_LOGS,
+            }),
+            &transaction(PROXY_PROGRAM),
+        )
+        .await;
+
+        // 没有unitsConsumed时按日志统计，计算预算指令没有consumed日志
+        assert_eq!(
+            result,
+            Err(SimulationError::Pump {
+                instruction: 3,
+                error: PumpError::BondingCurveComplete,
+                units_consumed: Some(40850),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_code_takes_name_from_logs() {
+        let logs = ["Program log: AnchorError occurred. Error Code: NewError. Error Number: 6042. Error Message: new."];
+        let result = simulate_with(
+            json!({ "err": { "InstructionError": [3, { "Custom": 6042 }] }, "logs": logs }),
+            &transaction(PUMP_PROGRAM_ID),
+        )
+        .await;
+
+        assert_eq!(
+            result,
+            Err(SimulationError::Pump {
+                instruction: 3,
+                error: PumpError::Other { code: 6042, name: Some("NewError".to_string()) },
+                units_consumed: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn other_program_errors_are_not_pump_errors() {
+        let result = simulate_with(
+            json!({ "err": { "InstructionError": [2, { "Custom": 1 }] }, "logs": [] }),
+            &transaction(PUMP_PROGRAM_ID),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(SimulationError::Instruction { instruction: 2, units_consumed: None, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn transaction_errors_are_reported_as_invalid() {
+        let result = simulate_with(json!({ "err": "BlockhashNotFound", "logs": [] }), &transaction(PUMP_PROGRAM_ID)).await;
+
+        assert_eq!(result, Err(SimulationError::Transaction(TransactionError::BlockhashNotFound.to_string())));
+    }
+
+    #[tokio::test]
+    async fn blockhash_unknown_to_simulator_is_replaced() {
+        // 模拟节点只认识自己的最新区块哈希，交易使用数据流中更新的区块哈希
+        let transaction = transaction(PUMP_PROGRAM_ID);
+        let mock = MockRpcServer::new(0.0);
+        let (url, _) = mock.clone().serve().await.unwrap();
+        let rpc_client = RpcClient::new(url);
+
+        let result = simulate(&rpc_client, &transaction).await.unwrap();
+        assert_eq!(result.units_consumed, Some(150));
+
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            ..Default::default()
+        };
+        let value = rpc_client.simulate_transaction_with_config(&transaction, config).await.unwrap().value;
+        assert_eq!(value.err, Some(TransactionError::BlockhashNotFound));
+    }
+
+    #[tokio::test]
+    async fn success_returns_units_and_logs() {
+        let result = simulate_with(
+            json!({ "err": null, "logs": &PUMP_LOGS[..2], "unitsConsumed": 150 }),
+            &transaction(PUMP_PROGRAM_ID),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.units_consumed, Some(150));
+        assert_eq!(result.logs.len(), 2);
+    }
+}
@@ -0,0 +1,192 @@
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use spl_associated_token_account::get_associated_token_address;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use super::sender::TransactionSender;
+use super::PumpRoute;
+use crate::bonding_curve::bonding_curve_pda;
+
+/// 最多缓存的代币数量，超过后丢弃最早加入的代币
+const MAX_CACHED_MINTS: usize = 4096;
+
+/// 交易一个代币需要的地址
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MintAccounts {
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    // 用户的代币关联账户
+    pub associated_user: Pubkey,
+}
+
+impl MintAccounts {
+    /// 按种子推导全部地址，需要三次PDA搜索
+    ///
+    /// # 参数
+    ///
+    /// * `user` - 交易用户
+    /// * `mint` - 代币Mint地址
+    pub fn derive(user: &Pubkey, mint: Pubkey) -> Self {
+        let bonding_curve = bonding_curve_pda(&mint);
+        Self {
+            mint,
+            bonding_curve,
+            associated_bonding_curve: get_associated_token_address(&bonding_curve, &mint),
+            associated_user: get_associated_token_address(user, &mint),
+        }
+    }
+}
+
+/// 按代币缓存交易地址
+///
+/// 数据流中出现Create指令时直接使用指令中的曲线地址，只推导用户的关联账户，
+/// 之后买入和卖出该代币时不再搜索PDA。
+#[derive(Clone)]
+pub struct MintAccountCache {
+    user: Pubkey,
+    inner: Arc<StdMutex<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    accounts: HashMap<Pubkey, MintAccounts>,
+    // 加入顺序，用于丢弃最早的代币
+    order: VecDeque<Pubkey>,
+}
+
+impl MintAccountCache {
+    /// 创建缓存
+    ///
+    /// # 参数
+    ///
+    /// * `user` - 交易用户，用于推导用户的关联账户
+    pub fn new(user: Pubkey) -> Self {
+        Self {
+            user,
+            inner: Arc::new(StdMutex::new(CacheState::default())),
+        }
+    }
+
+    /// 记录数据流中新创建的代币
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 代币Mint地址
+    /// * `bonding_curve` - Create指令中的内盘曲线账户
+    /// * `associated_bonding_curve` - Create指令中的内盘曲线关联代币账户
+    pub fn insert_created(&self, mint: Pubkey, bonding_curve: Pubkey, associated_bonding_curve: Pubkey) {
+        // 在锁外推导，不阻塞交易路径上的查询
+        let accounts = MintAccounts {
+            mint,
+            bonding_curve,
+            associated_bonding_curve,
+            associated_user: get_associated_token_address(&self.user, &mint),
+        };
+        self.insert(accounts);
+    }
+
+    /// 代币的交易地址，未缓存时推导并加入缓存
+    pub fn get(&self, mint: &Pubkey) -> MintAccounts {
+        if let Some(accounts) = self.inner.lock().unwrap().accounts.get(mint) {
+            return *accounts;
+        }
+        let accounts = MintAccounts::derive(&self.user, *mint);
+        self.insert(accounts);
+        accounts
+    }
+
+    /// 代币的内盘曲线PDA，用于校验数据流中的指令账户
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 代币Mint地址
+    pub fn bonding_curve(&self, mint: &Pubkey) -> Pubkey {
+        self.get(mint).bonding_curve
+    }
+
+    fn insert(&self, accounts: MintAccounts) {
+        let mut state = self.inner.lock().unwrap();
+        if state.accounts.insert(accounts.mint, accounts).is_some() {
+            return;
+        }
+        state.order.push_back(accounts.mint);
+        while state.order.len() > MAX_CACHED_MINTS {
+            if let Some(oldest) = state.order.pop_front() {
+                state.accounts.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Pump交易的长期上下文
+///
+/// 保存解析好的签名密钥、发送和查询用的RPC连接、调用的程序，以及按代币缓存的交易地址，
+/// 从触发到签好交易的路径上不再解析私钥、建立连接或搜索PDA。
+#[derive(Clone)]
+pub struct PumpTrader {
+    signer: Arc<Keypair>,
+    sender: TransactionSender,
+    rpc_client: Arc<RpcClient>,
+    accounts: MintAccountCache,
+    route: PumpRoute,
+}
+
+impl PumpTrader {
+    /// 创建交易上下文
+    ///
+    /// # 参数
+    ///
+    /// * `signer` - 交易用户
+    /// * `sender` - 发送交易的节点
+    /// * `rpc_client` - 查询和模拟用的RPC连接
+    pub fn new(signer: Keypair, sender: TransactionSender, rpc_client: Arc<RpcClient>) -> Self {
+        let accounts = MintAccountCache::new(signer.pubkey());
+        Self {
+            signer: Arc::new(signer),
+            sender,
+            rpc_client,
+            accounts,
+            route: PumpRoute::default(),
+        }
+    }
+
+    /// 设置买入和卖出调用的程序
+    pub fn set_route(&mut self, route: PumpRoute) {
+        self.route = route;
+    }
+
+    /// 更换发送节点，地址缓存保留
+    pub fn set_sender(&mut self, sender: TransactionSender) {
+        self.sender = sender;
+    }
+
+    pub fn signer(&self) -> &Arc<Keypair> {
+        &self.signer
+    }
+
+    pub fn sender(&self) -> &TransactionSender {
+        &self.sender
+    }
+
+    pub fn rpc_client(&self) -> &Arc<RpcClient> {
+        &self.rpc_client
+    }
+
+    pub fn route(&self) -> PumpRoute {
+        self.route
+    }
+
+    /// 按代币缓存的交易地址，处理器在数据流中看到Create指令时加入
+    pub fn mint_accounts(&self) -> &MintAccountCache {
+        &self.accounts
+    }
+
+    /// 代币的交易地址，未缓存时推导
+    pub fn accounts(&self, mint: &Pubkey) -> MintAccounts {
+        self.accounts.get(mint)
+    }
+}
@@ -1,24 +1,127 @@
-use std::error::Error;
 use std::sync::Arc;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
 use std::str::FromStr;
-use tokio::task::JoinHandle;
 use crate::utils::redis::RedisClient;
-use crate::transaction::{pump_buy, pump_sell};
-use crate::utils::blockhash_cache::BlockhashCache;
+use crate::position::{Fill, PositionLedger};
+use crate::exit::ExitOrder;
+use crate::confirm::{Confirmation, ConfirmStatus, ConfirmationTracker, Trigger};
+use tokio::sync::{oneshot, Mutex};
+use crate::processor::mint_state::TradeSide;
 use redis::RedisError;
+use chrono::Local;
+use crate::transaction::{buy_instructions, pump_bundle, send_bundle_transaction, sell_instructions, sign_transaction, PumpRoute, FEE_RECIPIENT};
+use crate::transaction::error::TradeError;
+use crate::transaction::trader::{MintAccountCache, PumpTrader};
+use crate::fee::{ComputeKind, FeeOracle, FeePolicy};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::request::RpcRequest;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signer::Signer;
+use serde_json::{json, Value};
+use crate::transaction::resend::{ResendPolicy, Resender, Submission};
+use crate::transaction::sender::{SendEndpoint, TransactionSender};
+use crate::utils::blockhash_cache::BlockhashCache;
+use crate::utils::buy_template::{BuyTemplates, SignedBuy};
+use crate::bundle::BundleSender;
+use crate::bonding_curve::{fee, with_slippage_min, BondingCurve, CurveError};
+use solana_sdk::hash::Hash;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_program::program_pack::Pack;
+use spl_token::state::Account as TokenAccount;
+use std::env;
+
+/// 等待捆绑结果的最长时间
+const BUNDLE_RESULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// 查询已上链交易的次数，交易达到confirmed之前查询不到，RPC错误同样重试
+const FETCH_ATTEMPTS: u32 = 10;
+
+/// 狙击参数，实盘和回测共用
+///
+/// 价格区间和买入金额用于未配置策略文件时的默认价格区间策略。
+#[derive(Clone, Copy, Debug)]
+pub struct SnipeParams {
+    pub min_sol_price: u64,
+    pub max_sol_price: u64,
+    pub buy_amount: u64,     // 默认策略的买入金额 (lamports)
+    pub slippage_bps: u64,   // 买入滑点 (基点)，预留在买入金额之内
+}
+
+impl Default for SnipeParams {
+    fn default() -> Self {
+        Self {
+            min_sol_price: 500_000_000, // 0.5 SOL
+            max_sol_price: 1_000_000_000, // 1 SOL
+            buy_amount: 100_000_000, // 0.1 SOL
+            slippage_bps: 1500, // 15%
+        }
+    }
+}
+
+impl SnipeParams {
+    // 从环境变量读取狙击参数，SOL单位的浮点数转换为lamports整数
+    pub fn from_env() -> Self {
+        let sol_var = |name: &str, default: f64| {
+            let sol = env::var(name).ok().and_then(|v| v.parse::<f64>().ok()).unwrap_or(default);
+            (sol * 1_000_000_000.0) as u64
+        };
+        Self {
+            min_sol_price: sol_var("MIN_SOL_PRICE", 0.5),
+            max_sol_price: sol_var("MAX_SOL_PRICE", 3.0),
+            buy_amount: sol_var("BUY_SOL_AMOUNT", 0.1),
+            slippage_bps: env::var("SLIPPAGE_BPS").ok().and_then(|v| v.parse().ok()).unwrap_or(1500),
+        }
+    }
+    
+    // 按内盘曲线计算买入的代币数量(含精度)
+    // 按当前曲线花费不超过买入金额扣除滑点后的预算，买入金额本身作为最大花费
+    pub fn token_amount(&self, buy_amount: u64, curve: &BondingCurve, fee_basis_points: u64) -> Result<u64, CurveError> {
+        let budget = with_slippage_min(buy_amount, self.slippage_bps);
+        curve.buy_quote_with_fee(budget, fee_basis_points)
+    }
+}
+
+/// 已提交、等待确认的成交
+pub struct PendingFill {
+    pub fill: Fill,
+    pub signature: Signature,
+    confirmation: oneshot::Receiver<Confirmation>,
+}
+
+impl PendingFill {
+    fn submitted(fill: Fill, submission: Submission) -> Self {
+        Self {
+            fill,
+            signature: submission.signature,
+            confirmation: submission.confirmation,
+        }
+    }
+
+    /// 等待确认结果，跟踪器停止时视为过期
+    pub async fn wait(self) -> (Fill, Confirmation) {
+        let confirmation = self.confirmation.await.unwrap_or(Confirmation {
+            signature: self.signature,
+            status: ConfirmStatus::Expired,
+            landed_slot: None,
+            latency: Duration::ZERO,
+            slots: None,
+        });
+        (self.fill, confirmation)
+    }
+}
 
 pub struct AutoTrader {
-    redis_client: Arc<RedisClient>,
-    rpc_url: String,
-    private_key: String,
-    running: bool,
-    min_sol_price: u64,
-    max_sol_price: u64,
-    buy_amount: u64,     // 买入金额 (lamports)
-    sell_delay_ms: u64,  // 卖出延迟时间 (毫秒)
+    ledger: PositionLedger, // 持仓账本，成交写入Redis
+    trader: PumpTrader, // 签名密钥、RPC连接和按代币缓存的交易地址
+    params: SnipeParams,
     blockhash_cache: Arc<BlockhashCache>, // 添加区块哈希缓存
+    confirmations: ConfirmationTracker, // 已提交交易的确认跟踪
+    resender: Resender, // 通过RPC发送时在确认前持续重发
+    fees: FeeOracle, // 优先级费用和计算单元上限
+    bundle_sender: Option<Arc<BundleSender>>, // Jito捆绑发送器，未设置时通过RPC发送
+    bundle_tip_lamports: u64, // 捆绑小费 (lamports)
+    templates: Option<BuyTemplates>, // 新代币的预签名买入交易
 }
 
 impl AutoTrader {
@@ -26,258 +129,794 @@ impl AutoTrader {
     pub async fn new(
         redis_client: Arc<RedisClient>,
         rpc_url: String,
-        private_key: String,
+        signer: Keypair,
     ) -> Self {
-        // 默认设置
-        let min_sol_price = 500_000_000; // 0.5 SOL
-        let max_sol_price = 1_000_000_000; // 1 SOL
-        let buy_amount = 100_000_000; // 0.1 SOL
-        let sell_delay_ms = 5000; // 5秒后自动卖出
-        
         // 创建区块哈希缓存，缓存时间减小到500毫秒，以保持区块哈希更新但又不频繁请求
         let blockhash_cache = Arc::new(BlockhashCache::new(&rpc_url, 500));
+        let confirmations = ConfirmationTracker::new(rpc_url.clone());
+        let sender = TransactionSender::new(&[SendEndpoint::parse(&rpc_url)]);
+        let rpc_client = Arc::new(RpcClient::new(rpc_url.clone()));
+        let resender = Resender::new(sender.clone(), blockhash_cache.clone(), confirmations.clone(), ResendPolicy::default());
         
         Self {
-            redis_client,
-            rpc_url,
-            private_key,
-            running: false,
-            min_sol_price,
-            max_sol_price,
-            buy_amount,
-            sell_delay_ms,
+            ledger: PositionLedger::with_redis(redis_client),
+            trader: PumpTrader::new(signer, sender, rpc_client),
+            params: SnipeParams::default(),
             blockhash_cache,
+            confirmations,
+            resender,
+            fees: FeeOracle::new(FeePolicy::default()),
+            bundle_sender: None,
+            bundle_tip_lamports: 0,
+            templates: None,
         }
     }
     
-    // 设置价格范围
-    pub async fn set_price_range(&mut self, min_sol_price: u64, max_sol_price: u64) {
-        self.min_sol_price = min_sol_price;
-        self.max_sol_price = max_sol_price;
-        println!("设置狙击价格范围: {} - {} SOL", 
-                 min_sol_price as f64 / 1_000_000_000.0,
-                 max_sol_price as f64 / 1_000_000_000.0);
+    // 设置Jito捆绑发送器，设置后买入和卖出都通过捆绑提交
+    pub fn set_bundle_sender(&mut self, bundle_sender: Arc<BundleSender>, tip_lamports: u64) {
+        self.bundle_sender = Some(bundle_sender);
+        self.bundle_tip_lamports = tip_lamports;
+        println!("启用Jito捆绑发送，小费: {} lamports", tip_lamports);
     }
     
-    // 设置买入金额
-    pub async fn set_buy_amount(&mut self, buy_amount: u64) {
-        self.buy_amount = buy_amount;
-        println!("设置狙击购买金额: {} SOL", buy_amount as f64 / 1_000_000_000.0);
+    // 设置通过RPC发送交易的节点和重发策略，买入和卖出共用
+    // simulate为true时发送前先在RPC_URL节点模拟，模拟失败的交易不发送
+    pub fn set_send_endpoints(&mut self, endpoints: &[SendEndpoint], policy: ResendPolicy, simulate: bool) {
+        let sender = if simulate {
+            TransactionSender::with_simulator(endpoints, &self.trader.rpc_client().url())
+        } else {
+            TransactionSender::new(endpoints)
+        };
+        self.resender = Resender::new(sender.clone(), self.blockhash_cache.clone(), self.confirmations.clone(), policy);
+        self.trader.set_sender(sender);
+        let names: Vec<&str> = endpoints.iter().map(|endpoint| endpoint.name.as_str()).collect();
+        println!(
+            "RPC发送节点: {}，重发间隔: {}ms，最多重建: {}次，发送前模拟: {}",
+            names.join(", "),
+            policy.interval.as_millis(),
+            policy.max_rebuilds,
+            if simulate { "是" } else { "否" }
+        );
     }
     
-    // 设置卖出延迟时间
-    pub async fn set_sell_delay(&mut self, sell_delay_ms: u64) {
-        self.sell_delay_ms = sell_delay_ms;
-        println!("设置自动卖出延迟: {}ms", sell_delay_ms);
+    // 设置买入和卖出调用的程序，需要在启用预签名买入模板之前调用
+    pub fn set_pump_route(&mut self, route: PumpRoute) {
+        self.trader.set_route(route);
+        println!("交易路径: {}", route);
     }
     
-    // 启动自动交易后台任务
-    pub fn start(&mut self) -> JoinHandle<Result<(), Box<dyn Error + Send + Sync>>> {
-        self.running = true;
-        let rpc_url = self.rpc_url.clone();
-        let private_key = self.private_key.clone();
-        let redis_client = self.redis_client.clone();
-        let blockhash_cache = self.blockhash_cache.clone(); // 克隆缓存引用
-        
-        println!("启动自动交易后台任务");
+    // 设置优先级费用的计算方式
+    pub fn set_fee_policy(&mut self, policy: FeePolicy) {
+        self.fees.set_policy(policy);
+        println!("优先级费用: {}", policy);
+    }
+    
+    // 获取费用预言机，用于从数据流中学习优先级费用
+    pub fn fee_oracle(&self) -> FeeOracle {
+        self.fees.clone()
+    }
+    
+    // 获取交易发送器，用于输出各节点的统计
+    pub fn transaction_sender(&self) -> TransactionSender {
+        self.trader.sender().clone()
+    }
+    
+    // 获取按代币缓存的交易地址，由处理器在看到Create指令时填充
+    pub fn mint_accounts(&self) -> MintAccountCache {
+        self.trader.mint_accounts().clone()
+    }
+    
+    // 为新代币预先签好默认买入金额的买入交易，需要在设置发送方式、费用和滑点之后调用
+    // 返回的模板集合交给处理器，在看到Create指令时准备模板
+    pub fn enable_buy_templates(&mut self, buy_sol: u64) -> BuyTemplates {
+        let bundle = self.bundle_sender.as_ref().map(|sender| (Arc::clone(sender), self.bundle_tip_lamports));
+        let templates = BuyTemplates::new(
+            self.trader.clone(),
+            self.fees.clone(),
+            self.blockhash_cache.clone(),
+            self.params,
+            buy_sol,
+            bundle,
+        );
+        templates.spawn_signer();
+        self.templates = Some(templates.clone());
+        println!("启用预签名买入模板，买入金额: {} SOL", buy_sol as f64 / 1_000_000_000.0);
+        templates
+    }
+    
+    // 获取区块哈希缓存，用于从数据流中更新区块哈希
+    pub fn blockhash_cache(&self) -> Arc<BlockhashCache> {
+        self.blockhash_cache.clone()
+    }
+    
+    // 获取确认跟踪器，用于在数据流中查找已提交的交易
+    pub fn confirmation_tracker(&self) -> ConfirmationTracker {
+        self.confirmations.clone()
+    }
+    
+    // 从Redis恢复持仓账本，返回重放的成交数量
+    pub async fn load_positions(&mut self) -> Result<usize, RedisError> {
+        self.ledger.load().await
+    }
+    
+    // 持仓账本
+    pub fn ledger(&self) -> &PositionLedger {
+        &self.ledger
+    }
+    
+    // 记录成交，写入Redis失败时只打印错误，交易已经发出
+    async fn record_fill(&mut self, fill: Fill) {
+        if let Err(e) = self.ledger.record(fill).await {
+            println!("保存成交记录失败: {:?}", e);
+        }
+    }
+    
+    // 设置买入滑点
+    pub async fn set_slippage(&mut self, slippage_bps: u64) {
+        self.params.slippage_bps = slippage_bps;
+        println!("设置买入滑点: {}%", slippage_bps as f64 / 100.0);
+    }
+    
+    // 克隆发送交易需要的句柄，调用方释放锁后再发送，发送和等待响应期间不阻塞其他交易和结算
+    pub fn handles(&self) -> TradeHandles {
+        TradeHandles {
+            trader: self.trader.clone(),
+            params: self.params,
+            blockhash_cache: self.blockhash_cache.clone(),
+            confirmations: self.confirmations.clone(),
+            resender: self.resender.clone(),
+            fees: self.fees.clone(),
+            bundle_sender: self.bundle_sender.clone(),
+            bundle_tip_lamports: self.bundle_tip_lamports,
+            templates: self.templates.clone(),
+        }
+    }
+    
+    // 按确认结果结算成交，上链成功时按交易的实际余额变化记入持仓账本并返回记录的成交，失败或过期时回滚
+    // 执行结果未知时重新查询交易，仍查询不到时按链上代币余额核对，不直接回滚
+    // 查询上链交易期间不持有自动交易器的锁，因此以共享句柄调用
+    pub async fn settle(auto_trader: &Arc<Mutex<AutoTrader>>, mut fill: Fill, confirmation: &Confirmation) -> Option<Fill> {
+        let side = match fill.side {
+            TradeSide::Buy => "买入",
+            TradeSide::Sell => "卖出",
+        };
+        let unknown = confirmation.status == ConfirmStatus::Unknown;
+        if !unknown && !confirmation.is_landed() {
+            println!("{} {} 未成交，已回滚: {}", side, fill.mint, confirmation);
+            return None;
+        }
         
-        // 创建后台任务处理自动卖出逻辑
-        tokio::spawn(async move {
-            // 自动卖出检查任务
-            let sell_task = tokio::spawn({
-                let redis_client = redis_client.clone();
-                let rpc_url = rpc_url.clone();
-                let private_key = private_key.clone();
-                let blockhash_cache = blockhash_cache.clone(); // 为内部任务克隆缓存引用
-                
-                async move {
-                    println!("启动自动卖出检查");
-                    
-                    loop {
-                        // 获取并移除所有需要卖出的代币 - 异步版本
-                        match redis_client.get_and_remove_mints_to_sell().await {
-                            Ok(mints) => {
-                                if !mints.is_empty() {
-                                    // 如果有代币要卖出，预先获取一次区块哈希
-                                    // 这样可以减少每个交易单独获取哈希的次数
-                                    let blockhash = match blockhash_cache.get_latest_blockhash().await {
-                                        Ok(hash) => Some(hash),
-                                        Err(e) => {
-                                            println!("获取区块哈希失败: {:?}", e);
-                                            None
-                                        }
-                                    };
-
-                                    for mint in mints {
-                                        // 执行自动卖出操作
-                                        match Pubkey::from_str(&mint) {
-                                            Ok(mint_pubkey) => {
-                                                println!("执行自动卖出: {}", mint);
-                                                
-                                                // 获取之前存储的代币数量
-                                                match redis_client.get_mint_amount(&mint).await {
-                                                    Ok(Some(token_amount)) => {
-                                                        println!("尝试卖出: {} 代币", token_amount);
-                                                        
-                                                        if let Err(e) = pump_sell(
-                                                            &rpc_url,
-                                                            &private_key,
-                                                            mint_pubkey,
-                                                            token_amount, // 使用存储的代币数量
-                                                            0, // 最低接收0 SOL
-                                                            None, // 不使用特定的slot
-                                                            blockhash.clone() // 使用缓存的区块哈希
-                                                        ).await {
-                                                            println!("自动卖出失败: {:?}", e);
-                                                        }
-                                                    },
-                                                    Ok(None) => {
-                                                        // 如果找不到存储的数量，使用估算的数量
-                                                        // 这种情况应该很少发生，因为我们在买入时会存储数量
-                                                        let buy_sol = 100_000_000; // 0.1 SOL in lamports
-                                                        
-                                                        // 使用默认价格估计值
-                                                        let default_price = 0.000000033;
-                                                        
-                                                        // 将SOL转换为实际单位
-                                                        let buy_sol_f64 = buy_sol as f64 / 1_000_000_000.0;
-                                                        
-                                                        // 计算不含精度的代币数量
-                                                        let token_amount_no_precision = buy_sol_f64 / default_price;
-                                                        
-                                                        // 减少15%的数量以避免滑点错误
-                                                        let reduced_amount = token_amount_no_precision * 0.85;
-                                                        
-                                                        // 精度因子为10^6
-                                                        let precision_factor = 1_000_000.0;
-                                                        
-                                                        // 计算含精度的代币数量，向下取整
-                                                        let token_amount = (reduced_amount * precision_factor).floor() as u64;
-                                                        
-                                                        println!("未找到存储的代币数量，使用估算值: {} 代币(含精度)", token_amount);
-                                                        
-                                                        if let Err(e) = pump_sell(
-                                                            &rpc_url,
-                                                            &private_key,
-                                                            mint_pubkey,
-                                                            token_amount,
-                                                            0, // 最低接收0 SOL
-                                                            None, // 不使用特定的slot
-                                                            blockhash.clone() // 使用缓存的区块哈希
-                                                        ).await {
-                                                            println!("自动卖出失败: {:?}", e);
-                                                        }
-                                                    },
-                                                    Err(e) => {
-                                                        println!("获取代币数量失败: {:?}", e);
-                                                    }
-                                                }
-                                            },
-                                            Err(e) => {
-                                                println!("代币地址无效: {} - {:?}", mint, e);
-                                            }
-                                        }
-                                    }
-                                }
-                            },
-                            Err(e) => println!("获取要卖出的代币失败: {:?}", e)
+        let (rpc_client, fees, owner, token_account) = {
+            let trader = auto_trader.lock().await;
+            (
+                Arc::clone(trader.trader.rpc_client()),
+                trader.fees.clone(),
+                trader.trader.signer().pubkey(),
+                trader.trader.accounts(&fill.mint).associated_user,
+            )
+        };
+        // 重建过的交易以最终上链的签名为准
+        fill.signature = confirmation.signature.to_string();
+        fill.slot = confirmation.landed_slot;
+        let transaction = fetch_transaction(&rpc_client, &confirmation.signature).await;
+        if unknown {
+            match &transaction {
+                Some(transaction) if transaction["meta"]["err"].is_null() => {
+                    println!("{} {} 执行结果未知，重新查询到交易已上链: {}", side, fill.mint, confirmation.signature);
+                }
+                Some(transaction) => {
+                    println!("{} {} 执行失败，已回滚: {} {}", side, fill.mint, confirmation.signature, transaction["meta"]["err"]);
+                    return None;
+                }
+                None => {
+                    // 交易所在slot可能被跳过，也可能已上链但查询不到，按代币余额与账本持仓的差额判断是否成交
+                    let balance = token_account_balance(&rpc_client, &token_account).await;
+                    let mut trader = auto_trader.lock().await;
+                    let held = trader.held_tokens(&fill.mint);
+                    let fill_mint = fill.mint;
+                    let reconciled = match balance {
+                        Some(balance) => unknown_fill(fill, held, balance),
+                        None => {
+                            // 交易已出现在数据流中，大概率已上链，按报价记录并等待人工核对
+                            fill.unreconciled = true;
+                            Some(fill)
+                        }
+                    };
+                    return match reconciled {
+                        Some(fill) => {
+                            println!("{} {} 执行结果未知，按链上余额记录 {} 代币并标记为未核对: {}", side, fill.mint, fill.token_amount, confirmation);
+                            trader.record_fill(fill.clone()).await;
+                            Some(fill)
+                        }
+                        None => {
+                            println!("{} {} 执行结果未知，链上余额没有变化，已回滚: {}", side, fill_mint, confirmation);
+                            None
                         }
-                        
-                        // 每秒检查一次
-                        sleep(Duration::from_secs(1)).await;
-                    }
+                    };
                 }
-            });
-            
-            // 等待卖出任务完成（理论上不会完成，除非出错）
-            if let Err(e) = sell_task.await {
-                println!("自动卖出任务异常终止: {:?}", e);
             }
-            
-            Ok(())
-        })
+        } else {
+            println!("{} {} 已确认: {}", side, fill.mint, confirmation);
+        }
+        if let Some(slot) = transaction.as_ref().and_then(|transaction| transaction["slot"].as_u64()) {
+            fill.slot = Some(slot);
+        }
+        
+        let execution = match transaction {
+            Some(transaction) => {
+                // 实际消耗的计算单元用于设置之后交易的计算单元上限
+                if let Some(units) = transaction["meta"]["computeUnitsConsumed"].as_u64() {
+                    println!("交易 {} 消耗计算单元: {}", confirmation.signature, units);
+                    fees.record_compute_units(fill.side.into(), units as u32);
+                }
+                let execution = parse_execution(&transaction, &owner, &fill.mint, fill.side, fill.tip_lamports);
+                if execution.is_none() {
+                    println!("交易 {} 缺少余额变化", confirmation.signature);
+                }
+                execution
+            }
+            None => None,
+        };
+        match execution {
+            Some(execution) => {
+                println!(
+                    "{} {} 实际成交: {} 代币，{:.6} SOL，手续费 {:.6} SOL (报价 {} 代币，{:.6} SOL)",
+                    side,
+                    fill.mint,
+                    execution.token_amount,
+                    execution.lamports as f64 / 1_000_000_000.0,
+                    execution.fee_lamports as f64 / 1_000_000_000.0,
+                    fill.token_amount,
+                    fill.lamports as f64 / 1_000_000_000.0
+                );
+                fill.token_amount = execution.token_amount;
+                fill.lamports = execution.lamports;
+                fill.fee_lamports = execution.fee_lamports;
+            }
+            None => {
+                // 交易已上链，仍计入持仓以便卖出，但金额未经核对，需要按链上余额检查
+                println!("无法核对 {} {} 的实际成交，按报价记录并标记为未核对: {}", side, fill.mint, confirmation.signature);
+                fill.unreconciled = true;
+            }
+        }
+        auto_trader.lock().await.record_fill(fill.clone()).await;
+        Some(fill)
     }
     
-    // 狙击指定代币
-    pub async fn snipe_token(&self, token_mint: &str, token_price: f64, slot: Option<u64>) -> Result<(), Box<dyn Error>> {
+    // 账本中所有策略持有的该代币数量，与钱包的代币账户余额对应
+    fn held_tokens(&self, mint: &Pubkey) -> u64 {
+        self.ledger
+            .open_positions()
+            .filter(|(_, position_mint, _)| *position_mint == mint)
+            .map(|(_, _, position)| position.token_amount)
+            .sum()
+    }
+}
+
+/// 发送买入和卖出需要的句柄，由自动交易器克隆得到
+///
+/// 持仓账本留在自动交易器中，发送交易时不需要持有自动交易器的锁。
+#[derive(Clone)]
+pub struct TradeHandles {
+    trader: PumpTrader,
+    params: SnipeParams,
+    blockhash_cache: Arc<BlockhashCache>,
+    confirmations: ConfirmationTracker,
+    resender: Resender,
+    fees: FeeOracle,
+    bundle_sender: Option<Arc<BundleSender>>,
+    bundle_tip_lamports: u64,
+    templates: Option<BuyTemplates>,
+}
+
+impl TradeHandles {
+    // 狙击指定代币，买入金额由策略决定并作为最大花费
+    // 返回等待确认的买入，确认上链后再通过settle计入持仓账本
+    pub async fn snipe_token(&self, strategy: &str, token_mint: &str, buy_sol: u64, curve: &BondingCurve, fee_basis_points: u64, trigger: Trigger) -> Result<PendingFill, TradeError> {
         // 将代币地址转为Pubkey
-        let mint_pubkey = Pubkey::from_str(token_mint)?;
-        
-        // 使用配置的买入金额
-        let buy_sol = self.buy_amount;
+        let mint_pubkey = Pubkey::from_str(token_mint)
+            .map_err(|e| TradeError::InvalidOrder(format!("代币地址无效 {}: {}", token_mint, e)))?;
         
-        // 将buy_sol转换为SOL单位(从lamports)
-        let buy_sol_f64 = buy_sol as f64 / 1_000_000_000.0;
-        
-        // 确保价格不为零，避免除零错误
-        if token_price <= 0.0 {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other, 
-                format!("无效的代币价格: {}", token_price)
-            )));
+        // 有预先签好的买入交易时按其中的数量买入，否则按曲线精确计算买入数量
+        let template = self.templates.as_ref().and_then(|templates| templates.take(&mint_pubkey, buy_sol, curve, fee_basis_points));
+        let token_amount = match &template {
+            Some(signed) => signed.token_amount,
+            None => self.params.token_amount(buy_sol, curve, fee_basis_points)?,
+        };
+        if token_amount == 0 {
+            return Err(TradeError::InvalidOrder(format!("买入金额不足以购买代币: {}", token_mint)));
         }
-        
-        // 计算不含精度的代币数量
-        let token_amount_no_precision = buy_sol_f64 / token_price;
-        
-        // 精度因子为10^6
-        let precision_factor = 1_000_000.0;
-        
-        // 计算含精度的代币数量，向下取整
-        // 减少15%的购买数量，以避免滑点错误
-        let reduced_amount = token_amount_no_precision * 0.85; 
-        let token_amount = (reduced_amount * precision_factor).floor() as u64;
+        let expected_cost = curve.buy_cost(token_amount)?;
+        let fee_lamports = fee(expected_cost, fee_basis_points);
+        let total_cost = expected_cost + fee_lamports;
+        // 先按报价记录成交，上链后在settle中按实际余额变化更新
+        let fill = |signature: Signature, tip_lamports: u64| Fill {
+            strategy: strategy.to_string(),
+            mint: mint_pubkey,
+            signature: signature.to_string(),
+            slot: None,
+            side: TradeSide::Buy,
+            token_amount,
+            lamports: total_cost,
+            fee_lamports,
+            tip_lamports,
+            timestamp_ms: Local::now().timestamp_millis() as u64,
+            unreconciled: false,
+        };
         
         // 记录开始狙击的时间戳
         let start_time = std::time::Instant::now();
         
-        println!("开始狙击代币 {} (slot: {:?})", token_mint, slot);
-        println!("投入: {} SOL", buy_sol_f64);
-        println!("实际价格: {} SOL/token", token_price);
-        println!("计算得出代币数量: {:.2} (无精度)", token_amount_no_precision);
-        println!("减少后的数量: {:.2} (无精度)", reduced_amount);
+        println!("开始狙击代币 {} (slot: {})", token_mint, trigger.slot);
+        println!("最大花费: {} SOL", buy_sol as f64 / 1_000_000_000.0);
+        println!("当前价格: {:.9} SOL/token", curve.price_sol());
+        println!("预计花费: {} lamports (不含手续费)", expected_cost);
         println!("尝试购买: {} 代币(含精度)", token_amount);
         
+        // 预签名的交易直接发送，发送路径上不再构造和签名
+        if let Some(signed) = template {
+            println!("使用预签名买入交易 (区块哈希slot: {})", signed.blockhash_slot);
+            let (tip_lamports, blockhash) = (signed.tip_lamports, signed.blockhash());
+            let result = match &self.bundle_sender {
+                Some(bundle_sender) => {
+                    let submitted = send_bundle_transaction(bundle_sender, &signed.transaction).await;
+                    report_bundle(bundle_sender, submitted, format!("狙击 {}", token_mint))
+                        .map(|signature| self.watch(fill(signature, tip_lamports), signature, trigger, Some(blockhash)))
+                }
+                None => self
+                    .submit_template(signed, trigger)
+                    .await
+                    .map(|submission| PendingFill::submitted(fill(submission.signature, 0), submission)),
+            };
+            match &result {
+                Ok(_) => println!("狙击总耗时: {:.3}ms", start_time.elapsed().as_millis()),
+                Err(e) => {
+                    println!("狙击失败: {}", e);
+                    println!("失败耗时: {:.3}ms", start_time.elapsed().as_millis());
+                }
+            }
+            return result;
+        }
+        
         // 获取缓存的区块哈希，快速路径优先
         let blockhash = match self.blockhash_cache.get_latest_blockhash().await {
             Ok(hash) => Some(hash),
             Err(e) => {
-                println!("获取区块哈希失败: {:?}", e);
+                println!("获取区块哈希失败: {}", e);
                 None
             }
         };
         
-        // 买入代币，使用缓存的区块哈希
-        match pump_buy(
-            &self.rpc_url,
-            &self.private_key,
-            mint_pubkey,
-            token_amount,
-            buy_sol,
-            slot,
-            blockhash
-        ).await {
-            Ok(signature) => {
+        // 按数据流中的费用和实测用量设置优先级费用和计算单元上限
+        // 代币地址通常已在看到Create指令时缓存，这里不再搜索PDA
+        let signer = Arc::clone(self.trader.signer());
+        let route = self.trader.route();
+        let accounts = self.trader.accounts(&mint_pubkey);
+        let compute_unit_price = self.fees.compute_unit_price(Some(&mint_pubkey));
+        let compute_unit_limit = self.fees.compute_unit_limit(ComputeKind::Buy);
+        println!("优先级费用: {} micro-lamports/CU，计算单元上限: {}", compute_unit_price, compute_unit_limit);
+        
+        // 配置了捆绑发送器时通过Jito捆绑买入
+        if let Some(bundle_sender) = &self.bundle_sender {
+            let blockhash = blockhash.ok_or_else(|| TradeError::BlockhashUnavailable("捆绑发送需要区块哈希".to_string()))?;
+            let instructions = buy_instructions(route, &signer.pubkey(), &accounts, token_amount, buy_sol, compute_unit_price, compute_unit_limit);
+            let label = format!("狙击 {}", token_mint);
+            return match submit_bundle(bundle_sender, &signer, instructions, self.bundle_tip_lamports, blockhash, label).await {
+                Ok(signature) => {
+                    println!("狙击总耗时: {:.3}ms", start_time.elapsed().as_millis());
+                    Ok(self.watch(fill(signature, self.bundle_tip_lamports), signature, trigger, Some(blockhash)))
+                },
+                Err(e) => {
+                    println!("狙击捆绑提交失败: {}", e);
+                    println!("失败耗时: {:.3}ms", start_time.elapsed().as_millis());
+                    Err(e)
+                }
+            };
+        }
+        
+        // 买入代币，使用缓存的区块哈希，确认前持续重发
+        let build = move |blockhash: Hash, compute_unit_price: u64| {
+            let instructions = buy_instructions(route, &signer.pubkey(), &accounts, token_amount, buy_sol, compute_unit_price, compute_unit_limit);
+            sign_transaction(&signer, &instructions, blockhash)
+        };
+        match self.resender.submit(build, blockhash, compute_unit_price, trigger).await {
+            Ok(submission) => {
                 let elapsed = start_time.elapsed();
-                println!("狙击成功! 交易签名: {}", signature);
+                println!("狙击交易已提交: {}", submission.signature);
                 println!("狙击总耗时: {:.3}ms", elapsed.as_millis());
+                self.record_simulation(ComputeKind::Buy, &submission);
                 
-                // 买入成功后，存储代币地址和购买数量到Redis，设置延迟后自动卖出
-                self.redis_client.store_mint_with_amount(token_mint, token_amount, self.sell_delay_ms).await?;
-                
-                Ok(())
+                // 确认上链前不计入持仓，卖出由退出引擎决定
+                Ok(PendingFill::submitted(fill(submission.signature, 0), submission))
             },
             Err(e) => {
                 let elapsed = start_time.elapsed();
-                println!("狙击失败: {:?}", e);
+                println!("狙击失败: {}", e);
                 println!("失败耗时: {:.3}ms", elapsed.as_millis());
-                Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, format!("狙击失败: {:?}", e))))
+                Err(e)
             }
         }
     }
     
-    // 判断是否应该狙击
-    pub fn should_snipe(&self, sol_amount: u64) -> bool {
-        sol_amount >= self.min_sol_price && sol_amount <= self.max_sol_price
+    // 通过RPC发送预签名的买入交易，区块哈希过期时按模板中的数量和最大花费重建
+    async fn submit_template(&self, signed: SignedBuy, trigger: Trigger) -> Result<Submission, TradeError> {
+        let signer = Arc::clone(self.trader.signer());
+        let route = self.trader.route();
+        let SignedBuy { transaction, accounts, token_amount, max_sol_cost, compute_unit_price, compute_unit_limit, .. } = signed;
+        let build = move |blockhash: Hash, compute_unit_price: u64| {
+            let instructions = buy_instructions(route, &signer.pubkey(), &accounts, token_amount, max_sol_cost, compute_unit_price, compute_unit_limit);
+            sign_transaction(&signer, &instructions, blockhash)
+        };
+        let submission = self.resender.submit_signed(transaction, build, compute_unit_price, trigger).await?;
+        println!("狙击交易已提交: {}", submission.signature);
+        self.record_simulation(ComputeKind::Buy, &submission);
+        Ok(submission)
     }
-}
\ No newline at end of file
+    
+    // 卖出代币，数量和最少接收由退出引擎按当前曲线和滑点计算
+    // 返回等待确认的卖出，确认上链后再通过settle计入持仓账本
+    pub async fn sell_token(&self, exit: &ExitOrder, trigger: Trigger) -> Result<PendingFill, TradeError> {
+        let blockhash = match self.blockhash_cache.get_latest_blockhash().await {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                println!("获取区块哈希失败: {}", e);
+                None
+            }
+        };
+        
+        // 先按预计所得记录成交，上链后在settle中按实际余额变化更新
+        let fill = |signature: Signature, tip_lamports: u64| Fill {
+            strategy: exit.strategy.clone(),
+            mint: exit.mint,
+            signature: signature.to_string(),
+            slot: None,
+            side: TradeSide::Sell,
+            token_amount: exit.token_amount,
+            lamports: exit.expected_sol_output,
+            fee_lamports: exit.fee_lamports,
+            tip_lamports,
+            timestamp_ms: Local::now().timestamp_millis() as u64,
+            unreconciled: false,
+        };
+        
+        let signer = Arc::clone(self.trader.signer());
+        let route = self.trader.route();
+        let accounts = self.trader.accounts(&exit.mint);
+        let (mint, token_amount, min_sol_output) = (exit.mint, exit.token_amount, exit.min_sol_output);
+        let compute_unit_price = self.fees.compute_unit_price(Some(&mint));
+        let compute_unit_limit = self.fees.compute_unit_limit(ComputeKind::Sell);
+        
+        // 配置了捆绑发送器时通过Jito捆绑卖出
+        if let Some(bundle_sender) = &self.bundle_sender {
+            let blockhash = blockhash.ok_or_else(|| TradeError::BlockhashUnavailable("捆绑发送需要区块哈希".to_string()))?;
+            let instructions = sell_instructions(route, &signer.pubkey(), &accounts, token_amount, min_sol_output, compute_unit_price, compute_unit_limit);
+            let label = format!("卖出 {}", mint);
+            let signature = submit_bundle(bundle_sender, &signer, instructions, self.bundle_tip_lamports, blockhash, label).await?;
+            return Ok(self.watch(fill(signature, self.bundle_tip_lamports), signature, trigger, Some(blockhash)));
+        }
+        
+        // 通过RPC卖出，确认前持续重发
+        let build = move |blockhash: Hash, compute_unit_price: u64| {
+            let instructions = sell_instructions(route, &signer.pubkey(), &accounts, token_amount, min_sol_output, compute_unit_price, compute_unit_limit);
+            sign_transaction(&signer, &instructions, blockhash)
+        };
+        let submission = self.resender.submit(build, blockhash, compute_unit_price, trigger).await?;
+        println!("卖出交易已提交: {}", submission.signature);
+        self.record_simulation(ComputeKind::Sell, &submission);
+        Ok(PendingFill::submitted(fill(submission.signature, 0), submission))
+    }
+    
+    // 模拟得到的计算单元用量同样用于设置之后交易的计算单元上限
+    fn record_simulation(&self, kind: ComputeKind, submission: &Submission) {
+        if let Some(units) = submission.simulation.as_ref().and_then(|simulation| simulation.units_consumed) {
+            self.fees.record_compute_units(kind, units as u32);
+        }
+    }
+    
+    // 开始跟踪已提交的交易
+    fn watch(&self, fill: Fill, signature: Signature, trigger: Trigger, blockhash: Option<Hash>) -> PendingFill {
+        let blockhash_slot = blockhash.and_then(|hash| self.blockhash_cache.blockhash_slot(&hash));
+        PendingFill {
+            fill,
+            signature,
+            confirmation: self.confirmations.watch(signature, trigger, blockhash_slot),
+        }
+    }
+}
+
+// 执行结果未知的成交按代币余额核对：余额比账本多出的部分为买入所得，少于账本的部分为卖出数量，没有差额时视为未成交
+fn unknown_fill(mut fill: Fill, held: u64, balance: u64) -> Option<Fill> {
+    let token_amount = match fill.side {
+        TradeSide::Buy => balance.checked_sub(held)?,
+        TradeSide::Sell => held.checked_sub(balance)?.min(fill.token_amount),
+    };
+    if token_amount == 0 {
+        return None;
+    }
+    // 花费或所得无法从余额得到，仍按报价记录
+    fill.token_amount = token_amount;
+    fill.unreconciled = true;
+    Some(fill)
+}
+
+// 查询代币账户的confirmed余额，账户不存在时为0，查询失败时返回None
+async fn token_account_balance(rpc_client: &RpcClient, token_account: &Pubkey) -> Option<u64> {
+    match rpc_client.get_account_with_commitment(token_account, CommitmentConfig::confirmed()).await {
+        Ok(response) => match response.value {
+            Some(account) => {
+                let data = account.data.get(..TokenAccount::LEN)?;
+                TokenAccount::unpack_from_slice(data).ok().map(|account| account.amount)
+            }
+            None => Some(0),
+        },
+        Err(e) => {
+            println!("查询代币账户 {} 失败: {:?}", token_account, e);
+            None
+        }
+    }
+}
+
+// 通过Jito捆绑发送交易并在后台等待捆绑结果，返回交易签名
+async fn submit_bundle(
+    sender: &Arc<BundleSender>,
+    signer: &Keypair,
+    instructions: Vec<Instruction>,
+    bundle_tip_lamports: u64,
+    blockhash: Hash,
+    label: String,
+) -> Result<Signature, TradeError> {
+    let submitted = pump_bundle(sender, signer, instructions, bundle_tip_lamports, blockhash).await;
+    report_bundle(sender, submitted, label)
+}
+
+// 报告捆绑提交结果并在后台等待捆绑结果，返回交易签名
+fn report_bundle(
+    sender: &Arc<BundleSender>,
+    submitted: Result<(String, Signature), TradeError>,
+    label: String,
+) -> Result<Signature, TradeError> {
+    let (bundle_id, signature) = submitted?;
+    println!("{} 捆绑已提交: {} (交易: {})", label, bundle_id, signature);
+    
+    // 在后台等待捆绑结果，报告接受/拒绝/丢弃
+    let sender = Arc::clone(sender);
+    tokio::spawn(async move {
+        match sender.wait_for_result(&bundle_id, BUNDLE_RESULT_TIMEOUT).await {
+            Ok(Some(outcome)) => println!("{} 捆绑 {} 结果: {:?}", label, bundle_id, outcome),
+            Ok(None) => println!("{} 捆绑 {} 等待结果超时", label, bundle_id),
+            Err(e) => println!("{} 捆绑 {} 结果未知: {}", label, bundle_id, e),
+        }
+    });
+    Ok(signature)
+}
+
+// 查询已上链的交易，交易达到confirmed之前返回null，与RPC错误一样稍后重试
+async fn fetch_transaction(rpc_client: &RpcClient, signature: &Signature) -> Option<Value> {
+    let params = json!([
+        signature.to_string(),
+        { "encoding": "json", "commitment": "confirmed", "maxSupportedTransactionVersion": 0 }
+    ]);
+    for attempt in 0..FETCH_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        match rpc_client.send::<Value>(RpcRequest::GetTransaction, params.clone()).await {
+            Ok(transaction) if !transaction["meta"].is_null() => return Some(transaction),
+            Ok(_) => {}
+            Err(e) => println!("查询交易 {} 失败 (第{}次): {:?}", signature, attempt + 1, e),
+        }
+    }
+    None
+}
+
+/// 从上链交易的余额变化得到的实际成交
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Execution {
+    // 买入的实际花费或卖出的实际所得，含协议手续费，不含网络费用、小费和新建代币账户的租金
+    lamports: u64,
+    token_amount: u64,
+    // 手续费接收账户收到的协议手续费
+    fee_lamports: u64,
+}
+
+// 交易中指定账户的余额变化，账户顺序为静态账户、查找表加载的可写账户、只读账户
+fn balance_change(transaction: &Value, account: &Pubkey) -> Option<u64> {
+    let account = account.to_string();
+    let meta = &transaction["meta"];
+    let keys = transaction["transaction"]["message"]["accountKeys"].as_array()?;
+    let loaded = &meta["loadedAddresses"];
+    let empty = Vec::new();
+    let index = keys
+        .iter()
+        .chain(loaded["writable"].as_array().unwrap_or(&empty))
+        .chain(loaded["readonly"].as_array().unwrap_or(&empty))
+        .position(|key| key.as_str() == Some(account.as_str()))?;
+    meta["postBalances"][index].as_u64()?.checked_sub(meta["preBalances"][index].as_u64()?)
+}
+
+// 交易中`owner`持有的`mint`代币账户的索引和余额
+fn token_balance(balances: &Value, owner: &Pubkey, mint: &Pubkey) -> Option<(usize, u64)> {
+    let (owner, mint) = (owner.to_string(), mint.to_string());
+    balances.as_array()?.iter().find_map(|balance| {
+        if balance["owner"].as_str() != Some(owner.as_str()) || balance["mint"].as_str() != Some(mint.as_str()) {
+            return None;
+        }
+        let index = balance["accountIndex"].as_u64()? as usize;
+        let amount = balance["uiTokenAmount"]["amount"].as_str()?.parse().ok()?;
+        Some((index, amount))
+    })
+}
+
+// 按getTransaction返回的meta计算实际成交，付款账户为交易的第一个账户
+//
+// 付款账户的余额变化扣除网络费用和小费，买入时再扣除新建代币账户的租金，得到与曲线报价口径一致的金额。
+//
+// 协议手续费取自手续费接收账户的余额变化，交易中没有该账户时无法核对，返回None。
+fn parse_execution(transaction: &Value, owner: &Pubkey, mint: &Pubkey, side: TradeSide, tip_lamports: u64) -> Option<Execution> {
+    let meta = &transaction["meta"];
+    if !meta["err"].is_null() {
+        return None;
+    }
+    let network_fee = meta["fee"].as_u64()?;
+    let pre_lamports = meta["preBalances"][0].as_u64()?;
+    let post_lamports = meta["postBalances"][0].as_u64()?;
+    let pre_tokens = token_balance(&meta["preTokenBalances"], owner, mint);
+    let (account_index, post_tokens) = token_balance(&meta["postTokenBalances"], owner, mint)?;
+    let fee_lamports = balance_change(transaction, &FEE_RECIPIENT)?;
+
+    match side {
+        TradeSide::Buy => {
+            // 交易前不存在的代币账户由本交易创建并支付租金
+            let (rent, pre_tokens) = match pre_tokens {
+                Some((_, amount)) => (0, amount),
+                None => (meta["postBalances"][account_index].as_u64()?, 0),
+            };
+            Some(Execution {
+                lamports: pre_lamports
+                    .checked_sub(post_lamports)?
+                    .saturating_sub(network_fee)
+                    .saturating_sub(tip_lamports)
+                    .saturating_sub(rent),
+                token_amount: post_tokens.checked_sub(pre_tokens)?,
+                fee_lamports,
+            })
+        }
+        TradeSide::Sell => Some(Execution {
+            lamports: (post_lamports + network_fee + tip_lamports).checked_sub(pre_lamports)?,
+            token_amount: pre_tokens?.1.checked_sub(post_tokens)?,
+            fee_lamports,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENT: u64 = 2_039_280;
+
+    fn token_balance_json(index: u64, owner: &Pubkey, mint: &Pubkey, amount: u64) -> Value {
+        json!({
+            "accountIndex": index,
+            "mint": mint.to_string(),
+            "owner": owner.to_string(),
+            "uiTokenAmount": { "amount": amount.to_string(), "decimals": 6 }
+        })
+    }
+
+    // 付款账户、用户的代币账户和手续费接收账户
+    fn transaction(owner: &Pubkey, pre: [u64; 3], post: [u64; 3], pre_tokens: Vec<Value>, post_tokens: Vec<Value>) -> Value {
+        json!({
+            "slot": 100,
+            "transaction": {
+                "message": {
+                    "accountKeys": [owner.to_string(), Pubkey::new_unique().to_string(), FEE_RECIPIENT.to_string()]
+                }
+            },
+            "meta": {
+                "err": null,
+                "fee": 5_000,
+                "preBalances": pre,
+                "postBalances": post,
+                "preTokenBalances": pre_tokens,
+                "postTokenBalances": post_tokens,
+                "loadedAddresses": { "writable": [], "readonly": [] },
+                "computeUnitsConsumed": 60_000
+            }
+        })
+    }
+
+    #[test]
+    fn buy_excludes_network_fee_tip_and_new_account_rent() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let spent = 101_000_000;
+        let protocol_fee = 1_000_000;
+        let tip = 10_000;
+        let pre = 1_000_000_000;
+        let post = pre - spent - 5_000 - tip - RENT;
+        let tx = transaction(
+            &owner,
+            [pre, 0, 50],
+            [post, RENT, 50 + protocol_fee],
+            vec![],
+            vec![token_balance_json(1, &owner, &mint, 3_500_000)],
+        );
+        let execution = parse_execution(&tx, &owner, &mint, TradeSide::Buy, tip).unwrap();
+        assert_eq!(execution, Execution { lamports: spent, token_amount: 3_500_000, fee_lamports: protocol_fee });
+    }
+
+    #[test]
+    fn sell_adds_back_network_fee() {
+        let (owner, mint, other) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let received = 80_000_000;
+        let protocol_fee = 808_080;
+        let pre = 500_000_000;
+        let tx = transaction(
+            &owner,
+            [pre, RENT, 0],
+            [pre + received - 5_000, RENT, protocol_fee],
+            vec![token_balance_json(1, &owner, &mint, 3_500_000), token_balance_json(2, &other, &mint, 9)],
+            vec![token_balance_json(1, &owner, &mint, 500_000), token_balance_json(2, &other, &mint, 9)],
+        );
+        let execution = parse_execution(&tx, &owner, &mint, TradeSide::Sell, 0).unwrap();
+        assert_eq!(execution, Execution { lamports: received, token_amount: 3_000_000, fee_lamports: protocol_fee });
+    }
+
+    #[test]
+    fn fee_recipient_may_come_from_lookup_table() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut tx = transaction(&owner, [100, 0, 0], [40, 0, 0], vec![], vec![token_balance_json(1, &owner, &mint, 1)]);
+        tx["transaction"]["message"]["accountKeys"][2] = json!(Pubkey::new_unique().to_string());
+        tx["meta"]["loadedAddresses"]["writable"] = json!([FEE_RECIPIENT.to_string()]);
+        tx["meta"]["preBalances"] = json!([100, 0, 0, 7]);
+        tx["meta"]["postBalances"] = json!([40, 0, 0, 9]);
+        let execution = parse_execution(&tx, &owner, &mint, TradeSide::Buy, 0).unwrap();
+        assert_eq!(execution.fee_lamports, 2);
+    }
+
+    #[test]
+    fn failed_or_incomplete_transaction_has_no_execution() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut tx = transaction(&owner, [10, 0, 0], [5, 0, 0], vec![], vec![token_balance_json(1, &owner, &mint, 1)]);
+        tx["meta"]["err"] = json!({ "InstructionError": [2, { "Custom": 6002 }] });
+        assert!(parse_execution(&tx, &owner, &mint, TradeSide::Buy, 0).is_none());
+
+        let tx = transaction(&owner, [10, 0, 0], [5, 0, 0], vec![], vec![]);
+        assert!(parse_execution(&tx, &owner, &mint, TradeSide::Buy, 0).is_none());
+
+        // 交易中没有手续费接收账户时无法核对手续费
+        let mut tx = transaction(&owner, [10, 0, 0], [5, 0, 0], vec![], vec![token_balance_json(1, &owner, &mint, 1)]);
+        tx["transaction"]["message"]["accountKeys"][2] = json!(Pubkey::new_unique().to_string());
+        assert!(parse_execution(&tx, &owner, &mint, TradeSide::Buy, 0).is_none());
+    }
+
+    fn quoted(side: TradeSide, token_amount: u64) -> Fill {
+        Fill {
+            strategy: "default".to_string(),
+            mint: Pubkey::new_unique(),
+            signature: Signature::new_unique().to_string(),
+            slot: None,
+            side,
+            token_amount,
+            lamports: 1_000_000,
+            fee_lamports: 0,
+            tip_lamports: 0,
+            timestamp_ms: 0,
+            unreconciled: false,
+        }
+    }
+
+    #[test]
+    fn unknown_fill_follows_token_balance() {
+        let buy = unknown_fill(quoted(TradeSide::Buy, 1_000), 500, 1_400).unwrap();
+        assert_eq!(buy.token_amount, 900);
+        assert!(buy.unreconciled);
+        assert!(unknown_fill(quoted(TradeSide::Buy, 1_000), 500, 500).is_none());
+
+        let sell = unknown_fill(quoted(TradeSide::Sell, 300), 1_000, 600).unwrap();
+        assert_eq!(sell.token_amount, 300);
+        assert!(sell.unreconciled);
+        assert!(unknown_fill(quoted(TradeSide::Sell, 300), 1_000, 1_000).is_none());
+        assert!(unknown_fill(quoted(TradeSide::Sell, 300), 1_000, 1_200).is_none());
+    }
+}
@@ -1,21 +1,92 @@
+use solana_entry::entry::Entry;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{commitment_config::{CommitmentConfig, CommitmentLevel}, hash::Hash};
-use std::sync::Arc;
+use solana_sdk::{
+    clock::{DEFAULT_TICKS_PER_SLOT, MAX_PROCESSING_AGE},
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+use crate::transaction::error::TradeError;
+
+/// 数据流停滞判定时间，超过该时间未收到新的slot则回退到RPC
+const DEFAULT_STREAM_STALL_MS: u64 = 2_000;
+
+/// 使用的区块哈希落后数据流最新slot的slot数
+///
+/// 数据流是processed级别，最新的slot可能属于被放弃的分叉，落后几个slot的slot通常已经confirmed。
+pub const CONFIRMED_DEPTH_SLOTS: u64 = 4;
+
+/// 从数据流中得到的区块哈希
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotBlockhash {
+    pub slot: u64,
+    pub blockhash: Hash,
+}
+
+/// 数据流中正在接收的slot状态
+struct PendingSlot {
+    slot: u64,
+    last_hash: Hash,
+    tick_count: u64,
+}
+
+/// 由Entry流维护的最近区块哈希窗口
+struct StreamState {
+    pending: Option<PendingSlot>,
+    // 按slot递增排列，最多保留MAX_PROCESSING_AGE个
+    recent: VecDeque<SlotBlockhash>,
+    // 数据流中观察到的最新slot
+    latest_slot: u64,
+    last_update: Option<Instant>,
+}
+
+impl StreamState {
+    // 只有收到完整tick数量的slot才记录，缺少Entry时最后一个哈希不是该slot的区块哈希
+    fn finalize_pending(&mut self) {
+        let pending = match self.pending.take() {
+            Some(pending) if pending.tick_count >= DEFAULT_TICKS_PER_SLOT => pending,
+            _ => return,
+        };
+        // 同一个slot只记录一次
+        if self.recent.back().is_none_or(|last| last.slot < pending.slot) {
+            self.recent.push_back(SlotBlockhash {
+                slot: pending.slot,
+                blockhash: pending.last_hash,
+            });
+            while self.recent.len() > MAX_PROCESSING_AGE {
+                self.recent.pop_front();
+            }
+        }
+    }
+
+    // 落后最新slot至少CONFIRMED_DEPTH_SLOTS的最新区块哈希
+    fn confirmed_blockhash(&self) -> Option<SlotBlockhash> {
+        let confirmed_slot = self.latest_slot.checked_sub(CONFIRMED_DEPTH_SLOTS)?;
+        self.recent.iter().rev().find(|b| b.slot <= confirmed_slot).copied()
+    }
+}
+
 /// 区块哈希缓存，用于减少RPC调用
+///
+/// 优先使用Shredstream数据流推导的区块哈希：每个slot的最后一个Entry的哈希即为该slot的区块哈希。
+/// 只有在数据流停滞时才回退到RPC查询。
 pub struct BlockhashCache {
     rpc_client: RpcClient,
     cached_blockhash: Arc<Mutex<Option<(Hash, Instant)>>>,
     max_age: Duration,
+    stream: StdMutex<StreamState>,
+    stream_stall: Duration,
 }
 
 impl BlockhashCache {
     /// 创建一个新的区块哈希缓存
-    /// 
+    ///
     /// # 参数
-    /// 
+    ///
     /// * `rpc_url` - RPC节点URL
     /// * `max_age_ms` - 缓存的最大有效期（毫秒）
     pub fn new(rpc_url: &str, max_age_ms: u64) -> Self {
@@ -23,33 +94,199 @@ impl BlockhashCache {
             rpc_client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()),
             cached_blockhash: Arc::new(Mutex::new(None)),
             max_age: Duration::from_millis(max_age_ms),
+            stream: StdMutex::new(StreamState {
+                pending: None,
+                recent: VecDeque::with_capacity(MAX_PROCESSING_AGE),
+                latest_slot: 0,
+                last_update: None,
+            }),
+            stream_stall: Duration::from_millis(DEFAULT_STREAM_STALL_MS),
         }
     }
-    
-    /// 获取最新的区块哈希，如果缓存有效则从缓存中获取
-    pub async fn get_latest_blockhash(&self) -> Result<Hash, Box<dyn std::error::Error + Send + Sync>> {
+
+    /// 用数据流中收到的Entry更新区块哈希窗口
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - Entry所属的slot（来自proto `Entry.slot`）
+    /// * `entries` - 反序列化后的Entry列表
+    pub fn record_entries(&self, slot: u64, entries: &[Entry]) {
+        let last = match entries.last() {
+            Some(entry) => entry.hash,
+            None => return,
+        };
+        let ticks = entries.iter().filter(|entry| entry.is_tick()).count() as u64;
+
+        let mut state = self.stream.lock().unwrap();
+
+        match &mut state.pending {
+            Some(pending) if pending.slot == slot => {
+                pending.last_hash = last;
+                pending.tick_count += ticks;
+            }
+            Some(pending) if pending.slot > slot => {
+                // 迟到的旧slot数据，忽略
+                return;
+            }
+            _ => {
+                // 新slot开始，前一个slot没有收到完整的tick数量时丢弃
+                state.pending = None;
+                if state.recent.back().is_some_and(|last| last.slot >= slot) {
+                    return;
+                }
+                state.pending = Some(PendingSlot {
+                    slot,
+                    last_hash: last,
+                    tick_count: ticks,
+                });
+            }
+        }
+
+        // 收到完整的tick数量说明该slot已结束，最后一个tick的哈希即为其区块哈希
+        if state.pending.as_ref().is_some_and(|p| p.tick_count >= DEFAULT_TICKS_PER_SLOT) {
+            state.finalize_pending();
+        }
+        state.latest_slot = state.latest_slot.max(slot);
+        state.last_update = Some(Instant::now());
+    }
+
+    /// 数据流推导出的区块哈希，取落后最新slot至少`CONFIRMED_DEPTH_SLOTS`的slot，数据流停滞时返回None
+    pub fn stream_blockhash(&self) -> Option<SlotBlockhash> {
+        let state = self.stream.lock().unwrap();
+        match state.last_update {
+            Some(updated) if updated.elapsed() < self.stream_stall => state.confirmed_blockhash(),
+            _ => None,
+        }
+    }
+
+    /// 查询某个区块哈希所属的slot（仅限窗口内的区块哈希）
+    pub fn blockhash_slot(&self, blockhash: &Hash) -> Option<u64> {
+        let state = self.stream.lock().unwrap();
+        state
+            .recent
+            .iter()
+            .rev()
+            .find(|b| &b.blockhash == blockhash)
+            .map(|b| b.slot)
+    }
+
+    /// 获取最新的区块哈希，优先使用数据流，数据流停滞时回退到RPC
+    pub async fn get_latest_blockhash(&self) -> Result<Hash, TradeError> {
+        if let Some(latest) = self.stream_blockhash() {
+            return Ok(latest.blockhash);
+        }
+
         let mut cache = self.cached_blockhash.lock().await;
-        
+
         // 检查缓存是否有效
         if let Some((hash, timestamp)) = &*cache {
             if timestamp.elapsed() < self.max_age {
-                println!("使用缓存的区块哈希");
                 return Ok(*hash);
             }
         }
-        
-        // 缓存不存在或已过期，从RPC获取
-        println!("获取新的区块哈希");
+
+        // 数据流停滞且缓存不存在或已过期，从RPC获取
+        println!("数据流停滞，从RPC获取新的区块哈希");
         let blockhash = self.rpc_client
             .get_latest_blockhash_with_commitment(CommitmentConfig {
                 commitment: CommitmentLevel::Confirmed,
             })
-            .await?
+            .await
+            .map_err(|e| TradeError::BlockhashUnavailable(e.to_string()))?
             .0;
-        
+
         // 更新缓存
         *cache = Some((blockhash, Instant::now()));
-        
+
         Ok(blockhash)
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::mock::MockRpcServer;
+
+    fn tick(hash: Hash) -> Entry {
+        Entry {
+            num_hashes: 1,
+            hash,
+            transactions: vec![],
+        }
+    }
+
+    // 一个slot的完整tick，返回最后一个tick的哈希
+    fn record_slot(cache: &BlockhashCache, slot: u64) -> Hash {
+        let ticks: Vec<Entry> = (0..DEFAULT_TICKS_PER_SLOT).map(|_| tick(Hash::new_unique())).collect();
+        // 分两批到达
+        let (first, rest) = ticks.split_at(ticks.len() / 2);
+        cache.record_entries(slot, first);
+        cache.record_entries(slot, rest);
+        rest.last().unwrap().hash
+    }
+
+    fn recent(cache: &BlockhashCache) -> Vec<u64> {
+        cache.stream.lock().unwrap().recent.iter().map(|b| b.slot).collect()
+    }
+
+    #[test]
+    fn finalizes_slot_at_full_tick_count() {
+        let cache = BlockhashCache::new("http://127.0.0.1:1", 0);
+        let first = Hash::new_unique();
+        cache.record_entries(10, &[tick(first)]);
+        assert!(recent(&cache).is_empty());
+
+        let last = record_slot(&cache, 10);
+        assert_eq!(recent(&cache), vec![10]);
+        assert_eq!(cache.blockhash_slot(&last), Some(10));
+        assert_eq!(cache.blockhash_slot(&first), None);
+    }
+
+    #[test]
+    fn incomplete_slot_is_dropped() {
+        let cache = BlockhashCache::new("http://127.0.0.1:1", 0);
+        let partial = Hash::new_unique();
+        cache.record_entries(10, &[tick(partial)]);
+        record_slot(&cache, 11);
+
+        // 缺少Entry的slot不记录
+        assert_eq!(recent(&cache), vec![11]);
+        assert_eq!(cache.blockhash_slot(&partial), None);
+
+        // 迟到的旧slot数据被忽略
+        record_slot(&cache, 10);
+        assert_eq!(recent(&cache), vec![11]);
+    }
+
+    #[test]
+    fn serves_blockhash_confirmed_depth_back() {
+        let cache = BlockhashCache::new("http://127.0.0.1:1", 0);
+        let hashes: Vec<Hash> = (100..=100 + CONFIRMED_DEPTH_SLOTS).map(|slot| record_slot(&cache, slot)).collect();
+
+        let latest = cache.stream_blockhash().unwrap();
+        assert_eq!(latest, SlotBlockhash { slot: 100, blockhash: hashes[0] });
+
+        // 只看到最新的slot而没有落后的slot时不使用数据流
+        let cache = BlockhashCache::new("http://127.0.0.1:1", 0);
+        record_slot(&cache, 100);
+        assert_eq!(cache.stream_blockhash(), None);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_rpc_when_stream_stalls() {
+        let mock = MockRpcServer::new(0.0);
+        let (url, _) = mock.clone().serve().await.unwrap();
+        let mut cache = BlockhashCache::new(&url, 60_000);
+        let hashes: Vec<Hash> = (1..=1 + CONFIRMED_DEPTH_SLOTS).map(|slot| record_slot(&cache, slot)).collect();
+        let stream_hash = hashes[0];
+        assert_eq!(cache.get_latest_blockhash().await.unwrap(), stream_hash);
+
+        cache.stream_stall = Duration::ZERO;
+        let rpc_hash = cache.get_latest_blockhash().await.unwrap();
+        assert_ne!(rpc_hash, stream_hash);
+
+        // 缓存有效期内不再查询RPC
+        mock.advance_slots(1);
+        assert_eq!(cache.get_latest_blockhash().await.unwrap(), rpc_hash);
+    }
+}
@@ -0,0 +1,510 @@
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::Transaction;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::time::Duration;
+
+use crate::bonding_curve::{fee, BondingCurve};
+use crate::bundle::{BundleError, BundleSender};
+use crate::fee::{ComputeKind, FeeOracle};
+use crate::transaction::{buy_instructions, sign_transaction};
+use crate::transaction::trader::{MintAccounts, PumpTrader};
+use crate::utils::auto_trader::SnipeParams;
+use crate::utils::blockhash_cache::{BlockhashCache, SlotBlockhash};
+
+/// 检查未签名模板的间隔
+const SIGN_INTERVAL: Duration = Duration::from_millis(50);
+/// 最多同时保留的模板，超过后丢弃最早创建的代币
+const MAX_TEMPLATES: usize = 256;
+/// 超过该slot数没有成交的代币视为冷门，丢弃其模板
+const STALE_SLOTS: u64 = 300;
+/// 模板区块哈希的最大slot差，超过时取出模板时重新签名
+const MAX_BLOCKHASH_AGE_SLOTS: u64 = 32;
+
+/// 已签名、可直接发送的买入交易
+pub struct SignedBuy {
+    pub transaction: Arc<Transaction>,
+    pub accounts: MintAccounts,
+    pub token_amount: u64,
+    // 最大SOL花费，即模板的买入金额
+    pub max_sol_cost: u64,
+    pub compute_unit_price: u64,
+    pub compute_unit_limit: u32,
+    // 交易中的捆绑小费，通过RPC发送时为0
+    pub tip_lamports: u64,
+    // 区块哈希所属的slot
+    pub blockhash_slot: u64,
+}
+
+struct Template {
+    accounts: MintAccounts,
+    // 最近一次成交后的内盘曲线，签名时按该曲线计算买入数量
+    curve: BondingCurve,
+    fee_basis_points: u64,
+    last_slot: u64,
+    signed: Option<SignedBuy>,
+}
+
+#[derive(Default)]
+struct TemplateState {
+    templates: HashMap<Pubkey, Template>,
+    // 创建顺序，用于丢弃最早的代币
+    order: VecDeque<Pubkey>,
+    // 最近一次获取的小费转账指令和小费，所有模板共用
+    tip: Option<(Instruction, u64)>,
+}
+
+// 等待签名的模板
+struct SignJob {
+    mint: Pubkey,
+    accounts: MintAccounts,
+    curve: BondingCurve,
+    fee_basis_points: u64,
+}
+
+/// 新代币的预签名买入模板
+///
+/// 数据流中出现Create指令时为新代币准备买入交易（地址、创建ATA、计算预算、小费），
+/// 后台任务用数据流推导的区块哈希为新模板签名一次，策略决定买入时直接发送。
+/// 签名的区块哈希过旧或价格上涨超出滑点预留时，在取出时按最新的区块哈希和曲线只重新签名这一笔，
+/// 不会随每个新区块哈希重新签名所有模板。代币长时间没有成交、内盘完成或流动性迁移时丢弃其模板。
+/// 模板只为默认买入金额准备，其他金额的买入仍在发送时构造。
+#[derive(Clone)]
+pub struct BuyTemplates {
+    trader: PumpTrader,
+    fees: FeeOracle,
+    blockhash_cache: Arc<BlockhashCache>,
+    params: SnipeParams,
+    buy_sol: u64,
+    // 捆绑发送器和小费，设置时交易末尾附加小费转账
+    bundle: Option<(Arc<BundleSender>, u64)>,
+    state: Arc<StdMutex<TemplateState>>,
+}
+
+impl BuyTemplates {
+    /// 创建模板集合，需要调用`spawn_signer`启动后台签名
+    ///
+    /// # 参数
+    ///
+    /// * `trader` - 交易上下文，提供签名密钥和缓存的代币地址
+    /// * `fees` - 签名时按策略计算优先级费用和计算单元上限
+    /// * `blockhash_cache` - 数据流推导的区块哈希
+    /// * `params` - 狙击参数，按其中的滑点计算买入数量
+    /// * `buy_sol` - 模板的买入金额 (lamports)，同时作为最大花费
+    /// * `bundle` - 通过Jito捆绑发送时的捆绑发送器和小费
+    pub fn new(
+        trader: PumpTrader,
+        fees: FeeOracle,
+        blockhash_cache: Arc<BlockhashCache>,
+        params: SnipeParams,
+        buy_sol: u64,
+        bundle: Option<(Arc<BundleSender>, u64)>,
+    ) -> Self {
+        Self {
+            trader,
+            fees,
+            blockhash_cache,
+            params,
+            buy_sol,
+            bundle,
+            state: Arc::new(StdMutex::new(TemplateState::default())),
+        }
+    }
+
+    /// 为新创建的代币准备模板，在下一个区块哈希到达时签名，同时丢弃冷门代币的模板
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 代币Mint地址
+    /// * `slot` - Create指令所在的slot
+    /// * `curve` - 新代币的初始内盘曲线
+    /// * `fee_basis_points` - 交易手续费 (基点)
+    pub fn prepare(&self, mint: Pubkey, slot: u64, curve: &BondingCurve, fee_basis_points: u64) {
+        let accounts = self.trader.accounts(&mint);
+        let mut state = self.state.lock().unwrap();
+        if state.templates.contains_key(&mint) {
+            return;
+        }
+        state.templates.retain(|_, template| slot.saturating_sub(template.last_slot) <= STALE_SLOTS);
+        let TemplateState { templates, order, .. } = &mut *state;
+        order.retain(|mint| templates.contains_key(mint));
+
+        state.templates.insert(
+            mint,
+            Template {
+                accounts,
+                curve: curve.clone(),
+                fee_basis_points,
+                last_slot: slot,
+                signed: None,
+            },
+        );
+        state.order.push_back(mint);
+        while state.order.len() > MAX_TEMPLATES {
+            if let Some(oldest) = state.order.pop_front() {
+                state.templates.remove(&oldest);
+            }
+        }
+    }
+
+    /// 记录代币的成交，内盘已完成时丢弃模板，否则下次签名按新的曲线计算买入数量
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 代币Mint地址
+    /// * `slot` - 成交所在的slot
+    /// * `curve` - 应用成交之后的内盘曲线
+    pub fn observe(&self, mint: &Pubkey, slot: u64, curve: &BondingCurve) {
+        if curve.complete {
+            self.evict(mint);
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some(template) = state.templates.get_mut(mint) {
+            template.curve = curve.clone();
+            template.last_slot = template.last_slot.max(slot);
+        }
+    }
+
+    /// 丢弃代币的模板，用于内盘完成、流动性迁移的代币
+    pub fn evict(&self, mint: &Pubkey) {
+        let mut state = self.state.lock().unwrap();
+        if state.templates.remove(mint).is_some() {
+            state.order.retain(|m| m != mint);
+        }
+    }
+
+    /// 取出可以直接发送的买入交易，取出后模板不再保留
+    ///
+    /// 区块哈希过旧、按当前曲线超出最大花费或后台尚未签名时，按最新的区块哈希和当前曲线重新签名。
+    /// 买入金额与模板不同、内盘已完成、数据流停滞或没有模板时返回None，由调用方现场构造交易。
+    ///
+    /// # 参数
+    ///
+    /// * `mint` - 代币Mint地址
+    /// * `buy_sol` - 策略的买入金额 (lamports)
+    /// * `curve` - 当前的内盘曲线
+    /// * `fee_basis_points` - 交易手续费 (基点)
+    pub fn take(&self, mint: &Pubkey, buy_sol: u64, curve: &BondingCurve, fee_basis_points: u64) -> Option<SignedBuy> {
+        if buy_sol != self.buy_sol || curve.complete {
+            return None;
+        }
+        let latest = self.blockhash_cache.stream_blockhash()?;
+        let (template, tip) = {
+            let mut state = self.state.lock().unwrap();
+            let template = state.templates.remove(mint)?;
+            state.order.retain(|m| m != mint);
+            (template, state.tip.clone())
+        };
+
+        if let Some(signed) = template.signed {
+            if latest.slot.saturating_sub(signed.blockhash_slot) <= MAX_BLOCKHASH_AGE_SLOTS && signed.affordable(curve, fee_basis_points) {
+                return Some(signed);
+            }
+        }
+        // 捆绑发送时还没有小费指令，无法在这里签名
+        if self.bundle.is_some() && tip.is_none() {
+            return None;
+        }
+        self.sign(mint, template.accounts, curve, fee_basis_points, latest, tip.as_ref())
+    }
+
+    /// 启动后台任务，数据流推导出区块哈希后为尚未签名的新模板签名
+    pub fn spawn_signer(&self) {
+        let templates = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SIGN_INTERVAL);
+            loop {
+                interval.tick().await;
+                if !templates.has_unsigned() {
+                    continue;
+                }
+                // 数据流停滞时不签名，取出模板时再签名或现场构造
+                if let Some(latest) = templates.blockhash_cache.stream_blockhash() {
+                    templates.sign_pending(latest).await;
+                }
+            }
+        });
+    }
+
+    fn has_unsigned(&self) -> bool {
+        self.state.lock().unwrap().templates.values().any(|template| template.signed.is_none())
+    }
+
+    // 为尚未签名的模板签名，签名在锁外进行，不阻塞数据流处理
+    async fn sign_pending(&self, latest: SlotBlockhash) {
+        let jobs = self.sign_jobs();
+        if jobs.is_empty() {
+            return;
+        }
+        let tip = match self.tip().await {
+            Ok(tip) => tip,
+            Err(e) => {
+                println!("买入模板获取小费账户失败: {}", e);
+                return;
+            }
+        };
+
+        for job in jobs {
+            let signed = match self.sign(&job.mint, job.accounts, &job.curve, job.fee_basis_points, latest, tip.as_ref()) {
+                Some(signed) => signed,
+                None => continue,
+            };
+            // 签名期间代币可能已被取出或丢弃
+            if let Some(template) = self.state.lock().unwrap().templates.get_mut(&job.mint) {
+                template.signed = Some(signed);
+            }
+        }
+    }
+
+    fn sign_jobs(&self) -> Vec<SignJob> {
+        self.state
+            .lock()
+            .unwrap()
+            .templates
+            .iter()
+            .filter(|(_, template)| template.signed.is_none())
+            .map(|(mint, template)| SignJob {
+                mint: *mint,
+                accounts: template.accounts,
+                curve: template.curve.clone(),
+                fee_basis_points: template.fee_basis_points,
+            })
+            .collect()
+    }
+
+    // 捆绑发送时的小费转账指令，第一次获取后缓存，通过RPC发送时为None
+    async fn tip(&self) -> Result<Option<(Instruction, u64)>, BundleError> {
+        let (bundle_sender, tip_lamports) = match &self.bundle {
+            Some(bundle) => bundle,
+            None => return Ok(None),
+        };
+        if let Some(tip) = self.state.lock().unwrap().tip.clone() {
+            return Ok(Some(tip));
+        }
+        let instruction = bundle_sender.tip_instruction(&self.trader.signer().pubkey(), *tip_lamports).await?;
+        let tip = (instruction, *tip_lamports);
+        self.state.lock().unwrap().tip = Some(tip.clone());
+        Ok(Some(tip))
+    }
+
+    // 按曲线计算买入数量并用给定的区块哈希签名，买入数量为0时返回None
+    fn sign(
+        &self,
+        mint: &Pubkey,
+        accounts: MintAccounts,
+        curve: &BondingCurve,
+        fee_basis_points: u64,
+        latest: SlotBlockhash,
+        tip: Option<&(Instruction, u64)>,
+    ) -> Option<SignedBuy> {
+        let token_amount = match self.params.token_amount(self.buy_sol, curve, fee_basis_points) {
+            Ok(token_amount) if token_amount > 0 => token_amount,
+            _ => return None,
+        };
+        let signer = self.trader.signer();
+        let compute_unit_price = self.fees.compute_unit_price(Some(mint));
+        let compute_unit_limit = self.fees.compute_unit_limit(ComputeKind::Buy);
+        let mut instructions = buy_instructions(
+            self.trader.route(),
+            &signer.pubkey(),
+            &accounts,
+            token_amount,
+            self.buy_sol,
+            compute_unit_price,
+            compute_unit_limit,
+        );
+        if let Some((instruction, _)) = tip {
+            instructions.push(instruction.clone());
+        }
+        let transaction = sign_transaction(signer, &instructions, latest.blockhash);
+        Some(SignedBuy {
+            transaction: Arc::new(transaction),
+            accounts,
+            token_amount,
+            max_sol_cost: self.buy_sol,
+            compute_unit_price,
+            compute_unit_limit,
+            tip_lamports: tip.map_or(0, |(_, tip_lamports)| *tip_lamports),
+            blockhash_slot: latest.slot,
+        })
+    }
+}
+
+impl SignedBuy {
+    /// 交易使用的区块哈希
+    pub fn blockhash(&self) -> Hash {
+        self.transaction.message.recent_blockhash
+    }
+
+    // 交易按固定的代币数量买入，曲线上涨超出滑点预留时会因超出最大花费而失败
+    fn affordable(&self, curve: &BondingCurve, fee_basis_points: u64) -> bool {
+        match curve.buy_cost(self.token_amount) {
+            Ok(cost) => cost + fee(cost, fee_basis_points) <= self.max_sol_cost,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bonding_curve::GlobalAccount;
+    use crate::fee::FeePolicy;
+    use crate::transaction::sender::TransactionSender;
+    use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+    use solana_entry::entry::Entry;
+    use solana_sdk::clock::DEFAULT_TICKS_PER_SLOT;
+    use solana_sdk::signature::Keypair;
+    use std::ops::RangeInclusive;
+
+    const BUY_SOL: u64 = 100_000_000;
+
+    fn templates() -> BuyTemplates {
+        let trader = PumpTrader::new(
+            Keypair::new(),
+            TransactionSender::new(&[]),
+            Arc::new(RpcClient::new("http://127.0.0.1:1".to_string())),
+        );
+        BuyTemplates::new(
+            trader,
+            FeeOracle::new(FeePolicy::default()),
+            Arc::new(BlockhashCache::new("http://127.0.0.1:1", 0)),
+            SnipeParams::default(),
+            BUY_SOL,
+            None,
+        )
+    }
+
+    // 数据流依次产出这些slot的完整tick，返回之后模板使用的区块哈希
+    fn advance_stream(templates: &BuyTemplates, slots: RangeInclusive<u64>) -> SlotBlockhash {
+        for slot in slots {
+            let ticks: Vec<Entry> = (0..DEFAULT_TICKS_PER_SLOT)
+                .map(|_| Entry {
+                    num_hashes: 1,
+                    hash: Hash::new_unique(),
+                    transactions: vec![],
+                })
+                .collect();
+            templates.blockhash_cache.record_entries(slot, &ticks);
+        }
+        templates.blockhash_cache.stream_blockhash().unwrap()
+    }
+
+    fn new_curve() -> BondingCurve {
+        GlobalAccount::default().new_curve()
+    }
+
+    #[tokio::test]
+    async fn signed_template_is_taken_once() {
+        let templates = templates();
+        let mint = Pubkey::new_unique();
+        let curve = new_curve();
+        templates.prepare(mint, 1, &curve, 100);
+        let latest = advance_stream(&templates, 1..=5);
+        templates.sign_pending(latest).await;
+        assert!(!templates.has_unsigned());
+
+        assert!(templates.take(&mint, BUY_SOL + 1, &curve, 100).is_none());
+        let signed = templates.take(&mint, BUY_SOL, &curve, 100).unwrap();
+        assert_eq!(signed.blockhash(), latest.blockhash);
+        assert_eq!(signed.blockhash_slot, latest.slot);
+        assert_eq!(signed.token_amount, SnipeParams::default().token_amount(BUY_SOL, &curve, 100).unwrap());
+        assert_eq!(signed.max_sol_cost, BUY_SOL);
+        assert!(templates.take(&mint, BUY_SOL, &curve, 100).is_none());
+    }
+
+    #[tokio::test]
+    async fn old_blockhash_is_resigned_on_take() {
+        let templates = templates();
+        let mint = Pubkey::new_unique();
+        let curve = new_curve();
+        templates.prepare(mint, 1, &curve, 100);
+        let signed_at = advance_stream(&templates, 1..=5);
+        templates.sign_pending(signed_at).await;
+
+        let latest = advance_stream(&templates, 6..=6 + MAX_BLOCKHASH_AGE_SLOTS);
+        assert!(latest.slot - signed_at.slot > MAX_BLOCKHASH_AGE_SLOTS);
+        let signed = templates.take(&mint, BUY_SOL, &curve, 100).unwrap();
+        assert_eq!(signed.blockhash(), latest.blockhash);
+        assert_eq!(signed.blockhash_slot, latest.slot);
+    }
+
+    #[tokio::test]
+    async fn price_rise_beyond_slippage_resigns_with_current_curve() {
+        let templates = templates();
+        let mint = Pubkey::new_unique();
+        let curve = new_curve();
+        templates.prepare(mint, 1, &curve, 100);
+        let latest = advance_stream(&templates, 1..=5);
+        templates.sign_pending(latest).await;
+        let signed_amount = templates.state.lock().unwrap().templates[&mint].signed.as_ref().unwrap().token_amount;
+
+        let mut risen = curve.clone();
+        risen.apply_buy(risen.buy_quote(10_000_000_000).unwrap()).unwrap();
+        let signed = templates.take(&mint, BUY_SOL, &risen, 100).unwrap();
+        assert!(signed.token_amount < signed_amount);
+        assert_eq!(signed.token_amount, SnipeParams::default().token_amount(BUY_SOL, &risen, 100).unwrap());
+        assert!(signed.affordable(&risen, 100));
+    }
+
+    #[test]
+    fn unsigned_template_is_signed_on_take() {
+        let templates = templates();
+        let mint = Pubkey::new_unique();
+        let curve = new_curve();
+        templates.prepare(mint, 1, &curve, 100);
+        // 数据流还没有区块哈希时不取出模板
+        assert!(templates.take(&mint, BUY_SOL, &curve, 100).is_none());
+
+        let latest = advance_stream(&templates, 1..=5);
+        let signed = templates.take(&mint, BUY_SOL, &curve, 100).unwrap();
+        assert_eq!(signed.blockhash(), latest.blockhash);
+    }
+
+    #[test]
+    fn complete_curve_evicts_template() {
+        let templates = templates();
+        let mint = Pubkey::new_unique();
+        let curve = new_curve();
+        templates.prepare(mint, 1, &curve, 100);
+        advance_stream(&templates, 1..=5);
+
+        let mut complete = curve.clone();
+        complete.complete = true;
+        assert!(templates.take(&mint, BUY_SOL, &complete, 100).is_none());
+        templates.observe(&mint, 2, &complete);
+        assert!(templates.take(&mint, BUY_SOL, &curve, 100).is_none());
+    }
+
+    #[test]
+    fn prepare_drops_stale_and_oldest_templates() {
+        let templates = templates();
+        let curve = new_curve();
+        let stale = Pubkey::new_unique();
+        let traded = Pubkey::new_unique();
+        templates.prepare(stale, 1, &curve, 100);
+        templates.prepare(traded, 1, &curve, 100);
+        templates.observe(&traded, 100, &curve);
+
+        let first = Pubkey::new_unique();
+        templates.prepare(first, STALE_SLOTS + 2, &curve, 100);
+        {
+            let state = templates.state.lock().unwrap();
+            assert!(!state.templates.contains_key(&stale));
+            assert!(state.templates.contains_key(&traded));
+            assert_eq!(state.order, VecDeque::from([traded, first]));
+        }
+
+        for _ in 0..MAX_TEMPLATES - 1 {
+            templates.prepare(Pubkey::new_unique(), STALE_SLOTS + 2, &curve, 100);
+        }
+        let state = templates.state.lock().unwrap();
+        assert_eq!(state.templates.len(), MAX_TEMPLATES);
+        assert!(!state.templates.contains_key(&traded));
+        assert!(state.templates.contains_key(&first));
+    }
+}
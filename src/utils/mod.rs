@@ -4,6 +4,7 @@ use bincode::Error as BincodeError;
 pub mod redis;
 pub mod auto_trader;
 pub mod blockhash_cache;
+pub mod buy_template;
 
 pub fn deserialize_entries(data: &[u8]) -> Result<Vec<Entry>, BincodeError> {
     bincode::deserialize::<Vec<Entry>>(data)